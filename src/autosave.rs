@@ -0,0 +1,226 @@
+//! Periodically saves documents with unsaved changes to the cache folder, so they can be recovered after a crash.
+
+use crate::general::{AtomicFileWriter, StorageFormat, StorageWriter, StoragePath};
+use crate::library::{Library, LibraryHolder};
+use crate::schematic::{Schematic, SchematicHolder};
+
+/// How often documents with unsaved changes are autosaved
+pub const AUTOSAVE_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// The name of the autosave file for the schematic
+const SCHEMATIC_FILE: &str = "schematic.urf";
+
+#[derive(serde::Serialize, serde::Deserialize)]
+/// The contents of an autosave file
+pub struct AutosaveFile<T> {
+    /// Where the document is normally saved, if anywhere
+    pub original: Option<StoragePath>,
+    /// The autosaved document
+    pub contents: T,
+}
+
+/// A document that can be recovered from an autosave
+pub enum Recoverable {
+    /// A schematic
+    Schematic(AutosaveFile<Schematic>),
+    /// A library
+    Library(AutosaveFile<Library>),
+}
+
+impl Recoverable {
+    /// A description of the document for the user
+    pub fn describe(&self) -> String {
+        match self {
+            Recoverable::Schematic(f) => match &f.original {
+                Some(p) => format!("schematic {}", p),
+                None => "unsaved schematic".to_string(),
+            },
+            Recoverable::Library(f) => format!("library {}", f.contents.name),
+        }
+    }
+}
+
+/// Keeps track of when autosaves happen, and where they go
+pub struct Autosaver {
+    /// When the last autosave happened
+    last: std::time::Instant,
+    /// The folder that autosave files go in
+    folder: Option<std::path::PathBuf>,
+}
+
+impl Autosaver {
+    /// Create an autosaver that saves to the cache folder
+    pub fn new(dirs: &Option<directories::ProjectDirs>) -> Self {
+        Self {
+            last: std::time::Instant::now(),
+            folder: dirs.as_ref().map(|d| d.cache_dir().join("autosave")),
+        }
+    }
+
+    /// The autosave file for a library. Every byte of the name that is not an ascii letter or digit is escaped as `_` and two hex digits, so distinct names get distinct files.
+    fn library_file(&self, name: &str) -> Option<std::path::PathBuf> {
+        let name: String = name
+            .bytes()
+            .map(|b| {
+                if b.is_ascii_alphanumeric() {
+                    (b as char).to_string()
+                } else {
+                    format!("_{:02x}", b)
+                }
+            })
+            .collect();
+        self.folder
+            .as_ref()
+            .map(|f| f.join(format!("{}.uol", name)))
+    }
+
+    /// The autosave file for the schematic
+    fn schematic_file(&self) -> Option<std::path::PathBuf> {
+        self.folder.as_ref().map(|f| f.join(SCHEMATIC_FILE))
+    }
+
+    /// Write an autosave file. No backup copies are kept, since an autosave only matters until the document is saved.
+    fn write<T: serde::Serialize>(
+        path: std::path::PathBuf,
+        original: &Option<StoragePath>,
+        contents: &T,
+    ) -> Result<(), crate::general::StorageSaveError> {
+        let path = path.into_os_string().into_string().unwrap();
+        let mut writer = Box::new(AtomicFileWriter::without_backups(&path)?);
+        let file = AutosaveFile {
+            original: original.clone(),
+            contents,
+        };
+        StorageFormat::Toml.save(&mut writer, &file)?;
+        writer.commit()?;
+        Ok(())
+    }
+
+    /// Autosave everything that has unsaved changes, if enough time has passed since the last autosave.
+    /// Autosave files for documents without unsaved changes are removed.
+    pub fn check(
+        &mut self,
+        schematic: &Option<SchematicHolder>,
+        libraries: &std::collections::HashMap<String, LibraryHolder>,
+    ) {
+        if self.last.elapsed() < AUTOSAVE_INTERVAL {
+            return;
+        }
+        self.last = std::time::Instant::now();
+        let folder = if let Some(f) = &self.folder {
+            f
+        } else {
+            return;
+        };
+        let _ = std::fs::create_dir_all(folder);
+        if let Some(path) = self.schematic_file() {
+            match schematic {
+                Some(sch) if sch.has_unsaved_changes() => {
+                    if let Err(e) = Self::write(path, &sch.path, &sch.schematic) {
+                        println!("ERROR autosaving schematic {}", e);
+                    }
+                }
+                _ => {
+                    let _ = std::fs::remove_file(path);
+                }
+            }
+        }
+        for (name, libh) in libraries {
+            if let (Some(path), Some(lib)) = (self.library_file(name), &libh.library) {
+                if !libh.has_unsaved_changes() {
+                    let _ = std::fs::remove_file(path);
+                } else if let Err(e) = Self::write(path, &libh.path, lib) {
+                    println!("ERROR autosaving library {} {}", name, e);
+                }
+            }
+        }
+    }
+
+    /// Find autosaved documents that are newer than the saved document, along with the autosave file for each.
+    /// Autosave files that are older than their document, or unreadable, are removed.
+    pub fn find_recoverable(&self) -> Vec<(std::path::PathBuf, Recoverable)> {
+        let mut found = Vec::new();
+        let folder = if let Some(f) = &self.folder {
+            f
+        } else {
+            return found;
+        };
+        let entries = if let Ok(e) = std::fs::read_dir(folder) {
+            e
+        } else {
+            return found;
+        };
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            let rec = if path.file_name().map_or(false, |n| n == SCHEMATIC_FILE) {
+                Self::read::<Schematic>(&path).map(Recoverable::Schematic)
+            } else if path.extension().map_or(false, |e| e == "uol") {
+                Self::read::<Library>(&path).map(Recoverable::Library)
+            } else {
+                continue;
+            };
+            let original = match &rec {
+                Some(Recoverable::Schematic(f)) => &f.original,
+                Some(Recoverable::Library(f)) => &f.original,
+                None => {
+                    let _ = std::fs::remove_file(&path);
+                    continue;
+                }
+            };
+            if Self::is_newer(&path, original) {
+                if let Some(rec) = rec {
+                    found.push((path, rec));
+                }
+            } else {
+                let _ = std::fs::remove_file(&path);
+            }
+        }
+        found
+    }
+
+    /// Read an autosave file
+    fn read<T>(path: &std::path::Path) -> Option<AutosaveFile<T>>
+    where
+        T: for<'a> serde::Deserialize<'a>,
+    {
+        let mut reader = std::fs::File::open(path).ok()?;
+        StorageFormat::Toml.load(&mut reader).ok()
+    }
+
+    /// Is the autosave file newer than the original document? It always is when the document has no local file to compare with.
+    fn is_newer(autosave: &std::path::Path, original: &Option<StoragePath>) -> bool {
+        let orig = original
+            .as_ref()
+            .and_then(|o| o.open_path())
+            .and_then(|p| std::fs::metadata(p).ok())
+            .and_then(|m| m.modified().ok());
+        let auto = std::fs::metadata(autosave).and_then(|m| m.modified());
+        match (orig, auto) {
+            (Some(orig), Ok(auto)) => auto > orig,
+            (None, _) => true,
+            (_, Err(_)) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn library_files_stay_distinct() {
+        let saver = Autosaver {
+            last: std::time::Instant::now(),
+            folder: Some(std::path::PathBuf::from("autosave")),
+        };
+        let names = ["a b", "a_b", "a.b", "ab", "a_20b"];
+        let files: std::collections::HashSet<_> = names
+            .iter()
+            .map(|n| saver.library_file(n).unwrap())
+            .collect();
+        assert_eq!(files.len(), names.len());
+        assert_eq!(
+            saver.library_file("a b").unwrap(),
+            std::path::PathBuf::from("autosave").join("a_20b.uol")
+        );
+    }
+}
@@ -0,0 +1,174 @@
+//! Measures how long the schematic editor takes to draw a page with many objects on it, without a window. Run it
+//! with `electronics_design benchmark [count]`, where count is the number of objects on the page, 10000 by default.
+
+use std::collections::HashMap;
+
+use egui_multiwin::egui;
+
+use crate::general::{Coordinates, Length};
+
+/// The number of objects put on the page when no count is given
+const DEFAULT_COUNT: usize = 10_000;
+
+/// The number of frames drawn and thrown away before timing starts
+const WARMUP_FRAMES: usize = 3;
+
+/// The number of frames timed for each view of the page
+const TIMED_FRAMES: usize = 20;
+
+/// The number of objects in each row of the page
+const COLUMNS: usize = 100;
+
+/// The distance between neighboring objects on the page, in inches
+const SPACING: f32 = 0.5;
+
+/// The size of the screen the page is drawn on, in points
+const SCREEN: egui::Vec2 = egui::vec2(1280.0, 800.0);
+
+/// The name of the library the benchmark component is stored as coming from
+const LIBRARY: &str = "benchmark";
+
+/// A schematic with one page holding count objects, alternating between resistors and text
+fn example(count: usize) -> crate::schematic::SchematicHolder {
+    let mut symbol = crate::symbol::SymbolDefinition::new("R".to_string());
+    symbol.texts.push(crate::schematic::TextOnPage {
+        text: "R".to_string(),
+        location: Coordinates::from_mils(50, 0),
+        color: crate::schematic::Colors::Standard,
+        size: crate::general::Length::from_inches(0.1),
+    });
+    for (y, rotation) in [(300, 0.0), (-300, 180.0)] {
+        symbol.pins.push(crate::symbol::Pin {
+            location: Coordinates::from_mils(0, y),
+            rotation,
+            number: String::new(),
+        });
+    }
+    let mut variant = crate::component::ComponentVariant::new("Default".to_string());
+    variant.symbol = Some(crate::symbol::SymbolReference {
+        lib: crate::symbol::LibraryReference::ThisOne,
+        sym: "R".to_string(),
+    });
+    let mut component = crate::component::ComponentDefinition::new("R".to_string());
+    component.variants.insert("Default".to_string(), variant);
+
+    let mut holder = crate::schematic::SchematicHolder::new_example();
+    let sch = &mut holder.schematic;
+    sch.cache.swap_symbol(LIBRARY, "R", &mut Some(symbol));
+    sch.cache.swap_component(LIBRARY, "R", &mut Some(component));
+    let page = &mut sch.pages[0];
+    page.syms.clear();
+    page.texts.clear();
+    for n in 0..count {
+        let pos = Coordinates::from_inches(
+            (n % COLUMNS) as f32 * SPACING,
+            (n / COLUMNS) as f32 * SPACING,
+        );
+        if n % 2 == 0 {
+            let mut var = crate::component::ComponentVariantReference::new(
+                LIBRARY.to_string(),
+                "R".to_string(),
+                "Default".to_string(),
+                pos,
+            );
+            var.designator = Some(format!("R{}", n / 2 + 1));
+            page.syms.push(var);
+        } else {
+            page.texts.push(crate::schematic::TextOnPage {
+                text: format!("net{}", n / 2),
+                location: pos,
+                color: crate::schematic::Colors::Standard,
+                size: Length::from_mils(50),
+            });
+        }
+    }
+    let rows = (count + COLUMNS - 1) / COLUMNS;
+    page.size = Coordinates::from_inches(COLUMNS as f32 * SPACING, rows as f32 * SPACING);
+    holder
+}
+
+/// The average time in milliseconds to draw and tessellate a frame of the page at a zoom, centered on the page
+fn time_frames(
+    ctx: &egui::Context,
+    holder: &mut crate::schematic::SchematicHolder,
+    zoom: f32,
+) -> f64 {
+    let libs = HashMap::new();
+    let (width, height) = holder.schematic.pages[0].size.get_inches();
+    let mut origin = Coordinates::from_inches(-width / 2.0, -height / 2.0);
+    let mut zoom = zoom;
+    let mut mm = crate::schematic::MouseMode::Selection;
+    let mut selection = Vec::new();
+    let input = egui::RawInput {
+        screen_rect: Some(egui::Rect::from_min_size(egui::Pos2::ZERO, SCREEN)),
+        ..Default::default()
+    };
+    let mut frame = || {
+        let output = ctx.run(input.clone(), |ctx| {
+            egui::CentralPanel::default().show(ctx, |ui| {
+                ui.add(crate::schematic::SchematicWidget::new(
+                    holder,
+                    &mut mm,
+                    &mut selection,
+                    &mut origin,
+                    &mut zoom,
+                    None,
+                    &libs,
+                ));
+            });
+        });
+        ctx.tessellate(output.shapes, output.pixels_per_point);
+    };
+    for _ in 0..WARMUP_FRAMES {
+        frame();
+    }
+    let start = std::time::Instant::now();
+    for _ in 0..TIMED_FRAMES {
+        frame();
+    }
+    start.elapsed().as_secs_f64() * 1000.0 / TIMED_FRAMES as f64
+}
+
+/// Run the benchmark with the arguments after the benchmark command, printing the results
+pub fn run(args: &[String]) -> i32 {
+    let count = match args.first().map(|a| a.parse::<usize>()) {
+        None => DEFAULT_COUNT,
+        Some(Ok(c)) => c,
+        Some(Err(_)) => {
+            println!("Usage: electronics_design benchmark [count]");
+            return 2;
+        }
+    };
+    let ctx = egui::Context::default();
+    let mut fonts = egui::FontDefinitions::default();
+    let mut fd = egui::FontData::from_static(crate::COMPUTER_MODERN_FONT);
+    fd.tweak.y_offset_factor = 1.0 / 3.0;
+    fonts.font_data.insert("computermodern".to_string(), fd);
+    fonts.families.insert(
+        egui::FontFamily::Name("computermodern".into()),
+        vec!["computermodern".to_string()],
+    );
+    ctx.set_fonts(fonts);
+
+    let mut holder = example(count);
+    let start = std::time::Instant::now();
+    holder.schematic.build_index(0, &HashMap::new());
+    let build = start.elapsed().as_secs_f64() * 1000.0;
+
+    let (width, height) = holder.schematic.pages[0].size.get_inches();
+    let fit = (SCREEN.x / width).min(SCREEN.y / height);
+    println!(
+        "A page of {} objects, drawn on a {} by {} screen",
+        count, SCREEN.x, SCREEN.y
+    );
+    println!("Building the index: {:.2} ms", build);
+    println!(
+        "Whole page in view: {:.2} ms per frame",
+        time_frames(&ctx, &mut holder, fit)
+    );
+    println!(
+        "Zoomed in: {:.2} ms per frame",
+        time_frames(&ctx, &mut holder, 115.0)
+    );
+    0
+}
@@ -0,0 +1,222 @@
+//! Buses carry a group of signals, drawn as one thick wire. A bus label placed on a bus names its members in vector
+//! notation, such as D[0..15] for D0 up to D15 or ADDR[7:0] for ADDR7 down to ADDR0. A bus entry breaks one member out
+//! of a bus, and the pins at the far end of the entry connect to the net of that member. Members with the same name
+//! are the same net anywhere in the schematic.
+
+use crate::general::Coordinates;
+
+/// The offset from where a new bus entry touches its bus to where its member leaves it
+pub const ENTRY_SIZE: Coordinates = Coordinates::from_mils(100, 100);
+
+/// A piece of a bus on a schematic page
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+#[serde(tag = "type", content = "args")]
+pub enum BusPart {
+    /// A straight piece of a bus
+    Wire {
+        /// One end of the wire
+        from: Coordinates,
+        /// The other end of the wire
+        to: Coordinates,
+    },
+    /// A short line from a bus to where one member of the bus leaves it
+    Entry {
+        /// The end of the entry on the bus
+        from: Coordinates,
+        /// The end of the entry where the member leaves the bus
+        to: Coordinates,
+        /// The name of the member, such as D3
+        member: String,
+    },
+    /// The name of the bus it is placed on, listing the members of the bus
+    Label {
+        /// Where the label is, on the bus it names
+        location: Coordinates,
+        /// The name of the bus in vector notation
+        text: String,
+    },
+}
+
+impl BusPart {
+    /// Where the part is placed, which is the point that moves to the grid
+    pub fn anchor(&self) -> Coordinates {
+        match self {
+            BusPart::Wire { from, .. } | BusPart::Entry { from, .. } => *from,
+            BusPart::Label { location, .. } => *location,
+        }
+    }
+
+    /// The part with every point of it changed by f
+    pub fn map_points(&self, f: impl Fn(Coordinates) -> Coordinates) -> Self {
+        match self {
+            BusPart::Wire { from, to } => BusPart::Wire {
+                from: f(*from),
+                to: f(*to),
+            },
+            BusPart::Entry { from, to, member } => BusPart::Entry {
+                from: f(*from),
+                to: f(*to),
+                member: member.clone(),
+            },
+            BusPart::Label { location, text } => BusPart::Label {
+                location: f(*location),
+                text: text.clone(),
+            },
+        }
+    }
+
+    /// The points of the part that other objects snap to
+    pub fn snap_points(&self) -> Vec<Coordinates> {
+        match self {
+            BusPart::Wire { from, to } | BusPart::Entry { from, to, .. } => vec![*from, *to],
+            BusPart::Label { location, .. } => vec![*location],
+        }
+    }
+}
+
+/// The most members a bus can have, so that a mistyped range such as D[0..4000000000] is not expanded
+pub const MAX_MEMBERS: u32 = 1024;
+
+/// The prefix and the first and last numbers of a bus named in vector notation. None when the name is not in vector
+/// notation.
+pub fn range(name: &str) -> Option<(&str, u32, u32)> {
+    let (prefix, range) = name.trim().strip_suffix(']')?.split_once('[')?;
+    let (first, last) = range.split_once("..").or_else(|| range.split_once(':'))?;
+    let (first, last): (u32, u32) = (first.trim().parse().ok()?, last.trim().parse().ok()?);
+    if prefix.is_empty() {
+        return None;
+    }
+    Some((prefix, first, last))
+}
+
+/// The members of a bus named in vector notation, in the order given. None when the name is not in vector notation or
+/// names more than MAX_MEMBERS members.
+pub fn members(name: &str) -> Option<Vec<String>> {
+    let (prefix, first, last) = range(name)?;
+    if first.abs_diff(last) >= MAX_MEMBERS {
+        return None;
+    }
+    let numbers: Vec<u32> = if first <= last {
+        (first..=last).collect()
+    } else {
+        (last..=first).rev().collect()
+    };
+    Some(
+        numbers
+            .into_iter()
+            .map(|n| format!("{}{}", prefix, n))
+            .collect(),
+    )
+}
+
+/// Returns true when p is on the straight segment from a to b, ends included
+pub fn on_segment(p: Coordinates, a: Coordinates, b: Coordinates) -> bool {
+    let (ab, ap) = (b - a, p - a);
+    let cross = ab.x as i128 * ap.y as i128 - ab.y as i128 * ap.x as i128;
+    cross == 0
+        && p.x >= a.x.min(b.x)
+        && p.x <= a.x.max(b.x)
+        && p.y >= a.y.min(b.y)
+        && p.y <= a.y.max(b.y)
+}
+
+/// Bus wires of a page joined into one bus, because the end of one is on another
+pub struct BusNetwork {
+    /// The wires, by their index in the bus parts of the page
+    pub wires: Vec<usize>,
+    /// The members named by the labels on the bus, empty when it has no label
+    pub members: Vec<String>,
+}
+
+impl BusNetwork {
+    /// Returns true when the point is on one of the wires of the bus
+    pub fn touches(&self, parts: &[BusPart], p: Coordinates) -> bool {
+        self.wires.iter().any(|w| match &parts[*w] {
+            BusPart::Wire { from, to } => on_segment(p, *from, *to),
+            _ => false,
+        })
+    }
+}
+
+/// The group a wire belongs to, where group lists the wire each wire was joined to
+fn root(group: &mut [usize], mut i: usize) -> usize {
+    while group[i] != i {
+        group[i] = group[group[i]];
+        i = group[i];
+    }
+    i
+}
+
+/// The buses made by the bus parts of a page, with the members named by the labels on them
+pub fn networks(parts: &[BusPart]) -> Vec<BusNetwork> {
+    let wires: Vec<(usize, Coordinates, Coordinates)> = parts
+        .iter()
+        .enumerate()
+        .filter_map(|(i, p)| match p {
+            BusPart::Wire { from, to } => Some((i, *from, *to)),
+            _ => None,
+        })
+        .collect();
+    // Each wire starts in its own group, and groups are joined when an end of one wire is on another
+    let mut group: Vec<usize> = (0..wires.len()).collect();
+    for (a, (_, af, at)) in wires.iter().enumerate() {
+        for (b, (_, bf, bt)) in wires.iter().enumerate().skip(a + 1) {
+            let joined = on_segment(*af, *bf, *bt)
+                || on_segment(*at, *bf, *bt)
+                || on_segment(*bf, *af, *at)
+                || on_segment(*bt, *af, *at);
+            if joined {
+                let (ra, rb) = (root(&mut group, a), root(&mut group, b));
+                group[ra] = rb;
+            }
+        }
+    }
+    let mut networks: Vec<(usize, BusNetwork)> = Vec::new();
+    for (w, (i, _, _)) in wires.iter().enumerate() {
+        let r = root(&mut group, w);
+        match networks.iter_mut().find(|(n, _)| *n == r) {
+            Some((_, n)) => n.wires.push(*i),
+            None => networks.push((
+                r,
+                BusNetwork {
+                    wires: vec![*i],
+                    members: Vec::new(),
+                },
+            )),
+        }
+    }
+    let mut networks: Vec<BusNetwork> = networks.into_iter().map(|(_, n)| n).collect();
+    for p in parts {
+        if let BusPart::Label { location, text } = p {
+            let names = members(text).unwrap_or_default();
+            if let Some(n) = networks.iter_mut().find(|n| n.touches(parts, *location)) {
+                for m in names {
+                    if !n.members.contains(&m) {
+                        n.members.push(m);
+                    }
+                }
+            }
+        }
+    }
+    networks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn members_in_both_directions() {
+        assert_eq!(members("D[0..2]").unwrap(), vec!["D0", "D1", "D2"]);
+        assert_eq!(members("A[2:1]").unwrap(), vec!["A2", "A1"]);
+        assert!(members("D0").is_none());
+    }
+
+    #[test]
+    fn oversized_ranges_are_not_expanded() {
+        assert_eq!(members("D[1..1024]").unwrap().len(), MAX_MEMBERS as usize);
+        assert!(members("D[0..1024]").is_none());
+        assert!(members("D[4000000000:0]").is_none());
+        assert_eq!(range("D[4000000000:0]"), Some(("D", 4000000000, 0)));
+    }
+}
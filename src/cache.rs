@@ -0,0 +1,401 @@
+//! Defines the copies of library items that are stored inside a schematic, so that a schematic looks the same wherever it is opened.
+
+use std::collections::HashMap;
+
+use crate::{
+    component::{ComponentDefinition, ComponentVariantReference},
+    library::{Library, LibraryHolder, LibraryItem},
+    sheet::TitleBlockReference,
+    symbol::{LibraryReference, SymbolDefinition},
+};
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default)]
+/// Copies of the components used by a schematic, along with the symbols used by those components.
+/// Items are stored in partial libraries with the same names as the libraries they were copied from.
+/// Derived components are stored with everything they inherit included, so their parents are not needed.
+pub struct LibraryCache {
+    /// The cached items, by library name
+    pub libraries: HashMap<String, Library>,
+}
+
+/// A difference between an item in the cache and the same item in the library it came from
+pub struct CacheDifference {
+    /// The item that differs
+    pub item: LibraryItem,
+    /// A description of each change, empty when the item no longer exists in the library
+    pub changes: Vec<String>,
+}
+
+impl CacheDifference {
+    /// Returns true when the item can no longer be found in the library
+    pub fn missing(&self) -> bool {
+        self.changes.is_empty()
+    }
+}
+
+/// Returns true when two items would be saved identically
+fn same<T: serde::Serialize>(a: &T, b: &T) -> bool {
+    serde_json::to_value(a).ok() == serde_json::to_value(b).ok()
+}
+
+/// Describe a symbol reference for the user
+fn describe_symbol(s: &Option<crate::symbol::SymbolReference>) -> String {
+    match s {
+        Some(s) => match &s.lib {
+            LibraryReference::ThisOne => s.sym.clone(),
+            LibraryReference::Another(l) => format!("{} from {}", s.sym, l),
+        },
+        None => "no symbol".to_string(),
+    }
+}
+
+/// Describe the changes between two versions of a component
+fn component_changes(old: &ComponentDefinition, new: &ComponentDefinition) -> Vec<String> {
+    let mut changes = Vec::new();
+    for (name, var) in &new.variants {
+        match old.variants.get(name) {
+            None => changes.push(format!("Variant {} added", name)),
+            Some(oldvar) => {
+                if !same(&oldvar.symbol, &var.symbol) {
+                    changes.push(format!(
+                        "Variant {} symbol changed from {} to {}",
+                        name,
+                        describe_symbol(&oldvar.symbol),
+                        describe_symbol(&var.symbol)
+                    ));
+                }
+                for unit in &var.units {
+                    match oldvar.unit(&unit.name) {
+                        None => changes.push(format!("Variant {} unit {} added", name, unit.name)),
+                        Some(oldunit) => {
+                            if !same(&oldunit.symbol, &unit.symbol) {
+                                changes.push(format!(
+                                    "Variant {} unit {} symbol changed from {} to {}",
+                                    name,
+                                    unit.name,
+                                    describe_symbol(&oldunit.symbol),
+                                    describe_symbol(&unit.symbol)
+                                ));
+                            }
+                        }
+                    }
+                }
+                for unit in &oldvar.units {
+                    if var.unit(&unit.name).is_none() {
+                        changes.push(format!("Variant {} unit {} removed", name, unit.name));
+                    }
+                }
+            }
+        }
+    }
+    for name in old.variants.keys() {
+        if !new.variants.contains_key(name) {
+            changes.push(format!("Variant {} removed", name));
+        }
+    }
+    if changes.is_empty() && !same(old, new) {
+        changes.push("Component changed".to_string());
+    }
+    changes.sort();
+    changes
+}
+
+/// Describe the changes between two versions of a symbol
+fn symbol_changes(old: &SymbolDefinition, new: &SymbolDefinition) -> Vec<String> {
+    let mut changes = Vec::new();
+    if old.pins.len() != new.pins.len() {
+        changes.push(format!("Pins {} to {}", old.pins.len(), new.pins.len()));
+    } else if !same(&old.pins, &new.pins) {
+        changes.push("Pins moved".to_string());
+    }
+    if old.texts.len() != new.texts.len() {
+        changes.push(format!("Texts {} to {}", old.texts.len(), new.texts.len()));
+    } else if !same(&old.texts, &new.texts) {
+        changes.push("Texts changed".to_string());
+    }
+    if old.styles.len() != new.styles.len() {
+        changes.push(format!(
+            "Body styles {} to {}",
+            old.styles.len(),
+            new.styles.len()
+        ));
+    } else if !same(&old.styles, &new.styles) {
+        changes.push("Body styles changed".to_string());
+    }
+    if changes.is_empty() && !same(old, new) {
+        changes.push("Symbol changed".to_string());
+    }
+    changes
+}
+
+impl LibraryCache {
+    /// Get the cached component for a reference
+    pub fn cached_component(
+        &self,
+        var: &ComponentVariantReference,
+    ) -> Option<&ComponentDefinition> {
+        self.libraries.get(&var.lib)?.components.get(&var.com)
+    }
+
+    /// Get the cached symbol for a reference
+    pub fn cached_symbol(&self, var: &ComponentVariantReference) -> Option<&SymbolDefinition> {
+        let lib = self.libraries.get(&var.lib)?;
+        let sref = lib
+            .components
+            .get(&var.com)?
+            .variants
+            .get(&var.var)?
+            .unit_symbol(var.unit.as_deref())?;
+        let symlib = match &sref.lib {
+            LibraryReference::ThisOne => lib,
+            LibraryReference::Another(l) => self.libraries.get(l)?,
+        };
+        symlib.syms.get(&sref.sym)
+    }
+
+    /// Get the symbol for a placed component, from the cache when it is there, otherwise from the libraries.
+    /// A cached component whose symbol could not be copied into the cache uses the symbol from the libraries.
+    pub fn get_symbol<'a>(
+        &'a self,
+        var: &ComponentVariantReference,
+        libs: &'a HashMap<String, LibraryHolder>,
+    ) -> Option<&'a SymbolDefinition> {
+        self.cached_symbol(var).or_else(|| var.get_symbol(libs))
+    }
+
+    /// Returns true when a placed component is not in the cache, but can be copied into it from the libraries
+    pub fn can_add(
+        &self,
+        var: &ComponentVariantReference,
+        libs: &HashMap<String, LibraryHolder>,
+    ) -> bool {
+        self.cached_component(var).is_none()
+            && libs
+                .get(&var.lib)
+                .and_then(|l| l.library.as_ref())
+                .map_or(false, |l| l.components.contains_key(&var.com))
+    }
+
+    /// Get the component for a placed component, from the cache when it is there, otherwise from the libraries
+    pub fn get_component(
+        &self,
+        var: &ComponentVariantReference,
+        libs: &HashMap<String, LibraryHolder>,
+    ) -> Option<ComponentDefinition> {
+        match self.cached_component(var) {
+            Some(c) => Some(c.clone()),
+            None => var
+                .get_library(libs)
+                .and_then(|l| l.flattened_component(&var.com, libs)),
+        }
+    }
+
+    /// Get the designator prefix for a placed component
+    pub fn get_prefix(
+        &self,
+        var: &ComponentVariantReference,
+        libs: &HashMap<String, LibraryHolder>,
+    ) -> String {
+        match self.cached_component(var) {
+            Some(c) => c.prefix().to_string(),
+            None => self
+                .get_component(var, libs)
+                .map(|c| c.prefix().to_string())
+                .unwrap_or_else(|| crate::component::DEFAULT_PREFIX.to_string()),
+        }
+    }
+
+    /// Get the designator prefix and the properties of the component for a placed component, which give the text of its fields
+    pub fn get_field_defaults(
+        &self,
+        var: &ComponentVariantReference,
+        libs: &HashMap<String, LibraryHolder>,
+    ) -> (String, HashMap<String, String>) {
+        match self.get_component(var, libs) {
+            Some(c) => (c.prefix().to_string(), c.properties),
+            None => (crate::component::DEFAULT_PREFIX.to_string(), HashMap::new()),
+        }
+    }
+
+    /// Get a mutable reference to the cached library with the given name, creating it if needed
+    fn library_mut(&mut self, name: &str) -> &mut Library {
+        self.libraries
+            .entry(name.to_string())
+            .or_insert_with(|| Library::new(name.to_string()))
+    }
+
+    /// Copy a symbol from the libraries into the cache, if it is not already there
+    fn add_symbol(
+        &mut self,
+        old: &LibraryCache,
+        lib: &str,
+        sym: &str,
+        libs: &HashMap<String, LibraryHolder>,
+    ) {
+        if self
+            .libraries
+            .get(lib)
+            .map_or(false, |l| l.syms.contains_key(sym))
+        {
+            return;
+        }
+        let symbol = old
+            .libraries
+            .get(lib)
+            .and_then(|l| l.syms.get(sym))
+            .or_else(|| {
+                libs.get(lib)
+                    .and_then(|l| l.library.as_ref())
+                    .and_then(|l| l.syms.get(sym))
+            });
+        if let Some(s) = symbol {
+            self.library_mut(lib)
+                .syms
+                .insert(sym.to_string(), s.clone());
+        }
+    }
+
+    /// Rebuild the cache so that it contains exactly the components in use and their symbols, along with the title blocks in use.
+    /// Components and symbols already in the cache are kept as they are, new items are copied from the libraries.
+    /// Title blocks are copied from the libraries when they are available, since they are always drawn from there when they are.
+    pub fn refresh<'a>(
+        &mut self,
+        used: impl Iterator<Item = &'a ComponentVariantReference>,
+        title_blocks: impl Iterator<Item = &'a TitleBlockReference>,
+        libs: &HashMap<String, LibraryHolder>,
+    ) {
+        let old = std::mem::take(self);
+        for var in used {
+            if self.cached_component(var).is_some() {
+                continue;
+            }
+            let com = old.cached_component(var).cloned().or_else(|| {
+                libs.get(&var.lib)
+                    .and_then(|l| l.library.as_ref())
+                    .and_then(|l| l.flattened_component(&var.com, libs))
+            });
+            let com = if let Some(c) = com {
+                c
+            } else {
+                continue;
+            };
+            for sref in com.variants.values().flat_map(|v| v.symbols()) {
+                let symlib = sref.lib.name(&var.lib).to_string();
+                self.add_symbol(&old, &symlib, &sref.sym, libs);
+            }
+            self.library_mut(&var.lib)
+                .components
+                .insert(var.com.clone(), com);
+        }
+        for tref in title_blocks {
+            let block = libs
+                .get(&tref.lib)
+                .and_then(|l| l.library.as_ref())
+                .and_then(|l| l.title_blocks.get(&tref.name))
+                .or_else(|| {
+                    old.libraries
+                        .get(&tref.lib)
+                        .and_then(|l| l.title_blocks.get(&tref.name))
+                });
+            if let Some(b) = block {
+                self.library_mut(&tref.lib)
+                    .title_blocks
+                    .insert(tref.name.clone(), b.clone());
+            }
+        }
+    }
+
+    /// Find the cached items that differ from the libraries they came from
+    pub fn differences(&self, libs: &HashMap<String, LibraryHolder>) -> Vec<CacheDifference> {
+        let mut diffs = Vec::new();
+        for (libname, cached) in &self.libraries {
+            let lib = libs.get(libname).and_then(|l| l.library.as_ref());
+            for (name, com) in &cached.components {
+                let item = LibraryItem::Component(libname.clone(), name.clone());
+                match lib.and_then(|l| l.flattened_component(name, libs)) {
+                    Some(newcom) => {
+                        let changes = component_changes(com, &newcom);
+                        if !changes.is_empty() {
+                            diffs.push(CacheDifference { item, changes });
+                        }
+                    }
+                    None => diffs.push(CacheDifference {
+                        item,
+                        changes: Vec::new(),
+                    }),
+                }
+            }
+            for (name, sym) in &cached.syms {
+                let item = LibraryItem::Symbol(libname.clone(), name.clone());
+                match lib.and_then(|l| l.syms.get(name)) {
+                    Some(newsym) => {
+                        let changes = symbol_changes(sym, newsym);
+                        if !changes.is_empty() {
+                            diffs.push(CacheDifference { item, changes });
+                        }
+                    }
+                    None => diffs.push(CacheDifference {
+                        item,
+                        changes: Vec::new(),
+                    }),
+                }
+            }
+        }
+        diffs.sort_by_key(|d| d.item.to_string());
+        diffs
+    }
+
+    /// Move or rename a cached item to match the same change made in a library
+    pub fn item_moved(&mut self, item: &LibraryItem, newlib: &str, newname: &str) {
+        match item {
+            LibraryItem::Component(lib, com) => {
+                let cached = self
+                    .libraries
+                    .get_mut(lib)
+                    .and_then(|l| l.components.remove(com));
+                if let Some(mut cached) = cached {
+                    cached.rename(newname.to_string());
+                    cached.rebase(lib, newlib);
+                    self.library_mut(newlib)
+                        .components
+                        .insert(newname.to_string(), cached);
+                }
+            }
+            LibraryItem::Symbol(lib, sym) => {
+                let cached = self.libraries.get_mut(lib).and_then(|l| l.syms.remove(sym));
+                if let Some(mut cached) = cached {
+                    cached.rename(newname.to_string());
+                    self.library_mut(newlib)
+                        .syms
+                        .insert(newname.to_string(), cached);
+                }
+                for l in self.libraries.values_mut() {
+                    l.symbol_moved(lib, sym, newlib, newname);
+                }
+            }
+        }
+    }
+
+    /// Swap a cached component with the given one. A component of None removes the component from the cache.
+    pub fn swap_component(
+        &mut self,
+        lib: &str,
+        com: &str,
+        component: &mut Option<ComponentDefinition>,
+    ) {
+        let old = match component.take() {
+            Some(c) => self.library_mut(lib).components.insert(com.to_string(), c),
+            None => self.library_mut(lib).components.remove(com),
+        };
+        *component = old;
+    }
+
+    /// Swap a cached symbol with the given one. A symbol of None removes the symbol from the cache.
+    pub fn swap_symbol(&mut self, lib: &str, sym: &str, symbol: &mut Option<SymbolDefinition>) {
+        let old = match symbol.take() {
+            Some(s) => self.library_mut(lib).syms.insert(sym.to_string(), s),
+            None => self.library_mut(lib).syms.remove(sym),
+        };
+        *symbol = old;
+    }
+}
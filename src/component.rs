@@ -4,9 +4,57 @@ use std::collections::HashMap;
 
 use crate::{
     library::{Library, LibraryHolder},
-    symbol::SymbolDefinition,
+    symbol::{LibraryReference, SymbolDefinition, SymbolReference},
 };
 
+/// The name of the component property that holds the prefix of designators, such as U or R
+pub const PREFIX_PROPERTY: &str = "Prefix";
+
+/// The designator prefix used when a component does not have one
+pub const DEFAULT_PREFIX: &str = "U";
+
+/// The name of the field that shows the designator of a placed component, along with its unit
+pub const DESIGNATOR_FIELD: &str = "Designator";
+
+/// The name of the component property that holds the part number
+pub const PART_NUMBER_PROPERTY: &str = "Part Number";
+
+/// The name of the component property that holds the value, such as 10k or 100nF
+pub const VALUE_PROPERTY: &str = "Value";
+
+/// The height of the text of fields shown on a placed component
+pub const FIELD_SIZE: crate::general::Length = crate::general::Length::from_mils(100);
+
+/// The name given to the power unit of a component
+pub const POWER_UNIT: &str = "PWR";
+
+/// The most components that can be derived from each other in a chain, which stops a component that derives from itself
+const MAX_DERIVE_DEPTH: usize = 16;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[non_exhaustive]
+/// A property of a placed component that is shown as text next to it
+pub struct PropertyField {
+    /// The name of the property shown
+    pub name: String,
+    /// Where the top left corner of the text is, relative to the position of the component
+    pub location: crate::general::Coordinates,
+}
+
+/// The fields shown on a placed component when it does not list any, the designator with the value below it
+fn default_fields() -> Vec<PropertyField> {
+    vec![
+        PropertyField {
+            name: DESIGNATOR_FIELD.to_string(),
+            location: crate::general::Coordinates::from_nm(0, 0),
+        },
+        PropertyField {
+            name: VALUE_PROPERTY.to_string(),
+            location: crate::general::Coordinates::from_nm(0, -FIELD_SIZE.0),
+        },
+    ]
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Clone)]
 #[non_exhaustive]
 /// A reference to a variant of a component
@@ -19,23 +67,44 @@ pub struct ComponentVariantReference {
     pub var: String,
     /// The position of the variant
     pub pos: crate::general::Coordinates,
+    /// The unit of the part that is placed, for components with more than one unit
+    #[serde(default)]
+    pub unit: Option<String>,
+    /// The designator of the part, such as U1. Units of the same part share a designator. None until the schematic is annotated.
+    #[serde(default)]
+    pub designator: Option<String>,
+    /// The alternate body style of the symbol to draw, None for the normal body style
+    #[serde(default)]
+    pub style: Option<String>,
+    /// How the symbol is turned and mirrored
+    #[serde(default)]
+    pub orientation: crate::general::Orientation,
+    /// The properties of this placed component that differ from the properties of the component
+    #[serde(default)]
+    pub properties: HashMap<String, String>,
+    /// The properties shown as text next to the component
+    #[serde(default = "default_fields")]
+    pub fields: Vec<PropertyField>,
+    /// The numbers of the pins marked as left unconnected on purpose
+    #[serde(default)]
+    pub no_connect: Vec<String>,
 }
 
 impl ComponentVariantReference {
-    /// Get the componentvariant that the reference refers to
+    /// Get the library that the reference refers to. Libraries are loaded from the library search path,
+    /// so this is the first library with the name found in the search path.
+    pub fn get_library<'a>(&self, libs: &'a HashMap<String, LibraryHolder>) -> Option<&'a Library> {
+        libs.get(&self.lib).and_then(|l| l.library.as_ref())
+    }
+
+    /// Get the componentvariant that the reference refers to, along with the library that defines the variant.
+    /// The variant can be inherited from a parent component in another library.
     pub fn get_component<'a>(
         &self,
         libs: &'a HashMap<String, LibraryHolder>,
-    ) -> Option<&'a ComponentVariant> {
-        let mut ret = None;
-        if let Some(libh) = libs.get(&self.lib) {
-            if let Some(lib) = &libh.library {
-                if let Some(component) = lib.components.get(&self.com) {
-                    ret = component.variants.get(&self.var);
-                }
-            }
-        }
-        ret
+    ) -> Option<(&'a Library, &'a ComponentVariant)> {
+        let lib = self.get_library(libs)?;
+        lib.components.get(&self.com)?.variant(&self.var, lib, libs)
     }
 
     /// Get a SymbolDefinition from the componentvariantreference.
@@ -44,48 +113,285 @@ impl ComponentVariantReference {
         libs: &'a HashMap<String, LibraryHolder>,
     ) -> Option<&'a SymbolDefinition> {
         let mut ret = None;
-        if let Some(component) = self.get_component(libs) {
-            if let Some(sym) = &component.symbol {
-                if let Some(libraryh) = libs.get(&self.lib) {
-                    if let Some(library) = &libraryh.library {
-                        if let Some(libh) = libs.get(&sym.lib.get_name(library)) {
-                            if let Some(lib) = &libh.library {
-                                ret = lib.syms.get(&sym.sym);
-                            }
-                        }
-                    }
-                }
+        if let Some((library, component)) = self.get_component(libs) {
+            if let Some(sym) = component.unit_symbol(self.unit.as_deref()) {
+                ret = sym.get_symbol(library, libs);
             }
         }
         ret
     }
+
+    /// How the symbol of the placed component is drawn
+    pub fn placement(&self) -> crate::symbol::SymbolPlacement<'_> {
+        crate::symbol::SymbolPlacement {
+            style: self.style.as_deref(),
+            orientation: self.orientation,
+            variables: None,
+        }
+    }
+
+    /// The text variables of the placed component, which are the variables of its page along with its properties,
+    /// its designator as REF and its value as VALUE
+    pub fn variables(
+        &self,
+        page_vars: &crate::variables::TextVariables,
+        prefix: &str,
+        defaults: &HashMap<String, String>,
+    ) -> crate::variables::TextVariables {
+        let mut vars = page_vars.clone();
+        for (name, value) in defaults.iter().chain(self.properties.iter()) {
+            vars.set(name, value.clone());
+        }
+        vars.set(crate::variables::REF, self.label(prefix));
+        if let Some(v) = self.property(VALUE_PROPERTY, defaults) {
+            vars.set(crate::variables::VALUE, v.clone());
+        }
+        vars
+    }
+
+    /// Create a reference to a variant of a component, placed at pos, with the default fields shown
+    pub fn new(lib: String, com: String, var: String, pos: crate::general::Coordinates) -> Self {
+        Self {
+            lib,
+            com,
+            var,
+            pos,
+            unit: None,
+            designator: None,
+            style: None,
+            orientation: crate::general::Orientation::default(),
+            properties: HashMap::new(),
+            fields: default_fields(),
+            no_connect: Vec::new(),
+        }
+    }
+
+    /// The number of a pin of the placed symbol in a netlist, where index is the place of the pin in the symbol.
+    /// A pin without a number is numbered by the unit and its place.
+    pub fn pin_number(&self, index: usize, pin: &crate::symbol::Pin) -> String {
+        if pin.number.is_empty() {
+            format!("{}{}", self.unit.clone().unwrap_or_default(), index + 1)
+        } else {
+            pin.number.clone()
+        }
+    }
+
+    /// Swap the no-connect marker of a pin with the given one, where true means the pin is marked
+    pub fn swap_no_connect(&mut self, pin: &str, marked: &mut bool) {
+        let old = self.no_connect.iter().any(|p| p == pin);
+        if *marked && !old {
+            self.no_connect.push(pin.to_string());
+        } else if !*marked && old {
+            self.no_connect.retain(|p| p != pin);
+        }
+        *marked = old;
+    }
+
+    /// The value of a property of the placed component, where defaults are the properties of the component
+    pub fn property<'a>(
+        &'a self,
+        name: &str,
+        defaults: &'a HashMap<String, String>,
+    ) -> Option<&'a String> {
+        self.properties.get(name).or_else(|| defaults.get(name))
+    }
+
+    /// The text of a field, where defaults are the properties of the component and prefix is its designator prefix.
+    /// Returns None when the property does not exist.
+    pub fn field_text(
+        &self,
+        name: &str,
+        prefix: &str,
+        defaults: &HashMap<String, String>,
+    ) -> Option<String> {
+        if name == DESIGNATOR_FIELD {
+            Some(self.label(prefix))
+        } else {
+            self.property(name, defaults).cloned()
+        }
+    }
+
+    /// Get a shown field by name
+    pub fn field(&self, name: &str) -> Option<&PropertyField> {
+        self.fields.iter().find(|f| f.name == name)
+    }
+
+    /// Where a newly shown field goes, below the fields already shown
+    pub fn next_field_location(&self) -> crate::general::Coordinates {
+        let lowest = self
+            .fields
+            .iter()
+            .map(|f| f.location.y)
+            .fold(FIELD_SIZE.0, i64::min);
+        crate::general::Coordinates::from_nm(0, lowest - FIELD_SIZE.0)
+    }
+
+    /// Swap a property of the placed component with the given value. A value of None means the property of the component is used.
+    pub fn swap_property(&mut self, name: &str, value: &mut Option<String>) {
+        let old = match value.take() {
+            Some(v) => self.properties.insert(name.to_string(), v),
+            None => self.properties.remove(name),
+        };
+        *value = old;
+    }
+
+    /// Swap the location of a field with the given location. A location of None means the field is not shown.
+    pub fn swap_field(&mut self, name: &str, location: &mut Option<crate::general::Coordinates>) {
+        let old = match self.fields.iter().position(|f| f.name == name) {
+            Some(i) => match location.take() {
+                Some(l) => Some(std::mem::replace(&mut self.fields[i].location, l)),
+                None => Some(self.fields.remove(i).location),
+            },
+            None => {
+                if let Some(l) = location.take() {
+                    self.fields.push(PropertyField {
+                        name: name.to_string(),
+                        location: l,
+                    });
+                }
+                None
+            }
+        };
+        *location = old;
+    }
+
+    /// The designator and unit shown for the placed unit, such as U1A, with a question mark for the number when it is not annotated
+    pub fn label(&self, prefix: &str) -> String {
+        let designator = match &self.designator {
+            Some(d) => d.clone(),
+            None => format!("{}?", prefix),
+        };
+        match &self.unit {
+            Some(u) if u != POWER_UNIT => format!("{}{}", designator, u),
+            Some(u) => format!("{} {}", designator, u),
+            None => designator,
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[non_exhaustive]
+/// One of the units of a part that has more than one, such as one gate of a quad nand gate. Each unit is placed separately.
+pub struct ComponentUnit {
+    /// The name of the unit, such as A
+    pub name: String,
+    /// The symbol for the unit
+    pub symbol: Option<SymbolReference>,
+    /// True for the unit that holds the power pins of the part
+    #[serde(default)]
+    pub power: bool,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Clone)]
 #[non_exhaustive]
 /// A variant of a component, with links to one schematic symbol and one or more pcb footprints that might apply to it
 pub struct ComponentVariant {
-    /// The symbol for the variant
+    /// The symbol for the variant, used when the variant has no units
     pub symbol: Option<crate::symbol::SymbolReference>,
     /// The name of the variant
     pub name: String,
+    /// The units of the part, when it has more than one
+    #[serde(default)]
+    pub units: Vec<ComponentUnit>,
 }
 
 impl ComponentVariant {
     /// Create a named component variant
     pub fn new(name: String) -> Self {
-        Self { symbol: None, name }
+        Self {
+            symbol: None,
+            name,
+            units: Vec::new(),
+        }
+    }
+
+    /// Get a unit by name
+    pub fn unit(&self, name: &str) -> Option<&ComponentUnit> {
+        self.units.iter().find(|u| u.name == name)
+    }
+
+    /// The symbol for the named unit, or the symbol of the variant when unit is None
+    pub fn unit_symbol(&self, unit: Option<&str>) -> Option<&SymbolReference> {
+        match unit {
+            Some(u) => self.unit(u).and_then(|u| u.symbol.as_ref()),
+            None => self.symbol.as_ref(),
+        }
+    }
+
+    /// All the symbols used by the variant and its units
+    pub fn symbols(&self) -> impl Iterator<Item = &SymbolReference> {
+        self.symbol
+            .iter()
+            .chain(self.units.iter().filter_map(|u| u.symbol.as_ref()))
+    }
+
+    /// All the symbols used by the variant and its units, for modification
+    pub fn symbols_mut(&mut self) -> impl Iterator<Item = &mut SymbolReference> {
+        self.symbol
+            .iter_mut()
+            .chain(self.units.iter_mut().filter_map(|u| u.symbol.as_mut()))
+    }
+
+    /// The name for the next unit added to the variant. Units are lettered A, B, C and so on, and the power unit is named PWR.
+    pub fn next_unit_name(&self, power: bool) -> String {
+        if power {
+            return POWER_UNIT.to_string();
+        }
+        let mut n = self.units.iter().filter(|u| !u.power).count();
+        loop {
+            let mut name = String::new();
+            let mut i = n;
+            loop {
+                name.insert(0, (b'A' + (i % 26) as u8) as char);
+                if i < 26 {
+                    break;
+                }
+                i = i / 26 - 1;
+            }
+            if self.unit(&name).is_none() {
+                return name;
+            }
+            n += 1;
+        }
     }
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[non_exhaustive]
+/// A reference to a component in a library somewhere
+pub struct ComponentReference {
+    /// The library the component belongs to
+    pub lib: LibraryReference,
+    /// The component name in the library
+    pub com: String,
+}
+
+impl ComponentReference {
+    /// Get the component that is referenced, along with the library it is in, where r is the library containing the reference
+    pub fn get_component<'a>(
+        &self,
+        r: &'a Library,
+        libs: &'a HashMap<String, LibraryHolder>,
+    ) -> Option<(&'a Library, &'a ComponentDefinition)> {
+        let lib = self.lib.resolve(r, libs)?;
+        Some((lib, lib.components.get(&self.com)?))
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
 #[non_exhaustive]
 /// The definition of a component
 pub struct ComponentDefinition {
     /// The name of the component
     name: String,
+    /// The component this one is derived from. The variants and properties of the parent are used, except where this component has its own.
+    #[serde(default)]
+    pub parent: Option<ComponentReference>,
     /// The variants of a component are intended to be somewhat interchangable with each other.
     pub variants: HashMap<String, ComponentVariant>,
+    /// The properties of the component, such as value or manufacturer, by name
+    #[serde(default)]
+    pub properties: HashMap<String, String>,
 }
 
 impl ComponentDefinition {
@@ -93,7 +399,173 @@ impl ComponentDefinition {
     pub fn new(name: String) -> Self {
         Self {
             name,
+            parent: None,
             variants: HashMap::new(),
+            properties: HashMap::new(),
+        }
+    }
+
+    /// Create a component derived from the component named parent in the library named parentlib, for a component in the library named lib
+    pub fn derived(name: String, parentlib: &str, parent: String, lib: &str) -> Self {
+        let mut c = Self::new(name);
+        c.parent = Some(ComponentReference {
+            lib: LibraryReference::to(parentlib, lib),
+            com: parent,
+        });
+        c
+    }
+
+    /// Find a variant of the component, looking in the parent components when this one does not have it.
+    /// lib is the library that contains this component. Returns the variant along with the library that defines it.
+    pub fn variant<'a>(
+        &'a self,
+        name: &str,
+        lib: &'a Library,
+        libs: &'a HashMap<String, LibraryHolder>,
+    ) -> Option<(&'a Library, &'a ComponentVariant)> {
+        let mut com = self;
+        let mut lib = lib;
+        for _ in 0..MAX_DERIVE_DEPTH {
+            if let Some(v) = com.variants.get(name) {
+                return Some((lib, v));
+            }
+            let (plib, parent) = com.parent.as_ref()?.get_component(lib, libs)?;
+            com = parent;
+            lib = plib;
+        }
+        None
+    }
+
+    /// The prefix for designators of the component, such as U or R
+    pub fn prefix(&self) -> &str {
+        self.properties
+            .get(PREFIX_PROPERTY)
+            .map(|p| p.as_str())
+            .filter(|p| !p.is_empty())
+            .unwrap_or(DEFAULT_PREFIX)
+    }
+
+    /// Returns true when the variant is inherited from a parent instead of being defined by this component
+    pub fn inherits(&self, name: &str) -> bool {
+        self.parent.is_some() && !self.variants.contains_key(name)
+    }
+
+    /// Returns a copy of the component with everything inherited from its parents included, so that it has no parent.
+    /// lib is the library that contains this component. Symbol references in the copy are relative to lib.
+    pub fn flattened(&self, lib: &Library, libs: &HashMap<String, LibraryHolder>) -> Self {
+        self.flattened_depth(lib, libs, 0)
+    }
+
+    /// Does the work of flattened, stopping after too many parents
+    fn flattened_depth(
+        &self,
+        lib: &Library,
+        libs: &HashMap<String, LibraryHolder>,
+        depth: usize,
+    ) -> Self {
+        let parent = if depth < MAX_DERIVE_DEPTH {
+            self.parent
+                .as_ref()
+                .and_then(|p| p.get_component(lib, libs))
+        } else {
+            None
+        };
+        let mut flat = match parent {
+            Some((plib, parent)) => {
+                let mut p = parent.flattened_depth(plib, libs, depth + 1);
+                p.rebase(&plib.name, &lib.name);
+                p
+            }
+            None => Self::new(self.name.clone()),
+        };
+        flat.name = self.name.clone();
+        flat.parent = None;
+        for (name, var) in &self.variants {
+            flat.variants.insert(name.clone(), var.clone());
+        }
+        for (name, value) in &self.properties {
+            flat.properties.insert(name.clone(), value.clone());
+        }
+        flat
+    }
+
+    /// Describes a problem with the parent of the component, when there is one. lib is the library that contains this component.
+    pub fn parent_problem(
+        &self,
+        lib: &Library,
+        libs: &HashMap<String, LibraryHolder>,
+    ) -> Option<String> {
+        let mut com = self;
+        let mut lib = lib;
+        for _ in 0..MAX_DERIVE_DEPTH {
+            let p = if let Some(p) = &com.parent {
+                p
+            } else {
+                return None;
+            };
+            match p.get_component(lib, libs) {
+                Some((plib, parent)) => {
+                    com = parent;
+                    lib = plib;
+                }
+                None => {
+                    return Some(format!(
+                        "Parent component \"{}\" does not exist in library \"{}\"",
+                        p.com,
+                        p.lib.name(&lib.name)
+                    ))
+                }
+            }
+        }
+        Some("The component is derived from itself".to_string())
+    }
+
+    /// Change the name of the component
+    pub fn rename(&mut self, name: String) {
+        self.name = name;
+    }
+
+    /// Fix the symbol references of the component after it moves from the library named from to the library named to
+    pub fn rebase(&mut self, from: &str, to: &str) {
+        for sref in self.variants.values_mut().flat_map(|v| v.symbols_mut()) {
+            let target = sref.lib.name(from).to_string();
+            sref.lib = LibraryReference::to(&target, to);
+        }
+        if let Some(p) = &mut self.parent {
+            let target = p.lib.name(from).to_string();
+            p.lib = LibraryReference::to(&target, to);
+        }
+    }
+}
+
+/// A flattened component kept from one frame to the next, for windows that show one. It is flattened again only when
+/// a different component is asked for or the libraries changed.
+#[derive(Default)]
+pub struct FlattenedCache {
+    /// The library and name of the component, with the number of library changes when it was flattened
+    key: Option<(String, String, usize)>,
+    /// The flattened component, None when it was not found
+    flat: Option<ComponentDefinition>,
+}
+
+impl FlattenedCache {
+    /// The component name in library lib with everything it inherits included. changes is the number of changes made
+    /// to the libraries so far, see [crate::journal::JournaledRecord::changes].
+    pub fn get(
+        &mut self,
+        lib: &str,
+        name: &str,
+        libs: &HashMap<String, LibraryHolder>,
+        changes: usize,
+    ) -> Option<&ComponentDefinition> {
+        let fresh = matches!(&self.key, Some((l, n, c)) if l == lib && n == name && *c == changes);
+        if !fresh {
+            self.flat = libs
+                .get(lib)
+                .and_then(|l| l.library.as_ref())
+                .and_then(|l| l.flattened_component(name, libs));
+            self.key = Some((lib.to_string(), name.to_string(), changes));
         }
+        self.flat.as_ref()
     }
 }
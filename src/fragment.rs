@@ -0,0 +1,165 @@
+//! Defines the part of a schematic that is cut or copied to the clipboard, so it can be pasted on another page,
+//! in another schematic, or in another running copy of the program.
+//!
+//! A fragment is stored on the clipboard as text, a header line followed by json, and carries copies of the components
+//! and symbols it uses so that it can be pasted into a schematic that does not have them.
+
+use std::collections::HashMap;
+
+use crate::bus::BusPart;
+use crate::cache::LibraryCache;
+use crate::component::ComponentVariantReference;
+use crate::library::LibraryHolder;
+use crate::schematic::{
+    split_designator, Schematic, SchematicAction, SchematicSelection, TextOnPage,
+};
+
+/// The first line of a fragment on the clipboard, which tells a fragment apart from other text
+const FRAGMENT_HEADER: &str = "electronics_design schematic fragment";
+
+/// How far each paste is moved from where the objects were copied, so pasted objects do not hide the originals
+pub const PASTE_OFFSET: crate::general::Coordinates =
+    crate::general::Coordinates::from_mils(500, -500);
+
+/// Objects copied from a schematic
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct SchematicFragment {
+    /// The placed components
+    pub syms: Vec<ComponentVariantReference>,
+    /// The text
+    pub texts: Vec<TextOnPage>,
+    /// The bus wires, bus entries and bus labels
+    #[serde(default)]
+    pub bus: Vec<BusPart>,
+    /// Copies of the components and symbols used by the placed components
+    pub cache: LibraryCache,
+}
+
+impl SchematicFragment {
+    /// Copy the selected objects of a schematic, None when nothing that exists is selected
+    pub fn copy(
+        sch: &Schematic,
+        selection: &[SchematicSelection],
+        libs: &HashMap<String, LibraryHolder>,
+    ) -> Option<Self> {
+        let mut syms = Vec::new();
+        let mut texts = Vec::new();
+        let mut bus = Vec::new();
+        for s in selection.iter().filter(|s| s.is_valid(sch)) {
+            match s {
+                SchematicSelection::Text { page, textnum } => {
+                    texts.push(sch.pages[*page].texts[*textnum].clone())
+                }
+                SchematicSelection::Symbol { page, sym } => {
+                    syms.push(sch.pages[*page].syms[*sym].clone())
+                }
+                SchematicSelection::Bus { page, partnum } => {
+                    bus.push(sch.pages[*page].bus[*partnum].clone())
+                }
+            }
+        }
+        if syms.is_empty() && texts.is_empty() && bus.is_empty() {
+            return None;
+        }
+        // Starting from the cache of the schematic keeps the copies it has, even when the library is not loaded
+        let mut cache = sch.cache.clone();
+        cache.refresh(syms.iter(), std::iter::empty(), libs);
+        Some(Self {
+            syms,
+            texts,
+            bus,
+            cache,
+        })
+    }
+
+    /// The fragment as text for the clipboard
+    pub fn to_clipboard(&self) -> Option<String> {
+        let json = serde_json::to_string(self).ok()?;
+        Some(format!("{}\n{}", FRAGMENT_HEADER, json))
+    }
+
+    /// Read a fragment from the text on the clipboard, None when the text is not a fragment
+    pub fn from_clipboard(text: &str) -> Option<Self> {
+        let (header, json) = text.split_once('\n')?;
+        if header.trim_end() != FRAGMENT_HEADER {
+            return None;
+        }
+        serde_json::from_str(json).ok()
+    }
+
+    /// The action that pastes the fragment onto a page of a schematic, moved by offset.
+    /// Designators already used in the schematic are changed to the next free number, keeping units of the same part together.
+    /// Copies of components and symbols the schematic does not have are added to it.
+    pub fn paste_action(
+        mut self,
+        sch: &Schematic,
+        pagenum: usize,
+        offset: crate::general::Coordinates,
+    ) -> SchematicAction {
+        let mut used: Vec<(String, u32)> = sch
+            .placed()
+            .filter_map(|(_, _, s)| s.designator.as_deref().and_then(split_designator))
+            .collect();
+        let mut renamed: HashMap<String, String> = HashMap::new();
+        for s in &mut self.syms {
+            let old = if let Some(d) = &s.designator {
+                d.clone()
+            } else {
+                continue;
+            };
+            if let Some(new) = renamed.get(&old) {
+                s.designator = Some(new.clone());
+                continue;
+            }
+            let (prefix, number) = if let Some(p) = split_designator(&old) {
+                p
+            } else {
+                continue;
+            };
+            let mut newnumber = number;
+            while used.iter().any(|(p, n)| *p == prefix && *n == newnumber) {
+                newnumber += 1;
+            }
+            used.push((prefix.clone(), newnumber));
+            let new = format!("{}{}", prefix, newnumber);
+            renamed.insert(old, new.clone());
+            s.designator = Some(new);
+        }
+
+        let mut actions = Vec::new();
+        for (libname, lib) in self.cache.libraries {
+            let have = sch.cache.libraries.get(&libname);
+            for (comname, com) in lib.components {
+                if !have.map_or(false, |l| l.components.contains_key(&comname)) {
+                    actions.push(SchematicAction::UpdateCachedComponent {
+                        libname: libname.clone(),
+                        comname,
+                        component: Some(com),
+                    });
+                }
+            }
+            for (symname, sym) in lib.syms {
+                if !have.map_or(false, |l| l.syms.contains_key(&symname)) {
+                    actions.push(SchematicAction::UpdateCachedSymbol {
+                        libname: libname.clone(),
+                        symname,
+                        symbol: Some(sym),
+                    });
+                }
+            }
+        }
+        for mut var in self.syms {
+            var.pos += offset;
+            actions.push(SchematicAction::AddComponentVariant { pagenum, var });
+        }
+        for mut text in self.texts {
+            text.location += offset;
+            actions.push(SchematicAction::CreateText { pagenum, text });
+        }
+        for part in self.bus {
+            let part = part.map_points(|c| c + offset);
+            actions.push(SchematicAction::CreateBusPart { pagenum, part });
+        }
+        SchematicAction::Compound { actions }
+    }
+}
@@ -1,31 +1,188 @@
 //! This module stores general usage items.
 
 use egui_multiwin::egui;
-/// interacts with ui elements (rectangles), combining them all into a single response
-pub fn respond(ui: &mut egui::Ui, id: String, rects: Vec<egui::Rect>) -> egui::Response {
-    let mut resp = ui.interact(
-        rects[0],
-        egui::Id::from(format!("{}.{}", id, 0)),
-        egui::Sense {
-            click: true,
-            drag: true,
-            focusable: true,
-        },
-    );
-    for (num, r) in rects.iter().skip(1).enumerate() {
-        let num = num + 1;
-        let resp2 = ui.interact(
-            *r,
-            egui::Id::from(format!("{}.{}", id, num)),
+/// interacts with ui elements (areas of the screen), combining them all into a single response.
+/// An area only takes clicks and drags when the pointer is inside its shape, not just inside the box around it.
+pub fn respond<A: Into<crate::geometry::HitArea>>(
+    ui: &mut egui::Ui,
+    id: String,
+    areas: Vec<A>,
+) -> egui::Response {
+    let hover = ui
+        .input(|i| i.pointer.hover_pos())
+        .map(crate::geometry::Point::from);
+    let mut resp: Option<egui::Response> = None;
+    for (num, a) in areas.into_iter().enumerate() {
+        let a = a.into();
+        let aid = egui::Id::from(format!("{}.{}", id, num));
+        let sense = if hover.map_or(false, |p| a.contains(p)) || ui.ctx().is_being_dragged(aid) {
             egui::Sense {
                 click: true,
                 drag: true,
                 focusable: true,
-            },
+            }
+        } else {
+            egui::Sense::hover()
+        };
+        let resp2 = ui.interact(a.bounding_box().to_rect(), aid, sense);
+        resp = Some(match resp {
+            Some(r) => r.union(resp2),
+            None => resp2,
+        });
+    }
+    resp.unwrap_or_else(|| {
+        ui.interact(
+            egui::Rect::NOTHING,
+            egui::Id::from(id),
+            egui::Sense::hover(),
+        )
+    })
+}
+
+/// Change a selection for a click on an item. Shift or ctrl clicking adds the item, or removes it when it is already selected.
+/// A plain click selects only the item.
+pub fn click_select<T: PartialEq>(selection: &mut Vec<T>, item: T, modifiers: egui::Modifiers) {
+    if modifiers.shift || modifiers.command {
+        if let Some(i) = selection.iter().position(|s| *s == item) {
+            selection.remove(i);
+        } else {
+            selection.push(item);
+        }
+    } else {
+        selection.clear();
+        selection.push(item);
+    }
+}
+
+/// A box dragged out over empty space to select objects
+#[derive(Clone, Copy)]
+pub struct RubberBand {
+    /// The area of the box on the screen
+    pub rect: egui::Rect,
+    /// True when dragged from left to right, which selects only objects entirely inside the box.
+    /// Dragged from right to left, any object the box touches is selected.
+    pub window: bool,
+}
+
+impl RubberBand {
+    /// Track a box being dragged on background, drawing it with pntr. Returns the box when the drag finishes.
+    pub fn track(ui: &egui::Ui, background: &egui::Response, pntr: &egui::Painter) -> Option<Self> {
+        let id = background.id.with("rubber band");
+        if background.drag_started_by(egui::PointerButton::Primary) {
+            if let Some(start) = ui.input(|i| i.pointer.press_origin()) {
+                ui.data_mut(|d| d.insert_temp(id, start));
+            }
+        }
+        let start: egui::Pos2 = ui.data(|d| d.get_temp(id))?;
+        let end = ui.input(|i| i.pointer.interact_pos()).unwrap_or(start);
+        let band = Self {
+            rect: egui::Rect::from_two_pos(start, end),
+            window: end.x >= start.x,
+        };
+        if background.drag_stopped() || !background.dragged() {
+            ui.data_mut(|d| d.remove::<egui::Pos2>(id));
+            return Some(band).filter(|_| background.drag_stopped());
+        }
+        let color = ui.visuals().selection.stroke.color;
+        pntr.rect_filled(band.rect, 0.0, color.gamma_multiply(0.1));
+        pntr.rect_stroke(
+            band.rect,
+            0.0,
+            egui::Stroke::new(if band.window { 1.5 } else { 0.5 }, color),
         );
-        resp = resp.union(resp2);
+        None
+    }
+
+    /// Returns true when an object covering the given areas of the screen is selected by the box
+    pub fn selects(&self, areas: &[crate::geometry::HitArea]) -> bool {
+        let band = crate::geometry::BoundingBox::from(self.rect);
+        if self.window {
+            !areas.is_empty()
+                && areas.iter().all(|a| {
+                    let b = a.bounding_box();
+                    band.contains(b.min) && band.contains(b.max)
+                })
+        } else {
+            areas.iter().any(|a| a.intersects(&band))
+        }
+    }
+}
+
+/// Draw the outline of a selected object, made up of the given areas of the screen
+pub fn draw_selected(ui: &egui::Ui, pntr: &egui::Painter, areas: &[crate::geometry::HitArea]) {
+    if let Some(b) = areas
+        .iter()
+        .map(|a| a.bounding_box())
+        .reduce(|a, b| a.union(&b))
+    {
+        pntr.rect_stroke(b.expand(2.0).to_rect(), 2.0, ui.visuals().selection.stroke);
+    }
+}
+
+/// Which edge of a group of objects to line them up on
+#[derive(Clone, Copy, PartialEq)]
+pub enum Alignment {
+    /// Line up on the leftmost object
+    Left,
+    /// Line up on the rightmost object
+    Right,
+    /// Line up on the highest object
+    Top,
+    /// Line up on the lowest object
+    Bottom,
+}
+
+impl std::fmt::Display for Alignment {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Left => write!(f, "Align left"),
+            Self::Right => write!(f, "Align right"),
+            Self::Top => write!(f, "Align top"),
+            Self::Bottom => write!(f, "Align bottom"),
+        }
+    }
+}
+
+impl Alignment {
+    /// All of the alignments, in the order they are offered to the user
+    pub const ALL: [Alignment; 4] = [Self::Left, Self::Right, Self::Top, Self::Bottom];
+
+    /// The amount to move each of the given locations so that they line up
+    pub fn deltas(&self, locations: &[Coordinates]) -> Vec<Coordinates> {
+        let pick = |f: fn(i64, i64) -> i64, get: fn(&Coordinates) -> i64| {
+            locations.iter().map(get).reduce(f).unwrap_or_default()
+        };
+        match self {
+            Self::Left => {
+                let x = pick(i64::min, |p| p.x);
+                locations
+                    .iter()
+                    .map(|p| Coordinates::from_nm(x - p.x, 0))
+                    .collect()
+            }
+            Self::Right => {
+                let x = pick(i64::max, |p| p.x);
+                locations
+                    .iter()
+                    .map(|p| Coordinates::from_nm(x - p.x, 0))
+                    .collect()
+            }
+            Self::Top => {
+                let y = pick(i64::max, |p| p.y);
+                locations
+                    .iter()
+                    .map(|p| Coordinates::from_nm(0, y - p.y))
+                    .collect()
+            }
+            Self::Bottom => {
+                let y = pick(i64::min, |p| p.y);
+                locations
+                    .iter()
+                    .map(|p| Coordinates::from_nm(0, y - p.y))
+                    .collect()
+            }
+        }
     }
-    resp
 }
 
 /// The kinds of file formats that can be used for various files that are exported
@@ -33,6 +190,8 @@ pub fn respond(ui: &mut egui::Ui, id: String, rects: Vec<egui::Rect>) -> egui::R
 pub enum StorageFormat {
     /// The toml format
     Toml,
+    /// The json format, used for talking to library servers
+    Json,
 }
 
 impl Default for StorageFormat {
@@ -56,6 +215,10 @@ impl StorageFormat {
                 Ok(obj) => Ok(writer.write_all(obj.as_bytes())?),
                 Err(e) => Err(StorageSaveError::SerializeError(e.to_string())),
             },
+            Self::Json => match serde_json::to_vec(object) {
+                Ok(obj) => Ok(writer.write_all(&obj)?),
+                Err(e) => Err(StorageSaveError::SerializeError(e.to_string())),
+            },
         }
     }
 
@@ -75,15 +238,18 @@ impl StorageFormat {
                     Err(e) => Err(StorageLoadError::IoError(e)),
                 }
             }
+            Self::Json => Ok(serde_json::from_reader(reader)?),
         }
     }
 }
 
 /// The ways design files can be saved
-#[derive(strum::EnumIter, PartialEq, Clone)]
+#[derive(strum::EnumIter, PartialEq, Clone, serde::Serialize, serde::Deserialize)]
 pub enum StoragePath {
     /// The design file is saved to a file on a local filesystem
     LocalFilesystem(String),
+    /// The design file is stored on a library server, at the given url. See [crate::remote]
+    Remote(String),
 }
 
 impl StoragePath {
@@ -91,6 +257,7 @@ impl StoragePath {
     pub fn display(&self) -> String {
         match self {
             StoragePath::LocalFilesystem(_) => "Local Filesystem".to_string(),
+            StoragePath::Remote(_) => "Library Server".to_string(),
         }
     }
 
@@ -98,6 +265,15 @@ impl StoragePath {
     pub fn open_path(&self) -> Option<std::path::PathBuf> {
         match self {
             StoragePath::LocalFilesystem(p) => Some(std::path::PathBuf::from(p)),
+            StoragePath::Remote(_) => None,
+        }
+    }
+
+    /// The file format that should normally be used with this kind of storage
+    pub fn default_format(&self) -> StorageFormat {
+        match self {
+            StoragePath::LocalFilesystem(_) => StorageFormat::Toml,
+            StoragePath::Remote(_) => StorageFormat::Json,
         }
     }
 }
@@ -109,6 +285,7 @@ impl std::fmt::Display for StoragePath {
             "{}",
             match self {
                 StoragePath::LocalFilesystem(p) => format!("Local Filesystem {}", p),
+                StoragePath::Remote(u) => format!("Library Server {}", u),
             }
         )
     }
@@ -142,6 +319,12 @@ impl From<toml::de::Error> for StorageLoadError {
     }
 }
 
+impl From<serde_json::Error> for StorageLoadError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::DeserializeError(value.to_string())
+    }
+}
+
 impl From<StoragePathError> for StorageLoadError {
     fn from(value: StoragePathError) -> Self {
         Self::StoragePathError(value)
@@ -167,6 +350,14 @@ impl std::fmt::Display for StorageLoadError {
 pub enum StoragePathError {
     /// A generic filesystem error
     IoError(std::io::Error),
+    /// A library server could not be used
+    RemoteError(crate::remote::RemoteError),
+}
+
+impl From<crate::remote::RemoteError> for StoragePathError {
+    fn from(value: crate::remote::RemoteError) -> Self {
+        Self::RemoteError(value)
+    }
 }
 
 impl From<std::io::Error> for StoragePathError {
@@ -182,6 +373,7 @@ impl std::fmt::Display for StoragePathError {
             "{}",
             match self {
                 Self::IoError(e) => e.to_string(),
+                Self::RemoteError(e) => e.to_string(),
             }
         )
     }
@@ -195,6 +387,8 @@ pub enum StorageSaveError {
     SerializeError(String),
     /// A storage path error occurred
     StoragePathError(StoragePathError),
+    /// A library on a library server was saved, when it can only be changed by publishing a new version
+    RemoteSaveError,
 }
 
 impl From<std::io::Error> for StorageSaveError {
@@ -218,61 +412,193 @@ impl std::fmt::Display for StorageSaveError {
                 Self::IoError(e) => e.to_string(),
                 Self::SerializeError(e) => e.clone(),
                 Self::StoragePathError(e) => e.to_string(),
+                Self::RemoteSaveError =>
+                    "A library on a library server is changed by publishing a new version of it"
+                        .to_string(),
             }
         )
     }
 }
 
 impl StoragePath {
-    ///Create a writer for the storage path
-    pub fn writer(&self) -> Result<impl std::io::Write, StoragePathError> {
+    ///Create a writer for the storage path. Nothing is actually stored until the writer is committed.
+    pub fn writer(&self) -> Result<Box<dyn StorageWriter>, StoragePathError> {
         match self {
-            Self::LocalFilesystem(pathname) => {
-                let file = std::fs::OpenOptions::new()
-                    .truncate(true)
-                    .create(true)
-                    .write(true)
-                    .open(pathname)?;
-                Ok(file)
-            }
+            Self::LocalFilesystem(pathname) => Ok(Box::new(AtomicFileWriter::new(pathname)?)),
+            Self::Remote(url) => Ok(Box::new(crate::remote::RemoteWriter::new(url.clone()))),
         }
     }
 
     ///Create a reader for the storage path
-    pub fn reader(&self) -> Result<impl std::io::Read, StoragePathError> {
+    pub fn reader(&self) -> Result<Box<dyn std::io::Read>, StoragePathError> {
         match self {
             Self::LocalFilesystem(pathname) => {
                 let file = std::fs::OpenOptions::new().read(true).open(pathname)?;
-                Ok(file)
+                Ok(Box::new(file))
+            }
+            Self::Remote(url) => Ok(Box::new(crate::remote::get(url)?)),
+        }
+    }
+}
+
+/// A writer for a storage path, that only replaces what is stored when it is committed
+pub trait StorageWriter: std::io::Write {
+    /// Finish writing, replacing whatever was stored before. Dropping the writer without committing leaves the stored data alone.
+    fn commit(self: Box<Self>) -> Result<(), StoragePathError>;
+}
+
+/// The number of backup copies kept when a local file is replaced
+const BACKUP_COUNT: usize = 3;
+
+/// The name of a backup copy of a file, 1 being the most recent
+fn backup_name(path: &std::path::Path, num: usize) -> std::path::PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".{}.bak", num));
+    std::path::PathBuf::from(name)
+}
+
+/// Writes to a temporary file next to the destination, then renames it over the destination when committed.
+/// Previous versions of the destination are kept as rotating backup copies, unless the writer was made without backups.
+pub struct AtomicFileWriter {
+    /// The temporary file being written
+    file: std::fs::File,
+    /// The path of the temporary file
+    temp: std::path::PathBuf,
+    /// The final destination for the file
+    dest: std::path::PathBuf,
+    /// Set once the temporary file has been renamed to the destination
+    committed: bool,
+    /// The number of backup copies of the destination to keep
+    backups: usize,
+}
+
+impl AtomicFileWriter {
+    /// Create a writer for the given destination file
+    pub fn new(dest: &str) -> Result<Self, std::io::Error> {
+        Self::with_backups(dest, BACKUP_COUNT)
+    }
+
+    /// Create a writer for the given destination file that does not keep backup copies of what it replaces
+    pub fn without_backups(dest: &str) -> Result<Self, std::io::Error> {
+        Self::with_backups(dest, 0)
+    }
+
+    /// Create a writer for the given destination file that keeps the given number of backup copies
+    fn with_backups(dest: &str, backups: usize) -> Result<Self, std::io::Error> {
+        let dest = std::path::PathBuf::from(dest);
+        let mut temp = dest.as_os_str().to_owned();
+        temp.push(".tmp");
+        let temp = std::path::PathBuf::from(temp);
+        let file = std::fs::OpenOptions::new()
+            .truncate(true)
+            .create(true)
+            .write(true)
+            .open(&temp)?;
+        Ok(Self {
+            file,
+            temp,
+            dest,
+            committed: false,
+            backups,
+        })
+    }
+}
+
+impl std::io::Write for AtomicFileWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.file.write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
+    }
+}
+
+impl StorageWriter for AtomicFileWriter {
+    fn commit(mut self: Box<Self>) -> Result<(), StoragePathError> {
+        use std::io::Write;
+        self.file.flush()?;
+        self.file.sync_all()?;
+        if self.backups > 0 && self.dest.exists() {
+            for i in (1..self.backups).rev() {
+                let older = backup_name(&self.dest, i);
+                if older.exists() {
+                    std::fs::rename(&older, backup_name(&self.dest, i + 1))?;
+                }
             }
+            std::fs::copy(&self.dest, backup_name(&self.dest, 1))?;
+        }
+        std::fs::rename(&self.temp, &self.dest)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for AtomicFileWriter {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = std::fs::remove_file(&self.temp);
         }
     }
 }
 
-/// Coordinates that can be used in the program
-#[derive(serde::Serialize, serde::Deserialize, Copy, Clone)]
+/// The number of nanometers in an inch
+pub const NM_PER_INCH: i64 = 25_400_000;
+
+/// The number of nanometers in a millimeter
+pub const NM_PER_MM: i64 = 1_000_000;
+
+/// Round a distance given in a unit with per_unit nanometers to whole nanometers
+fn to_nm(v: f32, per_unit: i64) -> i64 {
+    (v as f64 * per_unit as f64).round() as i64
+}
+
+/// Coordinates that can be used in the program, in whole nanometers. Adding and subtracting them is exact,
+/// so moving an object and undoing the move always puts it back where it was.
+/// Inches and millimeters are only used for showing and entering coordinates.
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, PartialEq, Eq, Default, Debug)]
+#[serde(from = "StoredCoordinates", into = "StoredCoordinates")]
+pub struct Coordinates {
+    /// The x coordinate in nanometers, increasing to the right
+    pub x: i64,
+    /// The y coordinate in nanometers, increasing upwards
+    pub y: i64,
+}
+
+/// Coordinates as they are stored in files. Files written before coordinates were whole nanometers
+/// have inches or millimeters, which are rounded to the nearest nanometer when loaded.
+#[derive(serde::Serialize, serde::Deserialize)]
 #[serde(tag = "type", content = "args")]
-pub enum Coordinates {
+enum StoredCoordinates {
     /// Imperial inches. Specified in fractional inches
     Inches(f32, f32),
     /// Metric millimeters. Units are specified in fractional millimeters
     Millimeters(f32, f32),
+    /// Whole nanometers
+    Nanometers(i64, i64),
+}
+
+impl From<StoredCoordinates> for Coordinates {
+    fn from(c: StoredCoordinates) -> Self {
+        match c {
+            StoredCoordinates::Inches(x, y) => Self::from_inches(x, y),
+            StoredCoordinates::Millimeters(x, y) => Self::from_mm(x, y),
+            StoredCoordinates::Nanometers(x, y) => Self::from_nm(x, y),
+        }
+    }
+}
+
+impl From<Coordinates> for StoredCoordinates {
+    fn from(c: Coordinates) -> Self {
+        Self::Nanometers(c.x, c.y)
+    }
 }
 
 impl std::ops::Sub for Coordinates {
     type Output = Coordinates;
 
     fn sub(self, rhs: Self) -> Self::Output {
-        match self {
-            Self::Inches(x, y) => match rhs {
-                Self::Inches(x2, y2) => Self::Inches(x - x2, y - y2),
-                Self::Millimeters(x2, y2) => Self::Inches(x - x2 * 25.4, y - y2 * 25.4),
-            },
-            Self::Millimeters(x, y) => match rhs {
-                Self::Inches(x2, y2) => Self::Millimeters(x - x2 * 25.4, y - y2 * 25.4),
-                Self::Millimeters(x2, y2) => Self::Millimeters(x - x2, y - y2),
-            },
-        }
+        Self::from_nm(self.x - rhs.x, self.y - rhs.y)
     }
 }
 
@@ -292,44 +618,55 @@ impl std::ops::Add for Coordinates {
     type Output = Coordinates;
 
     fn add(self, rhs: Self) -> Self::Output {
-        match self {
-            Self::Inches(x, y) => match rhs {
-                Self::Inches(x2, y2) => Self::Inches(x + x2, y + y2),
-                Self::Millimeters(x2, y2) => Self::Inches(x + x2 * 25.4, y + y2 * 25.4),
-            },
-            Self::Millimeters(x, y) => match rhs {
-                Self::Inches(x2, y2) => Self::Millimeters(x + x2 * 25.4, y + y2 * 25.4),
-                Self::Millimeters(x2, y2) => Self::Millimeters(x + x2, y + y2),
-            },
-        }
+        Self::from_nm(self.x + rhs.x, self.y + rhs.y)
     }
 }
 
 impl Coordinates {
+    /// Coordinates from whole nanometers
+    pub const fn from_nm(x: i64, y: i64) -> Self {
+        Self { x, y }
+    }
+    /// Coordinates from whole thousandths of an inch
+    pub const fn from_mils(x: i64, y: i64) -> Self {
+        Self::from_nm(x * (NM_PER_INCH / 1000), y * (NM_PER_INCH / 1000))
+    }
+    /// Coordinates from fractional inches, rounded to the nearest nanometer
+    pub fn from_inches(x: f32, y: f32) -> Self {
+        Self::from_nm(to_nm(x, NM_PER_INCH), to_nm(y, NM_PER_INCH))
+    }
+    /// Coordinates from fractional millimeters, rounded to the nearest nanometer
+    pub fn from_mm(x: f32, y: f32) -> Self {
+        Self::from_nm(to_nm(x, NM_PER_MM), to_nm(y, NM_PER_MM))
+    }
     /// Get the coordinates in millimeters
     pub fn get_mm(&self) -> (f32, f32) {
-        match self {
-            Self::Inches(x, y) => (x * 25.4, y * 25.4),
-            Self::Millimeters(x, y) => (*x, *y),
-        }
+        (
+            (self.x as f64 / NM_PER_MM as f64) as f32,
+            (self.y as f64 / NM_PER_MM as f64) as f32,
+        )
     }
     /// Get the coordinates in inches
     pub fn get_inches(&self) -> (f32, f32) {
-        match self {
-            Self::Inches(x, y) => (*x, *y),
-            Self::Millimeters(x, y) => (x / 25.4, y / 25.4),
-        }
+        (
+            (self.x as f64 / NM_PER_INCH as f64) as f32,
+            (self.y as f64 / NM_PER_INCH as f64) as f32,
+        )
     }
     /// Get coordinates according to the specified units
     pub fn get_units(&self, units: DisplayMode) -> (f32, f32) {
-        match units {
-            DisplayMode::Inches => self.get_inches(),
-            DisplayMode::Millimeters => self.get_mm(),
-        }
+        (
+            (self.x as f64 / units.nanometers() as f64) as f32,
+            (self.y as f64 / units.nanometers() as f64) as f32,
+        )
     }
     /// Get coordinates from screen position
     pub fn from_pos2(pos2: egui_multiwin::egui::Pos2, zoom: f32) -> Self {
-        Self::Inches(pos2.x / zoom, pos2.y * -1.0 / zoom)
+        let to_mm = crate::geometry::Transform::screen(zoom, egui_multiwin::egui::Pos2::ZERO)
+            .inverse()
+            .unwrap_or_default();
+        let p = to_mm.apply(pos2.into());
+        Self::from_mm(p.x, p.y)
     }
     /// Convert coordinates to screen position in pixels
     pub fn get_pos2(
@@ -337,78 +674,290 @@ impl Coordinates {
         zoom: f32,
         zoom_center: egui_multiwin::egui::Pos2,
     ) -> egui_multiwin::egui::Pos2 {
-        match self {
-            Self::Inches(x, y) => {
-                egui_multiwin::egui::pos2(*x * zoom, *y * -zoom) + zoom_center.to_vec2()
-            }
-            Self::Millimeters(x, y) => {
-                egui_multiwin::egui::pos2(zoom * x / 25.4, -zoom * y / 25.4) + zoom_center.to_vec2()
-            }
+        crate::geometry::Transform::screen(zoom, zoom_center)
+            .apply((*self).into())
+            .to_pos2()
+    }
+    /// The move that puts the x coordinate at x nanometers
+    pub fn delta_to_x(&self, x: i64) -> Self {
+        Self::from_nm(x - self.x, 0)
+    }
+    /// The move that puts the y coordinate at y nanometers
+    pub fn delta_to_y(&self, y: i64) -> Self {
+        Self::from_nm(0, y - self.y)
+    }
+    /// Are both coordinates exactly 0?
+    pub fn is_zero(&self) -> bool {
+        self.x == 0 && self.y == 0
+    }
+}
+
+/// How a placed item is turned. The item is mirrored first, then rotated counterclockwise in steps of 90 degrees.
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, PartialEq, Default)]
+pub struct Orientation {
+    /// The number of quarter turns counterclockwise, from 0 to 3
+    #[serde(default)]
+    pub rotation: u8,
+    /// The item is mirrored left to right
+    #[serde(default)]
+    pub mirror_x: bool,
+    /// The item is mirrored top to bottom
+    #[serde(default)]
+    pub mirror_y: bool,
+}
+
+impl Orientation {
+    /// The orientation after turning a quarter turn counterclockwise
+    pub fn rotated(self) -> Self {
+        Self {
+            rotation: (self.rotation + 1) % 4,
+            ..self
         }
     }
-    /// Are both coordinates effectively 0?
-    pub fn less_than_epsilon(&self) -> bool {
-        match self {
-            Self::Inches(x, y) => *x < f32::EPSILON && *y < f32::EPSILON,
-            Self::Millimeters(x, y) => *x < f32::EPSILON && *y < f32::EPSILON,
+
+    /// The orientation after mirroring left to right, as seen on the page
+    pub fn mirrored_x(self) -> Self {
+        Self {
+            rotation: (4 - self.rotation % 4) % 4,
+            mirror_x: !self.mirror_x,
+            ..self
         }
     }
-    /// Did the x value change at all from the given value?
-    pub fn changed_x(&self, p: f32) -> bool {
-        match self {
-            Self::Inches(x, _y) => (*x - p).abs() > f32::EPSILON,
-            Self::Millimeters(x, _y) => (*x - p).abs() > f32::EPSILON,
+
+    /// The orientation after mirroring top to bottom, as seen on the page
+    pub fn mirrored_y(self) -> Self {
+        Self {
+            rotation: (4 - self.rotation % 4) % 4,
+            mirror_y: !self.mirror_y,
+            ..self
         }
     }
-    /// Did the y value change at all from the given value?
-    pub fn changed_y(&self, p: f32) -> bool {
-        match self {
-            Self::Inches(_x, y) => (*y - p).abs() > f32::EPSILON,
-            Self::Millimeters(_x, y) => (*y - p).abs() > f32::EPSILON,
+
+    /// Turn an offset from the origin of the item, with y pointing up
+    pub fn apply<T: std::ops::Neg<Output = T>>(&self, x: T, y: T) -> (T, T) {
+        let x = if self.mirror_x { -x } else { x };
+        let y = if self.mirror_y { -y } else { y };
+        match self.rotation % 4 {
+            0 => (x, y),
+            1 => (-y, x),
+            2 => (-x, -y),
+            _ => (y, -x),
         }
     }
+
+    /// Turn coordinates relative to the origin of the item
+    pub fn transform(&self, c: Coordinates) -> Coordinates {
+        let (x, y) = self.apply(c.x, c.y);
+        Coordinates::from_nm(x, y)
+    }
 }
 
-/// A single dimension value of length
-#[derive(serde::Serialize, serde::Deserialize, Clone)]
-pub enum Length {
+/// A single dimension value of length, in whole nanometers
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, PartialEq, Eq, Default, Debug)]
+#[serde(from = "StoredLength", into = "StoredLength")]
+pub struct Length(pub i64);
+
+/// A length as it is stored in files. Files written before lengths were whole nanometers
+/// have inches or millimeters, which are rounded to the nearest nanometer when loaded.
+#[derive(serde::Serialize, serde::Deserialize)]
+enum StoredLength {
     /// Imperial inches. Specified in fractional inches
     Inches(f32),
     /// Metric millimeters. Units are specified in fractional millimeters
     Millimeters(f32),
+    /// Whole nanometers
+    Nanometers(i64),
+}
+
+impl From<StoredLength> for Length {
+    fn from(l: StoredLength) -> Self {
+        match l {
+            StoredLength::Inches(i) => Self::from_inches(i),
+            StoredLength::Millimeters(mm) => Self::from_mm(mm),
+            StoredLength::Nanometers(nm) => Self(nm),
+        }
+    }
+}
+
+impl From<Length> for StoredLength {
+    fn from(l: Length) -> Self {
+        Self::Nanometers(l.0)
+    }
 }
 
 impl Length {
+    /// A length from whole thousandths of an inch
+    pub const fn from_mils(mils: i64) -> Self {
+        Self(mils * (NM_PER_INCH / 1000))
+    }
+    /// A length from fractional inches, rounded to the nearest nanometer
+    pub fn from_inches(i: f32) -> Self {
+        Self(to_nm(i, NM_PER_INCH))
+    }
+    /// A length from fractional millimeters, rounded to the nearest nanometer
+    pub fn from_mm(mm: f32) -> Self {
+        Self(to_nm(mm, NM_PER_MM))
+    }
     /// Get the millimeters of this length
     pub fn get_mm(&self) -> f32 {
-        match self {
-            Self::Inches(i) => i * 25.4,
-            Self::Millimeters(mm) => *mm,
-        }
+        (self.0 as f64 / NM_PER_MM as f64) as f32
     }
     /// Get the inches of this length
     pub fn get_inches(&self) -> f32 {
-        match self {
-            Self::Inches(i) => *i,
-            Self::Millimeters(mm) => mm / 25.4,
-        }
+        (self.0 as f64 / NM_PER_INCH as f64) as f32
     }
     /// Convert the length to screen units
     pub fn get_screen(&self, zoom: f32, _zoom_center: egui_multiwin::egui::Pos2) -> f32 {
-        match self {
-            Self::Inches(i) => *i * zoom,
-            Self::Millimeters(mm) => zoom * *mm / 25.4,
-        }
+        self.get_inches() * zoom
     }
 }
 
 /// The units mode for the program
-#[derive(Copy, Clone)]
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, PartialEq)]
 pub enum DisplayMode {
     /// Imperial inches
     Inches,
     /// Standard millimeters
     Millimeters,
+    /// Thousandths of an inch
+    Mils,
+    /// Thousandths of a millimeter
+    Micrometers,
+}
+
+impl std::fmt::Display for DisplayMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Inches => write!(f, "in"),
+            Self::Millimeters => write!(f, "mm"),
+            Self::Mils => write!(f, "mil"),
+            Self::Micrometers => write!(f, "µm"),
+        }
+    }
+}
+
+impl Default for DisplayMode {
+    fn default() -> Self {
+        Self::Inches
+    }
+}
+
+impl DisplayMode {
+    /// All of the units, in the order they are offered to the user
+    pub const ALL: [DisplayMode; 4] = [
+        Self::Inches,
+        Self::Mils,
+        Self::Millimeters,
+        Self::Micrometers,
+    ];
+
+    /// The number of nanometers in one of these units
+    pub fn nanometers(&self) -> i64 {
+        match self {
+            Self::Inches => NM_PER_INCH,
+            Self::Millimeters => NM_PER_MM,
+            Self::Mils => NM_PER_INCH / 1000,
+            Self::Micrometers => NM_PER_MM / 1000,
+        }
+    }
+
+    /// The number of decimal places shown for values in these units
+    pub fn decimals(&self) -> usize {
+        match self {
+            Self::Inches | Self::Millimeters => 4,
+            Self::Mils | Self::Micrometers => 1,
+        }
+    }
+
+    /// Show a distance in nanometers in these units, with the unit after it
+    pub fn format(&self, nm: i64) -> String {
+        format!(
+            "{:.*}{}",
+            self.decimals(),
+            nm as f64 / self.nanometers() as f64,
+            self
+        )
+    }
+}
+
+/// Read a distance typed by the user, returning it in nanometers. The value is a decimal number or a fraction
+/// such as 1/16, followed by in, ", mil, mm, um or µm. A value without units is in the given units.
+pub fn parse_length(text: &str, units: DisplayMode) -> Option<i64> {
+    let text = text.trim();
+    let (number, suffix) = text.split_at(
+        text.find(|c: char| c.is_alphabetic() || c == '"')
+            .unwrap_or(text.len()),
+    );
+    let units = match suffix.trim().to_lowercase().as_str() {
+        "" => units,
+        "in" | "inch" | "inches" | "\"" => DisplayMode::Inches,
+        "mm" => DisplayMode::Millimeters,
+        "mil" | "mils" | "thou" => DisplayMode::Mils,
+        "um" | "µm" | "μm" | "micron" | "microns" => DisplayMode::Micrometers,
+        _ => return None,
+    };
+    let value = match number.split_once('/') {
+        Some((n, d)) => n.trim().parse::<f64>().ok()? / d.trim().parse::<f64>().ok()?,
+        None => number.trim().parse::<f64>().ok()?,
+    };
+    value
+        .is_finite()
+        .then(|| (value * units.nanometers() as f64).round() as i64)
+}
+
+/// A field for entering a distance in nanometers, shown in the given units. Any of the units can be typed, see [parse_length].
+/// Returns the new distance when the user finishes editing with a valid distance different from value. Leaving the field
+/// without changing its text returns None, so that value is not rounded to the places shown.
+pub fn length_edit(
+    ui: &mut egui::Ui,
+    id_source: impl std::hash::Hash,
+    value: i64,
+    units: DisplayMode,
+) -> Option<i64> {
+    let id = ui.make_persistent_id(id_source);
+    let shown = units.format(value);
+    let mut text = ui
+        .data(|d| d.get_temp::<String>(id.with("text")))
+        .unwrap_or_else(|| shown.clone());
+    let valid = parse_length(&text, units).is_some();
+    let mut edit = egui::TextEdit::singleline(&mut text)
+        .id(id)
+        .desired_width(100.0);
+    if !valid {
+        edit = edit.text_color(ui.visuals().error_fg_color);
+    }
+    let response = ui.add(edit);
+    if response.has_focus() {
+        ui.data_mut(|d| d.insert_temp(id.with("text"), text));
+        None
+    } else {
+        ui.data_mut(|d| d.remove::<String>(id.with("text")));
+        (response.lost_focus() && text != shown)
+            .then(|| parse_length(&text, units))
+            .flatten()
+            .filter(|v| *v != value)
+    }
+}
+
+/// A single line field for editing text. Returns the new text when the user finishes editing it with text different
+/// from value, so that typing a word is one change instead of one for each letter.
+pub fn text_edit(
+    ui: &mut egui::Ui,
+    id_source: impl std::hash::Hash,
+    value: &str,
+) -> Option<String> {
+    let id = ui.make_persistent_id(id_source);
+    let mut text = ui
+        .data(|d| d.get_temp::<String>(id.with("text")))
+        .unwrap_or_else(|| value.to_string());
+    let response = ui.add(egui::TextEdit::singleline(&mut text).id(id));
+    if response.has_focus() {
+        ui.data_mut(|d| d.insert_temp(id.with("text"), text));
+        None
+    } else {
+        ui.data_mut(|d| d.remove::<String>(id.with("text")));
+        (response.lost_focus() && text != value).then(|| text)
+    }
 }
 
 /// The various modes of interpreting colors for the system
@@ -420,3 +969,104 @@ pub enum ColorMode {
     /// The colors for printing to pdf
     PrintingMode,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn add_and_subtract_mixed_units() {
+        let inch = Coordinates::from_inches(1.0, -1.0);
+        let mm = Coordinates::from_mm(25.4, -25.4);
+        assert_eq!(inch + mm, Coordinates::from_inches(2.0, -2.0));
+        assert_eq!(mm + inch, Coordinates::from_nm(50_800_000, -50_800_000));
+        assert!((inch - mm).is_zero());
+        assert_eq!(
+            inch - Coordinates::from_mm(1.0, 0.0),
+            Coordinates::from_mm(24.4, -25.4)
+        );
+    }
+
+    #[test]
+    fn negative_moves_are_not_zero() {
+        assert!(!Coordinates::from_mm(-1.0, 0.0).is_zero());
+        assert!(!Coordinates::from_mm(0.0, -1.0).is_zero());
+        assert!(!Coordinates::from_nm(-1, -1).is_zero());
+        assert!(Coordinates::from_mm(0.0, -0.0).is_zero());
+    }
+
+    /// A value stored in a toml file, which needs a table at the top
+    #[derive(serde::Deserialize)]
+    struct Stored {
+        /// A position
+        at: Coordinates,
+        /// A size
+        size: Length,
+    }
+
+    #[test]
+    fn load_old_json_units() {
+        let c: Coordinates =
+            serde_json::from_str(r#"{"type":"Inches","args":[0.5,-2.0]}"#).unwrap();
+        assert_eq!(c, Coordinates::from_nm(12_700_000, -50_800_000));
+        let c: Coordinates =
+            serde_json::from_str(r#"{"type":"Millimeters","args":[2.5,-0.25]}"#).unwrap();
+        assert_eq!(c, Coordinates::from_nm(2_500_000, -250_000));
+        let l: Length = serde_json::from_str(r#"{"Inches":0.25}"#).unwrap();
+        assert_eq!(l, Length(6_350_000));
+        let l: Length = serde_json::from_str(r#"{"Millimeters":1.5}"#).unwrap();
+        assert_eq!(l, Length(1_500_000));
+    }
+
+    #[test]
+    fn load_old_toml_units() {
+        let s: Stored = toml::from_str(
+            r#"
+            at = { type = "Inches", args = [0.5, -2.0] }
+            size = { Millimeters = 1.5 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(s.at, Coordinates::from_nm(12_700_000, -50_800_000));
+        assert_eq!(s.size, Length(1_500_000));
+        let s: Stored = toml::from_str(
+            r#"
+            at = { type = "Millimeters", args = [2.5, -0.25] }
+            size = { Inches = 0.25 }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(s.at, Coordinates::from_nm(2_500_000, -250_000));
+        assert_eq!(s.size, Length(6_350_000));
+    }
+
+    #[test]
+    fn saved_units_load_unchanged() {
+        let c = Coordinates::from_nm(123_456_789, -987_654_321);
+        let json = serde_json::to_string(&c).unwrap();
+        assert_eq!(serde_json::from_str::<Coordinates>(&json).unwrap(), c);
+    }
+
+    #[test]
+    fn undoing_moves_restores_exact_positions() {
+        let mut sch = crate::schematic::Schematic::new_example();
+        let mut log = crate::journal::JournaledRecord::new();
+        let start = sch.pages[0].texts[0].location;
+        for delta in [
+            Coordinates::from_mm(0.1, -0.3),
+            Coordinates::from_inches(0.7, 0.01),
+        ] {
+            log.apply(
+                &mut sch,
+                crate::schematic::SchematicAction::MoveText {
+                    pagenum: 0,
+                    textnum: 0,
+                    delta,
+                },
+            );
+        }
+        assert_ne!(sch.pages[0].texts[0].location, start);
+        while log.undo(&mut sch).is_some() {}
+        assert_eq!(sch.pages[0].texts[0].location, start);
+    }
+}
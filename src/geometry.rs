@@ -0,0 +1,502 @@
+//! Geometry shared by everything that draws or hit-tests schematics and symbols, so that what is drawn on the screen
+//! and in a pdf comes from the same numbers. Shapes are described in millimeters with y pointing up, and a transform
+//! takes them to where they are drawn, such as a pdf page or the screen.
+
+use egui_multiwin::egui;
+
+use crate::general::{Coordinates, Orientation};
+
+/// A point, or the offset between two points
+#[derive(Copy, Clone, PartialEq, Debug, Default)]
+pub struct Point {
+    /// The x coordinate
+    pub x: f32,
+    /// The y coordinate
+    pub y: f32,
+}
+
+impl Point {
+    /// A point at x, y
+    pub const fn new(x: f32, y: f32) -> Self {
+        Self { x, y }
+    }
+
+    /// The distance from this point to another
+    pub fn distance(self, other: Point) -> f32 {
+        let d = other - self;
+        (d.x * d.x + d.y * d.y).sqrt()
+    }
+
+    /// The point as a position on the screen, for a point that has been transformed to the screen
+    pub fn to_pos2(self) -> egui::Pos2 {
+        egui::pos2(self.x, self.y)
+    }
+
+    /// The point as a position on a pdf page, for a point in millimeters
+    pub fn to_pdf(self) -> printpdf::Point {
+        printpdf::Point::new(printpdf::Mm(self.x.into()), printpdf::Mm(self.y.into()))
+    }
+}
+
+impl From<Coordinates> for Point {
+    fn from(c: Coordinates) -> Self {
+        let (x, y) = c.get_mm();
+        Self { x, y }
+    }
+}
+
+impl From<(f32, f32)> for Point {
+    fn from((x, y): (f32, f32)) -> Self {
+        Self { x, y }
+    }
+}
+
+impl From<egui::Pos2> for Point {
+    fn from(p: egui::Pos2) -> Self {
+        Self { x: p.x, y: p.y }
+    }
+}
+
+impl std::ops::Add for Point {
+    type Output = Point;
+
+    fn add(self, rhs: Self) -> Self::Output {
+        Self::new(self.x + rhs.x, self.y + rhs.y)
+    }
+}
+
+impl std::ops::Sub for Point {
+    type Output = Point;
+
+    fn sub(self, rhs: Self) -> Self::Output {
+        Self::new(self.x - rhs.x, self.y - rhs.y)
+    }
+}
+
+impl std::ops::Mul<f32> for Point {
+    type Output = Point;
+
+    fn mul(self, rhs: f32) -> Self::Output {
+        Self::new(self.x * rhs, self.y * rhs)
+    }
+}
+
+/// The distance from a point to the closest point of the straight segment from a to b
+pub fn distance_to_segment(p: Point, a: Point, b: Point) -> f32 {
+    let ab = b - a;
+    let length = ab.x * ab.x + ab.y * ab.y;
+    if length <= 0.0 {
+        return p.distance(a);
+    }
+    let ap = p - a;
+    let t = ((ap.x * ab.x + ap.y * ab.y) / length).clamp(0.0, 1.0);
+    p.distance(a + ab * t)
+}
+
+/// An affine transform, which takes x, y to a x + c y + e, b x + d y + f
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct Transform {
+    /// How far x moves along x for each unit of x
+    a: f32,
+    /// How far x moves along y for each unit of x
+    b: f32,
+    /// How far x moves for each unit of y
+    c: f32,
+    /// How far y moves for each unit of y
+    d: f32,
+    /// The x offset
+    e: f32,
+    /// The y offset
+    f: f32,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl Transform {
+    /// The transform that leaves every point where it is
+    pub const IDENTITY: Transform = Transform {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: 1.0,
+        e: 0.0,
+        f: 0.0,
+    };
+
+    /// Move every point by an offset
+    pub fn translate(offset: Point) -> Self {
+        Self {
+            e: offset.x,
+            f: offset.y,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Scale x and y separately
+    pub fn scale(x: f32, y: f32) -> Self {
+        Self {
+            a: x,
+            d: y,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Turn counterclockwise about the origin by an angle in degrees
+    pub fn rotate(degrees: f32) -> Self {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Self {
+            a: cos,
+            b: sin,
+            c: -sin,
+            d: cos,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Mirror and turn about the origin as a placed item is
+    pub fn orientation(orientation: Orientation) -> Self {
+        let (a, b) = orientation.apply(1.0, 0.0);
+        let (c, d) = orientation.apply(0.0, 1.0);
+        Self {
+            a,
+            b,
+            c,
+            d,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Take millimeters with y pointing up to the screen, where y points down, with zoom screen points per inch
+    /// and the origin drawn at origin
+    pub fn screen(zoom: f32, origin: egui::Pos2) -> Self {
+        Self::scale(zoom / 25.4, -zoom / 25.4).then(&Self::translate(origin.into()))
+    }
+
+    /// The transform that does this transform and then next
+    pub fn then(&self, next: &Transform) -> Self {
+        Self {
+            a: next.a * self.a + next.c * self.b,
+            b: next.b * self.a + next.d * self.b,
+            c: next.a * self.c + next.c * self.d,
+            d: next.b * self.c + next.d * self.d,
+            e: next.a * self.e + next.c * self.f + next.e,
+            f: next.b * self.e + next.d * self.f + next.f,
+        }
+    }
+
+    /// The transform that undoes this one, None when this transform flattens everything onto a line
+    pub fn inverse(&self) -> Option<Self> {
+        let det = self.a * self.d - self.b * self.c;
+        if det == 0.0 {
+            return None;
+        }
+        let (a, b, c, d) = (self.d / det, -self.b / det, -self.c / det, self.a / det);
+        Some(Self {
+            a,
+            b,
+            c,
+            d,
+            e: -(a * self.e + c * self.f),
+            f: -(b * self.e + d * self.f),
+        })
+    }
+
+    /// Transform a point
+    pub fn apply(&self, p: Point) -> Point {
+        Point::new(
+            self.a * p.x + self.c * p.y + self.e,
+            self.b * p.x + self.d * p.y + self.f,
+        )
+    }
+
+    /// Transform an offset between points, which is not moved by the translation of the transform
+    pub fn apply_vector(&self, v: Point) -> Point {
+        Point::new(self.a * v.x + self.c * v.y, self.b * v.x + self.d * v.y)
+    }
+
+    /// How much a length grows, for a transform that scales x and y equally
+    pub fn scale_factor(&self) -> f32 {
+        (self.a * self.d - self.b * self.c).abs().sqrt()
+    }
+
+    /// Returns true when the transform turns horizontal lines upright, so that text along them reads vertically
+    pub fn sideways(&self) -> bool {
+        self.a.abs() < self.b.abs()
+    }
+}
+
+/// A rectangle with sides along the x and y axes
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct BoundingBox {
+    /// The corner with the smallest coordinates
+    pub min: Point,
+    /// The corner with the largest coordinates
+    pub max: Point,
+}
+
+impl BoundingBox {
+    /// The smallest box holding all of the points, None when there are no points
+    pub fn from_points(points: impl IntoIterator<Item = Point>) -> Option<Self> {
+        points.into_iter().fold(None, |b: Option<Self>, p| {
+            Some(match b {
+                Some(b) => Self {
+                    min: Point::new(b.min.x.min(p.x), b.min.y.min(p.y)),
+                    max: Point::new(b.max.x.max(p.x), b.max.y.max(p.y)),
+                },
+                None => Self { min: p, max: p },
+            })
+        })
+    }
+
+    /// A box centered on a point, reaching half_size from it in each direction
+    pub fn around(center: Point, half_size: f32) -> Self {
+        Self {
+            min: center - Point::new(half_size, half_size),
+            max: center + Point::new(half_size, half_size),
+        }
+    }
+
+    /// The smallest box holding this box and another
+    pub fn union(&self, other: &BoundingBox) -> Self {
+        Self {
+            min: Point::new(self.min.x.min(other.min.x), self.min.y.min(other.min.y)),
+            max: Point::new(self.max.x.max(other.max.x), self.max.y.max(other.max.y)),
+        }
+    }
+
+    /// Returns true when the boxes overlap or touch
+    pub fn intersects(&self, other: &BoundingBox) -> bool {
+        self.min.x <= other.max.x
+            && other.min.x <= self.max.x
+            && self.min.y <= other.max.y
+            && other.min.y <= self.max.y
+    }
+
+    /// The four corners of the box
+    pub fn corners(&self) -> [Point; 4] {
+        [
+            self.min,
+            Point::new(self.max.x, self.min.y),
+            self.max,
+            Point::new(self.min.x, self.max.y),
+        ]
+    }
+
+    /// The box holding this box after it is transformed
+    pub fn transformed(&self, t: &Transform) -> Self {
+        Polygon::from(*self)
+            .transformed(t)
+            .bounding_box()
+            .unwrap_or(*self)
+    }
+
+    /// The box as an area of the screen, for a box that has been transformed to the screen
+    pub fn to_rect(self) -> egui::Rect {
+        egui::Rect::from_min_max(self.min.to_pos2(), self.max.to_pos2())
+    }
+
+    /// Returns true when the point is inside the box or on its edge
+    pub fn contains(&self, p: Point) -> bool {
+        self.min.x <= p.x && p.x <= self.max.x && self.min.y <= p.y && p.y <= self.max.y
+    }
+
+    /// The box grown by an amount on every side
+    pub fn expand(&self, amount: f32) -> Self {
+        Self {
+            min: self.min - Point::new(amount, amount),
+            max: self.max + Point::new(amount, amount),
+        }
+    }
+}
+
+impl From<egui::Rect> for BoundingBox {
+    fn from(r: egui::Rect) -> Self {
+        Self {
+            min: r.min.into(),
+            max: r.max.into(),
+        }
+    }
+}
+
+/// A closed shape with straight sides
+#[derive(Clone, PartialEq, Debug)]
+pub struct Polygon {
+    /// The corners, in order around the edge
+    pub points: Vec<Point>,
+}
+
+impl From<BoundingBox> for Polygon {
+    fn from(b: BoundingBox) -> Self {
+        Self {
+            points: b.corners().to_vec(),
+        }
+    }
+}
+
+impl Polygon {
+    /// The polygon after it is transformed
+    pub fn transformed(&self, t: &Transform) -> Self {
+        Self {
+            points: self.points.iter().map(|p| t.apply(*p)).collect(),
+        }
+    }
+
+    /// The smallest box holding the polygon, None when it has no corners
+    pub fn bounding_box(&self) -> Option<BoundingBox> {
+        BoundingBox::from_points(self.points.iter().copied())
+    }
+
+    /// The sides of the polygon, each as the corners at its ends
+    pub fn edges(&self) -> impl Iterator<Item = (Point, Point)> + '_ {
+        self.points
+            .iter()
+            .copied()
+            .zip(self.points.iter().copied().cycle().skip(1))
+    }
+}
+
+/// Which side of the line through a and b the point p is on, positive on the left, negative on the right and zero on the line
+fn side(a: Point, b: Point, p: Point) -> f32 {
+    (b.x - a.x) * (p.y - a.y) - (b.y - a.y) * (p.x - a.x)
+}
+
+/// Returns true when the segment from a1 to a2 crosses or touches the segment from b1 to b2
+pub fn segments_intersect(a1: Point, a2: Point, b1: Point, b2: Point) -> bool {
+    let (d1, d2) = (side(b1, b2, a1), side(b1, b2, a2));
+    let (d3, d4) = (side(a1, a2, b1), side(a1, a2, b2));
+    if ((d1 > 0.0 && d2 < 0.0) || (d1 < 0.0 && d2 > 0.0))
+        && ((d3 > 0.0 && d4 < 0.0) || (d3 < 0.0 && d4 > 0.0))
+    {
+        return true;
+    }
+    // Ends touching the other segment
+    distance_to_segment(a1, b1, b2) <= f32::EPSILON
+        || distance_to_segment(a2, b1, b2) <= f32::EPSILON
+        || distance_to_segment(b1, a1, a2) <= f32::EPSILON
+        || distance_to_segment(b2, a1, a2) <= f32::EPSILON
+}
+
+/// An area of the screen that picks an object when the pointer is over it
+#[derive(Clone, PartialEq, Debug)]
+pub enum HitArea {
+    /// A box, such as the area taken by text
+    Box(BoundingBox),
+    /// The points within reach of a straight segment, such as a wire
+    Segment {
+        /// One end of the segment
+        from: Point,
+        /// The other end of the segment
+        to: Point,
+        /// How far from the segment the pointer can be
+        reach: f32,
+    },
+}
+
+impl From<egui::Rect> for HitArea {
+    fn from(r: egui::Rect) -> Self {
+        Self::Box(r.into())
+    }
+}
+
+impl HitArea {
+    /// Returns true when the point picks the object
+    pub fn contains(&self, p: Point) -> bool {
+        match self {
+            Self::Box(b) => b.contains(p),
+            Self::Segment { from, to, reach } => distance_to_segment(p, *from, *to) <= *reach,
+        }
+    }
+
+    /// Returns true when the area and the box overlap or touch
+    pub fn intersects(&self, other: &BoundingBox) -> bool {
+        match self {
+            Self::Box(b) => b.intersects(other),
+            Self::Segment { from, to, reach } => {
+                let grown = other.expand(*reach);
+                grown.contains(*from)
+                    || grown.contains(*to)
+                    || Polygon::from(grown)
+                        .edges()
+                        .any(|(a, b)| segments_intersect(*from, *to, a, b))
+            }
+        }
+    }
+
+    /// The smallest box holding the area
+    pub fn bounding_box(&self) -> BoundingBox {
+        match self {
+            Self::Box(b) => *b,
+            Self::Segment { from, to, reach } => BoundingBox::from_points([*from, *to])
+                .unwrap_or(BoundingBox::around(*from, 0.0))
+                .expand(*reach),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns true when two numbers are the same, apart from rounding
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn transform_inverse_undoes_transform() {
+        let t = Transform::rotate(30.0)
+            .then(&Transform::scale(2.0, -2.0))
+            .then(&Transform::translate(Point::new(5.0, 7.0)));
+        let p = Point::new(1.5, -3.0);
+        let back = t.inverse().unwrap().apply(t.apply(p));
+        assert!(close(back.x, p.x) && close(back.y, p.y));
+    }
+
+    #[test]
+    fn bounding_box_contains() {
+        let b = BoundingBox::around(Point::new(1.0, 1.0), 1.0);
+        assert!(b.contains(Point::new(1.0, 1.0)));
+        assert!(b.contains(Point::new(2.0, 0.0)));
+        assert!(!b.contains(Point::new(2.1, 1.0)));
+        assert!(!b.contains(Point::new(1.0, -0.1)));
+    }
+
+    #[test]
+    fn segments_cross() {
+        let (a, b) = (Point::new(0.0, 0.0), Point::new(2.0, 2.0));
+        assert!(segments_intersect(
+            a,
+            b,
+            Point::new(0.0, 2.0),
+            Point::new(2.0, 0.0)
+        ));
+        assert!(segments_intersect(
+            a,
+            b,
+            Point::new(2.0, 2.0),
+            Point::new(3.0, 0.0)
+        ));
+        assert!(!segments_intersect(
+            a,
+            b,
+            Point::new(1.0, 0.0),
+            Point::new(3.0, 2.0)
+        ));
+    }
+
+    #[test]
+    fn hit_area_segment() {
+        let wire = HitArea::Segment {
+            from: Point::new(0.0, 0.0),
+            to: Point::new(10.0, 10.0),
+            reach: 1.0,
+        };
+        assert!(wire.contains(Point::new(5.0, 5.5)));
+        assert!(!wire.contains(Point::new(9.0, 1.0)));
+        assert!(wire.bounding_box().contains(Point::new(9.0, 1.0)));
+    }
+}
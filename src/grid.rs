@@ -0,0 +1,262 @@
+//! Defines the grid that objects are placed on in the schematic and symbol editors, how it is drawn, and snapping to it.
+
+use egui_multiwin::egui;
+
+use crate::general::Coordinates;
+
+/// How close in points the pointer has to be to a snap target for an object to snap to the target instead of the grid
+const SNAP_DISTANCE: f32 = 8.0;
+
+/// The smallest distance in points between grid dots or lines. Coarser multiples of the grid are drawn when zoomed out.
+const MIN_DRAWN_SPACING: f32 = 6.0;
+
+/// The distance between points of the grid
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, PartialEq)]
+pub enum GridSize {
+    /// 25 thousandths of an inch
+    Mil25,
+    /// 50 thousandths of an inch
+    Mil50,
+    /// 100 thousandths of an inch
+    Mil100,
+    /// 1.27 millimeters
+    Mm1_27,
+    /// 2.54 millimeters
+    Mm2_54,
+}
+
+impl GridSize {
+    /// All of the grid sizes, in the order they are offered to the user
+    pub const ALL: [GridSize; 5] = [
+        Self::Mil25,
+        Self::Mil50,
+        Self::Mil100,
+        Self::Mm1_27,
+        Self::Mm2_54,
+    ];
+
+    /// The distance between grid points, as both coordinates of a point
+    fn step(&self) -> Coordinates {
+        match self {
+            Self::Mil25 => Coordinates::from_mils(25, 25),
+            Self::Mil50 => Coordinates::from_mils(50, 50),
+            Self::Mil100 => Coordinates::from_mils(100, 100),
+            Self::Mm1_27 => Coordinates::from_nm(1_270_000, 1_270_000),
+            Self::Mm2_54 => Coordinates::from_nm(2_540_000, 2_540_000),
+        }
+    }
+}
+
+impl std::fmt::Display for GridSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Mil25 => write!(f, "25 mil"),
+            Self::Mil50 => write!(f, "50 mil"),
+            Self::Mil100 => write!(f, "100 mil"),
+            Self::Mm1_27 => write!(f, "1.27 mm"),
+            Self::Mm2_54 => write!(f, "2.54 mm"),
+        }
+    }
+}
+
+/// How the grid is shown
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, PartialEq)]
+pub enum GridStyle {
+    /// The grid is not drawn
+    Hidden,
+    /// A dot at each point of the grid
+    Dots,
+    /// Lines through the points of the grid
+    Lines,
+}
+
+impl std::fmt::Display for GridStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Hidden => write!(f, "Hidden"),
+            Self::Dots => write!(f, "Dots"),
+            Self::Lines => write!(f, "Lines"),
+        }
+    }
+}
+
+/// The grid of the editors
+#[derive(serde::Serialize, serde::Deserialize, Copy, Clone, PartialEq)]
+pub struct Grid {
+    /// The distance between grid points
+    pub size: GridSize,
+    /// How the grid is shown
+    pub style: GridStyle,
+    /// Objects that are placed or moved land on the grid, or on a nearby snap target, when true
+    pub snap: bool,
+}
+
+impl Default for Grid {
+    fn default() -> Self {
+        Self {
+            size: GridSize::Mil50,
+            style: GridStyle::Dots,
+            snap: true,
+        }
+    }
+}
+
+impl Grid {
+    /// The nearest point of the grid to a location
+    fn nearest(&self, c: Coordinates) -> Coordinates {
+        let step = self.size.step();
+        let round = |v: i64, step: i64| (v + step / 2).div_euclid(step) * step;
+        Coordinates::from_nm(round(c.x, step.x), round(c.y, step.y))
+    }
+
+    /// Where an object put at a location lands. A target, such as a pin or the anchor of another object,
+    /// close to the location on the screen wins over the grid. Nothing changes when snapping is off.
+    pub fn snap(&self, c: Coordinates, targets: &[Coordinates], zoom: f32) -> Coordinates {
+        if !self.snap {
+            return c;
+        }
+        let (x, y) = c.get_inches();
+        let distance = |t: &Coordinates| {
+            let (tx, ty) = t.get_inches();
+            ((tx - x) * (tx - x) + (ty - y) * (ty - y)).sqrt() * zoom
+        };
+        targets
+            .iter()
+            .map(|t| (t, distance(t)))
+            .filter(|(_, d)| *d < SNAP_DISTANCE)
+            .fold(
+                None,
+                |best: Option<(&Coordinates, f32)>, (t, d)| match best {
+                    Some((_, bd)) if bd <= d => best,
+                    _ => Some((t, d)),
+                },
+            )
+            .map(|(t, _)| *t)
+            .unwrap_or_else(|| self.nearest(c))
+    }
+
+    /// How far to move an object dragged by response, so that its anchor follows the pointer and lands where it snaps.
+    /// The distance between the pointer and the anchor when the drag starts is kept for the rest of the drag.
+    /// The anchor is not a target, so that the object does not stick to where it started.
+    pub fn drag_delta(
+        &self,
+        ui: &egui::Ui,
+        response: &egui::Response,
+        anchor: Coordinates,
+        pointer: Coordinates,
+        targets: &[Coordinates],
+        zoom: f32,
+    ) -> Coordinates {
+        let id = response.id.with("grab offset");
+        if response.drag_started() {
+            ui.data_mut(|d| d.insert_temp(id, anchor - pointer));
+        }
+        let offset: Coordinates = ui.data(|d| d.get_temp(id)).unwrap_or(anchor - pointer);
+        let others: Vec<Coordinates> = targets.iter().copied().filter(|t| *t != anchor).collect();
+        self.snap(pointer + offset, &others, zoom) - anchor
+    }
+
+    /// Draw the grid over an area of the screen, for a drawing with its origin at origin
+    pub fn draw(
+        &self,
+        ui: &egui::Ui,
+        pntr: &egui::Painter,
+        area: egui::Rect,
+        zoom: f32,
+        origin: egui::Pos2,
+    ) {
+        let (step, _) = self.size.step().get_inches();
+        let mut spacing = step * zoom;
+        if self.style == GridStyle::Hidden || spacing <= 0.0 {
+            return;
+        }
+        while spacing < MIN_DRAWN_SPACING {
+            spacing *= 2.0;
+        }
+        let color = ui.visuals().weak_text_color().gamma_multiply(0.5);
+        let first = |min: f32, o: f32| o + ((min - o) / spacing).ceil() * spacing;
+        let xs = std::iter::successors(Some(first(area.min.x, origin.x)), |x| Some(x + spacing))
+            .take_while(|x| *x <= area.max.x);
+        let ys = || {
+            std::iter::successors(Some(first(area.min.y, origin.y)), |y| Some(y + spacing))
+                .take_while(|y| *y <= area.max.y)
+        };
+        match self.style {
+            GridStyle::Hidden => {}
+            GridStyle::Dots => {
+                for x in xs {
+                    for y in ys() {
+                        pntr.circle_filled(egui::pos2(x, y), 0.75, color);
+                    }
+                }
+            }
+            GridStyle::Lines => {
+                let stroke = egui::Stroke::new(0.5, color);
+                for x in xs {
+                    pntr.line_segment(
+                        [egui::pos2(x, area.min.y), egui::pos2(x, area.max.y)],
+                        stroke,
+                    );
+                }
+                for y in ys() {
+                    pntr.line_segment(
+                        [egui::pos2(area.min.x, y), egui::pos2(area.max.x, y)],
+                        stroke,
+                    );
+                }
+            }
+        }
+    }
+
+    /// Show the controls that change the grid, returning true when it changed
+    pub fn controls(&mut self, ui: &mut egui::Ui) -> bool {
+        let old = *self;
+        egui::ComboBox::from_id_source("grid size")
+            .selected_text(self.size.to_string())
+            .show_ui(ui, |ui| {
+                for s in GridSize::ALL {
+                    ui.selectable_value(&mut self.size, s, s.to_string());
+                }
+            });
+        egui::ComboBox::from_id_source("grid style")
+            .selected_text(self.style.to_string())
+            .show_ui(ui, |ui| {
+                for s in [GridStyle::Hidden, GridStyle::Dots, GridStyle::Lines] {
+                    ui.selectable_value(&mut self.style, s, s.to_string());
+                }
+            });
+        ui.checkbox(&mut self.snap, "Snap");
+        *self != old
+    }
+}
+
+/// Show the position of the pointer in the given units, and the controls for choosing the units, returning true when
+/// the units changed
+pub fn cursor_readout(
+    ui: &mut egui::Ui,
+    cursor: Option<Coordinates>,
+    units: &mut crate::general::DisplayMode,
+) -> bool {
+    let old = *units;
+    match cursor {
+        Some(c) => {
+            let (x, y) = c.get_units(*units);
+            let d = units.decimals();
+            ui.monospace(format!(
+                "X {:>9.*} {}  Y {:>9.*} {}",
+                d, x, units, d, y, units
+            ));
+        }
+        None => {
+            ui.monospace(format!("X {:>9} {}  Y {:>9} {}", "-", units, "-", units));
+        }
+    }
+    egui::ComboBox::from_id_source("display units")
+        .selected_text(units.to_string())
+        .show_ui(ui, |ui| {
+            for u in crate::general::DisplayMode::ALL {
+                ui.selectable_value(units, u, u.to_string());
+            }
+        });
+    *units != old
+}
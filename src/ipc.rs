@@ -8,6 +8,8 @@ pub enum IpcMessage {
     NewLibrary,
     /// Create a new schematic window
     NewSchematic,
+    /// A library file was changed on disk, with the path to the file
+    LibraryFileChanged(String),
 }
 
 use egui_multiwin::winit::window::WindowId;
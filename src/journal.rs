@@ -0,0 +1,272 @@
+//! Keeps a journal of every action applied to or undone on a document, in a file next to the document.
+//!
+//! The journal is a text file with one json entry per line. A marker entry is added whenever the document is saved,
+//! so the entries after the last marker can be replayed onto the saved document to recover changes that were never saved.
+//! Every entry records who made the change and when, so the journal also serves as a history of changes to shared libraries.
+
+use std::io::Write;
+
+/// The kinds of entries in a journal
+#[derive(serde::Serialize, serde::Deserialize)]
+pub enum JournalOperation<A> {
+    /// An action was applied
+    Apply(A),
+    /// The most recent action was undone
+    Undo,
+    /// The most recently undone action was redone
+    Redo,
+    /// The document was saved
+    Saved,
+    /// Unsaved changes were thrown away instead of being replayed
+    Discarded,
+}
+
+/// A single entry in a journal
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct JournalEntry<A> {
+    /// The user that made the change
+    pub user: String,
+    /// When the change was made, in milliseconds since the unix epoch
+    pub time: u128,
+    /// What happened
+    pub op: JournalOperation<A>,
+}
+
+/// The name of the user running the program
+fn user() -> String {
+    std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "unknown".to_string())
+}
+
+/// The journal file for a document stored at the given path. Only local files have a journal.
+pub fn journal_path(path: &crate::general::StoragePath) -> Option<std::path::PathBuf> {
+    path.open_path().map(|p| {
+        let mut name = p.into_os_string();
+        name.push(".journal");
+        std::path::PathBuf::from(name)
+    })
+}
+
+/// Append an entry to a journal file
+fn append<A: serde::Serialize>(path: &std::path::Path, op: JournalOperation<A>) {
+    let entry = JournalEntry {
+        user: user(),
+        time: std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_millis())
+            .unwrap_or(0),
+        op,
+    };
+    let result = serde_json::to_string(&entry)
+        .map_err(|e| e.to_string())
+        .and_then(|line| {
+            std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut f| writeln!(f, "{}", line))
+                .map_err(|e| e.to_string())
+        });
+    if let Err(e) = result {
+        println!("ERROR writing journal {} {}", path.display(), e);
+    }
+}
+
+/// Add a marker to the journal for a document, so that earlier entries are no longer replayed.
+pub fn mark(path: &crate::general::StoragePath, discarded: bool) {
+    if let Some(p) = journal_path(path) {
+        let op: JournalOperation<()> = if discarded {
+            JournalOperation::Discarded
+        } else {
+            JournalOperation::Saved
+        };
+        append(&p, op);
+    }
+}
+
+/// Read the entries of a journal that come after the last save, or after unsaved changes were last discarded
+pub fn pending<A>(path: &crate::general::StoragePath) -> Vec<JournalEntry<A>>
+where
+    A: for<'a> serde::Deserialize<'a>,
+{
+    let mut entries = Vec::new();
+    if let Some(p) = journal_path(path) {
+        if let Ok(contents) = std::fs::read_to_string(p) {
+            for line in contents.lines() {
+                match serde_json::from_str::<JournalEntry<A>>(line) {
+                    Ok(entry) => match entry.op {
+                        JournalOperation::Saved | JournalOperation::Discarded => entries.clear(),
+                        _ => entries.push(entry),
+                    },
+                    Err(e) => println!("ERROR reading journal entry {}", e),
+                }
+            }
+        }
+    }
+    entries
+}
+
+/// Ask the user whether unsaved changes found in a journal should be replayed
+pub fn ask_replay(what: &str, count: usize) -> bool {
+    native_dialog::MessageDialog::new()
+        .set_type(native_dialog::MessageType::Warning)
+        .set_title("Unsaved changes found")
+        .set_text(&format!(
+            "The journal for {} has {} changes that were never saved. Replay them?",
+            what, count
+        ))
+        .show_confirm()
+        .unwrap_or(false)
+}
+
+/// An action that can be recorded in a journal
+pub trait Journaled:
+    undo::Action + serde::Serialize + for<'a> serde::Deserialize<'a> + Sized
+{
+    /// The journal file the action belongs in. default is the journal file of the record applying the action.
+    fn journal_file(
+        &self,
+        target: &Self::Target,
+        default: &Option<std::path::PathBuf>,
+    ) -> Option<std::path::PathBuf>;
+}
+
+/// An undo record that writes everything it does to a journal
+pub struct JournaledRecord<A> {
+    /// The undo record
+    record: undo::Record<A>,
+    /// The journal file for actions that do not pick their own journal
+    journal: Option<std::path::PathBuf>,
+    /// How many times the target was changed through the record
+    changes: usize,
+}
+
+impl<A: Journaled> Default for JournaledRecord<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<A: Journaled> JournaledRecord<A> {
+    /// Create a new empty record
+    pub fn new() -> Self {
+        Self {
+            record: undo::Record::new(),
+            journal: None,
+            changes: 0,
+        }
+    }
+
+    /// Set the journal file for actions that do not pick their own journal
+    pub fn set_journal(&mut self, path: Option<std::path::PathBuf>) {
+        self.journal = path;
+    }
+
+    /// Apply an action, recording it in the journal
+    pub fn apply(&mut self, target: &mut A::Target, action: A) -> A::Output {
+        if let Some(p) = action.journal_file(target, &self.journal) {
+            append(&p, JournalOperation::Apply(&action));
+        }
+        self.changes += 1;
+        self.record.apply(target, action)
+    }
+
+    /// How many times the target was changed through the record. Anything worked out from the target is out of date
+    /// when this differs from its value at the time.
+    pub fn changes(&self) -> usize {
+        self.changes
+    }
+
+    /// Count a change made to the target without an action, such as loading libraries from files
+    pub fn changed(&mut self) {
+        self.changes += 1;
+    }
+
+    /// The action that undo would undo, None when there is nothing to undo
+    pub fn next_undo(&self) -> Option<&A> {
+        let i = self.record.current().checked_sub(1)?;
+        self.record.actions().nth(i)
+    }
+
+    /// The action that redo would redo, None when there is nothing to redo
+    pub fn next_redo(&self) -> Option<&A> {
+        self.record.actions().nth(self.record.current())
+    }
+
+    /// Undo the most recent action, recording it in the journal
+    pub fn undo(&mut self, target: &mut A::Target) -> Option<A::Output> {
+        let journal = self
+            .next_undo()
+            .and_then(|a| a.journal_file(target, &self.journal));
+        let output = self.record.undo(target);
+        if output.is_some() {
+            self.changes += 1;
+        }
+        if let (Some(p), Some(_)) = (journal, &output) {
+            append::<A>(&p, JournalOperation::Undo);
+        }
+        output
+    }
+
+    /// Redo the most recently undone action, recording it in the journal
+    pub fn redo(&mut self, target: &mut A::Target) -> Option<A::Output> {
+        let journal = self
+            .next_redo()
+            .and_then(|a| a.journal_file(target, &self.journal));
+        let output = self.record.redo(target);
+        if output.is_some() {
+            self.changes += 1;
+        }
+        if let (Some(p), Some(_)) = (journal, &output) {
+            append::<A>(&p, JournalOperation::Redo);
+        }
+        output
+    }
+
+    /// Replay journal entries onto the target without writing them to the journal again.
+    /// Returns the number of entries that could not be replayed, because they undo or redo something from before the last save.
+    pub fn replay(&mut self, target: &mut A::Target, entries: Vec<JournalEntry<A>>) -> usize {
+        let mut skipped = 0;
+        self.changes += 1;
+        for entry in entries {
+            match entry.op {
+                JournalOperation::Apply(a) => {
+                    self.record.apply(target, a);
+                }
+                JournalOperation::Undo => {
+                    if self.record.undo(target).is_none() {
+                        skipped += 1;
+                    }
+                }
+                JournalOperation::Redo => {
+                    if self.record.redo(target).is_none() {
+                        skipped += 1;
+                    }
+                }
+                JournalOperation::Saved | JournalOperation::Discarded => {}
+            }
+        }
+        skipped
+    }
+
+    /// Returns true if there is an action to undo
+    pub fn can_undo(&self) -> bool {
+        self.record.can_undo()
+    }
+
+    /// Returns true if there is an action to redo
+    pub fn can_redo(&self) -> bool {
+        self.record.can_redo()
+    }
+
+    /// Returns true if the target is in a saved state
+    pub fn is_saved(&self) -> bool {
+        self.record.is_saved()
+    }
+
+    /// Mark the target as being saved or not
+    pub fn set_saved(&mut self, saved: bool) {
+        self.record.set_saved(saved);
+    }
+}
@@ -3,17 +3,19 @@
 use std::collections::HashMap;
 
 use crate::{
-    component::{ComponentDefinition, ComponentVariant},
-    symbol::{SymbolDefinition, SymbolReference},
+    component::{ComponentDefinition, ComponentUnit, ComponentVariant},
+    symbol::{SymbolDefinition, SymbolReference, SymbolStyle},
 };
 
 /// The actions that can be done to a library
+#[derive(serde::Serialize, serde::Deserialize)]
 pub enum LibraryAction {
     /// Create a new empty library, lib should be None
     CreateNewLibrary {
         /// The name of the library
         name: String,
         /// The holder for when creation is undone
+        #[serde(skip)]
         lib: Option<LibraryHolder>,
     },
     /// Delete an existing library, old_lib should be None
@@ -21,6 +23,7 @@ pub enum LibraryAction {
         /// The name of the library
         name: String,
         /// Hold the old library when required
+        #[serde(skip)]
         old_lib: Option<LibraryHolder>,
     },
     /// Move text on by a certain amount
@@ -29,6 +32,9 @@ pub enum LibraryAction {
         libname: String,
         /// The symbol name
         symname: String,
+        /// The body style of the symbol, None for the normal body style
+        #[serde(default)]
+        style: Option<String>,
         /// The text number
         textnum: usize,
         /// The delta to move by
@@ -40,6 +46,9 @@ pub enum LibraryAction {
         libname: String,
         /// The symbol name
         symname: String,
+        /// The body style of the symbol, None for the normal body style
+        #[serde(default)]
+        style: Option<String>,
         /// The new text
         text: crate::schematic::TextOnPage,
     },
@@ -49,6 +58,9 @@ pub enum LibraryAction {
         libname: String,
         /// The symbol name
         symname: String,
+        /// The body style of the symbol, None for the normal body style
+        #[serde(default)]
+        style: Option<String>,
         /// The text number
         textnum: usize,
         /// The old text
@@ -62,6 +74,9 @@ pub enum LibraryAction {
         libname: String,
         /// The symbol name
         symname: String,
+        /// The body style of the symbol, None for the normal body style
+        #[serde(default)]
+        style: Option<String>,
         /// The text number
         textnum: usize,
         /// The old text
@@ -76,6 +91,7 @@ pub enum LibraryAction {
         /// The name of the symbol to Delete
         symname: String,
         /// The deleted object
+        #[serde(skip)]
         symbol: Option<SymbolDefinition>,
     },
     /// Add a new blank symbol to the library
@@ -108,6 +124,7 @@ pub enum LibraryAction {
         /// The name of the symbol to Delete
         comname: String,
         /// The deleted object
+        #[serde(skip)]
         component: Option<ComponentDefinition>,
     },
     /// Create a variant for a component in a library, variant should be None
@@ -119,6 +136,7 @@ pub enum LibraryAction {
         /// The name of the variant
         varname: String,
         /// Temporary storage for component. This should be a None
+        #[serde(skip)]
         variant: Option<ComponentVariant>,
     },
     /// Delete a variant for a component in a library, variant should be None
@@ -130,6 +148,7 @@ pub enum LibraryAction {
         /// The name of the variant
         varname: String,
         /// Temporary storage for component. This should be a None
+        #[serde(skip)]
         variant: Option<ComponentVariant>,
     },
     /// Change the symbol for a variant of a component
@@ -140,9 +159,359 @@ pub enum LibraryAction {
         comname: String,
         /// The name of the variant
         varname: String,
+        /// The unit to change the symbol of, None for the symbol of the variant
+        #[serde(default)]
+        unit: Option<String>,
         /// The symbol reference
         sref: Option<SymbolReference>,
     },
+    /// Give a derived component its own copy of a variant it inherits
+    OverrideComponentVariant {
+        /// The name of the library
+        libname: String,
+        /// The name of the component to modify
+        comname: String,
+        /// The inherited variant, with symbol references relative to the library of the component
+        variant: ComponentVariant,
+    },
+    /// Add a unit to a variant of a component
+    CreateComponentUnit {
+        /// The name of the library
+        libname: String,
+        /// The name of the component to modify
+        comname: String,
+        /// The name of the variant
+        varname: String,
+        /// The name of the unit
+        unitname: String,
+        /// True when the unit holds the power pins
+        power: bool,
+    },
+    /// Delete a unit from a variant of a component, unit should be None
+    DeleteComponentUnit {
+        /// The name of the library
+        libname: String,
+        /// The name of the component to modify
+        comname: String,
+        /// The name of the variant
+        varname: String,
+        /// The name of the unit
+        unitname: String,
+        /// Temporary storage for the unit and where it was in the list of units
+        #[serde(skip)]
+        unit: Option<(usize, ComponentUnit)>,
+    },
+    /// Change the number of a pin, which identifies the pin in a netlist
+    SetPinNumber {
+        /// The name of the library
+        libname: String,
+        /// The symbol name
+        symname: String,
+        /// The index of the pin
+        pinnum: usize,
+        /// The number to swap with the current number
+        number: String,
+    },
+    /// Add an alternate body style to a symbol, starting with a copy of the normal body style
+    CreateSymbolStyle {
+        /// The name of the library
+        libname: String,
+        /// The symbol name
+        symname: String,
+        /// The name of the body style
+        stylename: String,
+    },
+    /// Delete an alternate body style from a symbol, style should be None
+    DeleteSymbolStyle {
+        /// The name of the library
+        libname: String,
+        /// The symbol name
+        symname: String,
+        /// The name of the body style
+        stylename: String,
+        /// Temporary storage for the body style and where it was in the list of styles
+        #[serde(skip)]
+        style: Option<(usize, SymbolStyle)>,
+    },
+    /// Rename a symbol, or move it to another library. References to the symbol in all libraries are changed to match.
+    RenameSymbol {
+        /// The name of the library
+        libname: String,
+        /// The name of the symbol
+        symname: String,
+        /// The library the symbol goes to, which can be the library it is in
+        newlib: String,
+        /// The new name of the symbol
+        newname: String,
+    },
+    /// Rename a component, or move it to another library
+    RenameComponent {
+        /// The name of the library
+        libname: String,
+        /// The name of the component
+        comname: String,
+        /// The library the component goes to, which can be the library it is in
+        newlib: String,
+        /// The new name of the component
+        newname: String,
+    },
+    /// Copy a symbol, to the same library or another one
+    DuplicateSymbol {
+        /// The name of the library
+        libname: String,
+        /// The name of the symbol to copy
+        symname: String,
+        /// The library the copy goes in
+        newlib: String,
+        /// The name of the copy
+        newname: String,
+    },
+    /// Copy a component, to the same library or another one
+    DuplicateComponent {
+        /// The name of the library
+        libname: String,
+        /// The name of the component to copy
+        comname: String,
+        /// The library the copy goes in
+        newlib: String,
+        /// The name of the copy
+        newname: String,
+        /// When true, the new component is derived from the component instead of being a copy of it
+        derive: bool,
+    },
+    /// Set or remove a property of a component. A value of None removes the property.
+    SetComponentProperty {
+        /// The name of the library
+        libname: String,
+        /// The name of the component
+        comname: String,
+        /// The name of the property
+        name: String,
+        /// The value to swap with the current value
+        value: Option<String>,
+    },
+    /// Add, replace or delete a title block template. A block of None deletes the title block.
+    SetTitleBlock {
+        /// The name of the library
+        libname: String,
+        /// The name of the title block
+        name: String,
+        /// The title block to swap with the current one
+        block: Option<crate::sheet::TitleBlockDefinition>,
+    },
+    /// Move a pin of a symbol by a certain amount
+    MovePin {
+        /// The name of the library
+        libname: String,
+        /// The symbol name
+        symname: String,
+        /// The index of the pin
+        pinnum: usize,
+        /// The delta to move by
+        delta: crate::general::Coordinates,
+    },
+    /// Change the angle of a pin, swapping it with the current angle
+    SetPinRotation {
+        /// The name of the library
+        libname: String,
+        /// The symbol name
+        symname: String,
+        /// The index of the pin
+        pinnum: usize,
+        /// The angle in degrees
+        rotation: f32,
+    },
+    /// Change the size of a text object, swapping it with the current size
+    SetTextSize {
+        /// The name of the library
+        libname: String,
+        /// The symbol name
+        symname: String,
+        /// The body style of the symbol, None for the normal body style
+        #[serde(default)]
+        style: Option<String>,
+        /// The text number
+        textnum: usize,
+        /// The size of the text
+        size: crate::general::Length,
+    },
+    /// Delete a pin from a symbol, pin should be None
+    DeletePin {
+        /// The name of the library
+        libname: String,
+        /// The symbol name
+        symname: String,
+        /// The index of the pin
+        pinnum: usize,
+        /// The deleted pin
+        #[serde(skip)]
+        pin: Option<crate::symbol::Pin>,
+    },
+    /// Delete a text from a symbol, text should be None
+    DeleteText {
+        /// The name of the library
+        libname: String,
+        /// The symbol name
+        symname: String,
+        /// The body style of the symbol, None for the normal body style
+        #[serde(default)]
+        style: Option<String>,
+        /// The text number
+        textnum: usize,
+        /// The deleted text
+        #[serde(skip)]
+        text: Option<crate::schematic::TextOnPage>,
+    },
+    /// Several actions that are done and undone as one, all in the same library
+    Compound {
+        /// The actions, in the order they are done
+        actions: Vec<LibraryAction>,
+    },
+}
+
+impl LibraryAction {
+    /// For an action that renames an item or moves it to another library, the item as it was before, the library it
+    /// goes to and its new name
+    pub fn renamed(&self) -> Option<(LibraryItem, &String, &String)> {
+        match self {
+            LibraryAction::RenameComponent {
+                libname,
+                comname,
+                newlib,
+                newname,
+            } => Some((
+                LibraryItem::Component(libname.clone(), comname.clone()),
+                newlib,
+                newname,
+            )),
+            LibraryAction::RenameSymbol {
+                libname,
+                symname,
+                newlib,
+                newname,
+            } => Some((
+                LibraryItem::Symbol(libname.clone(), symname.clone()),
+                newlib,
+                newname,
+            )),
+            _ => None,
+        }
+    }
+
+    /// The action that makes a schematic follow the action, for an action that renames an item, None for other actions
+    pub fn schematic_follow_up(&self) -> Option<crate::schematic::SchematicAction> {
+        self.renamed().map(|(item, newlib, newname)| {
+            crate::schematic::SchematicAction::RenameLibraryItem {
+                item,
+                newlib: newlib.clone(),
+                newname: newname.clone(),
+            }
+        })
+    }
+
+    /// The name of the library the action modifies
+    fn library_name(&self) -> &str {
+        match self {
+            LibraryAction::CreateNewLibrary { name, .. }
+            | LibraryAction::DeleteLibrary { name, .. } => name,
+            LibraryAction::MoveText { libname, .. }
+            | LibraryAction::CreateText { libname, .. }
+            | LibraryAction::EditText { libname, .. }
+            | LibraryAction::ChangeTextColor { libname, .. }
+            | LibraryAction::DeleteSymbol { libname, .. }
+            | LibraryAction::CreateSymbol { libname, .. }
+            | LibraryAction::CreatePin { libname, .. }
+            | LibraryAction::CreateComponent { libname, .. }
+            | LibraryAction::DeleteComponent { libname, .. }
+            | LibraryAction::CreateComponentVariant { libname, .. }
+            | LibraryAction::DeleteComponentVariant { libname, .. }
+            | LibraryAction::ChangeComponentVariantSymbol { libname, .. }
+            | LibraryAction::OverrideComponentVariant { libname, .. }
+            | LibraryAction::CreateComponentUnit { libname, .. }
+            | LibraryAction::DeleteComponentUnit { libname, .. }
+            | LibraryAction::SetPinNumber { libname, .. }
+            | LibraryAction::CreateSymbolStyle { libname, .. }
+            | LibraryAction::DeleteSymbolStyle { libname, .. }
+            | LibraryAction::RenameSymbol { libname, .. }
+            | LibraryAction::RenameComponent { libname, .. }
+            | LibraryAction::SetComponentProperty { libname, .. }
+            | LibraryAction::SetTitleBlock { libname, .. }
+            | LibraryAction::MovePin { libname, .. }
+            | LibraryAction::SetPinRotation { libname, .. }
+            | LibraryAction::SetTextSize { libname, .. }
+            | LibraryAction::DeletePin { libname, .. }
+            | LibraryAction::DeleteText { libname, .. } => libname,
+            LibraryAction::Compound { actions } => actions.first().map_or("", |a| a.library_name()),
+            LibraryAction::DuplicateSymbol { newlib, .. }
+            | LibraryAction::DuplicateComponent { newlib, .. } => newlib,
+        }
+    }
+
+    /// The amount a move action moves by, None for other actions
+    fn move_delta(&mut self) -> Option<&mut crate::general::Coordinates> {
+        match self {
+            LibraryAction::MoveText { delta, .. } | LibraryAction::MovePin { delta, .. } => {
+                Some(delta)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns true when both actions are moves of the same object
+    fn moves_same(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                LibraryAction::MoveText {
+                    libname,
+                    symname,
+                    style,
+                    textnum,
+                    ..
+                },
+                LibraryAction::MoveText {
+                    libname: libname2,
+                    symname: symname2,
+                    style: style2,
+                    textnum: textnum2,
+                    ..
+                },
+            ) => {
+                libname == libname2 && symname == symname2 && style == style2 && textnum == textnum2
+            }
+            (
+                LibraryAction::MovePin {
+                    libname,
+                    symname,
+                    pinnum,
+                    ..
+                },
+                LibraryAction::MovePin {
+                    libname: libname2,
+                    symname: symname2,
+                    pinnum: pinnum2,
+                    ..
+                },
+            ) => libname == libname2 && symname == symname2 && pinnum == pinnum2,
+            _ => false,
+        }
+    }
+}
+
+impl crate::journal::Journaled for LibraryAction {
+    fn journal_file(
+        &self,
+        target: &Self::Target,
+        _default: &Option<std::path::PathBuf>,
+    ) -> Option<std::path::PathBuf> {
+        match self {
+            // Creating and deleting whole libraries is not part of any single library's history
+            LibraryAction::CreateNewLibrary { .. } | LibraryAction::DeleteLibrary { .. } => None,
+            _ => target
+                .get(self.library_name())
+                .and_then(|l| l.path.as_ref())
+                .and_then(crate::journal::journal_path),
+        }
+    }
 }
 
 impl undo::Action for LibraryAction {
@@ -152,21 +521,237 @@ impl undo::Action for LibraryAction {
 
     fn apply(&mut self, target: &mut Self::Target) -> Self::Output {
         match self {
+            LibraryAction::DuplicateSymbol {
+                libname,
+                symname,
+                newlib,
+                newname,
+            } => {
+                let sym = target
+                    .get(libname)
+                    .and_then(|l| l.library.as_ref())
+                    .and_then(|l| l.syms.get(symname))
+                    .cloned();
+                if let Some(mut sym) = sym {
+                    sym.rename(newname.clone());
+                    if let Some(l) = target.get_mut(newlib).and_then(|l| l.library.as_mut()) {
+                        l.syms.insert(newname.clone(), sym);
+                    }
+                }
+            }
+            LibraryAction::DuplicateComponent {
+                libname,
+                comname,
+                newlib,
+                newname,
+                derive,
+            } => {
+                let com = if *derive {
+                    Some(ComponentDefinition::derived(
+                        newname.clone(),
+                        libname,
+                        comname.clone(),
+                        newlib,
+                    ))
+                } else {
+                    target
+                        .get(libname)
+                        .and_then(|l| l.library.as_ref())
+                        .and_then(|l| l.components.get(comname))
+                        .cloned()
+                };
+                if let Some(mut com) = com {
+                    com.rename(newname.clone());
+                    if !*derive {
+                        com.rebase(libname, newlib);
+                    }
+                    if let Some(l) = target.get_mut(newlib).and_then(|l| l.library.as_mut()) {
+                        l.components.insert(newname.clone(), com);
+                    }
+                }
+            }
+            LibraryAction::SetComponentProperty {
+                libname,
+                comname,
+                name,
+                value,
+            } => {
+                swap_property(target, libname, comname, name, value);
+            }
+            LibraryAction::SetTitleBlock {
+                libname,
+                name,
+                block,
+            } => {
+                swap_title_block(target, libname, name, block);
+            }
+            LibraryAction::MovePin {
+                libname,
+                symname,
+                pinnum,
+                delta,
+            } => {
+                if let Some(pin) =
+                    symbol_mut(target, libname, symname).and_then(|s| s.pins.get_mut(*pinnum))
+                {
+                    pin.location += *delta;
+                }
+            }
+            LibraryAction::SetPinRotation {
+                libname,
+                symname,
+                pinnum,
+                rotation,
+            } => {
+                if let Some(pin) =
+                    symbol_mut(target, libname, symname).and_then(|s| s.pins.get_mut(*pinnum))
+                {
+                    std::mem::swap(&mut pin.rotation, rotation);
+                }
+            }
+            LibraryAction::SetTextSize {
+                libname,
+                symname,
+                style,
+                textnum,
+                size,
+            } => {
+                if let Some(t) = symbol_texts_mut(target, libname, symname, style)
+                    .and_then(|texts| texts.get_mut(*textnum))
+                {
+                    std::mem::swap(&mut t.size, size);
+                }
+            }
+            LibraryAction::DeletePin {
+                libname,
+                symname,
+                pinnum,
+                pin,
+            } => {
+                if let Some(sym) =
+                    symbol_mut(target, libname, symname).filter(|s| *pinnum < s.pins.len())
+                {
+                    *pin = Some(sym.pins.remove(*pinnum));
+                }
+            }
+            LibraryAction::DeleteText {
+                libname,
+                symname,
+                style,
+                textnum,
+                text,
+            } => {
+                if let Some(texts) =
+                    symbol_texts_mut(target, libname, symname, style).filter(|t| *textnum < t.len())
+                {
+                    *text = Some(texts.remove(*textnum));
+                }
+            }
+            LibraryAction::Compound { actions } => {
+                for a in actions.iter_mut() {
+                    undo::Action::apply(a, target);
+                }
+            }
+            LibraryAction::RenameSymbol {
+                libname,
+                symname,
+                newlib,
+                newname,
+            } => {
+                move_symbol(target, libname, symname, newlib, newname);
+            }
+            LibraryAction::RenameComponent {
+                libname,
+                comname,
+                newlib,
+                newname,
+            } => {
+                move_component(target, libname, comname, newlib, newname);
+            }
             LibraryAction::ChangeComponentVariantSymbol {
                 libname,
                 comname,
                 varname,
+                unit,
                 sref,
             } => {
-                if let Some(target) = target.get_mut(libname) {
-                    if let Some(library) = &mut target.library {
-                        if let Some(comp) = library.components.get_mut(comname) {
-                            if let Some(var) = comp.variants.get_mut(varname) {
-                                let old = var.symbol.clone();
-                                var.symbol = sref.to_owned();
-                                *sref = old;
-                            }
-                        }
+                swap_variant_symbol(target, libname, comname, varname, unit, sref);
+            }
+            LibraryAction::OverrideComponentVariant {
+                libname,
+                comname,
+                variant,
+            } => {
+                if let Some(comp) = target
+                    .get_mut(libname)
+                    .and_then(|l| l.library.as_mut())
+                    .and_then(|l| l.components.get_mut(comname))
+                {
+                    comp.variants.insert(variant.name.clone(), variant.clone());
+                }
+            }
+            LibraryAction::CreateComponentUnit {
+                libname,
+                comname,
+                varname,
+                unitname,
+                power,
+            } => {
+                if let Some(var) = variant_mut(target, libname, comname, varname) {
+                    if var.unit(unitname).is_none() {
+                        var.units.push(ComponentUnit {
+                            name: unitname.clone(),
+                            symbol: None,
+                            power: *power,
+                        });
+                    }
+                }
+            }
+            LibraryAction::DeleteComponentUnit {
+                libname,
+                comname,
+                varname,
+                unitname,
+                unit,
+            } => {
+                if let Some(var) = variant_mut(target, libname, comname, varname) {
+                    if let Some(i) = var.units.iter().position(|u| u.name == *unitname) {
+                        *unit = Some((i, var.units.remove(i)));
+                    }
+                }
+            }
+            LibraryAction::SetPinNumber {
+                libname,
+                symname,
+                pinnum,
+                number,
+            } => {
+                swap_pin_number(target, libname, symname, *pinnum, number);
+            }
+            LibraryAction::CreateSymbolStyle {
+                libname,
+                symname,
+                stylename,
+            } => {
+                if let Some(sym) = symbol_mut(target, libname, symname) {
+                    if sym.style(stylename).is_none() {
+                        let texts = sym.texts.clone();
+                        sym.styles.push(SymbolStyle {
+                            name: stylename.clone(),
+                            texts,
+                        });
+                    }
+                }
+            }
+            LibraryAction::DeleteSymbolStyle {
+                libname,
+                symname,
+                stylename,
+                style,
+            } => {
+                if let Some(sym) = symbol_mut(target, libname, symname) {
+                    if let Some(i) = sym.styles.iter().position(|s| s.name == *stylename) {
+                        *style = Some((i, sym.styles.remove(i)));
                     }
                 }
             }
@@ -232,58 +817,46 @@ impl undo::Action for LibraryAction {
             LibraryAction::MoveText {
                 libname,
                 symname,
+                style,
                 textnum,
                 delta,
             } => {
-                if let Some(target) = target.get_mut(libname) {
-                    if let Some(library) = &mut target.library {
-                        if let Some(sym) = library.syms.get_mut(symname) {
-                            sym.texts[*textnum].location += *delta;
-                        }
-                    }
+                if let Some(texts) = symbol_texts_mut(target, libname, symname, style) {
+                    texts[*textnum].location += *delta;
                 }
             }
             LibraryAction::CreateText {
                 libname,
                 symname,
+                style,
                 text,
             } => {
-                if let Some(target) = target.get_mut(libname) {
-                    if let Some(library) = &mut target.library {
-                        if let Some(sym) = library.syms.get_mut(symname) {
-                            sym.texts.push(text.clone());
-                        }
-                    }
+                if let Some(texts) = symbol_texts_mut(target, libname, symname, style) {
+                    texts.push(text.clone());
                 }
             }
             LibraryAction::EditText {
                 libname,
                 symname,
+                style,
                 textnum,
                 old: _,
                 new,
             } => {
-                if let Some(target) = target.get_mut(libname) {
-                    if let Some(library) = &mut target.library {
-                        if let Some(sym) = library.syms.get_mut(symname) {
-                            sym.texts[*textnum].text = new.clone();
-                        }
-                    }
+                if let Some(texts) = symbol_texts_mut(target, libname, symname, style) {
+                    texts[*textnum].text = new.clone();
                 }
             }
             LibraryAction::ChangeTextColor {
                 libname,
                 symname,
+                style,
                 textnum,
                 old: _,
                 new,
             } => {
-                if let Some(target) = target.get_mut(libname) {
-                    if let Some(library) = &mut target.library {
-                        if let Some(sym) = library.syms.get_mut(symname) {
-                            sym.texts[*textnum].color = *new;
-                        }
-                    }
+                if let Some(texts) = symbol_texts_mut(target, libname, symname, style) {
+                    texts[*textnum].color = *new;
                 }
             }
             LibraryAction::DeleteSymbol {
@@ -297,50 +870,225 @@ impl undo::Action for LibraryAction {
                     }
                 }
             }
-            LibraryAction::CreateSymbol { libname, symname } => {
-                if let Some(target) = target.get_mut(libname) {
-                    if let Some(library) = &mut target.library {
-                        library
-                            .syms
-                            .insert(symname.clone(), SymbolDefinition::new(symname.clone()));
+            LibraryAction::CreateSymbol { libname, symname } => {
+                if let Some(target) = target.get_mut(libname) {
+                    if let Some(library) = &mut target.library {
+                        library
+                            .syms
+                            .insert(symname.clone(), SymbolDefinition::new(symname.clone()));
+                    }
+                }
+            }
+            LibraryAction::CreatePin {
+                libname,
+                symname,
+                pin,
+            } => {
+                if let Some(p) = pin.take() {
+                    if let Some(target) = target.get_mut(libname) {
+                        if let Some(library) = &mut target.library {
+                            if let Some(sym) = library.syms.get_mut(symname) {
+                                sym.pins.push(p);
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn undo(&mut self, target: &mut Self::Target) -> Self::Output {
+        match self {
+            LibraryAction::DuplicateSymbol {
+                libname: _,
+                symname: _,
+                newlib,
+                newname,
+            } => {
+                if let Some(l) = target.get_mut(newlib).and_then(|l| l.library.as_mut()) {
+                    l.syms.remove(newname);
+                }
+            }
+            LibraryAction::DuplicateComponent {
+                libname: _,
+                comname: _,
+                newlib,
+                newname,
+                derive: _,
+            } => {
+                if let Some(l) = target.get_mut(newlib).and_then(|l| l.library.as_mut()) {
+                    l.components.remove(newname);
+                }
+            }
+            LibraryAction::SetComponentProperty {
+                libname,
+                comname,
+                name,
+                value,
+            } => {
+                swap_property(target, libname, comname, name, value);
+            }
+            LibraryAction::SetTitleBlock {
+                libname,
+                name,
+                block,
+            } => {
+                swap_title_block(target, libname, name, block);
+            }
+            LibraryAction::MovePin {
+                libname,
+                symname,
+                pinnum,
+                delta,
+            } => {
+                if let Some(pin) =
+                    symbol_mut(target, libname, symname).and_then(|s| s.pins.get_mut(*pinnum))
+                {
+                    pin.location -= *delta;
+                }
+            }
+            LibraryAction::SetPinRotation {
+                libname,
+                symname,
+                pinnum,
+                rotation,
+            } => {
+                if let Some(pin) =
+                    symbol_mut(target, libname, symname).and_then(|s| s.pins.get_mut(*pinnum))
+                {
+                    std::mem::swap(&mut pin.rotation, rotation);
+                }
+            }
+            LibraryAction::SetTextSize {
+                libname,
+                symname,
+                style,
+                textnum,
+                size,
+            } => {
+                if let Some(t) = symbol_texts_mut(target, libname, symname, style)
+                    .and_then(|texts| texts.get_mut(*textnum))
+                {
+                    std::mem::swap(&mut t.size, size);
+                }
+            }
+            LibraryAction::DeletePin {
+                libname,
+                symname,
+                pinnum,
+                pin,
+            } => {
+                if let (Some(sym), Some(p)) = (symbol_mut(target, libname, symname), pin.take()) {
+                    sym.pins.insert((*pinnum).min(sym.pins.len()), p);
+                }
+            }
+            LibraryAction::DeleteText {
+                libname,
+                symname,
+                style,
+                textnum,
+                text,
+            } => {
+                if let (Some(texts), Some(t)) = (
+                    symbol_texts_mut(target, libname, symname, style),
+                    text.take(),
+                ) {
+                    texts.insert((*textnum).min(texts.len()), t);
+                }
+            }
+            LibraryAction::Compound { actions } => {
+                for a in actions.iter_mut().rev() {
+                    undo::Action::undo(a, target);
+                }
+            }
+            LibraryAction::RenameSymbol {
+                libname,
+                symname,
+                newlib,
+                newname,
+            } => {
+                move_symbol(target, newlib, newname, libname, symname);
+            }
+            LibraryAction::RenameComponent {
+                libname,
+                comname,
+                newlib,
+                newname,
+            } => {
+                move_component(target, newlib, newname, libname, comname);
+            }
+            LibraryAction::ChangeComponentVariantSymbol {
+                libname,
+                comname,
+                varname,
+                unit,
+                sref,
+            } => {
+                swap_variant_symbol(target, libname, comname, varname, unit, sref);
+            }
+            LibraryAction::OverrideComponentVariant {
+                libname,
+                comname,
+                variant,
+            } => {
+                if let Some(comp) = target
+                    .get_mut(libname)
+                    .and_then(|l| l.library.as_mut())
+                    .and_then(|l| l.components.get_mut(comname))
+                {
+                    comp.variants.remove(&variant.name);
+                }
+            }
+            LibraryAction::CreateComponentUnit {
+                libname,
+                comname,
+                varname,
+                unitname,
+                power: _,
+            } => {
+                if let Some(var) = variant_mut(target, libname, comname, varname) {
+                    var.units.retain(|u| u.name != *unitname);
+                }
+            }
+            LibraryAction::DeleteComponentUnit {
+                libname,
+                comname,
+                varname,
+                unitname: _,
+                unit,
+            } => {
+                if let Some((i, u)) = unit.take() {
+                    if let Some(var) = variant_mut(target, libname, comname, varname) {
+                        var.units.insert(i.min(var.units.len()), u);
                     }
                 }
             }
-            LibraryAction::CreatePin {
+            LibraryAction::SetPinNumber {
                 libname,
                 symname,
-                pin,
+                pinnum,
+                number,
             } => {
-                if let Some(p) = pin.take() {
-                    if let Some(target) = target.get_mut(libname) {
-                        if let Some(library) = &mut target.library {
-                            if let Some(sym) = library.syms.get_mut(symname) {
-                                sym.pins.push(p);
-                            }
-                        }
-                    }
+                swap_pin_number(target, libname, symname, *pinnum, number);
+            }
+            LibraryAction::CreateSymbolStyle {
+                libname,
+                symname,
+                stylename,
+            } => {
+                if let Some(sym) = symbol_mut(target, libname, symname) {
+                    sym.styles.retain(|s| s.name != *stylename);
                 }
             }
-        }
-    }
-
-    fn undo(&mut self, target: &mut Self::Target) -> Self::Output {
-        match self {
-            LibraryAction::ChangeComponentVariantSymbol {
+            LibraryAction::DeleteSymbolStyle {
                 libname,
-                comname,
-                varname,
-                sref,
+                symname,
+                stylename: _,
+                style,
             } => {
-                if let Some(target) = target.get_mut(libname) {
-                    if let Some(library) = &mut target.library {
-                        if let Some(comp) = library.components.get_mut(comname) {
-                            if let Some(var) = comp.variants.get_mut(varname) {
-                                let old = var.symbol.clone();
-                                var.symbol = sref.to_owned();
-                                *sref = old;
-                            }
-                        }
+                if let Some(sym) = symbol_mut(target, libname, symname) {
+                    if let Some((i, st)) = style.take() {
+                        sym.styles.insert(i.min(sym.styles.len()), st);
                     }
                 }
             }
@@ -385,58 +1133,46 @@ impl undo::Action for LibraryAction {
             LibraryAction::MoveText {
                 libname,
                 symname,
+                style,
                 textnum,
                 delta,
             } => {
-                if let Some(target) = target.get_mut(libname) {
-                    if let Some(library) = &mut target.library {
-                        if let Some(sym) = library.syms.get_mut(symname) {
-                            sym.texts[*textnum].location -= *delta;
-                        }
-                    }
+                if let Some(texts) = symbol_texts_mut(target, libname, symname, style) {
+                    texts[*textnum].location -= *delta;
                 }
             }
             LibraryAction::CreateText {
                 libname,
                 symname,
+                style,
                 text: _,
             } => {
-                if let Some(target) = target.get_mut(libname) {
-                    if let Some(library) = &mut target.library {
-                        if let Some(sym) = library.syms.get_mut(symname) {
-                            sym.texts.pop();
-                        }
-                    }
+                if let Some(texts) = symbol_texts_mut(target, libname, symname, style) {
+                    texts.pop();
                 }
             }
             LibraryAction::EditText {
                 libname,
                 symname,
+                style,
                 textnum,
                 old,
                 new: _,
             } => {
-                if let Some(target) = target.get_mut(libname) {
-                    if let Some(library) = &mut target.library {
-                        if let Some(sym) = library.syms.get_mut(symname) {
-                            sym.texts[*textnum].text = old.clone();
-                        }
-                    }
+                if let Some(texts) = symbol_texts_mut(target, libname, symname, style) {
+                    texts[*textnum].text = old.clone();
                 }
             }
             LibraryAction::ChangeTextColor {
                 libname,
                 symname,
+                style,
                 textnum,
                 old,
                 new: _,
             } => {
-                if let Some(target) = target.get_mut(libname) {
-                    if let Some(library) = &mut target.library {
-                        if let Some(sym) = library.syms.get_mut(symname) {
-                            sym.texts[*textnum].color = *old;
-                        }
-                    }
+                if let Some(texts) = symbol_texts_mut(target, libname, symname, style) {
+                    texts[*textnum].color = *old;
                 }
             }
             LibraryAction::DeleteSymbol {
@@ -507,21 +1243,155 @@ impl undo::Action for LibraryAction {
                 name: _,
                 old_lib: _,
             } => undo::Merged::No(other),
+            LibraryAction::DuplicateSymbol {
+                libname: _,
+                symname: _,
+                newlib: _,
+                newname: _,
+            } => undo::Merged::No(other),
+            LibraryAction::DuplicateComponent {
+                libname: _,
+                comname: _,
+                newlib: _,
+                newname: _,
+                derive: _,
+            } => undo::Merged::No(other),
+            LibraryAction::SetComponentProperty {
+                libname,
+                comname,
+                name,
+                value: _,
+            } => {
+                // The value kept is the one from before the first change, which is what undo restores
+                if let LibraryAction::SetComponentProperty {
+                    libname: libname2,
+                    comname: comname2,
+                    name: name2,
+                    value: value2,
+                } = other
+                {
+                    if *libname == libname2 && *comname == comname2 && *name == name2 {
+                        undo::Merged::Yes
+                    } else {
+                        undo::Merged::No(LibraryAction::SetComponentProperty {
+                            libname: libname2,
+                            comname: comname2,
+                            name: name2,
+                            value: value2,
+                        })
+                    }
+                } else {
+                    undo::Merged::No(other)
+                }
+            }
+            LibraryAction::SetTitleBlock {
+                libname: _,
+                name: _,
+                block: _,
+            } => undo::Merged::No(other),
+            LibraryAction::MovePin {
+                libname,
+                symname,
+                pinnum,
+                delta,
+            } => match other {
+                LibraryAction::MovePin {
+                    libname: libname2,
+                    symname: symname2,
+                    pinnum: pinnum2,
+                    delta: delta2,
+                } if *libname == libname2 && *symname == symname2 && *pinnum == pinnum2 => {
+                    if (*delta + delta2).is_zero() {
+                        undo::Merged::Annul
+                    } else {
+                        *delta += delta2;
+                        undo::Merged::Yes
+                    }
+                }
+                other => undo::Merged::No(other),
+            },
+            LibraryAction::SetPinRotation {
+                libname: _,
+                symname: _,
+                pinnum: _,
+                rotation: _,
+            } => undo::Merged::No(other),
+            LibraryAction::SetTextSize {
+                libname: _,
+                symname: _,
+                style: _,
+                textnum: _,
+                size: _,
+            } => undo::Merged::No(other),
+            LibraryAction::DeletePin {
+                libname: _,
+                symname: _,
+                pinnum: _,
+                pin: _,
+            } => undo::Merged::No(other),
+            LibraryAction::DeleteText {
+                libname: _,
+                symname: _,
+                style: _,
+                textnum: _,
+                text: _,
+            } => undo::Merged::No(other),
+            LibraryAction::Compound { actions } => match other {
+                // Moving a group of objects merges with the next move of the same group, like moving a single object
+                LibraryAction::Compound {
+                    actions: mut actions2,
+                } if actions.len() == actions2.len()
+                    && actions.iter().zip(&actions2).all(|(a, b)| a.moves_same(b)) =>
+                {
+                    for (a, b) in actions.iter_mut().zip(actions2.iter_mut()) {
+                        if let (Some(d), Some(d2)) = (a.move_delta(), b.move_delta()) {
+                            *d += *d2;
+                        }
+                    }
+                    if actions
+                        .iter_mut()
+                        .all(|a| a.move_delta().map_or(true, |d| d.is_zero()))
+                    {
+                        undo::Merged::Annul
+                    } else {
+                        undo::Merged::Yes
+                    }
+                }
+                other => undo::Merged::No(other),
+            },
+            LibraryAction::RenameSymbol {
+                libname: _,
+                symname: _,
+                newlib: _,
+                newname: _,
+            } => undo::Merged::No(other),
+            LibraryAction::RenameComponent {
+                libname: _,
+                comname: _,
+                newlib: _,
+                newname: _,
+            } => undo::Merged::No(other),
             LibraryAction::MoveText {
                 libname,
                 symname,
+                style,
                 textnum,
                 delta,
             } => {
                 if let LibraryAction::MoveText {
                     libname: libname2,
                     symname: symname2,
+                    style: style2,
                     textnum: tn2,
                     delta: delta2,
                 } = other
                 {
-                    if *libname == libname2 && *symname == symname2 && *textnum == tn2 {
-                        if (*delta + delta2).less_than_epsilon() {
+                    if *libname == libname2
+                        && *symname == symname2
+                        && *style == style2
+                        && *textnum == tn2
+                    {
+                        if (*delta + delta2).is_zero() {
                             undo::Merged::Annul
                         } else {
                             *delta += delta2;
@@ -531,6 +1401,7 @@ impl undo::Action for LibraryAction {
                         undo::Merged::No(LibraryAction::MoveText {
                             libname: libname2,
                             symname: symname2,
+                            style: style2,
                             textnum: tn2,
                             delta: delta2,
                         })
@@ -542,11 +1413,13 @@ impl undo::Action for LibraryAction {
             LibraryAction::CreateText {
                 libname: _,
                 symname: _,
+                style: _,
                 text: _,
             } => undo::Merged::No(other),
             LibraryAction::EditText {
                 libname,
                 symname,
+                style,
                 textnum,
                 old,
                 new,
@@ -554,12 +1427,17 @@ impl undo::Action for LibraryAction {
                 if let LibraryAction::EditText {
                     libname: libname2,
                     symname: symname2,
+                    style: style2,
                     textnum: textnum2,
                     old: old2,
                     new: new2,
                 } = other
                 {
-                    if *libname == libname2 && *symname == symname2 && *textnum == textnum2 {
+                    if *libname == libname2
+                        && *symname == symname2
+                        && *style == style2
+                        && *textnum == textnum2
+                    {
                         if *old == new2 {
                             undo::Merged::Annul
                         } else {
@@ -570,6 +1448,7 @@ impl undo::Action for LibraryAction {
                         undo::Merged::No(LibraryAction::EditText {
                             libname: libname2,
                             symname: symname2,
+                            style: style2,
                             textnum: textnum2,
                             old: old2,
                             new: new2,
@@ -582,6 +1461,7 @@ impl undo::Action for LibraryAction {
             LibraryAction::ChangeTextColor {
                 libname,
                 symname,
+                style,
                 textnum,
                 old,
                 new,
@@ -589,12 +1469,17 @@ impl undo::Action for LibraryAction {
                 if let LibraryAction::ChangeTextColor {
                     libname: libname2,
                     symname: symname2,
+                    style: style2,
                     textnum: textnum2,
                     old: old2,
                     new: new2,
                 } = other
                 {
-                    if *libname == libname2 && *symname == symname2 && *textnum == textnum2 {
+                    if *libname == libname2
+                        && *symname == symname2
+                        && *style == style2
+                        && *textnum == textnum2
+                    {
                         if *old == new2 {
                             undo::Merged::Annul
                         } else {
@@ -605,6 +1490,7 @@ impl undo::Action for LibraryAction {
                         undo::Merged::No(LibraryAction::ChangeTextColor {
                             libname: libname2,
                             symname: symname2,
+                            style: style2,
                             textnum: textnum2,
                             old: old2,
                             new: new2,
@@ -653,33 +1539,379 @@ impl undo::Action for LibraryAction {
                 libname: _,
                 comname: _,
                 varname: _,
+                unit: _,
                 sref: _,
             } => undo::Merged::No(other),
+            LibraryAction::OverrideComponentVariant {
+                libname: _,
+                comname: _,
+                variant: _,
+            } => undo::Merged::No(other),
+            LibraryAction::CreateComponentUnit {
+                libname: _,
+                comname: _,
+                varname: _,
+                unitname: _,
+                power: _,
+            } => undo::Merged::No(other),
+            LibraryAction::DeleteComponentUnit {
+                libname: _,
+                comname: _,
+                varname: _,
+                unitname: _,
+                unit: _,
+            } => undo::Merged::No(other),
+            LibraryAction::CreateSymbolStyle {
+                libname: _,
+                symname: _,
+                stylename: _,
+            } => undo::Merged::No(other),
+            LibraryAction::DeleteSymbolStyle {
+                libname: _,
+                symname: _,
+                stylename: _,
+                style: _,
+            } => undo::Merged::No(other),
+            LibraryAction::SetPinNumber {
+                libname,
+                symname,
+                pinnum,
+                number: _,
+            } => {
+                // The number kept is the one from before the first change, which is what undo restores
+                if let LibraryAction::SetPinNumber {
+                    libname: libname2,
+                    symname: symname2,
+                    pinnum: pinnum2,
+                    number: number2,
+                } = other
+                {
+                    if *libname == libname2 && *symname == symname2 && *pinnum == pinnum2 {
+                        undo::Merged::Yes
+                    } else {
+                        undo::Merged::No(LibraryAction::SetPinNumber {
+                            libname: libname2,
+                            symname: symname2,
+                            pinnum: pinnum2,
+                            number: number2,
+                        })
+                    }
+                } else {
+                    undo::Merged::No(other)
+                }
+            }
         }
     }
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
+/// Get a variant of a component for modification
+fn variant_mut<'a>(
+    target: &'a mut HashMap<String, LibraryHolder>,
+    libname: &str,
+    comname: &str,
+    varname: &str,
+) -> Option<&'a mut ComponentVariant> {
+    target
+        .get_mut(libname)?
+        .library
+        .as_mut()?
+        .components
+        .get_mut(comname)?
+        .variants
+        .get_mut(varname)
+}
+
+/// Swap the symbol of a variant, or of one of its units, with the given one
+fn swap_variant_symbol(
+    target: &mut HashMap<String, LibraryHolder>,
+    libname: &str,
+    comname: &str,
+    varname: &str,
+    unit: &Option<String>,
+    sref: &mut Option<SymbolReference>,
+) {
+    if let Some(var) = variant_mut(target, libname, comname, varname) {
+        let symbol = match unit {
+            Some(u) => var
+                .units
+                .iter_mut()
+                .find(|un| un.name == *u)
+                .map(|un| &mut un.symbol),
+            None => Some(&mut var.symbol),
+        };
+        if let Some(symbol) = symbol {
+            std::mem::swap(symbol, sref);
+        }
+    }
+}
+
+/// Get a symbol for modification
+fn symbol_mut<'a>(
+    target: &'a mut HashMap<String, LibraryHolder>,
+    libname: &str,
+    symname: &str,
+) -> Option<&'a mut SymbolDefinition> {
+    target
+        .get_mut(libname)?
+        .library
+        .as_mut()?
+        .syms
+        .get_mut(symname)
+}
+
+/// Get the text of a body style of a symbol for modification, the normal body style when style is None
+fn symbol_texts_mut<'a>(
+    target: &'a mut HashMap<String, LibraryHolder>,
+    libname: &str,
+    symname: &str,
+    style: &Option<String>,
+) -> Option<&'a mut Vec<crate::schematic::TextOnPage>> {
+    symbol_mut(target, libname, symname)?.style_texts_mut(style.as_deref())
+}
+
+/// Swap the number of a pin with the given one
+fn swap_pin_number(
+    target: &mut HashMap<String, LibraryHolder>,
+    libname: &str,
+    symname: &str,
+    pinnum: usize,
+    number: &mut String,
+) {
+    let pin = target
+        .get_mut(libname)
+        .and_then(|l| l.library.as_mut())
+        .and_then(|l| l.syms.get_mut(symname))
+        .and_then(|s| s.pins.get_mut(pinnum));
+    if let Some(pin) = pin {
+        std::mem::swap(&mut pin.number, number);
+    }
+}
+
+/// Swap the value of a component property with the given one. A value of None means the property does not exist.
+fn swap_property(
+    target: &mut HashMap<String, LibraryHolder>,
+    libname: &str,
+    comname: &str,
+    name: &str,
+    value: &mut Option<String>,
+) {
+    if let Some(com) = target
+        .get_mut(libname)
+        .and_then(|l| l.library.as_mut())
+        .and_then(|l| l.components.get_mut(comname))
+    {
+        let old = match value.take() {
+            Some(v) => com.properties.insert(name.to_string(), v),
+            None => com.properties.remove(name),
+        };
+        *value = old;
+    }
+}
+
+/// Swap a title block of a library with the given one, removing it when block is None
+fn swap_title_block(
+    target: &mut HashMap<String, LibraryHolder>,
+    libname: &str,
+    name: &str,
+    block: &mut Option<crate::sheet::TitleBlockDefinition>,
+) {
+    if let Some(l) = target.get_mut(libname).and_then(|l| l.library.as_mut()) {
+        let old = match block.take() {
+            Some(b) => l.title_blocks.insert(name.to_string(), b),
+            None => l.title_blocks.remove(name),
+        };
+        *block = old;
+    }
+}
+
+/// Move a symbol from one library to another, or rename it, changing all references to it
+fn move_symbol(
+    target: &mut HashMap<String, LibraryHolder>,
+    fromlib: &str,
+    from: &str,
+    tolib: &str,
+    to: &str,
+) {
+    if target.get(tolib).and_then(|l| l.library.as_ref()).is_none() {
+        return;
+    }
+    let sym = target
+        .get_mut(fromlib)
+        .and_then(|l| l.library.as_mut())
+        .and_then(|l| l.syms.remove(from));
+    if let Some(mut sym) = sym {
+        sym.rename(to.to_string());
+        if let Some(l) = target.get_mut(tolib).and_then(|l| l.library.as_mut()) {
+            l.syms.insert(to.to_string(), sym);
+        }
+    }
+    for l in target.values_mut().filter_map(|l| l.library.as_mut()) {
+        l.symbol_moved(fromlib, from, tolib, to);
+    }
+}
+
+/// Move a component from one library to another, or rename it
+fn move_component(
+    target: &mut HashMap<String, LibraryHolder>,
+    fromlib: &str,
+    from: &str,
+    tolib: &str,
+    to: &str,
+) {
+    if target.get(tolib).and_then(|l| l.library.as_ref()).is_none() {
+        return;
+    }
+    let com = target
+        .get_mut(fromlib)
+        .and_then(|l| l.library.as_mut())
+        .and_then(|l| l.components.remove(from));
+    if let Some(mut com) = com {
+        com.rename(to.to_string());
+        com.rebase(fromlib, tolib);
+        if let Some(l) = target.get_mut(tolib).and_then(|l| l.library.as_mut()) {
+            l.components.insert(to.to_string(), com);
+        }
+    }
+    for l in target.values_mut().filter_map(|l| l.library.as_mut()) {
+        l.component_moved(fromlib, from, tolib, to);
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+/// Identifies a single item in a library
+pub enum LibraryItem {
+    /// A component, with the library name and component name
+    Component(String, String),
+    /// A symbol, with the library name and symbol name
+    Symbol(String, String),
+}
+
+impl LibraryItem {
+    /// The same item after moving to newlib and newname, along with the library and name it had before
+    pub fn moved_to(&self, newlib: &str, newname: &str) -> (Self, String, String) {
+        match self {
+            Self::Component(lib, com) => (
+                Self::Component(newlib.to_string(), newname.to_string()),
+                lib.clone(),
+                com.clone(),
+            ),
+            Self::Symbol(lib, sym) => (
+                Self::Symbol(newlib.to_string(), newname.to_string()),
+                lib.clone(),
+                sym.clone(),
+            ),
+        }
+    }
+}
+
+impl std::fmt::Display for LibraryItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Component(lib, com) => write!(f, "Component {} from {}", com, lib),
+            Self::Symbol(lib, sym) => write!(f, "Symbol {} from {}", sym, lib),
+        }
+    }
+}
+
+#[derive(
+    serde::Serialize, serde::Deserialize, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord,
+)]
+/// The published version of a library. A change in the minor version must stay compatible with previous versions.
+pub struct LibraryVersion {
+    /// The major version, changed when something incompatible changes
+    pub major: u32,
+    /// The minor version, changed for compatible changes
+    pub minor: u32,
+}
+
+impl LibraryVersion {
+    /// The version that follows this one
+    pub fn next(&self, major: bool) -> Self {
+        if major {
+            Self {
+                major: self.major + 1,
+                minor: 0,
+            }
+        } else {
+            Self {
+                major: self.major,
+                minor: self.minor + 1,
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for LibraryVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
 #[non_exhaustive]
 /// A library. It is a collection of symbols, footprints, and 3d models
 pub struct Library {
     /// The name of the library. Must be unique for the system
     pub name: String,
+    /// The most recently published version of the library
+    #[serde(default)]
+    pub version: LibraryVersion,
     /// The symbols defined in the library
     pub syms: HashMap<String, crate::symbol::SymbolDefinition>,
     /// The components defined in the library
     pub components: HashMap<String, ComponentDefinition>,
+    /// The title block templates defined in the library
+    #[serde(default)]
+    pub title_blocks: HashMap<String, crate::sheet::TitleBlockDefinition>,
 }
 
 impl Library {
     /// Create a new blank library
-    fn new(name: String) -> Self {
+    pub fn new(name: String) -> Self {
         Self {
             name,
+            version: LibraryVersion::default(),
             syms: HashMap::new(),
             components: HashMap::new(),
+            title_blocks: HashMap::new(),
+        }
+    }
+
+    /// Change the references in this library to a symbol that moved from one library and name to another
+    pub fn symbol_moved(&mut self, fromlib: &str, from: &str, tolib: &str, to: &str) {
+        let srefs = self
+            .components
+            .values_mut()
+            .flat_map(|c| c.variants.values_mut())
+            .flat_map(|v| v.symbols_mut());
+        for sref in srefs {
+            if sref.lib.name(&self.name) == fromlib && sref.sym == from {
+                sref.lib = crate::symbol::LibraryReference::to(tolib, &self.name);
+                sref.sym = to.to_string();
+            }
+        }
+    }
+
+    /// Change the references in this library to a parent component that moved from one library and name to another
+    pub fn component_moved(&mut self, fromlib: &str, from: &str, tolib: &str, to: &str) {
+        for com in self.components.values_mut() {
+            if let Some(p) = &mut com.parent {
+                if p.lib.name(&self.name) == fromlib && p.com == from {
+                    p.lib = crate::symbol::LibraryReference::to(tolib, &self.name);
+                    p.com = to.to_string();
+                }
+            }
         }
     }
+
+    /// Get a component with everything it inherits from parent components included
+    pub fn flattened_component(
+        &self,
+        name: &str,
+        libs: &HashMap<String, LibraryHolder>,
+    ) -> Option<ComponentDefinition> {
+        self.components.get(name).map(|c| c.flattened(self, libs))
+    }
 }
 
 /// Separates data to be stored from data that is not to be stored
@@ -690,28 +1922,80 @@ pub struct LibraryHolder {
     pub path: Option<crate::general::StoragePath>,
     /// The file format to save the library in
     format: crate::general::StorageFormat,
+    /// A hash of the library as it was last loaded or saved, used to tell if it has been modified
+    pub saved_hash: Option<u64>,
 }
 
 impl LibraryHolder {
-    /// Sets the save path for the library
+    /// Sets the save path for the library, along with the file format that goes with it
     pub fn set_path(&mut self, p: crate::general::StoragePath) {
+        self.format = p.default_format();
         self.path = Some(p);
     }
 
     /// Returns true when the save function can probably run properly
     pub fn can_save(&self) -> bool {
-        self.path.is_some()
+        self.path.is_some() && !self.is_remote()
+    }
+
+    /// Returns true when the library is stored on a library server, where it is published instead of saved
+    pub fn is_remote(&self) -> bool {
+        matches!(self.path, Some(crate::general::StoragePath::Remote(_)))
     }
 
     /// Saves the library to wherever it has been configured to be saved
-    /// Will return Ok if the path is None
-    pub fn save(&self) -> Result<(), crate::general::StorageSaveError> {
+    /// Will return Ok if the path is None. A library on a library server cannot be saved, only published, because the
+    /// server only accepts newer versions.
+    pub fn save(&mut self) -> Result<(), crate::general::StorageSaveError> {
+        if self.is_remote() {
+            return Err(crate::general::StorageSaveError::RemoteSaveError);
+        }
+        self.write()
+    }
+
+    /// Write the library to its path, Ok when it has no path
+    fn write(&mut self) -> Result<(), crate::general::StorageSaveError> {
         if let Some(path) = &self.path {
             let mut writer = path.writer()?;
-            self.format.save(&mut writer, &self.library)
+            self.format.save(&mut writer, &self.library)?;
+            writer.commit()?;
+            crate::journal::mark(path, false);
+            self.saved_hash = self.contents_hash();
+        }
+        Ok(())
+    }
+
+    /// Calculate a hash of the contents of the library. It does not depend on the order of anything in the library.
+    pub fn contents_hash(&self) -> Option<u64> {
+        use std::hash::{Hash, Hasher};
+        // Converting to a json value sorts the keys of every map
+        let value = serde_json::to_value(self.library.as_ref()?).ok()?;
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        value.to_string().hash(&mut hasher);
+        Some(hasher.finish())
+    }
+
+    /// Returns true when the library differs from what was last loaded or saved
+    pub fn has_unsaved_changes(&self) -> bool {
+        self.contents_hash() != self.saved_hash
+    }
+
+    /// Publish the library as the next major or minor version. The version is left alone if saving fails.
+    pub fn publish(&mut self, major: bool) -> Result<(), crate::general::StorageSaveError> {
+        let old = if let Some(library) = &mut self.library {
+            let old = library.version;
+            library.version = old.next(major);
+            Some(old)
         } else {
-            Ok(())
+            None
+        };
+        let result = self.write();
+        if result.is_err() {
+            if let (Some(library), Some(old)) = (&mut self.library, old) {
+                library.version = old;
+            }
         }
+        result
     }
 
     /// Create a new blank library holder, with a new library
@@ -720,60 +2004,120 @@ impl LibraryHolder {
             library: Some(Library::new(name)),
             path: None,
             format: crate::general::StorageFormat::default(),
+            saved_hash: None,
         }
     }
 
-    /// Gets all user libraries
-    pub fn get_user_libraries(dirs: &Option<directories::ProjectDirs>) -> Vec<LibraryHolder> {
+    /// Load a library from the given path, using the normal format for that path
+    pub fn load(
+        path: crate::general::StoragePath,
+    ) -> Result<Self, crate::general::StorageLoadError> {
+        let format = path.default_format();
+        let mut reader = path.reader()?;
+        let lib = format.load::<Library>(&mut reader)?;
+        let mut libh = LibraryHolder {
+            library: Some(lib),
+            path: Some(path),
+            format,
+            saved_hash: None,
+        };
+        libh.saved_hash = libh.contents_hash();
+        Ok(libh)
+    }
+
+    /// Loads all libraries stored in the given folder
+    pub fn load_folder(folder: &std::path::Path) -> Vec<LibraryHolder> {
         let mut libs = Vec::new();
-        if let Some(dirs) = dirs {
-            let folder = dirs.data_dir();
-            let _ = std::fs::create_dir_all(folder);
-            if let Ok(p) = std::fs::read_dir(folder) {
-                let mut newlibs = p
-                    .filter_map(|res| res.ok())
-                    // Map the directory entries to paths
-                    .map(|dir_entry| dir_entry.path())
-                    // Filter out all paths with extensions other than `csv`
-                    .filter_map(|path| {
-                        if path.extension().map_or(false, |ext| ext == "uol") {
-                            Some(path)
+        if let Ok(p) = std::fs::read_dir(folder) {
+            let mut newlibs = p
+                .filter_map(|res| res.ok())
+                // Map the directory entries to paths
+                .map(|dir_entry| dir_entry.path())
+                // Filter out all paths with extensions other than `uol`
+                .filter_map(|path| {
+                    if path.extension().map_or(false, |ext| ext == "uol") {
+                        Some(path)
+                    } else {
+                        None
+                    }
+                })
+                .map(|path| {
+                    crate::general::StoragePath::LocalFilesystem(
+                        path.into_os_string().into_string().unwrap(),
+                    )
+                })
+                .filter_map(|path| match LibraryHolder::load(path.clone()) {
+                    Ok(lib) => Some(lib),
+                    Err(e) => {
+                        println!("ERROR Loading library {} {}", path, e);
+                        None
+                    }
+                })
+                .collect::<Vec<_>>();
+            libs.append(&mut newlibs);
+        }
+        libs
+    }
+
+    /// Load the libraries in a list of folders, searched in order. When more than one folder has a library with the same name,
+    /// the first one found is used, and the others are reported as conflicts.
+    pub fn load_search_path(
+        folders: &[crate::settings::LibraryFolder],
+    ) -> (Vec<LibraryHolder>, Vec<LibraryConflict>) {
+        let mut libs: Vec<LibraryHolder> = Vec::new();
+        let mut conflicts: Vec<LibraryConflict> = Vec::new();
+        for folder in folders {
+            for l in Self::load_folder(&folder.path) {
+                let (name, path) = match (&l.library, &l.path) {
+                    (Some(lib), Some(path)) => (lib.name.clone(), path.clone()),
+                    _ => continue,
+                };
+                let used = libs
+                    .iter()
+                    .find(|u| u.library.as_ref().map_or(false, |u| u.name == name))
+                    .and_then(|u| u.path.clone());
+                match used {
+                    Some(used) => {
+                        if let Some(c) = conflicts.iter_mut().find(|c| c.name == name) {
+                            c.ignored.push(path);
                         } else {
-                            None
-                        }
-                    })
-                    .filter_map(|path| {
-                        Some(crate::general::StoragePath::LocalFilesystem(
-                            path.into_os_string().into_string().unwrap(),
-                        ))
-                    })
-                    .filter_map(|path| {
-                        let reader = path.reader();
-                        match reader {
-                            Ok(mut reader) => {
-                                let format = crate::general::StorageFormat::default();
-                                match format.load::<Library>(&mut reader) {
-                                    Ok(lib) => Some(LibraryHolder {
-                                        library: Some(lib),
-                                        path: Some(path),
-                                        format,
-                                    }),
-                                    Err(e) => {
-                                        println!("ERROR Loading library {} {}", path, e);
-                                        None
-                                    }
-                                }
-                            }
-                            Err(e) => {
-                                println!("ERROR Loading library {} {}", path, e);
-                                None
-                            }
+                            conflicts.push(LibraryConflict {
+                                name,
+                                used,
+                                ignored: vec![path],
+                            });
                         }
-                    })
-                    .collect::<Vec<_>>();
-                libs.append(&mut newlibs);
+                    }
+                    None => libs.push(l),
+                }
             }
         }
-        libs
+        for c in &conflicts {
+            println!("WARNING {}", c);
+        }
+        (libs, conflicts)
+    }
+}
+
+/// A library name that was found in more than one place in the library search path
+pub struct LibraryConflict {
+    /// The name of the library
+    pub name: String,
+    /// The library that is used
+    pub used: crate::general::StoragePath,
+    /// The libraries with the same name that are not used
+    pub ignored: Vec<crate::general::StoragePath>,
+}
+
+impl std::fmt::Display for LibraryConflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ignored: Vec<String> = self.ignored.iter().map(|p| p.to_string()).collect();
+        write!(
+            f,
+            "Library {} is ambiguous, using {} instead of {}",
+            self.name,
+            self.used,
+            ignored.join(", ")
+        )
     }
 }
@@ -0,0 +1,347 @@
+//! Checks libraries for problems, such as references to symbols that do not exist.
+//!
+//! The checks can be run from the library editor, or without a user interface with `electronics_design lint [--deny-warnings] [files or folders]`.
+//! The exit code of the program is 1 when problems are found, so it can be used to check libraries before they are merged.
+
+use std::collections::HashMap;
+
+use crate::library::{Library, LibraryHolder};
+use crate::symbol::{LibraryReference, SymbolReference};
+
+/// How serious a problem is
+#[derive(Clone, Copy, PartialEq)]
+pub enum Severity {
+    /// The library is broken, something that uses it will not work
+    Error,
+    /// The library works, but something is probably a mistake
+    Warning,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// The part of a library a problem was found in
+#[derive(Clone, PartialEq)]
+pub enum LintLocation {
+    /// The library itself
+    Library,
+    /// A symbol, with the name of the symbol
+    Symbol(String),
+    /// A component, with the name of the component
+    Component(String),
+    /// A variant of a component, with the name of the component and the variant
+    Variant(String, String),
+}
+
+/// A problem found in a library
+#[derive(Clone)]
+pub struct LintIssue {
+    /// How serious the problem is
+    pub severity: Severity,
+    /// The name of the library with the problem
+    pub library: String,
+    /// Where in the library the problem is
+    pub location: LintLocation,
+    /// A description of the problem
+    pub message: String,
+}
+
+impl std::fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let location = match &self.location {
+            LintLocation::Library => String::new(),
+            LintLocation::Symbol(s) => format!(" symbol \"{}\"", s),
+            LintLocation::Component(c) => format!(" component \"{}\"", c),
+            LintLocation::Variant(c, v) => format!(" component \"{}\" variant \"{}\"", c, v),
+        };
+        write!(
+            f,
+            "{}: library \"{}\"{}: {}",
+            self.severity, self.library, location, self.message
+        )
+    }
+}
+
+/// The distance in millimeters below which two pins are considered to be in the same place
+const PIN_DISTANCE: f32 = 0.001;
+
+/// Check a symbol reference made from a library, returning a description of the problem if there is one
+fn check_reference(
+    sref: &SymbolReference,
+    lib: &Library,
+    libs: &HashMap<String, LibraryHolder>,
+) -> Option<String> {
+    match &sref.lib {
+        LibraryReference::ThisOne => {
+            if lib.syms.contains_key(&sref.sym) {
+                None
+            } else {
+                Some(format!("Symbol \"{}\" does not exist", sref.sym))
+            }
+        }
+        LibraryReference::Another(name) => match libs.get(name).and_then(|l| l.library.as_ref()) {
+            None => Some(format!(
+                "Symbol \"{}\" is in library \"{}\", which was not found",
+                sref.sym, name
+            )),
+            Some(other) => {
+                if other.syms.contains_key(&sref.sym) {
+                    None
+                } else {
+                    Some(format!(
+                        "Symbol \"{}\" does not exist in library \"{}\"",
+                        sref.sym, name
+                    ))
+                }
+            }
+        },
+    }
+}
+
+/// Returns true when any component in any library uses the given symbol of the given library
+fn symbol_used(
+    libname: &str,
+    sym: &str,
+    lib: &Library,
+    libs: &HashMap<String, LibraryHolder>,
+) -> bool {
+    let uses = |l: &Library| {
+        l.components
+            .values()
+            .flat_map(|c| c.variants.values())
+            .flat_map(|v| v.symbols())
+            .any(|s| {
+                s.sym == sym
+                    && match &s.lib {
+                        LibraryReference::ThisOne => l.name == libname,
+                        LibraryReference::Another(n) => n == libname,
+                    }
+            })
+    };
+    uses(lib)
+        || libs
+            .values()
+            .filter_map(|l| l.library.as_ref())
+            .filter(|l| l.name != libname)
+            .any(uses)
+}
+
+/// Check a library for problems. libs are the other libraries, used to check references to them.
+pub fn check_library(lib: &Library, libs: &HashMap<String, LibraryHolder>) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+    let mut issue = |severity, location, message: String| {
+        issues.push(LintIssue {
+            severity,
+            library: lib.name.clone(),
+            location,
+            message,
+        })
+    };
+
+    if lib.name.is_empty() {
+        issue(
+            Severity::Error,
+            LintLocation::Library,
+            "The library has no name".to_string(),
+        );
+    }
+
+    let mut symnames: Vec<&String> = lib.syms.keys().collect();
+    symnames.sort();
+    for name in symnames {
+        let sym = &lib.syms[name];
+        let location = LintLocation::Symbol(name.clone());
+        if name.is_empty() {
+            issue(
+                Severity::Error,
+                location.clone(),
+                "The symbol has no name".to_string(),
+            );
+        }
+        if !symbol_used(&lib.name, name, lib, libs) {
+            issue(
+                Severity::Warning,
+                location.clone(),
+                "The symbol is not used by any component".to_string(),
+            );
+        }
+        for (i, style) in sym.styles.iter().enumerate() {
+            if style.name.is_empty() {
+                issue(
+                    Severity::Error,
+                    location.clone(),
+                    "A body style has no name".to_string(),
+                );
+            } else if sym.styles[..i].iter().any(|s| s.name == style.name) {
+                issue(
+                    Severity::Error,
+                    location.clone(),
+                    format!("There is more than one body style named {}", style.name),
+                );
+            }
+        }
+        for (i, a) in sym.pins.iter().enumerate() {
+            let (ax, ay) = a.location.get_mm();
+            for (j, b) in sym.pins.iter().enumerate().skip(i + 1) {
+                let (bx, by) = b.location.get_mm();
+                if (ax - bx).abs() < PIN_DISTANCE && (ay - by).abs() < PIN_DISTANCE {
+                    issue(
+                        Severity::Error,
+                        location.clone(),
+                        format!("Pins {} and {} are in the same place", i + 1, j + 1),
+                    );
+                }
+            }
+        }
+    }
+
+    let mut comnames: Vec<&String> = lib.components.keys().collect();
+    comnames.sort();
+    for name in comnames {
+        let com = &lib.components[name];
+        if name.is_empty() {
+            issue(
+                Severity::Error,
+                LintLocation::Component(name.clone()),
+                "The component has no name".to_string(),
+            );
+        }
+        if let Some(problem) = com.parent_problem(lib, libs) {
+            issue(
+                Severity::Error,
+                LintLocation::Component(name.clone()),
+                problem,
+            );
+        } else if com.flattened(lib, libs).variants.is_empty() {
+            issue(
+                Severity::Warning,
+                LintLocation::Component(name.clone()),
+                "The component has no variants".to_string(),
+            );
+        }
+        let mut varnames: Vec<&String> = com.variants.keys().collect();
+        varnames.sort();
+        for varname in varnames {
+            let var = &com.variants[varname];
+            let location = LintLocation::Variant(name.clone(), varname.clone());
+            if varname.is_empty() {
+                issue(
+                    Severity::Error,
+                    location.clone(),
+                    "The variant has no name".to_string(),
+                );
+            }
+            if var.units.is_empty() {
+                match &var.symbol {
+                    None => issue(
+                        Severity::Warning,
+                        location,
+                        "The variant has no symbol".to_string(),
+                    ),
+                    Some(sref) => {
+                        if let Some(problem) = check_reference(sref, lib, libs) {
+                            issue(Severity::Error, location, problem);
+                        }
+                    }
+                }
+            } else {
+                for unit in &var.units {
+                    match &unit.symbol {
+                        None => issue(
+                            Severity::Warning,
+                            location.clone(),
+                            format!("Unit {} has no symbol", unit.name),
+                        ),
+                        Some(sref) => {
+                            if let Some(problem) = check_reference(sref, lib, libs) {
+                                issue(
+                                    Severity::Error,
+                                    location.clone(),
+                                    format!("Unit {}: {}", unit.name, problem),
+                                );
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    }
+    issues
+}
+
+/// Run the checks without a user interface, on the libraries named in args, printing the problems found.
+/// Folders in args are checked for libraries. When no libraries are named, the libraries in the library search path are checked.
+/// Returns the exit code for the program.
+pub fn run(args: &[String], dirs: &Option<directories::ProjectDirs>) -> i32 {
+    let deny_warnings = args.iter().any(|a| a == "--deny-warnings");
+    let paths: Vec<&String> = args.iter().filter(|a| !a.starts_with("--")).collect();
+    let mut errors = 0;
+
+    let mut checked = Vec::new();
+    for p in &paths {
+        let path = std::path::PathBuf::from(p);
+        if path.is_dir() {
+            checked.append(&mut LibraryHolder::load_folder(&path));
+        } else {
+            match LibraryHolder::load(crate::general::StoragePath::LocalFilesystem(p.to_string())) {
+                Ok(l) => checked.push(l),
+                Err(e) => {
+                    println!("error: unable to load {}: {}", p, e);
+                    errors += 1;
+                }
+            }
+        }
+    }
+
+    let settings = crate::settings::Settings::load(dirs);
+    let (searched, _conflicts) =
+        LibraryHolder::load_search_path(&settings.library_search_path(dirs, None));
+    let mut libs: HashMap<String, LibraryHolder> = HashMap::new();
+    let mut names = Vec::new();
+    for l in searched {
+        if let Some(lib) = &l.library {
+            if paths.is_empty() {
+                names.push(lib.name.clone());
+            }
+            libs.insert(lib.name.clone(), l);
+        }
+    }
+    for l in checked {
+        if let Some(lib) = &l.library {
+            names.push(lib.name.clone());
+            libs.insert(lib.name.clone(), l);
+        }
+    }
+    names.sort();
+    names.dedup();
+
+    let mut warnings = 0;
+    for name in &names {
+        if let Some(lib) = libs.get(name).and_then(|l| l.library.as_ref()) {
+            for i in check_library(lib, &libs) {
+                match i.severity {
+                    Severity::Error => errors += 1,
+                    Severity::Warning => warnings += 1,
+                }
+                println!("{}", i);
+            }
+        }
+    }
+    println!(
+        "Checked {} libraries, {} errors, {} warnings",
+        names.len(),
+        errors,
+        warnings
+    );
+    if errors > 0 || (deny_warnings && warnings > 0) {
+        1
+    } else {
+        0
+    }
+}
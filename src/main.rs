@@ -13,12 +13,28 @@ const COMPUTER_MODERN_FONT: &[u8] = include_bytes!("./cmunbtl.ttf");
 mod main_common;
 use main_common::*;
 
+mod autosave;
+mod benchmark;
+mod bus;
+mod cache;
 mod component;
+mod fragment;
 mod general;
+mod geometry;
+mod grid;
 mod ipc;
+mod journal;
 mod library;
+mod lint;
+mod netlist;
+mod remote;
 mod schematic;
+mod settings;
+mod sheet;
+mod spatial;
 mod symbol;
+mod variables;
+mod watch;
 
 use std::collections::HashMap;
 
@@ -43,16 +59,43 @@ use crate::schematic::SchematicHolder;
 mod window;
 
 fn main() {
-    let instance = single_instance::SingleInstance::new(PACKAGE_NAME).unwrap();
-
     let args: Vec<String> = std::env::args().collect();
 
     let dirs = DIRS.clone();
+
+    if args.len() > 1 && args[1] == "library-server" {
+        let folder = if let Some(f) = args.get(2) {
+            std::path::PathBuf::from(f)
+        } else if let Some(dirs) = &dirs {
+            dirs.data_dir().to_path_buf()
+        } else {
+            std::path::PathBuf::from(".")
+        };
+        let addr = args.get(3).map(|a| a.as_str()).unwrap_or("127.0.0.1:8080");
+        if let Err(e) = remote::run_server(&folder, addr) {
+            println!("Unable to run library server: {}", e);
+        }
+        return;
+    }
+
+    if args.len() > 1 && args[1] == "lint" {
+        std::process::exit(lint::run(&args[2..], &dirs));
+    }
+
+    if args.len() > 1 && args[1] == "benchmark" {
+        std::process::exit(benchmark::run(&args[2..]));
+    }
+
+    let instance = single_instance::SingleInstance::new(PACKAGE_NAME).unwrap();
     let mut ac = MyApp {
         schematic: None,
         libraries: HashMap::new(),
-        library_log: undo::Record::new(),
-        units: general::DisplayMode::Inches,
+        library_log: journal::JournaledRecord::new(),
+        autosave: autosave::Autosaver::new(&dirs),
+        watcher: None,
+        settings: settings::Settings::load(&dirs),
+        library_conflicts: Vec::new(),
+        project_folder: None,
         dirs,
         args,
     };
@@ -85,6 +128,11 @@ fn main() {
     let event_loop = event_loop.build().unwrap();
     let proxy = event_loop.create_proxy();
 
+    match watch::LibraryWatcher::new(proxy.clone()) {
+        Ok(w) => ac.watcher = Some(w),
+        Err(e) => println!("ERROR Unable to watch library files {}", e),
+    }
+
     std::thread::spawn(move || {
         let proxy = proxy.clone();
         #[cfg(any(target_os = "linux", target_os = "macos"))]
@@ -119,9 +167,54 @@ fn main() {
     fd.tweak.y_offset_factor = 1.0 / 3.0;
     multi_window.add_font("computermodern".to_string(), fd);
 
-    for l in crate::library::LibraryHolder::get_user_libraries(&ac.dirs) {
-        if let Some(lib) = &l.library {
-            ac.libraries.insert(lib.name.clone(), l);
+    if let Some(dirs) = &ac.dirs {
+        let _ = std::fs::create_dir_all(dirs.data_dir());
+    }
+    ac.load_libraries();
+
+    let mut recovered = Vec::new();
+    for (path, rec) in ac.autosave.find_recoverable() {
+        let recover = native_dialog::MessageDialog::new()
+            .set_type(native_dialog::MessageType::Warning)
+            .set_title("Recover unsaved changes")
+            .set_text(&format!(
+                "The {} has unsaved changes from a previous session. Recover them?",
+                rec.describe()
+            ))
+            .show_confirm()
+            .unwrap_or(false);
+        if recover {
+            if let autosave::Recoverable::Library(f) = &rec {
+                recovered.push(f.contents.name.clone());
+            }
+            ac.recover(rec);
+        } else {
+            let _ = std::fs::remove_file(path);
+        }
+    }
+
+    let mut pending = Vec::new();
+    let mut journaled = Vec::new();
+    for (name, libh) in &ac.libraries {
+        if let Some(path) = &libh.path {
+            if !recovered.contains(name) {
+                let mut p = journal::pending(path);
+                if !p.is_empty() {
+                    journaled.push((name.clone(), path.clone()));
+                    pending.append(&mut p);
+                }
+            }
+        }
+    }
+    if !journaled.is_empty() {
+        let names: Vec<String> = journaled.iter().map(|(n, _)| n.clone()).collect();
+        if journal::ask_replay(&format!("libraries {}", names.join(", ")), pending.len()) {
+            pending.sort_by_key(|e| e.time);
+            ac.library_log.replay(&mut ac.libraries, pending);
+        } else {
+            for (_, path) in journaled {
+                journal::mark(&path, true);
+            }
         }
     }
 
@@ -151,16 +244,300 @@ pub struct MyApp {
     /// The libraries for the current setup
     libraries: HashMap<String, library::LibraryHolder>,
     /// The undo log for all libraries
-    library_log: undo::Record<crate::library::LibraryAction>,
+    library_log: journal::JournaledRecord<crate::library::LibraryAction>,
     /// The directories for the system
     dirs: Option<directories::ProjectDirs>,
     /// The command line arguments to the program
     args: Vec<String>,
-    /// The units to display and modify for everything
-    units: general::DisplayMode,
+    /// Autosaves documents with unsaved changes
+    autosave: autosave::Autosaver,
+    /// Watches library files for changes made by other programs
+    watcher: Option<watch::LibraryWatcher>,
+    /// The settings for the program
+    settings: settings::Settings,
+    /// Library names found more than once in the library search path
+    library_conflicts: Vec<library::LibraryConflict>,
+    /// The library folder of the open schematic when libraries were last loaded
+    project_folder: Option<std::path::PathBuf>,
 }
 
 impl MyApp {
+    /// Apply an action to the libraries. When it renames an item that the schematic uses, the schematic follows the
+    /// rename.
+    fn apply_library(&mut self, a: library::LibraryAction) {
+        let follow = a.schematic_follow_up();
+        self.library_log.apply(&mut self.libraries, a);
+        self.follow_rename(follow);
+    }
+
+    /// Apply the action that makes the schematic follow a rename, when the schematic uses the renamed item
+    fn follow_rename(&mut self, follow: Option<schematic::SchematicAction>) {
+        if let (Some(follow), Some(sch)) = (follow, &mut self.schematic) {
+            if let schematic::SchematicAction::RenameLibraryItem { item, .. } = &follow {
+                if sch.schematic.uses(item) {
+                    sch.schematic_log.apply(&mut sch.schematic, follow);
+                }
+            }
+        }
+    }
+
+    /// Returns true when the next step undone in the libraries and in the schematic are a rename and the change that
+    /// made the schematic follow it, which are undone together
+    fn undo_paired(&self) -> bool {
+        match (self.library_log.next_undo(), &self.schematic) {
+            (Some(a), Some(sch)) => sch
+                .schematic_log
+                .next_undo()
+                .map_or(false, |s| s.follows(a)),
+            _ => false,
+        }
+    }
+
+    /// Returns true when the next step redone in the libraries and in the schematic are a rename and the change that
+    /// made the schematic follow it, which are redone together
+    fn redo_paired(&self) -> bool {
+        match (self.library_log.next_redo(), &self.schematic) {
+            (Some(a), Some(sch)) => sch
+                .schematic_log
+                .next_redo()
+                .map_or(false, |s| s.follows(a)),
+            _ => false,
+        }
+    }
+
+    /// Undo the most recent change to the libraries. A rename is undone along with the schematic following it, and
+    /// when the schematic changed since, it is made to follow the name going back instead.
+    fn undo_library(&mut self) {
+        let paired = self.undo_paired();
+        let back = self.library_log.next_undo().and_then(|a| a.renamed()).map(
+            |(item, newlib, newname)| {
+                let (moved, oldlib, oldname) = item.moved_to(newlib, newname);
+                schematic::SchematicAction::RenameLibraryItem {
+                    item: moved,
+                    newlib: oldlib,
+                    newname: oldname,
+                }
+            },
+        );
+        self.library_log.undo(&mut self.libraries);
+        if paired {
+            if let Some(sch) = &mut self.schematic {
+                sch.schematic_log.undo(&mut sch.schematic);
+            }
+        } else {
+            self.follow_rename(back);
+        }
+    }
+
+    /// Redo the most recently undone change to the libraries, along with the schematic following it for a rename
+    fn redo_library(&mut self) {
+        if self.redo_paired() {
+            self.library_log.redo(&mut self.libraries);
+            if let Some(sch) = &mut self.schematic {
+                sch.schematic_log.redo(&mut sch.schematic);
+            }
+        } else {
+            let follow = self
+                .library_log
+                .next_redo()
+                .and_then(|a| a.schematic_follow_up());
+            self.library_log.redo(&mut self.libraries);
+            self.follow_rename(follow);
+        }
+    }
+
+    /// Undo the most recent change to the schematic, along with the library rename it followed
+    fn undo_schematic(&mut self) {
+        let paired = self.undo_paired();
+        if let Some(sch) = &mut self.schematic {
+            sch.schematic_log.undo(&mut sch.schematic);
+        }
+        if paired {
+            self.library_log.undo(&mut self.libraries);
+        }
+    }
+
+    /// Redo the most recently undone change to the schematic, along with the library rename it followed
+    fn redo_schematic(&mut self) {
+        let paired = self.redo_paired();
+        if let Some(sch) = &mut self.schematic {
+            sch.schematic_log.redo(&mut sch.schematic);
+        }
+        if paired {
+            self.library_log.redo(&mut self.libraries);
+        }
+    }
+
+    /// Autosave documents with unsaved changes when it is time to do so. Windows call this when they redraw.
+    fn check_autosave(&mut self, ctx: &egui_multiwin::egui::Context) {
+        self.autosave.check(&self.schematic, &self.libraries);
+        ctx.request_repaint_after(autosave::AUTOSAVE_INTERVAL);
+    }
+
+    /// Make sure the files of all loaded libraries are being watched for changes. Windows call this when they redraw.
+    fn watch_libraries(&mut self) {
+        if let Some(w) = &mut self.watcher {
+            for libh in self.libraries.values() {
+                if let Some(p) = &libh.path {
+                    w.watch(p);
+                }
+            }
+        }
+    }
+
+    /// A library file was changed on disk by something else. Reload the library if it has no unsaved changes,
+    /// otherwise ask the user which version to keep. Reloading is not an edit, so it is not added to the undo history,
+    /// where undoing it would bring back the old copy for the next save to write over the file.
+    fn library_file_changed(&mut self, path: String) {
+        let changed = std::path::PathBuf::from(&path);
+        let name = self
+            .libraries
+            .iter()
+            .find(|(_, l)| l.path.as_ref().and_then(|p| p.open_path()).as_ref() == Some(&changed))
+            .map(|(n, _)| n.clone());
+        let (name, libh) = match name.and_then(|n| self.libraries.get_mut(&n).map(|l| (n, l))) {
+            Some(l) => l,
+            None => return,
+        };
+        let storage = general::StoragePath::LocalFilesystem(path);
+        let disk = match library::LibraryHolder::load(storage.clone()) {
+            Ok(l) => l,
+            Err(e) => {
+                // The file may still be in the middle of being written, another event will follow
+                println!("ERROR Reloading library {} {}", storage, e);
+                return;
+            }
+        };
+        // Saving the library from this program also changes the file
+        if disk.saved_hash == libh.saved_hash {
+            return;
+        }
+        if disk.library.as_ref().map(|l| &l.name) != Some(&name) {
+            println!(
+                "ERROR Library file {} no longer contains library {}",
+                storage, name
+            );
+            return;
+        }
+        if libh.has_unsaved_changes() {
+            let reload = native_dialog::MessageDialog::new()
+                .set_type(native_dialog::MessageType::Warning)
+                .set_title("Library changed on disk")
+                .set_text(&format!(
+                    "The library {} was changed on disk, but also has unsaved changes here. Reload it from disk and discard the unsaved changes? Otherwise saving the library will overwrite the changes on disk.",
+                    name
+                ))
+                .show_confirm()
+                .unwrap_or(false);
+            if !reload {
+                libh.saved_hash = disk.saved_hash;
+                return;
+            }
+        }
+        self.libraries.insert(name, disk);
+        self.library_log.changed();
+        journal::mark(&storage, false);
+        self.update_library_saved_status();
+    }
+
+    /// Mark the library log as saved when every library matches what was last loaded or saved
+    fn update_library_saved_status(&mut self) {
+        let all_saved = self.libraries.values().all(|l| !l.has_unsaved_changes());
+        self.library_log.set_saved(all_saved);
+    }
+
+    /// Load libraries from the library search path, including the library folder of the open schematic.
+    /// Libraries that are already loaded are kept, unless a library with the same name comes first in the search path.
+    /// Libraries loaded from the folder of a schematic that is no longer open are unloaded, unless they have unsaved changes.
+    /// Loading libraries is not an edit, so it is not added to the undo history.
+    fn load_libraries(&mut self) {
+        let folder_of = |l: &library::LibraryHolder| {
+            l.path
+                .as_ref()
+                .and_then(|p| p.open_path())
+                .and_then(|p| p.parent().map(|p| p.to_path_buf()))
+        };
+        let project = self
+            .schematic
+            .as_ref()
+            .and_then(|s| s.path.as_ref())
+            .and_then(|p| p.open_path())
+            .and_then(|p| p.parent().map(|p| p.to_path_buf()));
+        let folders = self
+            .settings
+            .library_search_path(&self.dirs, project.as_deref());
+        let (libs, mut conflicts) = library::LibraryHolder::load_search_path(&folders);
+        let mut replaced = false;
+        if let Some(old) = self
+            .project_folder
+            .take()
+            .filter(|old| Some(old) != project.as_ref())
+        {
+            let before = self.libraries.len();
+            self.libraries
+                .retain(|_, l| l.has_unsaved_changes() || folder_of(l).as_ref() != Some(&old));
+            replaced = self.libraries.len() != before;
+        }
+        self.project_folder = project;
+        for l in libs {
+            let (name, path) = match (&l.library, &l.path) {
+                (Some(lib), Some(path)) => (lib.name.clone(), path.clone()),
+                _ => continue,
+            };
+            let existing = match self.libraries.get(&name) {
+                Some(e) => e,
+                None => {
+                    self.libraries.insert(name, l);
+                    continue;
+                }
+            };
+            if existing.path.as_ref() == Some(&path) {
+                continue;
+            }
+            let searched =
+                folder_of(existing).map_or(false, |p| folders.iter().any(|f| f.path == p));
+            if searched && !existing.has_unsaved_changes() {
+                self.libraries.insert(name, l);
+                replaced = true;
+            } else if let Some(used) = existing.path.clone() {
+                conflicts.push(library::LibraryConflict {
+                    name,
+                    used,
+                    ignored: vec![path],
+                });
+            }
+        }
+        if replaced {
+            self.update_library_saved_status();
+        }
+        self.library_conflicts = conflicts;
+        self.library_log.changed();
+    }
+
+    /// Restore a document recovered from an autosave. The recovered document is marked as having unsaved changes.
+    fn recover(&mut self, rec: autosave::Recoverable) {
+        match rec {
+            autosave::Recoverable::Schematic(f) => {
+                self.schematic = Some(SchematicHolder::recovered(f.contents, f.original));
+            }
+            autosave::Recoverable::Library(f) => {
+                let name = f.contents.name.clone();
+                if let Some(libh) = self.libraries.get_mut(&name) {
+                    libh.library = Some(f.contents);
+                } else {
+                    let mut libh = library::LibraryHolder::new(name.clone());
+                    libh.library = Some(f.contents);
+                    if let Some(p) = f.original {
+                        libh.set_path(p);
+                    }
+                    self.libraries.insert(name, libh);
+                }
+                self.library_log.set_saved(false);
+                self.library_log.changed();
+            }
+        }
+    }
+
     fn process_event(&mut self, event: ipc::IpcMessage) -> Vec<NewWindowRequest> {
         let mut windows_to_create = vec![];
         match event {
@@ -170,6 +547,9 @@ impl MyApp {
             ipc::IpcMessage::NewLibrary => {
                 windows_to_create.push(window::library::Library::request());
             }
+            ipc::IpcMessage::LibraryFileChanged(path) => {
+                self.library_file_changed(path);
+            }
         }
         windows_to_create
     }
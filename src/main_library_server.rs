@@ -0,0 +1,21 @@
+//! This package is software for designing electronic schematics and associated circuit boards.
+//!
+//! Runs the reference library server. Usage is `electronics_library_server [folder] [address]`
+
+#![deny(missing_docs)]
+#![deny(clippy::missing_docs_in_private_items)]
+
+fn main() {
+    /// The name of the main executable
+    const NAME: &str = if cfg!(target_os = "windows") {
+        "./electronics_design.exe"
+    } else {
+        "./electronics_design"
+    };
+    let status = std::process::Command::new(NAME)
+        .arg("library-server")
+        .args(std::env::args().skip(1))
+        .status()
+        .expect("Failed to run main program");
+    std::process::exit(status.code().unwrap_or(1));
+}
@@ -0,0 +1,305 @@
+//! Builds the netlist of a schematic, which lists the physical parts of the design and the pins that are connected together.
+//!
+//! The units of a part with more than one unit, such as the gates of a quad nand gate, are placed separately but share a designator,
+//! so they are brought together into one part here. Pins connect when they are in the same place on the same page.
+//!
+//! Pins at the far end of a bus entry join the net named after the member of the entry, together with the pins of
+//! every other entry for that member, on any page. These nets are named after the member instead of being numbered.
+//!
+//! Pins with a no-connect marker are left out of the netlist, and are reported as a problem when something connects
+//! to them anyway.
+
+use std::collections::HashMap;
+
+use crate::bus::BusPart;
+use crate::library::LibraryHolder;
+use crate::schematic::Schematic;
+
+/// The page and location used to find pins in the same place. Locations are whole nanometers, so they are compared
+/// exactly.
+pub fn connection_key(pagenum: usize, c: crate::general::Coordinates) -> (usize, i64, i64) {
+    (pagenum, c.x, c.y)
+}
+
+/// A physical part of the design
+pub struct NetlistPart {
+    /// The designator of the part, such as U1
+    pub designator: String,
+    /// The library of the component
+    pub lib: String,
+    /// The name of the component
+    pub com: String,
+    /// The variant of the component
+    pub var: String,
+    /// The units of the part that are placed, empty for a part with a single unit
+    pub units: Vec<String>,
+}
+
+/// A pin of a part
+#[derive(Clone, PartialEq)]
+pub struct NetNode {
+    /// The designator of the part
+    pub designator: String,
+    /// The number of the pin on the part
+    pub pin: String,
+}
+
+impl std::fmt::Display for NetNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.designator, self.pin)
+    }
+}
+
+/// A set of pins connected together
+pub struct Net {
+    /// The name of the net
+    pub name: String,
+    /// The pins in the net
+    pub nodes: Vec<NetNode>,
+}
+
+/// The parts and connections of a schematic
+pub struct Netlist {
+    /// The parts, sorted by designator
+    pub parts: Vec<NetlistPart>,
+    /// The nets with more than one pin
+    pub nets: Vec<Net>,
+    /// The pins not connected to anything
+    pub unconnected: Vec<NetNode>,
+    /// Problems found while building the netlist, such as parts that are not annotated
+    pub problems: Vec<String>,
+}
+
+impl Netlist {
+    /// Build the netlist for a schematic
+    pub fn build(sch: &Schematic, libs: &HashMap<String, LibraryHolder>) -> Self {
+        let mut parts: Vec<NetlistPart> = Vec::new();
+        let mut problems = Vec::new();
+        let mut connections: Vec<((usize, i64, i64), Vec<NetNode>)> = Vec::new();
+        let mut marked: Vec<((usize, i64, i64), NetNode)> = Vec::new();
+
+        for (pagenum, _, s) in sch.placed() {
+            let prefix = sch.cache.get_prefix(s, libs);
+            let designator = match &s.designator {
+                Some(d) => d.clone(),
+                None => {
+                    problems.push(format!(
+                        "{} on page {} is not annotated",
+                        s.label(&prefix),
+                        pagenum + 1
+                    ));
+                    continue;
+                }
+            };
+            let unit = s.unit.clone().unwrap_or_default();
+            match parts.iter_mut().find(|p| p.designator == designator) {
+                Some(p) => {
+                    if p.lib != s.lib || p.com != s.com || p.var != s.var {
+                        problems.push(format!("Units of {} are different components", designator));
+                    } else if p.units.contains(&unit) || unit.is_empty() {
+                        problems.push(format!("{} is placed more than once", s.label(&prefix)));
+                    } else {
+                        p.units.push(unit.clone());
+                    }
+                }
+                None => parts.push(NetlistPart {
+                    designator: designator.clone(),
+                    lib: s.lib.clone(),
+                    com: s.com.clone(),
+                    var: s.var.clone(),
+                    units: if unit.is_empty() {
+                        Vec::new()
+                    } else {
+                        vec![unit.clone()]
+                    },
+                }),
+            }
+
+            if let Some(symbol) = sch.cache.get_symbol(s, libs) {
+                for (i, pin) in symbol.pins.iter().enumerate() {
+                    let pinnum = s.pin_number(i, pin);
+                    let key =
+                        connection_key(pagenum, s.pos + s.orientation.transform(pin.location));
+                    let marked_pin = s.no_connect.contains(&pinnum);
+                    let node = NetNode {
+                        designator: designator.clone(),
+                        pin: pinnum,
+                    };
+                    if marked_pin {
+                        marked.push((key, node));
+                        continue;
+                    }
+                    match connections.iter_mut().find(|(k, _)| *k == key) {
+                        Some((_, nodes)) => {
+                            if !nodes.contains(&node) {
+                                nodes.push(node);
+                            }
+                        }
+                        None => connections.push((key, vec![node])),
+                    }
+                }
+            }
+        }
+
+        for p in &parts {
+            let units = sch
+                .placed()
+                .map(|(_, _, s)| s)
+                .find(|s| s.designator.as_ref() == Some(&p.designator))
+                .and_then(|s| sch.cache.get_component(s, libs))
+                .and_then(|c| c.variants.get(&p.var).cloned())
+                .map(|v| v.units)
+                .unwrap_or_default();
+            for u in units {
+                if !p.units.contains(&u.name) {
+                    problems.push(format!("Unit {} of {} is not placed", u.name, p.designator));
+                }
+            }
+        }
+
+        // The member broken out of a bus at each place, from the bus entries of every page
+        let mut members: Vec<((usize, i64, i64), &String)> = Vec::new();
+        for (pagenum, page) in sch.pages.iter().enumerate() {
+            let networks = crate::bus::networks(&page.bus);
+            for b in &page.bus {
+                match b {
+                    BusPart::Entry { from, to, member } => {
+                        match networks.iter().find(|n| n.touches(&page.bus, *from)) {
+                            None => problems.push(format!(
+                                "Bus entry {} on page {} is not on a bus",
+                                member,
+                                pagenum + 1
+                            )),
+                            Some(n) if !n.members.is_empty() && !n.members.contains(member) => {
+                                problems.push(format!(
+                                    "{} on page {} is not a member of its bus",
+                                    member,
+                                    pagenum + 1
+                                ))
+                            }
+                            Some(_) => {}
+                        }
+                        members.push((connection_key(pagenum, *to), member));
+                    }
+                    BusPart::Label { location, text } => {
+                        if crate::bus::members(text).is_none() {
+                            if crate::bus::range(text).is_some() {
+                                problems.push(format!(
+                                    "Bus label {} on page {} names more than {} members",
+                                    text,
+                                    pagenum + 1,
+                                    crate::bus::MAX_MEMBERS
+                                ));
+                            } else {
+                                problems.push(format!(
+                                    "Bus label {} on page {} is not in vector notation",
+                                    text,
+                                    pagenum + 1
+                                ));
+                            }
+                        }
+                        if !networks.iter().any(|n| n.touches(&page.bus, *location)) {
+                            problems.push(format!(
+                                "Bus label {} on page {} is not on a bus",
+                                text,
+                                pagenum + 1
+                            ));
+                        }
+                    }
+                    BusPart::Wire { .. } => {}
+                }
+            }
+        }
+
+        for (key, node) in &marked {
+            let connected = connections.iter().any(|(k, _)| k == key)
+                || members.iter().any(|(k, _)| k == key)
+                || marked.iter().any(|(k, n)| k == key && n != node);
+            if connected {
+                problems.push(format!(
+                    "{} is marked as not connected but is connected",
+                    node
+                ));
+            }
+        }
+
+        parts.sort_by(|a, b| compare_designators(&a.designator, &b.designator));
+        for p in &mut parts {
+            p.units.sort();
+        }
+        let mut nets = Vec::new();
+        let mut unconnected = Vec::new();
+        let mut named: Vec<Net> = Vec::new();
+        for (key, mut nodes) in connections {
+            if let Some((_, member)) = members.iter().find(|(k, _)| *k == key) {
+                match named.iter_mut().find(|n| n.name == **member) {
+                    Some(n) => {
+                        for node in nodes {
+                            if !n.nodes.contains(&node) {
+                                n.nodes.push(node);
+                            }
+                        }
+                    }
+                    None => named.push(Net {
+                        name: member.to_string(),
+                        nodes,
+                    }),
+                }
+            } else if nodes.len() == 1 {
+                unconnected.append(&mut nodes);
+            } else {
+                nets.push(Net {
+                    name: format!("N{}", nets.len() + 1),
+                    nodes,
+                });
+            }
+        }
+        named.sort_by(|a, b| compare_designators(&a.name, &b.name));
+        for mut n in named {
+            if n.nodes.len() == 1 {
+                unconnected.append(&mut n.nodes);
+            } else {
+                nets.push(n);
+            }
+        }
+        Self {
+            parts,
+            nets,
+            unconnected,
+            problems,
+        }
+    }
+
+    /// Write the netlist as text
+    pub fn write(&self, w: &mut impl std::io::Write) -> std::io::Result<()> {
+        writeln!(w, "PARTS")?;
+        for p in &self.parts {
+            write!(w, "{} {} {} {}", p.designator, p.lib, p.com, p.var)?;
+            if !p.units.is_empty() {
+                write!(w, " units {}", p.units.join(" "))?;
+            }
+            writeln!(w)?;
+        }
+        writeln!(w, "NETS")?;
+        for n in &self.nets {
+            let nodes: Vec<String> = n.nodes.iter().map(|n| n.to_string()).collect();
+            writeln!(w, "{} {}", n.name, nodes.join(" "))?;
+        }
+        writeln!(w, "UNCONNECTED")?;
+        for n in &self.unconnected {
+            writeln!(w, "{}", n)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare designators so that U2 comes before U10
+fn compare_designators(a: &str, b: &str) -> std::cmp::Ordering {
+    match (
+        crate::schematic::split_designator(a),
+        crate::schematic::split_designator(b),
+    ) {
+        (Some(a), Some(b)) => a.cmp(&b),
+        _ => a.cmp(b),
+    }
+}
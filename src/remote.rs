@@ -0,0 +1,463 @@
+//! Defines the protocol for sharing libraries over a network, a client for that protocol, and a reference server.
+//!
+//! The protocol is plain http, with json bodies.
+//! * `GET /libraries` lists the libraries on the server, see [LibraryListing]
+//! * `GET /libraries/{lib}` fetches an entire library
+//! * `GET /libraries/{lib}/symbols/{sym}` fetches a single symbol from a library
+//! * `GET /libraries/{lib}/components/{com}` fetches a single component from a library
+//! * `PUT /libraries/{lib}` publishes a new version of a library. The version must be newer than the one on the server.
+//!
+//! Failed requests respond with an error status code and an [ErrorResponse] body.
+
+use crate::{
+    component::ComponentDefinition,
+    library::{Library, LibraryHolder, LibraryVersion},
+    symbol::SymbolDefinition,
+};
+
+/// A summary of a single library available on a library server
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct LibraryListing {
+    /// The name of the library
+    pub name: String,
+    /// The version of the library currently on the server
+    pub version: LibraryVersion,
+    /// The names of the symbols in the library
+    pub symbols: Vec<String>,
+    /// The names of the components in the library
+    pub components: Vec<String>,
+}
+
+impl LibraryListing {
+    /// Build the listing for a library
+    fn new(lib: &Library) -> Self {
+        let mut symbols: Vec<String> = lib.syms.keys().cloned().collect();
+        symbols.sort();
+        let mut components: Vec<String> = lib.components.keys().cloned().collect();
+        components.sort();
+        Self {
+            name: lib.name.clone(),
+            version: lib.version,
+            symbols,
+            components,
+        }
+    }
+}
+
+/// The body sent by a server when a request fails
+#[derive(serde::Serialize, serde::Deserialize)]
+pub struct ErrorResponse {
+    /// A description of what went wrong
+    pub error: String,
+}
+
+/// The kinds of errors that can occur when talking to a library server
+#[derive(Debug)]
+pub enum RemoteError {
+    /// The server could not be reached
+    Transport(String),
+    /// The server refused the request, with the status code and reason given
+    Rejected(u16, String),
+    /// The response from the server could not be understood
+    Decode(String),
+}
+
+impl std::fmt::Display for RemoteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Transport(e) => write!(f, "Unable to reach library server: {}", e),
+            Self::Rejected(code, e) => {
+                write!(f, "Library server refused request ({}): {}", code, e)
+            }
+            Self::Decode(e) => write!(f, "Invalid response from library server: {}", e),
+        }
+    }
+}
+
+/// Encode a name so that it can be used as a single url path segment
+fn encode(name: &str) -> String {
+    let mut s = String::new();
+    for b in name.bytes() {
+        if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) {
+            s.push(b as char);
+        } else {
+            s.push_str(&format!("%{:02X}", b));
+        }
+    }
+    s
+}
+
+/// Decode a url path segment created by [encode]
+fn decode(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = std::str::from_utf8(bytes.get(i + 1..i + 3)?).ok()?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Convert the result of a request into a response or a [RemoteError]
+fn check(r: Result<ureq::Response, ureq::Error>) -> Result<ureq::Response, RemoteError> {
+    match r {
+        Ok(r) => Ok(r),
+        Err(ureq::Error::Status(code, r)) => {
+            let reason = r
+                .into_string()
+                .ok()
+                .and_then(|s| serde_json::from_str::<ErrorResponse>(&s).ok())
+                .map(|e| e.error)
+                .unwrap_or_default();
+            Err(RemoteError::Rejected(code, reason))
+        }
+        Err(e) => Err(RemoteError::Transport(e.to_string())),
+    }
+}
+
+/// Perform a get request, returning a reader for the body of the response
+pub fn get(url: &str) -> Result<impl std::io::Read, RemoteError> {
+    Ok(check(ureq::get(url).call())?.into_reader())
+}
+
+/// Perform a put request with a json body
+pub fn put(url: &str, body: &[u8]) -> Result<(), RemoteError> {
+    check(
+        ureq::put(url)
+            .set("Content-Type", "application/json")
+            .send_bytes(body),
+    )?;
+    Ok(())
+}
+
+/// Perform a get request, decoding the json response
+fn get_json<T>(url: &str) -> Result<T, RemoteError>
+where
+    T: for<'a> serde::Deserialize<'a>,
+{
+    serde_json::from_reader(get(url)?).map_err(|e| RemoteError::Decode(e.to_string()))
+}
+
+/// Collects everything written to it, then sends it to a library server when committed
+pub struct RemoteWriter {
+    /// The url to send the data to
+    url: String,
+    /// The data waiting to be sent
+    buffer: Vec<u8>,
+}
+
+impl RemoteWriter {
+    /// Create a writer that sends to the given url
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            buffer: Vec::new(),
+        }
+    }
+}
+
+impl std::io::Write for RemoteWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl crate::general::StorageWriter for RemoteWriter {
+    fn commit(self: Box<Self>) -> Result<(), crate::general::StoragePathError> {
+        Ok(put(&self.url, &self.buffer)?)
+    }
+}
+
+/// A client for a library server
+#[derive(Clone)]
+pub struct Client {
+    /// The base url of the server, without a trailing slash
+    base: String,
+}
+
+impl Client {
+    /// Create a client for the server at the given url, such as `http://localhost:8080`
+    pub fn new(url: &str) -> Self {
+        Self {
+            base: url.trim().trim_end_matches('/').to_string(),
+        }
+    }
+
+    /// The url for a library on the server. This is what [crate::general::StoragePath::Remote] holds.
+    pub fn library_url(&self, lib: &str) -> String {
+        format!("{}/libraries/{}", self.base, encode(lib))
+    }
+
+    /// List the libraries available on the server
+    pub fn list(&self) -> Result<Vec<LibraryListing>, RemoteError> {
+        get_json(&format!("{}/libraries", self.base))
+    }
+
+    /// Fetch a single symbol from a library on the server
+    pub fn fetch_symbol(&self, lib: &str, sym: &str) -> Result<SymbolDefinition, RemoteError> {
+        get_json(&format!(
+            "{}/symbols/{}",
+            self.library_url(lib),
+            encode(sym)
+        ))
+    }
+
+    /// Fetch a single component from a library on the server
+    pub fn fetch_component(
+        &self,
+        lib: &str,
+        com: &str,
+    ) -> Result<ComponentDefinition, RemoteError> {
+        get_json(&format!(
+            "{}/components/{}",
+            self.library_url(lib),
+            encode(com)
+        ))
+    }
+}
+
+/// The response type used by the reference server
+type ServerResponse = tiny_http::Response<std::io::Cursor<Vec<u8>>>;
+
+/// Build a json response for the reference server
+fn respond_json<T: serde::Serialize>(code: u16, body: &T) -> ServerResponse {
+    let data = serde_json::to_vec(body).unwrap_or_default();
+    tiny_http::Response::from_data(data)
+        .with_status_code(code)
+        .with_header(
+            tiny_http::Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap(),
+        )
+}
+
+/// Build an error response for the reference server
+fn respond_error(code: u16, error: String) -> ServerResponse {
+    respond_json(code, &ErrorResponse { error })
+}
+
+/// Handle a single request for the reference server, serving libraries found in dir
+fn handle(dir: &std::path::Path, request: &mut tiny_http::Request) -> ServerResponse {
+    let url = request.url().split('?').next().unwrap_or("").to_string();
+    let segments: Option<Vec<String>> = url
+        .split('/')
+        .filter(|s| !s.is_empty())
+        .map(decode)
+        .collect();
+    let segments = match segments {
+        Some(s) => s,
+        None => return respond_error(400, "Invalid url".to_string()),
+    };
+    let libs = LibraryHolder::load_folder(dir);
+    let find = |name: &str| {
+        libs.iter()
+            .find(|l| l.library.as_ref().map_or(false, |l| l.name == name))
+    };
+    let segments: Vec<&str> = segments.iter().map(|s| s.as_str()).collect();
+    let method = request.method().clone();
+    match (method, &segments[..]) {
+        (tiny_http::Method::Get, ["libraries"]) => {
+            let mut list: Vec<LibraryListing> = libs
+                .iter()
+                .filter_map(|l| l.library.as_ref().map(LibraryListing::new))
+                .collect();
+            list.sort_by(|a, b| a.name.cmp(&b.name));
+            respond_json(200, &list)
+        }
+        (tiny_http::Method::Get, ["libraries", lib]) => match find(lib) {
+            Some(l) => respond_json(200, &l.library),
+            None => respond_error(404, format!("No library named {}", lib)),
+        },
+        (tiny_http::Method::Get, ["libraries", lib, "symbols", sym]) => {
+            match find(lib).and_then(|l| l.library.as_ref()) {
+                Some(l) => match l.syms.get(*sym) {
+                    Some(s) => respond_json(200, s),
+                    None => respond_error(404, format!("No symbol named {} in {}", sym, lib)),
+                },
+                None => respond_error(404, format!("No library named {}", lib)),
+            }
+        }
+        (tiny_http::Method::Get, ["libraries", lib, "components", com]) => {
+            match find(lib).and_then(|l| l.library.as_ref()) {
+                Some(l) => match l.components.get(*com) {
+                    Some(c) => respond_json(200, c),
+                    None => respond_error(404, format!("No component named {} in {}", com, lib)),
+                },
+                None => respond_error(404, format!("No library named {}", lib)),
+            }
+        }
+        (tiny_http::Method::Put, ["libraries", lib]) => {
+            let newlib: Library = match serde_json::from_reader(request.as_reader()) {
+                Ok(l) => l,
+                Err(e) => return respond_error(400, e.to_string()),
+            };
+            if newlib.name != *lib {
+                return respond_error(400, format!("Library {} sent to {}", newlib.name, lib));
+            }
+            let path = match find(lib) {
+                Some(old) => {
+                    if let Some(oldlib) = &old.library {
+                        if oldlib.version >= newlib.version {
+                            return respond_error(
+                                409,
+                                format!(
+                                    "Version {} must be newer than the published version {}",
+                                    newlib.version, oldlib.version
+                                ),
+                            );
+                        }
+                    }
+                    old.path.clone()
+                }
+                None => None,
+            };
+            let path = path.unwrap_or_else(|| {
+                let mut p = dir.to_path_buf();
+                p.push(format!("{}.uol", encode(lib)));
+                crate::general::StoragePath::LocalFilesystem(
+                    p.into_os_string().into_string().unwrap(),
+                )
+            });
+            let version = newlib.version;
+            let mut holder = LibraryHolder::new(newlib.name.clone());
+            holder.library = Some(newlib);
+            holder.set_path(path);
+            match holder.save() {
+                Ok(()) => {
+                    println!("Published {} version {}", lib, version);
+                    respond_json(200, &version)
+                }
+                Err(e) => respond_error(500, e.to_string()),
+            }
+        }
+        _ => respond_error(404, format!("Nothing at {}", url)),
+    }
+}
+
+/// Run the reference library server, serving the libraries in dir at the given address. Only returns when the server cannot be started.
+pub fn run_server(dir: &std::path::Path, addr: &str) -> Result<(), String> {
+    let server = tiny_http::Server::http(addr).map_err(|e| e.to_string())?;
+    println!("Serving libraries from {} at {}", dir.display(), addr);
+    serve(&server, dir);
+    Ok(())
+}
+
+/// Answer requests on an already bound server with the libraries in dir, forever
+fn serve(server: &tiny_http::Server, dir: &std::path::Path) {
+    for mut request in server.incoming_requests() {
+        let response = handle(dir, &mut request);
+        if let Err(e) = request.respond(response) {
+            println!("Error responding to request {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Start a reference server for a fresh temporary folder on a free local port, returning a client for it and the folder
+    fn start_server(test: &str) -> (Client, std::path::PathBuf) {
+        let dir = std::env::temp_dir().join(format!(
+            "uglyoldbob-remote-{}-{}",
+            test,
+            std::process::id()
+        ));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let server = tiny_http::Server::http("127.0.0.1:0").unwrap();
+        let port = server.server_addr().to_ip().unwrap().port();
+        let served = dir.clone();
+        std::thread::spawn(move || serve(&server, &served));
+        (Client::new(&format!("http://127.0.0.1:{}", port)), dir)
+    }
+
+    /// A library holder that publishes the library named name to the server behind client
+    fn remote_holder(client: &Client, name: &str) -> LibraryHolder {
+        let mut library = Library::new(name.to_string());
+        library
+            .syms
+            .insert("R".to_string(), SymbolDefinition::new("R".to_string()));
+        library.components.insert(
+            "Resistor".to_string(),
+            ComponentDefinition::new("Resistor".to_string()),
+        );
+        let mut holder = LibraryHolder::new(name.to_string());
+        holder.library = Some(library);
+        holder.set_path(crate::general::StoragePath::Remote(
+            client.library_url(name),
+        ));
+        holder
+    }
+
+    #[test]
+    fn publish_and_fetch() {
+        let (client, dir) = start_server("fetch");
+        assert!(client.list().unwrap().is_empty());
+
+        let mut holder = remote_holder(&client, "parts lib");
+        assert!(holder.publish(false).is_ok());
+
+        let listing = client.list().unwrap();
+        assert_eq!(listing.len(), 1);
+        assert_eq!(listing[0].name, "parts lib");
+        assert!(listing[0].version == LibraryVersion { major: 0, minor: 1 });
+        assert_eq!(listing[0].symbols, vec!["R".to_string()]);
+        assert_eq!(listing[0].components, vec!["Resistor".to_string()]);
+
+        assert!(client.fetch_symbol("parts lib", "R").is_ok());
+        assert!(client.fetch_component("parts lib", "Resistor").is_ok());
+        assert!(matches!(
+            client.fetch_symbol("parts lib", "C"),
+            Err(RemoteError::Rejected(404, _))
+        ));
+
+        let loaded = match LibraryHolder::load(crate::general::StoragePath::Remote(
+            client.library_url("parts lib"),
+        )) {
+            Ok(l) => l.library.unwrap(),
+            Err(e) => panic!("Failed to load the published library: {}", e),
+        };
+        assert!(loaded.version == LibraryVersion { major: 0, minor: 1 });
+        assert!(loaded.syms.contains_key("R"));
+        assert!(loaded.components.contains_key("Resistor"));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn publish_requires_newer_version() {
+        let (client, dir) = start_server("version");
+        let mut first = remote_holder(&client, "parts");
+        let mut second = remote_holder(&client, "parts");
+        assert!(first.publish(false).is_ok());
+
+        assert!(second.publish(false).is_err());
+        let version = second.library.as_ref().unwrap().version;
+        assert!(version == LibraryVersion::default());
+
+        let mut stale = Library::new("parts".to_string());
+        stale.version = LibraryVersion { major: 0, minor: 1 };
+        assert!(matches!(
+            put(
+                &client.library_url("parts"),
+                &serde_json::to_vec(&stale).unwrap()
+            ),
+            Err(RemoteError::Rejected(409, _))
+        ));
+
+        assert!(first.publish(true).is_ok());
+        let listing = client.list().unwrap();
+        assert!(listing[0].version == LibraryVersion { major: 1, minor: 0 });
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -5,10 +5,49 @@ use std::collections::HashMap;
 use egui_multiwin::egui::{self, Rect};
 
 use crate::{
-    component::ComponentVariantReference, general::StoragePath, library::LibraryHolder,
-    symbol::Symbol,
+    bus::BusPart,
+    component::ComponentVariantReference,
+    general::StoragePath,
+    geometry::{BoundingBox, Point},
+    library::LibraryHolder,
+    spatial::PageObject,
+    symbol::{Symbol, SymbolPlacement},
+    variables::TextVariables,
 };
 
+/// How far in millimeters a placed component is taken to reach from its position when its symbol is in neither the
+/// schematic copies nor the libraries
+const UNKNOWN_SYMBOL_REACH: f32 = 25.4;
+
+/// The width in points of the lines drawn for bus wires in a pdf, where other lines are one point wide
+const BUS_PDF_WIDTH: f64 = 3.0;
+
+/// The width in screen points of the lines drawn for bus wires, where other lines are two points wide
+const BUS_SCREEN_WIDTH: f32 = 5.0;
+
+/// The distance from the middle of a no-connect marker to its corners
+const NO_CONNECT_REACH: crate::general::Coordinates =
+    crate::general::Coordinates::from_mils(25, 25);
+
+/// The two lines of the X drawn for a no-connect marker on a pin at a location
+fn no_connect_lines(
+    at: crate::general::Coordinates,
+) -> [(crate::general::Coordinates, crate::general::Coordinates); 2] {
+    let r = NO_CONNECT_REACH;
+    let flipped = crate::general::Coordinates::from_nm(r.x, -r.y);
+    [(at - r, at + r), (at - flipped, at + flipped)]
+}
+
+/// The text of a newly placed bus label
+const NEW_BUS_LABEL: &str = "D[0..7]";
+
+/// The member given to a new bus entry that is not placed on a labeled bus
+const DEFAULT_BUS_MEMBER: &str = "D0";
+
+/// How far in millimeters from the pointer an object can be and still be picked, which makes up for the estimated
+/// width of text in the index of a page
+const PICK_DISTANCE: f32 = 2.54;
+
 #[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq)]
 #[serde(tag = "type", content = "args")]
 #[non_exhaustive]
@@ -18,6 +57,8 @@ pub enum Colors {
     Standard,
     /// The color for the paper border
     Border,
+    /// The color for buses, their entries and their labels
+    Bus,
     /// A custom color. See [egui::Color32] for the `to_srgba_unmultiplied` function
     Custom([u8; 4]),
 }
@@ -36,6 +77,11 @@ impl Colors {
                 crate::general::ColorMode::ScreenModeLight => egui::Color32::from_rgb(0, 0, 255),
                 crate::general::ColorMode::PrintingMode => egui::Color32::from_rgb(255, 255, 255),
             },
+            Colors::Bus => match mode {
+                crate::general::ColorMode::ScreenModeDark => egui::Color32::from_rgb(0, 200, 255),
+                crate::general::ColorMode::ScreenModeLight => egui::Color32::from_rgb(0, 100, 200),
+                crate::general::ColorMode::PrintingMode => egui::Color32::from_rgb(0, 0, 0),
+            },
             Colors::Custom(c) => egui::Color32::from_rgba_unmultiplied(c[0], c[1], c[2], c[3]),
         }
     }
@@ -55,57 +101,351 @@ pub struct TextOnPage {
     pub size: crate::general::Length,
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
 #[non_exhaustive]
 /// A single page of an electronic schematic
 pub struct Page {
+    /// The name of the page, which can be empty
+    #[serde(default)]
+    pub name: String,
     /// The symbols on the page
     pub syms: Vec<ComponentVariantReference>,
     /// The free text items on the page
     pub texts: Vec<TextOnPage>,
     /// The physical size of the page
     pub size: crate::general::Coordinates,
+    /// The paper size, border and title block of the page. The size of the page matches the paper size.
+    #[serde(default)]
+    pub format: crate::sheet::PageFormat,
+    /// The bus wires, bus entries and bus labels on the page
+    #[serde(default)]
+    pub bus: Vec<BusPart>,
+    /// Where the objects of the page are, built when it is first needed
+    #[serde(skip)]
+    pub index: crate::spatial::SpatialIndex,
 }
 
 impl Page {
-    /// Draw the page on the given pdf layer
-    pub fn draw_on(&self, layer: printpdf::PdfLayerReference, font: &printpdf::IndirectFontRef) {
-        let points1 = vec![
-            (
-                printpdf::Point::new(printpdf::Mm(10.0), printpdf::Mm(10.0)),
-                false,
-            ),
-            (
-                printpdf::Point::new(printpdf::Mm(10.0), printpdf::Mm(20.0)),
-                false,
-            ),
-            (
-                printpdf::Point::new(printpdf::Mm(30.0), printpdf::Mm(20.0)),
-                false,
-            ),
-            (
-                printpdf::Point::new(printpdf::Mm(30.0), printpdf::Mm(10.0)),
-                false,
-            ),
-        ];
-        let line = printpdf::Line {
-            points: points1,
-            is_closed: true,
-            has_fill: false,
-            has_stroke: true,
-            is_clipping_path: false,
+    /// The places on the page that objects snap to: the pins and positions of placed components and the locations of text.
+    /// The selected objects, such as the ones being moved, are left out.
+    pub fn snap_targets(
+        &self,
+        pagenum: usize,
+        cache: &crate::cache::LibraryCache,
+        libs: &HashMap<String, LibraryHolder>,
+        skip: &[SchematicSelection],
+    ) -> Vec<crate::general::Coordinates> {
+        let mut targets = Vec::new();
+        for (textnum, t) in self.texts.iter().enumerate() {
+            if !skip.contains(&SchematicSelection::Text {
+                page: pagenum,
+                textnum,
+            }) {
+                targets.push(t.location);
+            }
+        }
+        for (sym, s) in self.syms.iter().enumerate() {
+            if skip.contains(&SchematicSelection::Symbol { page: pagenum, sym }) {
+                continue;
+            }
+            targets.push(s.pos);
+            if let Some(symbol) = cache.get_symbol(s, libs) {
+                targets.extend(
+                    symbol
+                        .pins
+                        .iter()
+                        .map(|p| s.pos + s.orientation.transform(p.location)),
+                );
+            }
+        }
+        for (partnum, b) in self.bus.iter().enumerate() {
+            if !skip.contains(&SchematicSelection::Bus {
+                page: pagenum,
+                partnum,
+            }) {
+                targets.extend(b.snap_points());
+            }
+        }
+        targets
+    }
+
+    /// The pins of the placed components of the page, as the component number, the number of the pin and where it is
+    pub fn pins(
+        &self,
+        cache: &crate::cache::LibraryCache,
+        libs: &HashMap<String, LibraryHolder>,
+    ) -> Vec<(usize, String, crate::general::Coordinates)> {
+        let mut pins = Vec::new();
+        for (symnum, s) in self.syms.iter().enumerate() {
+            if let Some(symbol) = cache.get_symbol(s, libs) {
+                for (i, pin) in symbol.pins.iter().enumerate() {
+                    pins.push((
+                        symnum,
+                        s.pin_number(i, pin),
+                        s.pos + s.orientation.transform(pin.location),
+                    ));
+                }
+            }
+        }
+        pins
+    }
+
+    /// Create an empty page with the same size and format as another page
+    pub fn blank_like(other: &Page) -> Self {
+        Self {
+            name: String::new(),
+            syms: Vec::new(),
+            texts: Vec::new(),
+            size: other.size,
+            format: other.format.clone(),
+            bus: Vec::new(),
+            index: crate::spatial::SpatialIndex::default(),
+        }
+    }
+
+    /// A copy of the page with a new name, where the placed components have no designators so they can be annotated again
+    pub fn duplicate(&self, name: String) -> Self {
+        let mut page = self.clone();
+        page.name = name;
+        for s in &mut page.syms {
+            s.designator = None;
+        }
+        page.index.invalidate();
+        page
+    }
+
+    /// The member for a new bus entry placed at a point, the first member of the bus there that no entry on the page
+    /// breaks out yet
+    fn next_bus_member(&self, at: crate::general::Coordinates) -> String {
+        let used: Vec<&String> = self
+            .bus
+            .iter()
+            .filter_map(|b| match b {
+                BusPart::Entry { member, .. } => Some(member),
+                _ => None,
+            })
+            .collect();
+        crate::bus::networks(&self.bus)
+            .into_iter()
+            .find(|n| n.touches(&self.bus, at))
+            .and_then(|n| n.members.into_iter().find(|m| !used.contains(&m)))
+            .unwrap_or_else(|| DEFAULT_BUS_MEMBER.to_string())
+    }
+
+    /// The area covered by an object of the page in millimeters, from the copies of library items in cache, or from
+    /// libs for items not copied yet. The width of text is estimated from the text with page_vars filled in, and a
+    /// component whose symbol cannot be found is given room around its position.
+    pub fn object_box(
+        &self,
+        obj: PageObject,
+        cache: &crate::cache::LibraryCache,
+        libs: &HashMap<String, LibraryHolder>,
+        page_vars: &TextVariables,
+    ) -> BoundingBox {
+        let width = |text: &str, height: f32| {
+            text.chars().count() as f32 * height * crate::symbol::PDF_CHARACTER_WIDTH
         };
-        layer.add_shape(line);
+        match obj {
+            PageObject::Text(i) => {
+                let t = &self.texts[i];
+                let height = t.size.get_mm();
+                let min = Point::from(t.location);
+                BoundingBox {
+                    min,
+                    max: min + Point::new(width(&page_vars.substitute(&t.text), height), height),
+                }
+            }
+            PageObject::Symbol(i) => {
+                let s = &self.syms[i];
+                let (prefix, defaults) = cache.get_field_defaults(s, libs);
+                let vars = s.variables(page_vars, &prefix, &defaults);
+                let placement = SymbolPlacement {
+                    variables: Some(&vars),
+                    ..s.placement()
+                };
+                let body = cache
+                    .get_symbol(s, libs)
+                    .and_then(|symbol| symbol.bounding_box(s.pos, placement))
+                    .unwrap_or_else(|| BoundingBox::around(s.pos.into(), UNKNOWN_SYMBOL_REACH));
+                let height = crate::component::FIELD_SIZE.get_mm();
+                s.fields
+                    .iter()
+                    .filter_map(|f| {
+                        let text = vars
+                            .substitute(&s.field_text(&f.name, &prefix, &defaults)?)
+                            .into_owned();
+                        let corner = Point::from(s.pos + f.location);
+                        Some(BoundingBox {
+                            min: corner - Point::new(0.0, height),
+                            max: corner + Point::new(width(&text, height), 0.0),
+                        })
+                    })
+                    .fold(body, |a, b| a.union(&b))
+            }
+            PageObject::Bus(i) => {
+                let height = crate::component::FIELD_SIZE.get_mm();
+                match &self.bus[i] {
+                    BusPart::Wire { from, to } => {
+                        BoundingBox::from_points([(*from).into(), (*to).into()])
+                            .unwrap_or_else(|| BoundingBox::around((*from).into(), 0.0))
+                    }
+                    BusPart::Entry { from, to, member } => {
+                        let end = Point::from(*to);
+                        BoundingBox::around((*from).into(), 0.0).union(&BoundingBox {
+                            min: end - Point::new(0.0, height),
+                            max: end + Point::new(width(member, height), height),
+                        })
+                    }
+                    BusPart::Label { location, text } => {
+                        let min = Point::from(*location);
+                        BoundingBox {
+                            min,
+                            max: min + Point::new(width(text, height), height),
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    /// The name of the page to show to the user, where pagenum is the index of the page
+    pub fn title(&self, pagenum: usize) -> String {
+        if self.name.is_empty() {
+            format!("Page {}", pagenum + 1)
+        } else {
+            self.name.clone()
+        }
+    }
+
+    /// The border and title block of the page, with the title block text filled in from page_vars
+    pub fn sheet(
+        &self,
+        cache: &crate::cache::LibraryCache,
+        libs: &HashMap<String, LibraryHolder>,
+        page_vars: &TextVariables,
+    ) -> crate::sheet::SheetDrawing {
+        let block = self.format.get_title_block(cache, libs);
+        self.format.drawing(self.size, block, page_vars)
+    }
+
+    /// Draw the page on the given pdf layer, with the placed components drawn from the cache or the libraries.
+    /// page_vars are the text variables of the page.
+    pub fn draw_on(
+        &self,
+        layer: printpdf::PdfLayerReference,
+        font: &printpdf::IndirectFontRef,
+        cache: &crate::cache::LibraryCache,
+        libs: &HashMap<String, LibraryHolder>,
+        page_vars: &TextVariables,
+    ) {
+        let sheet = self.sheet(cache, libs, page_vars);
+        for (a, b) in &sheet.lines {
+            let line = printpdf::Line {
+                points: vec![
+                    (crate::geometry::Point::from(*a).to_pdf(), false),
+                    (crate::geometry::Point::from(*b).to_pdf(), false),
+                ],
+                is_closed: false,
+                has_fill: false,
+                has_stroke: true,
+                is_clipping_path: false,
+            };
+            layer.add_shape(line);
+        }
+        for t in &sheet.texts {
+            let (mut x, mut y) = t.location;
+            if t.centered {
+                x -= t.text.chars().count() as f32 * t.size * crate::symbol::PDF_CHARACTER_WIDTH
+                    / 2.0;
+                y -= t.size / 2.0;
+            }
+            layer.use_text(
+                t.text.clone(),
+                (t.size * 2.85).into(),
+                printpdf::Mm(x.into()),
+                printpdf::Mm(y.into()),
+                font,
+            );
+        }
         for text in &self.texts {
             let (x, y) = text.location.get_mm();
             layer.use_text(
-                text.text.clone(),
+                page_vars.substitute(&text.text),
                 (text.size.get_mm() * 2.85).into(),
                 printpdf::Mm(x.into()),
                 printpdf::Mm(y.into()),
                 font,
             );
         }
+        let line =
+            |from: crate::general::Coordinates, to: crate::general::Coordinates| printpdf::Line {
+                points: vec![
+                    (Point::from(from).to_pdf(), false),
+                    (Point::from(to).to_pdf(), false),
+                ],
+                is_closed: false,
+                has_fill: false,
+                has_stroke: true,
+                is_clipping_path: false,
+            };
+        let field_size = crate::component::FIELD_SIZE.get_mm();
+        for sym in &self.syms {
+            if let Some(symbol) = cache.get_symbol(sym, libs) {
+                let (prefix, defaults) = cache.get_field_defaults(sym, libs);
+                let vars = sym.variables(page_vars, &prefix, &defaults);
+                let placement = SymbolPlacement {
+                    variables: Some(&vars),
+                    ..sym.placement()
+                };
+                symbol.draw_on(&layer, font, sym.pos, placement);
+                for f in &sym.fields {
+                    if let Some(text) = sym.field_text(&f.name, &prefix, &defaults) {
+                        let text = vars.substitute(&text).into_owned();
+                        // Fields are placed by their top left corner, pdf text by its baseline
+                        let (x, y) = (sym.pos + f.location).get_mm();
+                        layer.use_text(
+                            text,
+                            (field_size * 2.85).into(),
+                            printpdf::Mm(x.into()),
+                            printpdf::Mm((y - field_size).into()),
+                            font,
+                        );
+                    }
+                }
+            }
+        }
+        let pins = self.pins(cache, libs);
+        for (_, _, at) in pins
+            .iter()
+            .filter(|(n, pin, _)| self.syms[*n].no_connect.contains(pin))
+        {
+            for (from, to) in no_connect_lines(*at) {
+                layer.add_shape(line(from, to));
+            }
+        }
+        for b in &self.bus {
+            let (text, at) = match b {
+                BusPart::Wire { from, to } => {
+                    layer.set_outline_thickness(BUS_PDF_WIDTH);
+                    layer.add_shape(line(*from, *to));
+                    layer.set_outline_thickness(1.0);
+                    continue;
+                }
+                BusPart::Entry { from, to, member } => {
+                    layer.add_shape(line(*from, *to));
+                    (member, *to)
+                }
+                BusPart::Label { location, text } => (text, *location),
+            };
+            let (x, y) = at.get_mm();
+            layer.use_text(
+                text.clone(),
+                (field_size * 2.85).into(),
+                printpdf::Mm(x.into()),
+                printpdf::Mm(y.into()),
+                font,
+            );
+        }
     }
 }
 
@@ -117,6 +457,19 @@ pub struct Schematic {
     pub pages: Vec<Page>,
     /// The name of the schematic
     name: String,
+    /// The libraries the schematic uses. Libraries that components are placed from are added when the schematic is saved.
+    #[serde(default)]
+    pub libraries: Vec<String>,
+    /// Copies of the library items used by the schematic
+    #[serde(default)]
+    pub cache: crate::cache::LibraryCache,
+    /// The text variables of the schematic, such as TITLE and REVISION, by name
+    #[serde(default)]
+    pub variables: HashMap<String, String>,
+    /// Counts changes to the number and order of the pages, so that a window holding a page number can tell when
+    /// the number may point at another page
+    #[serde(skip)]
+    pub page_changes: u32,
 }
 
 /// Defines the mode for mouse interaction for schematics
@@ -131,58 +484,610 @@ pub enum MouseMode {
     NewText,
     /// Allows a user to add components to a schematic
     NewComponent,
+    /// Allows a user to draw bus wires by dragging from one end to the other
+    NewBus,
+    /// Allows a user to add bus entries, breaking one member out of a bus
+    NewBusEntry,
+    /// Allows a user to add bus labels, naming the members of a bus
+    NewBusLabel,
+    /// Allows a user to mark pins as left unconnected on purpose, or remove the mark, by clicking them
+    NewNoConnect,
 }
 
 impl Schematic {
+    /// The name of the schematic
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// The text variables for a page of the schematic
+    pub fn page_variables(&self, pagenum: usize) -> TextVariables {
+        let mut vars = TextVariables::default();
+        vars.set(crate::variables::TITLE, self.name.clone());
+        vars.set(crate::variables::DATE, crate::variables::today());
+        for (name, value) in &self.variables {
+            vars.set(name, value.clone());
+        }
+        vars.set(crate::variables::PAGE, (pagenum + 1).to_string());
+        vars.set(crate::variables::PAGECOUNT, self.pages.len().to_string());
+        vars
+    }
+
+    /// Add the libraries of all placed components to the list of libraries the schematic uses
+    pub fn declare_used_libraries(&mut self) {
+        for page in &self.pages {
+            for sym in &page.syms {
+                if !self.libraries.contains(&sym.lib) {
+                    self.libraries.push(sym.lib.clone());
+                }
+            }
+        }
+        self.libraries.sort();
+    }
+
+    /// Update the copies of library items stored in the schematic, so that it has a copy of everything it uses
+    pub fn refresh_cache(&mut self, libs: &HashMap<String, LibraryHolder>) {
+        self.cache.refresh(
+            self.pages.iter().flat_map(|p| p.syms.iter()),
+            self.pages
+                .iter()
+                .filter_map(|p| p.format.title_block.as_ref()),
+            libs,
+        );
+        self.invalidate_indexes();
+    }
+
+    /// Copy the components placed since the cache was last refreshed into it, so that a component is drawn from
+    /// the same copy from when it is placed, instead of following the library until the schematic is saved
+    pub fn cache_new_components(&mut self, libs: &HashMap<String, LibraryHolder>) {
+        if self
+            .pages
+            .iter()
+            .flat_map(|p| p.syms.iter())
+            .any(|s| self.cache.can_add(s, libs))
+        {
+            self.refresh_cache(libs);
+        }
+    }
+
+    /// Returns true when the schematic refers to the library item
+    pub fn uses(&self, item: &crate::library::LibraryItem) -> bool {
+        match item {
+            crate::library::LibraryItem::Component(lib, com) => {
+                self.pages
+                    .iter()
+                    .flat_map(|p| p.syms.iter())
+                    .any(|s| s.lib == *lib && s.com == *com)
+                    || self
+                        .cache
+                        .libraries
+                        .get(lib)
+                        .map_or(false, |l| l.components.contains_key(com))
+            }
+            crate::library::LibraryItem::Symbol(lib, sym) => self
+                .cache
+                .libraries
+                .get(lib)
+                .map_or(false, |l| l.syms.contains_key(sym)),
+        }
+    }
+
+    /// Change the references to a library item that was renamed or moved to another library
+    pub fn library_item_moved(
+        &mut self,
+        item: &crate::library::LibraryItem,
+        newlib: &str,
+        newname: &str,
+    ) {
+        if let crate::library::LibraryItem::Component(lib, com) = item {
+            for s in self.pages.iter_mut().flat_map(|p| p.syms.iter_mut()) {
+                if s.lib == *lib && s.com == *com {
+                    s.lib = newlib.to_string();
+                    s.com = newname.to_string();
+                }
+            }
+        }
+        self.cache.item_moved(item, newlib, newname);
+    }
+
+    /// The placed components of the schematic, with the page number and symbol number of each
+    pub fn placed(&self) -> impl Iterator<Item = (usize, usize, &ComponentVariantReference)> {
+        self.pages
+            .iter()
+            .enumerate()
+            .flat_map(|(p, page)| page.syms.iter().enumerate().map(move |(i, s)| (p, i, s)))
+    }
+
+    /// The action that gives a designator to every placed component without one, or None when all have one.
+    /// Units of a component with more than one unit are put together in parts with the same designator, filling parts with free units first.
+    pub fn annotate(&self, libs: &HashMap<String, LibraryHolder>) -> Option<SchematicAction> {
+        /// A part found on the schematic, with the units of it that are placed
+        struct Part<'a> {
+            /// The designator prefix
+            prefix: String,
+            /// The designator number
+            number: u32,
+            /// The component placed
+            component: &'a ComponentVariantReference,
+            /// The units placed
+            units: Vec<Option<String>>,
+        }
+        let mut parts: Vec<Part> = Vec::new();
+        for (_, _, s) in self.placed() {
+            if let Some((prefix, number)) = s.designator.as_deref().and_then(split_designator) {
+                match parts
+                    .iter_mut()
+                    .find(|p| p.prefix == prefix && p.number == number)
+                {
+                    Some(p) => p.units.push(s.unit.clone()),
+                    None => parts.push(Part {
+                        prefix,
+                        number,
+                        component: s,
+                        units: vec![s.unit.clone()],
+                    }),
+                }
+            }
+        }
+
+        let mut unannotated: Vec<(usize, usize, &ComponentVariantReference)> = self
+            .placed()
+            .filter(|(_, _, s)| s.designator.is_none())
+            .collect();
+        // Number from the top left of each page, page by page
+        unannotated.sort_by(|a, b| {
+            let (ax, ay) = a.2.pos.get_mm();
+            let (bx, by) = b.2.pos.get_mm();
+            a.0.cmp(&b.0)
+                .then(by.partial_cmp(&ay).unwrap_or(std::cmp::Ordering::Equal))
+                .then(ax.partial_cmp(&bx).unwrap_or(std::cmp::Ordering::Equal))
+        });
+
+        let mut actions = Vec::new();
+        for (pagenum, symnum, s) in unannotated {
+            let prefix = self.cache.get_prefix(s, libs);
+            let same_component = |p: &&mut Part| {
+                p.prefix == prefix
+                    && p.component.lib == s.lib
+                    && p.component.com == s.com
+                    && p.component.var == s.var
+            };
+            let free = if s.unit.is_some() {
+                parts
+                    .iter_mut()
+                    .filter(same_component)
+                    .filter(|p| !p.units.contains(&s.unit))
+                    .min_by_key(|p| p.number)
+            } else {
+                None
+            };
+            let designator = match free {
+                Some(p) => {
+                    p.units.push(s.unit.clone());
+                    format!("{}{}", p.prefix, p.number)
+                }
+                None => {
+                    let mut number = 1;
+                    while parts
+                        .iter()
+                        .any(|p| p.prefix == prefix && p.number == number)
+                    {
+                        number += 1;
+                    }
+                    parts.push(Part {
+                        prefix: prefix.clone(),
+                        number,
+                        component: s,
+                        units: vec![s.unit.clone()],
+                    });
+                    format!("{}{}", prefix, number)
+                }
+            };
+            actions.push(SchematicAction::SetDesignator {
+                pagenum,
+                symnum,
+                designator: Some(designator),
+            });
+        }
+        if actions.is_empty() {
+            None
+        } else {
+            Some(SchematicAction::Compound { actions })
+        }
+    }
+
+    /// The action that removes the designator from every placed component, or None when none have one
+    pub fn clear_annotation(&self) -> Option<SchematicAction> {
+        let actions: Vec<SchematicAction> = self
+            .placed()
+            .filter(|(_, _, s)| s.designator.is_some())
+            .map(|(pagenum, symnum, _)| SchematicAction::SetDesignator {
+                pagenum,
+                symnum,
+                designator: None,
+            })
+            .collect();
+        if actions.is_empty() {
+            None
+        } else {
+            Some(SchematicAction::Compound { actions })
+        }
+    }
+
+    /// The action that deletes the selected objects, or None when nothing is selected.
+    /// Objects are deleted from the end of each list first, so that the indexes of the others stay the same.
+    pub fn delete_action(&self, selection: &[SchematicSelection]) -> Option<SchematicAction> {
+        let mut selection = selection.to_vec();
+        selection.sort_by_key(|s| match s {
+            SchematicSelection::Text { page, textnum } => (*page, 0, *textnum),
+            SchematicSelection::Symbol { page, sym } => (*page, 1, *sym),
+            SchematicSelection::Bus { page, partnum } => (*page, 2, *partnum),
+        });
+        selection.dedup();
+        let actions: Vec<SchematicAction> = selection
+            .iter()
+            .rev()
+            .filter(|s| s.is_valid(self))
+            .map(|s| match s {
+                SchematicSelection::Text { page, textnum } => SchematicAction::DeleteText {
+                    pagenum: *page,
+                    textnum: *textnum,
+                    text: None,
+                },
+                SchematicSelection::Symbol { page, sym } => SchematicAction::DeleteSymbol {
+                    pagenum: *page,
+                    symnum: *sym,
+                    sym: None,
+                },
+                SchematicSelection::Bus { page, partnum } => SchematicAction::DeleteBusPart {
+                    pagenum: *page,
+                    partnum: *partnum,
+                    part: None,
+                },
+            })
+            .collect();
+        if actions.is_empty() {
+            None
+        } else {
+            Some(SchematicAction::Compound { actions })
+        }
+    }
+
+    /// Where a selected object is placed, None when it does not exist
+    fn anchor(&self, sel: &SchematicSelection) -> Option<crate::general::Coordinates> {
+        match sel {
+            SchematicSelection::Text { page, textnum } => self
+                .pages
+                .get(*page)?
+                .texts
+                .get(*textnum)
+                .map(|t| t.location),
+            SchematicSelection::Symbol { page, sym } => {
+                self.pages.get(*page)?.syms.get(*sym).map(|s| s.pos)
+            }
+            SchematicSelection::Bus { page, partnum } => {
+                self.pages.get(*page)?.bus.get(*partnum).map(|b| b.anchor())
+            }
+        }
+    }
+
+    /// Every object on a page, for selecting all of them
+    pub fn select_all(&self, page: usize) -> Vec<SchematicSelection> {
+        let p = if let Some(p) = self.pages.get(page) {
+            p
+        } else {
+            return Vec::new();
+        };
+        (0..p.texts.len())
+            .map(|textnum| SchematicSelection::Text { page, textnum })
+            .chain((0..p.syms.len()).map(|sym| SchematicSelection::Symbol { page, sym }))
+            .chain((0..p.bus.len()).map(|partnum| SchematicSelection::Bus { page, partnum }))
+            .collect()
+    }
+
+    /// The action that moves each of the selected objects by its own amount, or None when nothing is selected
+    fn move_each(
+        &self,
+        moves: impl Iterator<Item = (SchematicSelection, crate::general::Coordinates)>,
+    ) -> Option<SchematicAction> {
+        let actions: Vec<SchematicAction> = moves
+            .filter(|(s, _)| s.is_valid(self))
+            .map(|(s, delta)| match s {
+                SchematicSelection::Text { page, textnum } => SchematicAction::MoveText {
+                    pagenum: page,
+                    textnum,
+                    delta,
+                },
+                SchematicSelection::Symbol { page, sym } => SchematicAction::MoveSymbol {
+                    pagenum: page,
+                    symnum: sym,
+                    delta,
+                },
+                SchematicSelection::Bus { page, partnum } => SchematicAction::MoveBusPart {
+                    pagenum: page,
+                    partnum,
+                    delta,
+                },
+            })
+            .collect();
+        if actions.is_empty() {
+            None
+        } else {
+            Some(SchematicAction::Compound { actions })
+        }
+    }
+
+    /// The action that moves all of the selected objects by delta, or None when nothing is selected
+    pub fn move_action(
+        &self,
+        selection: &[SchematicSelection],
+        delta: crate::general::Coordinates,
+    ) -> Option<SchematicAction> {
+        self.move_each(selection.iter().map(|s| (s.clone(), delta)))
+    }
+
+    /// The action that lines up the selected objects along one edge of the group, or None when nothing is selected
+    pub fn align_action(
+        &self,
+        selection: &[SchematicSelection],
+        alignment: crate::general::Alignment,
+    ) -> Option<SchematicAction> {
+        let placed: Vec<(SchematicSelection, crate::general::Coordinates)> = selection
+            .iter()
+            .filter_map(|s| self.anchor(s).map(|a| (s.clone(), a)))
+            .collect();
+        let anchors: Vec<crate::general::Coordinates> = placed.iter().map(|(_, a)| *a).collect();
+        let deltas = alignment.deltas(&anchors);
+        self.move_each(placed.into_iter().map(|(s, _)| s).zip(deltas))
+    }
+
+    /// The action that turns the selected objects as a group about the middle of the group, or None when nothing is selected.
+    /// turn changes the orientation of a single component, and is also used to move the objects around the middle.
+    pub fn turn_action(
+        &self,
+        selection: &[SchematicSelection],
+        turn: impl Fn(crate::general::Orientation) -> crate::general::Orientation,
+    ) -> Option<SchematicAction> {
+        let placed: Vec<(&SchematicSelection, crate::general::Coordinates)> = selection
+            .iter()
+            .filter_map(|s| self.anchor(s).map(|a| (s, a)))
+            .collect();
+        let first = placed.first()?.1;
+        let (min, max) = placed.iter().fold((first, first), |(min, max), (_, a)| {
+            (
+                crate::general::Coordinates::from_nm(min.x.min(a.x), min.y.min(a.y)),
+                crate::general::Coordinates::from_nm(max.x.max(a.x), max.y.max(a.y)),
+            )
+        });
+        let center = crate::general::Coordinates::from_nm((min.x + max.x) / 2, (min.y + max.y) / 2);
+        let op = turn(crate::general::Orientation::default());
+        let mut actions = Vec::new();
+        for (s, a) in placed {
+            let delta = center + op.transform(a - center) - a;
+            match s {
+                SchematicSelection::Text { page, textnum } => {
+                    actions.push(SchematicAction::MoveText {
+                        pagenum: *page,
+                        textnum: *textnum,
+                        delta,
+                    })
+                }
+                SchematicSelection::Symbol { page, sym } => {
+                    actions.push(SchematicAction::SetOrientation {
+                        pagenum: *page,
+                        symnum: *sym,
+                        orientation: turn(self.pages[*page].syms[*sym].orientation),
+                    });
+                    actions.push(SchematicAction::MoveSymbol {
+                        pagenum: *page,
+                        symnum: *sym,
+                        delta,
+                    });
+                }
+                SchematicSelection::Bus { page, partnum } => {
+                    actions.push(SchematicAction::SetBusPart {
+                        pagenum: *page,
+                        partnum: *partnum,
+                        part: self.pages[*page].bus[*partnum]
+                            .map_points(|c| center + op.transform(c - center)),
+                    })
+                }
+            }
+        }
+        Some(SchematicAction::Compound { actions })
+    }
+
+    /// The action that puts no-connect markers on the pins of the selected components that are not connected to
+    /// anything, which are pins with no other pin and no bus entry in the same place. None when there are no such pins.
+    pub fn no_connect_action(
+        &self,
+        selection: &[SchematicSelection],
+        libs: &HashMap<String, LibraryHolder>,
+    ) -> Option<SchematicAction> {
+        let mut actions = Vec::new();
+        for s in selection.iter().filter(|s| s.is_valid(self)) {
+            let (page, sym) = match s {
+                SchematicSelection::Symbol { page, sym } => (&self.pages[*page], *sym),
+                _ => continue,
+            };
+            let key =
+                |p: &crate::general::Coordinates| crate::netlist::connection_key(s.page(), *p);
+            let pins = page.pins(&self.cache, libs);
+            for (_, number, at) in pins.iter().filter(|(n, _, _)| *n == sym) {
+                let connected = pins
+                    .iter()
+                    .any(|(n, other, p)| key(p) == key(at) && (*n != sym || other != number))
+                    || page
+                        .bus
+                        .iter()
+                        .any(|b| matches!(b, BusPart::Entry { to, .. } if key(to) == key(at)));
+                if !connected && !page.syms[sym].no_connect.contains(number) {
+                    actions.push(SchematicAction::SetNoConnect {
+                        pagenum: s.page(),
+                        symnum: sym,
+                        pin: number.clone(),
+                        marked: true,
+                    });
+                }
+            }
+        }
+        if actions.is_empty() {
+            None
+        } else {
+            Some(SchematicAction::Compound { actions })
+        }
+    }
+
+    /// The libraries the schematic uses that are not loaded
+    pub fn missing_libraries(&self, libs: &HashMap<String, LibraryHolder>) -> Vec<String> {
+        self.libraries
+            .iter()
+            .filter(|l| !libs.contains_key(*l))
+            .cloned()
+            .collect()
+    }
+
+    /// Build the index of where the objects of a page are, when it has not been built yet
+    pub fn build_index(&mut self, pagenum: usize, libs: &HashMap<String, LibraryHolder>) {
+        if self.pages[pagenum].index.is_built() {
+            return;
+        }
+        let vars = self.page_variables(pagenum);
+        let page = &self.pages[pagenum];
+        let syms = (0..page.syms.len())
+            .map(|i| page.object_box(PageObject::Symbol(i), &self.cache, libs, &vars))
+            .collect();
+        let texts = (0..page.texts.len())
+            .map(|i| page.object_box(PageObject::Text(i), &self.cache, libs, &vars))
+            .collect();
+        let bus = (0..page.bus.len())
+            .map(|i| page.object_box(PageObject::Bus(i), &self.cache, libs, &vars))
+            .collect();
+        self.pages[pagenum].index.build(syms, texts, bus);
+    }
+
+    /// The area of an object changed by an action, which has no libraries to look at. None for a component whose
+    /// symbol is not in the cache, whose area has to come from the libraries when the index is built again.
+    fn changed_box(&self, pagenum: usize, obj: PageObject) -> Option<BoundingBox> {
+        let page = &self.pages[pagenum];
+        if let PageObject::Symbol(i) = obj {
+            self.cache.cached_symbol(&page.syms[i])?;
+        }
+        Some(page.object_box(
+            obj,
+            &self.cache,
+            &HashMap::new(),
+            &self.page_variables(pagenum),
+        ))
+    }
+
+    /// Add an object that was just inserted into a page to the index of the page
+    fn index_inserted(&mut self, pagenum: usize, obj: PageObject) {
+        if self.pages[pagenum].index.is_built() {
+            match self.changed_box(pagenum, obj) {
+                Some(b) => self.pages[pagenum].index.insert(obj, b),
+                None => self.pages[pagenum].index.invalidate(),
+            }
+        }
+    }
+
+    /// Update the index of a page for an object that moved or changed
+    fn index_changed(&mut self, pagenum: usize, obj: PageObject) {
+        if self.pages[pagenum].index.is_built() {
+            match self.changed_box(pagenum, obj) {
+                Some(b) => self.pages[pagenum].index.update(obj, b),
+                None => self.pages[pagenum].index.invalidate(),
+            }
+        }
+    }
+
+    /// Record that pages were added, removed or reordered, which also changes the page number and page count
+    /// variables that text on the pages can show
+    fn pages_changed(&mut self) {
+        self.page_changes = self.page_changes.wrapping_add(1);
+        self.invalidate_indexes();
+    }
+
+    /// Empty the index of every page, for a change to the stored library items or the text variables that the areas
+    /// of objects come from
+    fn invalidate_indexes(&mut self) {
+        for page in &mut self.pages {
+            page.index.invalidate();
+        }
+    }
+
     /// Create a new example schematic.
     pub fn new_example() -> Self {
         let mut p = Vec::new();
         let t = vec![
             TextOnPage {
                 text: "demo text".to_string(),
-                location: crate::general::Coordinates::Inches(0.0, 0.0),
+                location: crate::general::Coordinates::from_nm(0, 0),
                 color: Colors::Standard,
-                size: crate::general::Length::Inches(0.2),
+                size: crate::general::Length::from_inches(0.2),
             },
             TextOnPage {
                 text: "moredemo text".to_string(),
-                location: crate::general::Coordinates::Inches(0.0, 0.2),
+                location: crate::general::Coordinates::from_inches(0.0, 0.2),
                 color: Colors::Standard,
-                size: crate::general::Length::Inches(0.4),
+                size: crate::general::Length::from_inches(0.4),
             },
             TextOnPage {
                 text: "moredemo text".to_string(),
-                location: crate::general::Coordinates::Inches(0.0, 0.6),
+                location: crate::general::Coordinates::from_inches(0.0, 0.6),
                 color: Colors::Standard,
-                size: crate::general::Length::Inches(0.8),
+                size: crate::general::Length::from_inches(0.8),
             },
             TextOnPage {
                 text: "moredemo text".to_string(),
-                location: crate::general::Coordinates::Inches(0.0, 1.4),
+                location: crate::general::Coordinates::from_inches(0.0, 1.4),
                 color: Colors::Standard,
-                size: crate::general::Length::Inches(1.6),
+                size: crate::general::Length::from_inches(1.6),
             },
             TextOnPage {
                 text: "moredemo text".to_string(),
-                location: crate::general::Coordinates::Inches(0.0, 3.0),
+                location: crate::general::Coordinates::from_inches(0.0, 3.0),
                 color: Colors::Standard,
-                size: crate::general::Length::Inches(3.2),
+                size: crate::general::Length::from_inches(3.2),
             },
         ];
         let page = Page {
+            name: String::new(),
             syms: Vec::new(),
             texts: t,
-            size: crate::general::Coordinates::Inches(11.0, 8.5),
+            size: crate::general::Coordinates::from_inches(11.0, 8.5),
+            format: crate::sheet::PageFormat {
+                paper: crate::sheet::PaperSize::AnsiA,
+                orientation: crate::sheet::PageOrientation::Landscape,
+                border: crate::sheet::BorderStyle::Plain,
+                title_block: None,
+            },
+            bus: Vec::new(),
+            index: crate::spatial::SpatialIndex::default(),
         };
         p.push(page);
         Self {
             pages: p,
             name: "Example Schematic".to_string(),
+            libraries: Vec::new(),
+            cache: crate::cache::LibraryCache::default(),
+            variables: HashMap::new(),
+            page_changes: 0,
         }
     }
 }
 
-#[derive(Clone)]
+/// Split a designator such as U12 into its prefix and number
+pub fn split_designator(d: &str) -> Option<(String, u32)> {
+    let digits = d.len() - d.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 {
+        return None;
+    }
+    let (prefix, number) = d.split_at(d.len() - digits);
+    Some((prefix.to_string(), number.parse().ok()?))
+}
+
+#[derive(Clone, serde::Serialize, serde::Deserialize)]
 /// The actions that can be done to a schematic. This allows the undo/redo functionality to exist.
 pub enum SchematicAction {
     /// Move a symbol on the page by a certain amount
@@ -239,101 +1144,1211 @@ pub enum SchematicAction {
         /// The variant
         var: ComponentVariantReference,
     },
-}
-
-impl undo::Action for SchematicAction {
-    type Target = Schematic;
-
-    type Output = ();
-
-    fn apply(&mut self, target: &mut Self::Target) -> Self::Output {
-        match self {
-            SchematicAction::AddComponentVariant { pagenum, var } => {
-                target.pages[*pagenum].syms.push(var.to_owned());
-            }
-            SchematicAction::MoveSymbol {
-                pagenum,
-                symnum,
-                delta,
-            } => {
-                target.pages[*pagenum].syms[*symnum].pos += *delta;
-            }
-            SchematicAction::MoveText {
-                pagenum,
-                textnum,
-                delta,
-            } => {
-                target.pages[*pagenum].texts[*textnum].location += *delta;
-            }
-            SchematicAction::CreateText { pagenum, text } => {
-                target.pages[*pagenum].texts.push(text.clone());
-            }
-            SchematicAction::EditText {
-                pagenum,
-                textnum,
-                old: _,
-                new,
-            } => {
-                target.pages[*pagenum].texts[*textnum].text = new.clone();
-            }
-            SchematicAction::ChangeTextColor {
-                pagenum,
+    /// Set the designator of a placed component, swapping it with the current designator
+    SetDesignator {
+        /// The page number
+        pagenum: usize,
+        /// The symbol number
+        symnum: usize,
+        /// The designator
+        designator: Option<String>,
+    },
+    /// Set the unit of a placed component, swapping it with the current unit
+    SetUnit {
+        /// The page number
+        pagenum: usize,
+        /// The symbol number
+        symnum: usize,
+        /// The unit
+        unit: Option<String>,
+    },
+    /// Set the body style of a placed component, swapping it with the current body style
+    SetStyle {
+        /// The page number
+        pagenum: usize,
+        /// The symbol number
+        symnum: usize,
+        /// The body style, None for the normal body style
+        style: Option<String>,
+    },
+    /// Set how a placed component is turned, swapping it with the current orientation
+    SetOrientation {
+        /// The page number
+        pagenum: usize,
+        /// The symbol number
+        symnum: usize,
+        /// The orientation
+        orientation: crate::general::Orientation,
+    },
+    /// Set a property of a placed component, swapping it with the current value. A value of None uses the property of the component.
+    SetProperty {
+        /// The page number
+        pagenum: usize,
+        /// The symbol number
+        symnum: usize,
+        /// The name of the property
+        name: String,
+        /// The value
+        value: Option<String>,
+    },
+    /// Show or hide a field of a placed component, swapping the location with the current one. A location of None hides the field.
+    SetField {
+        /// The page number
+        pagenum: usize,
+        /// The symbol number
+        symnum: usize,
+        /// The name of the property the field shows
+        name: String,
+        /// Where the field is, relative to the position of the component
+        location: Option<crate::general::Coordinates>,
+    },
+    /// Mark a pin of a placed component as left unconnected on purpose, or remove the mark, swapping it with the current one
+    SetNoConnect {
+        /// The page number
+        pagenum: usize,
+        /// The symbol number
+        symnum: usize,
+        /// The number of the pin
+        pin: String,
+        /// True when the pin is marked
+        marked: bool,
+    },
+    /// Move a field of a placed component by a certain amount
+    MoveField {
+        /// The page number
+        pagenum: usize,
+        /// The symbol number
+        symnum: usize,
+        /// The name of the property the field shows
+        name: String,
+        /// The delta to move by
+        delta: crate::general::Coordinates,
+    },
+    /// Delete a placed component, sym should be None
+    DeleteSymbol {
+        /// The page number
+        pagenum: usize,
+        /// The symbol number
+        symnum: usize,
+        /// The deleted component
+        #[serde(skip)]
+        sym: Option<ComponentVariantReference>,
+    },
+    /// Delete a text from a page, text should be None
+    DeleteText {
+        /// The page number
+        pagenum: usize,
+        /// The text number
+        textnum: usize,
+        /// The deleted text
+        #[serde(skip)]
+        text: Option<TextOnPage>,
+    },
+    /// Add a piece of a bus to a page
+    CreateBusPart {
+        /// The page number
+        pagenum: usize,
+        /// The new bus part
+        part: BusPart,
+    },
+    /// Move a piece of a bus by a certain amount
+    MoveBusPart {
+        /// The page number
+        pagenum: usize,
+        /// The bus part number
+        partnum: usize,
+        /// The delta to move by
+        delta: crate::general::Coordinates,
+    },
+    /// Change a piece of a bus, swapping it with the current one
+    SetBusPart {
+        /// The page number
+        pagenum: usize,
+        /// The bus part number
+        partnum: usize,
+        /// The bus part
+        part: BusPart,
+    },
+    /// Delete a piece of a bus, part should be None
+    DeleteBusPart {
+        /// The page number
+        pagenum: usize,
+        /// The bus part number
+        partnum: usize,
+        /// The deleted bus part
+        #[serde(skip)]
+        part: Option<BusPart>,
+    },
+    /// Insert a page, page must be Some
+    InsertPage {
+        /// The index the page goes at
+        pagenum: usize,
+        /// The page to insert
+        page: Option<Page>,
+    },
+    /// Remove a page, page should be None
+    RemovePage {
+        /// The index of the page
+        pagenum: usize,
+        /// The removed page
+        #[serde(skip)]
+        page: Option<Page>,
+    },
+    /// Move a page to another position in the list of pages
+    MovePage {
+        /// The index of the page
+        from: usize,
+        /// The index of the page after it moves
+        to: usize,
+    },
+    /// Rename a page, swapping the name with the current name
+    RenamePage {
+        /// The index of the page
+        pagenum: usize,
+        /// The name of the page
+        name: String,
+    },
+    /// Set the format and size of a page, swapping them with the current format and size
+    SetPageFormat {
+        /// The page number
+        pagenum: usize,
+        /// The format of the page
+        format: crate::sheet::PageFormat,
+        /// The size of the page
+        size: crate::general::Coordinates,
+    },
+    /// Set a text variable of the schematic, swapping it with the current value. A value of None removes the variable.
+    SetVariable {
+        /// The name of the variable
+        name: String,
+        /// The value
+        value: Option<String>,
+    },
+    /// Several actions that are done and undone as one
+    Compound {
+        /// The actions, in the order they are done
+        actions: Vec<SchematicAction>,
+    },
+    /// Follow a library item that was renamed or moved to another library
+    RenameLibraryItem {
+        /// The item, with its old library and name
+        item: crate::library::LibraryItem,
+        /// The library the item went to
+        newlib: String,
+        /// The new name of the item
+        newname: String,
+    },
+    /// Replace the copy of a component stored in the schematic
+    UpdateCachedComponent {
+        /// The name of the library
+        libname: String,
+        /// The name of the component
+        comname: String,
+        /// The component to swap with the stored copy
+        component: Option<crate::component::ComponentDefinition>,
+    },
+    /// Replace the copy of a symbol stored in the schematic
+    UpdateCachedSymbol {
+        /// The name of the library
+        libname: String,
+        /// The name of the symbol
+        symname: String,
+        /// The symbol to swap with the stored copy
+        symbol: Option<crate::symbol::SymbolDefinition>,
+    },
+}
+
+impl SchematicAction {
+    /// Where a page is after the action is done, None when the action removes the page
+    pub fn page_after(&self, page: usize) -> Option<usize> {
+        match self {
+            SchematicAction::InsertPage { pagenum, page: _ } => {
+                Some(if page >= *pagenum { page + 1 } else { page })
+            }
+            SchematicAction::RemovePage { pagenum, page: _ } => {
+                if page == *pagenum {
+                    None
+                } else if page > *pagenum {
+                    Some(page - 1)
+                } else {
+                    Some(page)
+                }
+            }
+            SchematicAction::MovePage { from, to } => {
+                if page == *from {
+                    Some(*to)
+                } else if *from < page && page <= *to {
+                    Some(page - 1)
+                } else if *to <= page && page < *from {
+                    Some(page + 1)
+                } else {
+                    Some(page)
+                }
+            }
+            SchematicAction::Compound { actions } => {
+                actions.iter().try_fold(page, |p, a| a.page_after(p))
+            }
+            _ => Some(page),
+        }
+    }
+
+    /// Returns true when the action is the one that made the schematic follow a library action that renamed an item
+    pub fn follows(&self, a: &crate::library::LibraryAction) -> bool {
+        match (self, a.renamed()) {
+            (
+                SchematicAction::RenameLibraryItem {
+                    item,
+                    newlib,
+                    newname,
+                },
+                Some((old, lib, name)),
+            ) => *item == old && newlib == lib && newname == name,
+            _ => false,
+        }
+    }
+
+    /// Where a page is after the action is undone, None when undoing the action removes the page
+    pub fn page_before(&self, page: usize) -> Option<usize> {
+        match self {
+            SchematicAction::InsertPage { pagenum, page: _ } => SchematicAction::RemovePage {
+                pagenum: *pagenum,
+                page: None,
+            }
+            .page_after(page),
+            SchematicAction::RemovePage { pagenum, page: _ } => SchematicAction::InsertPage {
+                pagenum: *pagenum,
+                page: None,
+            }
+            .page_after(page),
+            SchematicAction::MovePage { from, to } => SchematicAction::MovePage {
+                from: *to,
+                to: *from,
+            }
+            .page_after(page),
+            SchematicAction::Compound { actions } => {
+                actions.iter().rev().try_fold(page, |p, a| a.page_before(p))
+            }
+            _ => Some(page),
+        }
+    }
+
+    /// The amount a move action moves by, None for other actions
+    fn move_delta(&mut self) -> Option<&mut crate::general::Coordinates> {
+        match self {
+            SchematicAction::MoveSymbol { delta, .. }
+            | SchematicAction::MoveText { delta, .. }
+            | SchematicAction::MoveField { delta, .. }
+            | SchematicAction::MoveBusPart { delta, .. } => Some(delta),
+            _ => None,
+        }
+    }
+
+    /// Returns true when both actions are moves of the same object
+    fn moves_same(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                SchematicAction::MoveSymbol {
+                    pagenum, symnum, ..
+                },
+                SchematicAction::MoveSymbol {
+                    pagenum: pn2,
+                    symnum: sn2,
+                    ..
+                },
+            ) => pagenum == pn2 && symnum == sn2,
+            (
+                SchematicAction::MoveText {
+                    pagenum, textnum, ..
+                },
+                SchematicAction::MoveText {
+                    pagenum: pn2,
+                    textnum: tn2,
+                    ..
+                },
+            ) => pagenum == pn2 && textnum == tn2,
+            (
+                SchematicAction::MoveField {
+                    pagenum,
+                    symnum,
+                    name,
+                    ..
+                },
+                SchematicAction::MoveField {
+                    pagenum: pn2,
+                    symnum: sn2,
+                    name: name2,
+                    ..
+                },
+            ) => pagenum == pn2 && symnum == sn2 && name == name2,
+            (
+                SchematicAction::MoveBusPart {
+                    pagenum, partnum, ..
+                },
+                SchematicAction::MoveBusPart {
+                    pagenum: pn2,
+                    partnum: pn3,
+                    ..
+                },
+            ) => pagenum == pn2 && partnum == pn3,
+            _ => false,
+        }
+    }
+
+    /// The page and object that the action moves or changes without adding or removing it, None for other actions
+    fn changed_object(&self) -> Option<(usize, PageObject)> {
+        match self {
+            SchematicAction::MoveSymbol {
+                pagenum, symnum, ..
+            }
+            | SchematicAction::SetDesignator {
+                pagenum, symnum, ..
+            }
+            | SchematicAction::SetUnit {
+                pagenum, symnum, ..
+            }
+            | SchematicAction::SetStyle {
+                pagenum, symnum, ..
+            }
+            | SchematicAction::SetOrientation {
+                pagenum, symnum, ..
+            }
+            | SchematicAction::SetProperty {
+                pagenum, symnum, ..
+            }
+            | SchematicAction::SetField {
+                pagenum, symnum, ..
+            }
+            | SchematicAction::SetNoConnect {
+                pagenum, symnum, ..
+            }
+            | SchematicAction::MoveField {
+                pagenum, symnum, ..
+            } => Some((*pagenum, PageObject::Symbol(*symnum))),
+            SchematicAction::MoveText {
+                pagenum, textnum, ..
+            }
+            | SchematicAction::EditText {
+                pagenum, textnum, ..
+            } => Some((*pagenum, PageObject::Text(*textnum))),
+            SchematicAction::MoveBusPart {
+                pagenum, partnum, ..
+            }
+            | SchematicAction::SetBusPart {
+                pagenum, partnum, ..
+            } => Some((*pagenum, PageObject::Bus(*partnum))),
+            _ => None,
+        }
+    }
+
+    /// The actions that update a stored copy of a library item to match the library.
+    /// Updating a component also stores any symbols it now uses that are not stored yet.
+    pub fn update_cache(
+        cache: &crate::cache::LibraryCache,
+        item: &crate::library::LibraryItem,
+        libs: &HashMap<String, LibraryHolder>,
+    ) -> Vec<Self> {
+        let mut actions = Vec::new();
+        match item {
+            crate::library::LibraryItem::Component(libname, comname) => {
+                let component = libs
+                    .get(libname)
+                    .and_then(|l| l.library.as_ref())
+                    .and_then(|l| l.flattened_component(comname, libs));
+                if let Some(component) = component {
+                    for sref in component.variants.values().flat_map(|v| v.symbols()) {
+                        let symlib = sref.lib.name(libname).to_string();
+                        let cached = cache
+                            .libraries
+                            .get(&symlib)
+                            .map_or(false, |l| l.syms.contains_key(&sref.sym));
+                        let symbol = libs
+                            .get(&symlib)
+                            .and_then(|l| l.library.as_ref())
+                            .and_then(|l| l.syms.get(&sref.sym));
+                        if let (false, Some(symbol)) = (cached, symbol) {
+                            actions.push(SchematicAction::UpdateCachedSymbol {
+                                libname: symlib,
+                                symname: sref.sym.clone(),
+                                symbol: Some(symbol.clone()),
+                            });
+                        }
+                    }
+                    actions.push(SchematicAction::UpdateCachedComponent {
+                        libname: libname.clone(),
+                        comname: comname.clone(),
+                        component: Some(component),
+                    });
+                }
+            }
+            crate::library::LibraryItem::Symbol(libname, symname) => {
+                let symbol = libs
+                    .get(libname)
+                    .and_then(|l| l.library.as_ref())
+                    .and_then(|l| l.syms.get(symname));
+                if let Some(symbol) = symbol {
+                    actions.push(SchematicAction::UpdateCachedSymbol {
+                        libname: libname.clone(),
+                        symname: symname.clone(),
+                        symbol: Some(symbol.clone()),
+                    });
+                }
+            }
+        }
+        actions
+    }
+}
+
+impl crate::journal::Journaled for SchematicAction {
+    fn journal_file(
+        &self,
+        _target: &Self::Target,
+        default: &Option<std::path::PathBuf>,
+    ) -> Option<std::path::PathBuf> {
+        default.clone()
+    }
+}
+
+impl undo::Action for SchematicAction {
+    type Target = Schematic;
+
+    type Output = ();
+
+    fn apply(&mut self, target: &mut Self::Target) -> Self::Output {
+        match self {
+            SchematicAction::RenameLibraryItem {
+                item,
+                newlib,
+                newname,
+            } => {
+                target.library_item_moved(item, newlib, newname);
+                target.invalidate_indexes();
+            }
+            SchematicAction::UpdateCachedComponent {
+                libname,
+                comname,
+                component,
+            } => {
+                target.cache.swap_component(libname, comname, component);
+                target.invalidate_indexes();
+            }
+            SchematicAction::UpdateCachedSymbol {
+                libname,
+                symname,
+                symbol,
+            } => {
+                target.cache.swap_symbol(libname, symname, symbol);
+                target.invalidate_indexes();
+            }
+            SchematicAction::AddComponentVariant { pagenum, var } => {
+                let page = &mut target.pages[*pagenum];
+                page.syms.push(var.to_owned());
+                let obj = PageObject::Symbol(page.syms.len() - 1);
+                target.index_inserted(*pagenum, obj);
+            }
+            SchematicAction::SetDesignator {
+                pagenum,
+                symnum,
+                designator,
+            } => {
+                std::mem::swap(
+                    &mut target.pages[*pagenum].syms[*symnum].designator,
+                    designator,
+                );
+            }
+            SchematicAction::SetUnit {
+                pagenum,
+                symnum,
+                unit,
+            } => {
+                std::mem::swap(&mut target.pages[*pagenum].syms[*symnum].unit, unit);
+            }
+            SchematicAction::SetStyle {
+                pagenum,
+                symnum,
+                style,
+            } => {
+                std::mem::swap(&mut target.pages[*pagenum].syms[*symnum].style, style);
+            }
+            SchematicAction::SetOrientation {
+                pagenum,
+                symnum,
+                orientation,
+            } => {
+                std::mem::swap(
+                    &mut target.pages[*pagenum].syms[*symnum].orientation,
+                    orientation,
+                );
+            }
+            SchematicAction::SetProperty {
+                pagenum,
+                symnum,
+                name,
+                value,
+            } => {
+                target.pages[*pagenum].syms[*symnum].swap_property(name, value);
+            }
+            SchematicAction::SetField {
+                pagenum,
+                symnum,
+                name,
+                location,
+            } => {
+                target.pages[*pagenum].syms[*symnum].swap_field(name, location);
+            }
+            SchematicAction::SetNoConnect {
+                pagenum,
+                symnum,
+                pin,
+                marked,
+            } => {
+                target.pages[*pagenum].syms[*symnum].swap_no_connect(pin, marked);
+            }
+            SchematicAction::DeleteSymbol {
+                pagenum,
+                symnum,
+                sym,
+            } => {
+                *sym = Some(target.pages[*pagenum].syms.remove(*symnum));
+                target.pages[*pagenum]
+                    .index
+                    .remove(PageObject::Symbol(*symnum));
+            }
+            SchematicAction::DeleteText {
+                pagenum,
+                textnum,
+                text,
+            } => {
+                *text = Some(target.pages[*pagenum].texts.remove(*textnum));
+                target.pages[*pagenum]
+                    .index
+                    .remove(PageObject::Text(*textnum));
+            }
+            SchematicAction::InsertPage { pagenum, page } => {
+                if let Some(p) = page.take() {
+                    target.pages.insert(*pagenum, p);
+                }
+                target.pages_changed();
+            }
+            SchematicAction::RemovePage { pagenum, page } => {
+                *page = Some(target.pages.remove(*pagenum));
+                target.pages_changed();
+            }
+            SchematicAction::MovePage { from, to } => {
+                let p = target.pages.remove(*from);
+                target.pages.insert(*to, p);
+                target.pages_changed();
+            }
+            SchematicAction::RenamePage { pagenum, name } => {
+                std::mem::swap(&mut target.pages[*pagenum].name, name);
+            }
+            SchematicAction::SetPageFormat {
+                pagenum,
+                format,
+                size,
+            } => {
+                let page = &mut target.pages[*pagenum];
+                std::mem::swap(&mut page.format, format);
+                std::mem::swap(&mut page.size, size);
+            }
+            SchematicAction::SetVariable { name, value } => {
+                let old = match value.take() {
+                    Some(v) => target.variables.insert(name.clone(), v),
+                    None => target.variables.remove(name),
+                };
+                *value = old;
+                target.invalidate_indexes();
+            }
+            SchematicAction::MoveField {
+                pagenum,
+                symnum,
+                name,
+                delta,
+            } => {
+                let sym = &mut target.pages[*pagenum].syms[*symnum];
+                if let Some(f) = sym.fields.iter_mut().find(|f| f.name == *name) {
+                    f.location += *delta;
+                }
+            }
+            SchematicAction::Compound { actions } => {
+                for a in actions.iter_mut() {
+                    undo::Action::apply(a, target);
+                }
+            }
+            SchematicAction::MoveSymbol {
+                pagenum,
+                symnum,
+                delta,
+            } => {
+                target.pages[*pagenum].syms[*symnum].pos += *delta;
+            }
+            SchematicAction::MoveText {
+                pagenum,
+                textnum,
+                delta,
+            } => {
+                target.pages[*pagenum].texts[*textnum].location += *delta;
+            }
+            SchematicAction::CreateText { pagenum, text } => {
+                let page = &mut target.pages[*pagenum];
+                page.texts.push(text.clone());
+                let obj = PageObject::Text(page.texts.len() - 1);
+                target.index_inserted(*pagenum, obj);
+            }
+            SchematicAction::EditText {
+                pagenum,
+                textnum,
+                old: _,
+                new,
+            } => {
+                target.pages[*pagenum].texts[*textnum].text = new.clone();
+            }
+            SchematicAction::ChangeTextColor {
+                pagenum,
                 textnum,
                 old: _,
                 new,
             } => {
                 target.pages[*pagenum].texts[*textnum].color = *new;
             }
+            SchematicAction::CreateBusPart { pagenum, part } => {
+                let page = &mut target.pages[*pagenum];
+                page.bus.push(part.clone());
+                let obj = PageObject::Bus(page.bus.len() - 1);
+                target.index_inserted(*pagenum, obj);
+            }
+            SchematicAction::MoveBusPart {
+                pagenum,
+                partnum,
+                delta,
+            } => {
+                let part = &mut target.pages[*pagenum].bus[*partnum];
+                *part = part.map_points(|c| c + *delta);
+            }
+            SchematicAction::SetBusPart {
+                pagenum,
+                partnum,
+                part,
+            } => {
+                std::mem::swap(&mut target.pages[*pagenum].bus[*partnum], part);
+            }
+            SchematicAction::DeleteBusPart {
+                pagenum,
+                partnum,
+                part,
+            } => {
+                *part = Some(target.pages[*pagenum].bus.remove(*partnum));
+                target.pages[*pagenum]
+                    .index
+                    .remove(PageObject::Bus(*partnum));
+            }
+        }
+        if let Some((pagenum, obj)) = self.changed_object() {
+            target.index_changed(pagenum, obj);
         }
     }
 
     fn undo(&mut self, target: &mut Self::Target) -> Self::Output {
         match self {
+            SchematicAction::RenameLibraryItem {
+                item,
+                newlib,
+                newname,
+            } => {
+                let (moved, oldlib, oldname) = item.moved_to(newlib, newname);
+                target.library_item_moved(&moved, &oldlib, &oldname);
+                target.invalidate_indexes();
+            }
+            SchematicAction::UpdateCachedComponent {
+                libname,
+                comname,
+                component,
+            } => {
+                target.cache.swap_component(libname, comname, component);
+                target.invalidate_indexes();
+            }
+            SchematicAction::UpdateCachedSymbol {
+                libname,
+                symname,
+                symbol,
+            } => {
+                target.cache.swap_symbol(libname, symname, symbol);
+                target.invalidate_indexes();
+            }
             SchematicAction::AddComponentVariant { pagenum, var } => {
-                *var = target.pages[*pagenum].syms.pop().unwrap();
+                let page = &mut target.pages[*pagenum];
+                *var = page.syms.pop().unwrap();
+                page.index.remove(PageObject::Symbol(page.syms.len()));
+            }
+            SchematicAction::SetDesignator {
+                pagenum,
+                symnum,
+                designator,
+            } => {
+                std::mem::swap(
+                    &mut target.pages[*pagenum].syms[*symnum].designator,
+                    designator,
+                );
+            }
+            SchematicAction::SetUnit {
+                pagenum,
+                symnum,
+                unit,
+            } => {
+                std::mem::swap(&mut target.pages[*pagenum].syms[*symnum].unit, unit);
+            }
+            SchematicAction::SetStyle {
+                pagenum,
+                symnum,
+                style,
+            } => {
+                std::mem::swap(&mut target.pages[*pagenum].syms[*symnum].style, style);
+            }
+            SchematicAction::SetOrientation {
+                pagenum,
+                symnum,
+                orientation,
+            } => {
+                std::mem::swap(
+                    &mut target.pages[*pagenum].syms[*symnum].orientation,
+                    orientation,
+                );
+            }
+            SchematicAction::SetProperty {
+                pagenum,
+                symnum,
+                name,
+                value,
+            } => {
+                target.pages[*pagenum].syms[*symnum].swap_property(name, value);
+            }
+            SchematicAction::SetField {
+                pagenum,
+                symnum,
+                name,
+                location,
+            } => {
+                target.pages[*pagenum].syms[*symnum].swap_field(name, location);
+            }
+            SchematicAction::SetNoConnect {
+                pagenum,
+                symnum,
+                pin,
+                marked,
+            } => {
+                target.pages[*pagenum].syms[*symnum].swap_no_connect(pin, marked);
+            }
+            SchematicAction::DeleteSymbol {
+                pagenum,
+                symnum,
+                sym,
+            } => {
+                if let Some(s) = sym.take() {
+                    target.pages[*pagenum].syms.insert(*symnum, s);
+                    target.index_inserted(*pagenum, PageObject::Symbol(*symnum));
+                }
+            }
+            SchematicAction::DeleteText {
+                pagenum,
+                textnum,
+                text,
+            } => {
+                if let Some(t) = text.take() {
+                    target.pages[*pagenum].texts.insert(*textnum, t);
+                    target.index_inserted(*pagenum, PageObject::Text(*textnum));
+                }
+            }
+            SchematicAction::InsertPage { pagenum, page } => {
+                *page = Some(target.pages.remove(*pagenum));
+                target.pages_changed();
+            }
+            SchematicAction::RemovePage { pagenum, page } => {
+                if let Some(p) = page.take() {
+                    target.pages.insert(*pagenum, p);
+                }
+                target.pages_changed();
+            }
+            SchematicAction::MovePage { from, to } => {
+                let p = target.pages.remove(*to);
+                target.pages.insert(*from, p);
+                target.pages_changed();
+            }
+            SchematicAction::RenamePage { pagenum, name } => {
+                std::mem::swap(&mut target.pages[*pagenum].name, name);
+            }
+            SchematicAction::SetPageFormat {
+                pagenum,
+                format,
+                size,
+            } => {
+                let page = &mut target.pages[*pagenum];
+                std::mem::swap(&mut page.format, format);
+                std::mem::swap(&mut page.size, size);
+            }
+            SchematicAction::SetVariable { name, value } => {
+                let old = match value.take() {
+                    Some(v) => target.variables.insert(name.clone(), v),
+                    None => target.variables.remove(name),
+                };
+                *value = old;
+                target.invalidate_indexes();
+            }
+            SchematicAction::MoveField {
+                pagenum,
+                symnum,
+                name,
+                delta,
+            } => {
+                let sym = &mut target.pages[*pagenum].syms[*symnum];
+                if let Some(f) = sym.fields.iter_mut().find(|f| f.name == *name) {
+                    f.location -= *delta;
+                }
+            }
+            SchematicAction::Compound { actions } => {
+                for a in actions.iter_mut().rev() {
+                    undo::Action::undo(a, target);
+                }
             }
             SchematicAction::MoveSymbol {
                 pagenum,
                 symnum,
                 delta,
             } => {
-                target.pages[*pagenum].syms[*symnum].pos -= *delta;
+                target.pages[*pagenum].syms[*symnum].pos -= *delta;
+            }
+            SchematicAction::MoveText {
+                pagenum,
+                textnum,
+                delta,
+            } => {
+                target.pages[*pagenum].texts[*textnum].location -= *delta;
+            }
+            SchematicAction::CreateText { pagenum, text: _ } => {
+                let page = &mut target.pages[*pagenum];
+                page.texts.pop();
+                page.index.remove(PageObject::Text(page.texts.len()));
+            }
+            SchematicAction::EditText {
+                pagenum,
+                textnum,
+                old,
+                new: _,
+            } => {
+                target.pages[*pagenum].texts[*textnum].text = old.clone();
+            }
+            SchematicAction::ChangeTextColor {
+                pagenum,
+                textnum,
+                old,
+                new: _,
+            } => {
+                target.pages[*pagenum].texts[*textnum].color = *old;
+            }
+            SchematicAction::CreateBusPart { pagenum, part: _ } => {
+                let page = &mut target.pages[*pagenum];
+                page.bus.pop();
+                page.index.remove(PageObject::Bus(page.bus.len()));
+            }
+            SchematicAction::MoveBusPart {
+                pagenum,
+                partnum,
+                delta,
+            } => {
+                let part = &mut target.pages[*pagenum].bus[*partnum];
+                *part = part.map_points(|c| c - *delta);
+            }
+            SchematicAction::SetBusPart {
+                pagenum,
+                partnum,
+                part,
+            } => {
+                std::mem::swap(&mut target.pages[*pagenum].bus[*partnum], part);
+            }
+            SchematicAction::DeleteBusPart {
+                pagenum,
+                partnum,
+                part,
+            } => {
+                if let Some(p) = part.take() {
+                    target.pages[*pagenum].bus.insert(*partnum, p);
+                    target.index_inserted(*pagenum, PageObject::Bus(*partnum));
+                }
+            }
+        }
+        if let Some((pagenum, obj)) = self.changed_object() {
+            target.index_changed(pagenum, obj);
+        }
+    }
+
+    fn merge(&mut self, other: Self) -> undo::Merged<Self>
+    where
+        Self: Sized,
+    {
+        match self {
+            SchematicAction::AddComponentVariant { pagenum: _, var: _ } => undo::Merged::No(other),
+            SchematicAction::SetDesignator {
+                pagenum,
+                symnum,
+                designator: _,
+            } => {
+                // The designator kept is the one from before the first change, which is what undo restores
+                if let SchematicAction::SetDesignator {
+                    pagenum: pagenum2,
+                    symnum: symnum2,
+                    designator: designator2,
+                } = other
+                {
+                    if *pagenum == pagenum2 && *symnum == symnum2 {
+                        undo::Merged::Yes
+                    } else {
+                        undo::Merged::No(SchematicAction::SetDesignator {
+                            pagenum: pagenum2,
+                            symnum: symnum2,
+                            designator: designator2,
+                        })
+                    }
+                } else {
+                    undo::Merged::No(other)
+                }
+            }
+            SchematicAction::SetUnit {
+                pagenum: _,
+                symnum: _,
+                unit: _,
+            } => undo::Merged::No(other),
+            SchematicAction::SetStyle {
+                pagenum: _,
+                symnum: _,
+                style: _,
+            } => undo::Merged::No(other),
+            SchematicAction::SetOrientation {
+                pagenum: _,
+                symnum: _,
+                orientation: _,
+            } => undo::Merged::No(other),
+            SchematicAction::SetProperty {
+                pagenum,
+                symnum,
+                name,
+                value: _,
+            } => {
+                // The value kept is the one from before the first change, which is what undo restores
+                if let SchematicAction::SetProperty {
+                    pagenum: pagenum2,
+                    symnum: symnum2,
+                    name: name2,
+                    value: value2,
+                } = other
+                {
+                    if *pagenum == pagenum2 && *symnum == symnum2 && *name == name2 {
+                        undo::Merged::Yes
+                    } else {
+                        undo::Merged::No(SchematicAction::SetProperty {
+                            pagenum: pagenum2,
+                            symnum: symnum2,
+                            name: name2,
+                            value: value2,
+                        })
+                    }
+                } else {
+                    undo::Merged::No(other)
+                }
+            }
+            SchematicAction::SetField {
+                pagenum: _,
+                symnum: _,
+                name: _,
+                location: _,
+            } => undo::Merged::No(other),
+            SchematicAction::SetNoConnect {
+                pagenum: _,
+                symnum: _,
+                pin: _,
+                marked: _,
+            } => undo::Merged::No(other),
+            SchematicAction::MoveField {
+                pagenum,
+                symnum,
+                name,
+                delta,
+            } => {
+                if let SchematicAction::MoveField {
+                    pagenum: pn2,
+                    symnum: sn2,
+                    name: name2,
+                    delta: delta2,
+                } = other.clone()
+                {
+                    if *pagenum == pn2 && *symnum == sn2 && *name == name2 {
+                        if (*delta + delta2).is_zero() {
+                            undo::Merged::Annul
+                        } else {
+                            *delta += delta2;
+                            undo::Merged::Yes
+                        }
+                    } else {
+                        undo::Merged::No(other)
+                    }
+                } else {
+                    undo::Merged::No(other)
+                }
             }
-            SchematicAction::MoveText {
-                pagenum,
-                textnum,
-                delta,
+            SchematicAction::DeleteSymbol {
+                pagenum: _,
+                symnum: _,
+                sym: _,
+            } => undo::Merged::No(other),
+            SchematicAction::DeleteText {
+                pagenum: _,
+                textnum: _,
+                text: _,
+            } => undo::Merged::No(other),
+            SchematicAction::CreateBusPart {
+                pagenum: _,
+                part: _,
+            } => undo::Merged::No(other),
+            SchematicAction::MoveBusPart { .. } => {
+                let mut other = other;
+                if self.moves_same(&other) {
+                    if let (Some(d), Some(d2)) = (self.move_delta(), other.move_delta()) {
+                        *d += *d2;
+                    }
+                    if self.move_delta().map_or(false, |d| d.is_zero()) {
+                        undo::Merged::Annul
+                    } else {
+                        undo::Merged::Yes
+                    }
+                } else {
+                    undo::Merged::No(other)
+                }
+            }
+            SchematicAction::SetBusPart {
+                pagenum, partnum, ..
             } => {
-                target.pages[*pagenum].texts[*textnum].location -= *delta;
+                // The part kept is the one from before the first change, which is what undo restores
+                match other {
+                    SchematicAction::SetBusPart {
+                        pagenum: pagenum2,
+                        partnum: partnum2,
+                        ..
+                    } if *pagenum == pagenum2 && *partnum == partnum2 => undo::Merged::Yes,
+                    other => undo::Merged::No(other),
+                }
             }
-            SchematicAction::CreateText { pagenum, text: _ } => {
-                target.pages[*pagenum].texts.pop();
+            SchematicAction::DeleteBusPart {
+                pagenum: _,
+                partnum: _,
+                part: _,
+            } => undo::Merged::No(other),
+            SchematicAction::InsertPage {
+                pagenum: _,
+                page: _,
+            } => undo::Merged::No(other),
+            SchematicAction::RemovePage {
+                pagenum: _,
+                page: _,
+            } => undo::Merged::No(other),
+            SchematicAction::MovePage { from: _, to: _ } => undo::Merged::No(other),
+            SchematicAction::RenamePage { pagenum, name: _ } => {
+                // The name kept is the one from before the first change, which is what undo restores
+                if let SchematicAction::RenamePage {
+                    pagenum: pagenum2,
+                    name: name2,
+                } = other
+                {
+                    if *pagenum == pagenum2 {
+                        undo::Merged::Yes
+                    } else {
+                        undo::Merged::No(SchematicAction::RenamePage {
+                            pagenum: pagenum2,
+                            name: name2,
+                        })
+                    }
+                } else {
+                    undo::Merged::No(other)
+                }
             }
-            SchematicAction::EditText {
+            SchematicAction::SetPageFormat {
                 pagenum,
-                textnum,
-                old,
-                new: _,
+                format: _,
+                size: _,
             } => {
-                target.pages[*pagenum].texts[*textnum].text = old.clone();
+                // The format kept is the one from before the first change, so that dragging a size is one step
+                if let SchematicAction::SetPageFormat {
+                    pagenum: pagenum2,
+                    format: format2,
+                    size: size2,
+                } = other
+                {
+                    if *pagenum == pagenum2 {
+                        undo::Merged::Yes
+                    } else {
+                        undo::Merged::No(SchematicAction::SetPageFormat {
+                            pagenum: pagenum2,
+                            format: format2,
+                            size: size2,
+                        })
+                    }
+                } else {
+                    undo::Merged::No(other)
+                }
             }
-            SchematicAction::ChangeTextColor {
-                pagenum,
-                textnum,
-                old,
-                new: _,
-            } => {
-                target.pages[*pagenum].texts[*textnum].color = *old;
+            SchematicAction::SetVariable { name, value: _ } => {
+                // The value kept is the one from before the first change, which is what undo restores
+                if let SchematicAction::SetVariable {
+                    name: name2,
+                    value: value2,
+                } = other
+                {
+                    if *name == name2 {
+                        undo::Merged::Yes
+                    } else {
+                        undo::Merged::No(SchematicAction::SetVariable {
+                            name: name2,
+                            value: value2,
+                        })
+                    }
+                } else {
+                    undo::Merged::No(other)
+                }
             }
-        }
-    }
-
-    fn merge(&mut self, other: Self) -> undo::Merged<Self>
-    where
-        Self: Sized,
-    {
-        match self {
-            SchematicAction::AddComponentVariant { pagenum: _, var: _ } => undo::Merged::No(other),
+            SchematicAction::Compound { actions } => match other {
+                // Moving a group of objects merges with the next move of the same group, like moving a single object
+                SchematicAction::Compound {
+                    actions: mut actions2,
+                } if actions.len() == actions2.len()
+                    && actions.iter().zip(&actions2).all(|(a, b)| a.moves_same(b)) =>
+                {
+                    for (a, b) in actions.iter_mut().zip(actions2.iter_mut()) {
+                        if let (Some(d), Some(d2)) = (a.move_delta(), b.move_delta()) {
+                            *d += *d2;
+                        }
+                    }
+                    if actions
+                        .iter_mut()
+                        .all(|a| a.move_delta().map_or(true, |d| d.is_zero()))
+                    {
+                        undo::Merged::Annul
+                    } else {
+                        undo::Merged::Yes
+                    }
+                }
+                other => undo::Merged::No(other),
+            },
+            SchematicAction::RenameLibraryItem {
+                item: _,
+                newlib: _,
+                newname: _,
+            } => undo::Merged::No(other),
+            SchematicAction::UpdateCachedComponent {
+                libname: _,
+                comname: _,
+                component: _,
+            } => undo::Merged::No(other),
+            SchematicAction::UpdateCachedSymbol {
+                libname: _,
+                symname: _,
+                symbol: _,
+            } => undo::Merged::No(other),
             SchematicAction::MoveSymbol {
                 pagenum,
                 symnum,
@@ -346,7 +2361,7 @@ impl undo::Action for SchematicAction {
                 } = other.clone()
                 {
                     if *pagenum == pn2 && *symnum == sn2 {
-                        if (*delta + delta2).less_than_epsilon() {
+                        if (*delta + delta2).is_zero() {
                             undo::Merged::Annul
                         } else {
                             *delta += delta2;
@@ -371,7 +2386,7 @@ impl undo::Action for SchematicAction {
                 } = other.clone()
                 {
                     if *pagenum == pn2 && *textnum == tn2 {
-                        if (*delta + delta2).less_than_epsilon() {
+                        if (*delta + delta2).is_zero() {
                             undo::Merged::Annul
                         } else {
                             *delta += delta2;
@@ -451,7 +2466,7 @@ pub struct SchematicHolder {
     /// The actual schematic, saved to disk when requested by the user
     pub schematic: Schematic,
     /// The history log for the schematic
-    pub schematic_log: undo::Record<SchematicAction>,
+    pub schematic_log: crate::journal::JournaledRecord<SchematicAction>,
     /// Flag that determines if the schematic has been saved
     pub schematic_was_saved: bool,
     /// The path where the schematic is saved.
@@ -463,7 +2478,7 @@ pub struct SchematicHolder {
 impl SchematicHolder {
     /// Create an example schematicHolder
     pub fn new_example() -> Self {
-        let mut rec = undo::Record::new();
+        let mut rec = crate::journal::JournaledRecord::new();
         rec.set_saved(false);
         Self {
             schematic: Schematic::new_example(),
@@ -474,6 +2489,41 @@ impl SchematicHolder {
         }
     }
 
+    /// Create a schematic holder for a schematic recovered from an autosave, marked as having unsaved changes
+    pub fn recovered(schematic: Schematic, path: Option<StoragePath>) -> Self {
+        let mut rec = crate::journal::JournaledRecord::new();
+        rec.set_saved(false);
+        rec.set_journal(path.as_ref().and_then(crate::journal::journal_path));
+        let format = path
+            .as_ref()
+            .map(|p| p.default_format())
+            .unwrap_or_default();
+        Self {
+            schematic,
+            schematic_log: rec,
+            schematic_was_saved: true,
+            path,
+            format,
+        }
+    }
+
+    /// Create a schematic holder for a schematic that was just loaded from the given path
+    pub fn loaded(
+        schematic: Schematic,
+        path: StoragePath,
+        format: crate::general::StorageFormat,
+    ) -> Self {
+        let mut rec = crate::journal::JournaledRecord::new();
+        rec.set_journal(crate::journal::journal_path(&path));
+        Self {
+            schematic,
+            schematic_log: rec,
+            schematic_was_saved: false,
+            path: Some(path),
+            format,
+        }
+    }
+
     /// Retrieve the name of the schematic
     pub fn name(&self) -> String {
         if self.path.is_some() {
@@ -495,14 +2545,24 @@ impl SchematicHolder {
 
     /// Set the path for the schematic when saving
     pub fn set_path(&mut self, p: StoragePath) {
+        self.schematic_log
+            .set_journal(crate::journal::journal_path(&p));
         self.path = Some(p);
     }
 
     /// Save the schematic information to the previously configured path. Will return ok if no path is set
-    pub fn save(&mut self) -> Result<(), crate::general::StorageSaveError> {
+    pub fn save(
+        &mut self,
+        libs: &HashMap<String, LibraryHolder>,
+    ) -> Result<(), crate::general::StorageSaveError> {
         if let Some(path) = &self.path {
+            self.schematic.declare_used_libraries();
+            self.schematic.refresh_cache(libs);
             let mut writer = path.writer()?;
-            return self.format.save(&mut writer, &self.schematic);
+            self.format.save(&mut writer, &self.schematic)?;
+            writer.commit()?;
+            crate::journal::mark(path, false);
+            self.schematic_log.set_saved(true);
         }
         Ok(())
     }
@@ -521,6 +2581,7 @@ impl SchematicHolder {
 }
 
 /// The things that can be selected on a schematic
+#[derive(Clone, PartialEq)]
 pub enum SchematicSelection {
     /// A text object is selected
     Text {
@@ -536,6 +2597,69 @@ pub enum SchematicSelection {
         /// The symbol number
         sym: usize,
     },
+    /// A piece of a bus has been selected
+    Bus {
+        /// The page number
+        page: usize,
+        /// The bus part number
+        partnum: usize,
+    },
+}
+
+impl SchematicSelection {
+    /// The page of the selected object
+    pub fn page(&self) -> usize {
+        match self {
+            SchematicSelection::Text { page, .. }
+            | SchematicSelection::Symbol { page, .. }
+            | SchematicSelection::Bus { page, .. } => *page,
+        }
+    }
+
+    /// The selection after the action moves pages around, None when the page of the selection is removed
+    pub fn after(&self, action: &SchematicAction) -> Option<Self> {
+        Some(self.on_page(action.page_after(self.page())?))
+    }
+
+    /// The selection after the action is undone, None when undoing it removes the page of the selection
+    pub fn before(&self, action: &SchematicAction) -> Option<Self> {
+        Some(self.on_page(action.page_before(self.page())?))
+    }
+
+    /// The same object, with its page moved to newpage
+    fn on_page(&self, newpage: usize) -> Self {
+        match self {
+            SchematicSelection::Text { page: _, textnum } => SchematicSelection::Text {
+                page: newpage,
+                textnum: *textnum,
+            },
+            SchematicSelection::Symbol { page: _, sym } => SchematicSelection::Symbol {
+                page: newpage,
+                sym: *sym,
+            },
+            SchematicSelection::Bus { page: _, partnum } => SchematicSelection::Bus {
+                page: newpage,
+                partnum: *partnum,
+            },
+        }
+    }
+
+    /// Returns true when the selected object exists in the schematic
+    pub fn is_valid(&self, sch: &Schematic) -> bool {
+        match self {
+            SchematicSelection::Text { page, textnum } => sch
+                .pages
+                .get(*page)
+                .map_or(false, |p| *textnum < p.texts.len()),
+            SchematicSelection::Symbol { page, sym } => {
+                sch.pages.get(*page).map_or(false, |p| *sym < p.syms.len())
+            }
+            SchematicSelection::Bus { page, partnum } => sch
+                .pages
+                .get(*page)
+                .map_or(false, |p| *partnum < p.bus.len()),
+        }
+    }
 }
 
 /// The widget is responsible for drawing the state of the schematic for the user
@@ -546,8 +2670,8 @@ pub struct SchematicWidget<'a> {
     mm: &'a mut MouseMode,
     /// The current page number that is being examined
     page: usize,
-    /// The object currently selected. Will eventually become Vec<SchematicSelection>
-    selection: &'a mut Option<SchematicSelection>,
+    /// The objects currently selected
+    selection: &'a mut Vec<SchematicSelection>,
     /// The origin modifier for panning the symbol around
     origin: &'a mut crate::general::Coordinates,
     /// The zoom factor
@@ -556,6 +2680,10 @@ pub struct SchematicWidget<'a> {
     component: Option<crate::component::ComponentVariantReference>,
     /// The libraries for the application
     libs: &'a HashMap<String, LibraryHolder>,
+    /// The grid that objects are placed on
+    grid: crate::grid::Grid,
+    /// Where the pointer is on the page, set when the widget is drawn
+    cursor: Option<&'a mut Option<crate::general::Coordinates>>,
 }
 
 impl<'a> SchematicWidget<'a> {
@@ -563,7 +2691,7 @@ impl<'a> SchematicWidget<'a> {
     pub fn new(
         sch: &'a mut SchematicHolder,
         mm: &'a mut MouseMode,
-        sel: &'a mut Option<SchematicSelection>,
+        sel: &'a mut Vec<SchematicSelection>,
         origin: &'a mut crate::general::Coordinates,
         zoom: &'a mut f32,
         component: Option<crate::component::ComponentVariantReference>,
@@ -578,8 +2706,28 @@ impl<'a> SchematicWidget<'a> {
             zoom,
             component,
             libs,
+            grid: crate::grid::Grid::default(),
+            cursor: None,
         }
     }
+
+    /// Show the given page of the schematic instead of the first one
+    pub fn with_page(mut self, page: usize) -> Self {
+        self.page = page;
+        self
+    }
+
+    /// Draw and snap to the given grid instead of the default one
+    pub fn with_grid(mut self, grid: crate::grid::Grid) -> Self {
+        self.grid = grid;
+        self
+    }
+
+    /// Report where the pointer is on the page, None when it is not over the widget
+    pub fn with_cursor(mut self, cursor: &'a mut Option<crate::general::Coordinates>) -> Self {
+        self.cursor = Some(cursor);
+        self
+    }
 }
 
 impl<'a> egui::Widget for SchematicWidget<'a> {
@@ -596,11 +2744,51 @@ impl<'a> egui::Widget for SchematicWidget<'a> {
         let size = egui::vec2(area.max.x - area.min.x, area.max.y - area.min.y);
 
         let (mut pr, pntr) = ui.allocate_painter(size, sense);
+        let background = pr.clone();
+        let modifiers = ui.input(|i| i.modifiers);
+        self.sch.schematic.cache_new_components(self.libs);
+        let page_vars = self.sch.schematic.page_variables(self.page);
+        let sheet = self.sch.schematic.pages[self.page].sheet(
+            &self.sch.schematic.cache,
+            self.libs,
+            &page_vars,
+        );
+        let targets = self.sch.schematic.pages[self.page].snap_targets(
+            self.page,
+            &self.sch.schematic.cache,
+            self.libs,
+            self.selection,
+        );
+        self.sch.schematic.build_index(self.page, self.libs);
         let cur_page = &mut self.sch.schematic.pages[self.page];
 
         let zoom_origin =
             (area.left_top().to_vec2() + egui::vec2(size.x / 2.0, size.y / 2.0)).to_pos2();
         let origin = self.origin.get_pos2(*self.zoom, zoom_origin);
+        let zoom = *self.zoom;
+        let to_page =
+            |p: egui::Pos2| crate::general::Coordinates::from_pos2((p - origin).to_pos2(), zoom);
+        let pointer = ui.input(|i| i.pointer.interact_pos()).map(to_page);
+        // Only the objects in view are drawn, and only the ones near the pointer or selected respond to it
+        let visible =
+            BoundingBox::from_points([area.min, area.max].into_iter().map(|p| to_page(p).into()))
+                .map(|view| cur_page.index.query(&view))
+                .unwrap_or_default();
+        let near = pointer
+            .map(|p| {
+                cur_page
+                    .index
+                    .query(&BoundingBox::around(p.into(), PICK_DISTANCE))
+            })
+            .unwrap_or_default();
+        if let Some(cursor) = self.cursor {
+            *cursor = ui
+                .input(|i| i.pointer.hover_pos())
+                .filter(|p| area.contains(*p))
+                .map(|p| self.grid.snap(to_page(p), &targets, zoom));
+        }
+
+        self.grid.draw(ui, &pntr, area, zoom, origin);
 
         //placeholder for drawing the crosshairs at the origin
         if false {
@@ -658,6 +2846,28 @@ impl<'a> egui::Widget for SchematicWidget<'a> {
             stroke,
         );
 
+        let sheet_stroke = egui_multiwin::egui::Stroke {
+            width: 1.0,
+            color: Colors::Standard.get_color32(crate::general::ColorMode::ScreenModeDark),
+        };
+        let screen = crate::geometry::Transform::screen(*self.zoom, origin);
+        let mm_pos = |p: (f32, f32)| screen.apply(p.into()).to_pos2();
+        for (a, b) in &sheet.lines {
+            pntr.line_segment([mm_pos(*a), mm_pos(*b)], sheet_stroke);
+        }
+        for t in &sheet.texts {
+            let align = if t.centered {
+                egui::Align2::CENTER_CENTER
+            } else {
+                egui::Align2::LEFT_BOTTOM
+            };
+            let font = egui::FontId {
+                size: crate::general::Length::from_mm(t.size).get_screen(*self.zoom, zoom_origin),
+                family: egui::FontFamily::Name("computermodern".into()),
+            };
+            pntr.text(mm_pos(t.location), align, &t.text, font, sheet_stroke.color);
+        }
+
         let mut actions = Vec::new();
 
         match &self.mm {
@@ -665,50 +2875,56 @@ impl<'a> egui::Widget for SchematicWidget<'a> {
                 if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
                     *self.mm = MouseMode::Selection;
                 }
-                if self.selection.is_some() {
-                    *self.selection = None;
-                }
+                self.selection.clear();
             }
             MouseMode::Selection => {
-                if ui.input(|i| i.key_pressed(egui::Key::Escape)) && self.selection.is_some() {
-                    *self.selection = None;
+                if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    self.selection.clear();
                 }
             }
-            MouseMode::NewComponent => {
+            MouseMode::NewComponent
+            | MouseMode::NewBus
+            | MouseMode::NewBusEntry
+            | MouseMode::NewBusLabel
+            | MouseMode::NewNoConnect => {
                 if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
                     *self.mm = MouseMode::Selection;
                 }
             }
         }
 
-        if pr.clicked() && self.mm == &MouseMode::Selection {
-            *self.selection = None;
+        if background.clicked()
+            && self.mm == &MouseMode::Selection
+            && !(modifiers.shift || modifiers.command)
+        {
+            self.selection.clear();
         }
 
+        // The objects that can be selected, with the areas of the screen they cover
+        let mut selectable: Vec<(SchematicSelection, Vec<crate::geometry::HitArea>)> = Vec::new();
+        // How far the selected objects are dragged this frame
+        let mut group_drag: Option<crate::general::Coordinates> = None;
+
         if let MouseMode::NewText = &self.mm {
-            let pos = ui.input(|i| i.pointer.interact_pos());
-            if let Some(pos) = pos {
+            if let Some(pointer) = pointer {
+                let location = self.grid.snap(pointer, &targets, zoom);
                 if pr.clicked() {
-                    let pos2 = pos - area.left_top();
                     actions.push(SchematicAction::CreateText {
                         pagenum: self.page,
                         text: TextOnPage {
                             text: "New text".to_string(),
-                            location: crate::general::Coordinates::from_pos2(
-                                pos2.to_pos2(),
-                                *self.zoom,
-                            ),
+                            location,
                             color: Colors::Standard,
-                            size: crate::general::Length::Inches(0.2),
+                            size: crate::general::Length::from_inches(0.2),
                         },
                     });
                 } else {
                     pntr.text(
-                        pos,
+                        location.get_pos2(zoom, origin),
                         egui::Align2::LEFT_BOTTOM,
                         "New text".to_string(),
                         egui::FontId {
-                            size: crate::general::Length::Inches(0.2)
+                            size: crate::general::Length::from_inches(0.2)
                                 .get_screen(*self.zoom, egui::pos2(0.0, 0.0)),
                             family: egui::FontFamily::Name("computermodern".into()),
                         },
@@ -718,7 +2934,11 @@ impl<'a> egui::Widget for SchematicWidget<'a> {
             }
         }
 
-        for (i, t) in cur_page.texts.iter().enumerate() {
+        for i in visible.iter().filter_map(|o| match o {
+            PageObject::Text(i) => Some(*i),
+            PageObject::Symbol(_) | PageObject::Bus(_) => None,
+        }) {
+            let t = &cur_page.texts[i];
             let pos = t.location.get_pos2(*self.zoom, origin);
             let align = egui::Align2::LEFT_BOTTOM;
             let font = egui::FontId {
@@ -728,21 +2948,38 @@ impl<'a> egui::Widget for SchematicWidget<'a> {
             let color = t
                 .color
                 .get_color32(crate::general::ColorMode::ScreenModeDark);
-            let r = pntr.text(pos, align, t.text.clone(), font, color);
+            let r = pntr.text(pos, align, page_vars.substitute(&t.text), font, color);
             let r = r.intersect(area);
             if r.is_positive() {
-                let id = egui::Id::new(1 + i);
-                let response = ui.interact(r.intersect(area), id, sense);
+                let item = SchematicSelection::Text {
+                    page: self.page,
+                    textnum: i,
+                };
+                selectable.push((item.clone(), vec![r.into()]));
+                if near.binary_search(&PageObject::Text(i)).is_err()
+                    && !self.selection.contains(&item)
+                {
+                    continue;
+                }
+                let response = crate::general::respond(ui, format!("text{}", i), vec![r]);
                 let response = match self.mm {
                     MouseMode::NewComponent => response,
                     MouseMode::NewText => response,
+                    MouseMode::NewBus | MouseMode::NewBusEntry | MouseMode::NewBusLabel => response,
+                    MouseMode::NewNoConnect => response,
                     MouseMode::Selection => {
                         if response.clicked() {
-                            println!("Clicked in selection mode");
-                            *self.selection = Some(SchematicSelection::Text {
-                                page: self.page,
-                                textnum: i,
-                            });
+                            crate::general::click_select(self.selection, item, modifiers);
+                        } else if let (true, Some(pointer)) =
+                            (response.dragged_by(egui::PointerButton::Primary), pointer)
+                        {
+                            if !self.selection.contains(&item) {
+                                *self.selection = vec![item];
+                            }
+                            group_drag = Some(
+                                self.grid
+                                    .drag_delta(ui, &response, t.location, pointer, &targets, zoom),
+                            );
                         }
                         let r = response.context_menu(|ui| {
                             if ui.button("Properties").clicked() {
@@ -755,17 +2992,17 @@ impl<'a> egui::Widget for SchematicWidget<'a> {
                         if response.clicked() {
                             println!("Clicked in drag mode");
                         }
-                        if response.dragged() {
-                            let amount = response.drag_delta();
-                            let a = SchematicAction::MoveText {
-                                pagenum: self.page,
-                                textnum: i,
-                                delta: crate::general::Coordinates::from_pos2(
-                                    amount.to_pos2(),
-                                    *self.zoom,
-                                ),
-                            };
-                            actions.push(a);
+                        if let (true, Some(pointer)) = (response.dragged(), pointer) {
+                            let delta = self
+                                .grid
+                                .drag_delta(ui, &response, t.location, pointer, &targets, zoom);
+                            if !delta.is_zero() {
+                                actions.push(SchematicAction::MoveText {
+                                    pagenum: self.page,
+                                    textnum: i,
+                                    delta,
+                                });
+                            }
                         }
                         let r = response.context_menu(|ui| {
                             if ui.button("Properties").clicked() {
@@ -786,53 +3023,247 @@ impl<'a> egui::Widget for SchematicWidget<'a> {
         let cur_page = &mut self.sch.schematic.pages[self.page];
         let mut actions = Vec::new();
 
-        for (i, sch) in &mut cur_page.syms.iter().enumerate() {
-            let mut sym = None;
-            if let Some(lib) = self.libs.get(&sch.lib) {
-                if let Some(lib) = &lib.library {
-                    if let Some(component) = lib.components.get(&sch.com) {
-                        if let Some(variant) = component.variants.get(&sch.var) {
-                            if let Some(symbol) = &variant.symbol {
-                                sym = Some((symbol, lib));
+        let no_connect_stroke = egui::Stroke::new(
+            2.0,
+            Colors::Standard.get_color32(crate::general::ColorMode::ScreenModeDark),
+        );
+        for i in visible.iter().filter_map(|o| match o {
+            PageObject::Symbol(i) => Some(*i),
+            PageObject::Text(_) | PageObject::Bus(_) => None,
+        }) {
+            let sch = &cur_page.syms[i];
+            let item = SchematicSelection::Symbol {
+                page: self.page,
+                sym: i,
+            };
+            let pickable = near.binary_search(&PageObject::Symbol(i)).is_ok()
+                || self.selection.contains(&item);
+            if let Some(symbol) = self.sch.schematic.cache.get_symbol(sch, self.libs) {
+                let pos = sch.pos.get_pos2(*self.zoom, origin) - zoom_origin.to_vec2();
+                let (prefix, defaults) =
+                    self.sch.schematic.cache.get_field_defaults(sch, self.libs);
+                let vars = sch.variables(&page_vars, &prefix, &defaults);
+                let placement = SymbolPlacement {
+                    variables: Some(&vars),
+                    ..sch.placement()
+                };
+                let mut rects = symbol.draw(*self.zoom, zoom_origin, &pntr, pos, area, placement);
+                for (n, pin) in symbol.pins.iter().enumerate() {
+                    if sch.no_connect.contains(&sch.pin_number(n, pin)) {
+                        let at = sch.pos + sch.orientation.transform(pin.location);
+                        for (a, b) in no_connect_lines(at) {
+                            pntr.line_segment(
+                                [a.get_pos2(zoom, origin), b.get_pos2(zoom, origin)],
+                                no_connect_stroke,
+                            );
+                        }
+                    }
+                }
+                for f in &sch.fields {
+                    let text = if let Some(t) = sch.field_text(&f.name, &prefix, &defaults) {
+                        vars.substitute(&t).into_owned()
+                    } else {
+                        continue;
+                    };
+                    let r = pntr.text(
+                        (sch.pos + f.location).get_pos2(*self.zoom, origin),
+                        egui::Align2::LEFT_TOP,
+                        text,
+                        egui::FontId {
+                            size: crate::component::FIELD_SIZE.get_screen(*self.zoom, zoom_origin),
+                            family: egui::FontFamily::Name("computermodern".into()),
+                        },
+                        Colors::Standard.get_color32(crate::general::ColorMode::ScreenModeDark),
+                    );
+                    let r = r.intersect(area);
+                    if !r.is_positive() {
+                        continue;
+                    }
+                    if let (MouseMode::TextDrag, true) = (&self.mm, pickable) {
+                        let response = crate::general::respond(
+                            ui,
+                            format!("symbol{}field{}", i, f.name),
+                            vec![r],
+                        );
+                        if let (true, Some(pointer)) = (response.dragged(), pointer) {
+                            let anchor = sch.pos + f.location;
+                            let delta = self
+                                .grid
+                                .drag_delta(ui, &response, anchor, pointer, &targets, zoom);
+                            if !delta.is_zero() {
+                                actions.push(SchematicAction::MoveField {
+                                    pagenum: self.page,
+                                    symnum: i,
+                                    name: f.name.clone(),
+                                    delta,
+                                });
                             }
                         }
+                        pr = pr.union(response);
+                    } else {
+                        rects.push(r);
                     }
                 }
-            }
-            if let Some((sym, lib)) = sym {
-                let libname = sym.lib.get_name(&lib);
-                if let Some(lib) = self.libs.get(&libname) {
-                    if let Some(lib) = &lib.library {
-                        if let Some(symbol) = lib.syms.get(&sym.sym) {
-                            let pos = sch.pos.get_pos2(*self.zoom, origin) - zoom_origin.to_vec2();
-                            let rects = symbol.draw(*self.zoom, zoom_origin, &pntr, pos, area);
-                            let response =
-                                crate::general::respond(ui, format!("symbol{}", i), rects);
-                            let response = match &self.mm {
-                                MouseMode::Selection => {
-                                    if response.clicked() {
-                                        *self.selection = Some(SchematicSelection::Symbol {
-                                            page: self.page,
-                                            sym: i,
-                                        });
-                                    }
-                                    response
-                                }
-                                MouseMode::TextDrag => response,
-                                MouseMode::NewText => response,
-                                MouseMode::NewComponent => response,
-                            };
-                            pr = pr.union(response);
+                if rects.is_empty() {
+                    continue;
+                }
+                selectable.push((item.clone(), rects.iter().map(|r| (*r).into()).collect()));
+                if !pickable {
+                    continue;
+                }
+                let response = crate::general::respond(ui, format!("symbol{}", i), rects);
+                let response = match &self.mm {
+                    MouseMode::Selection => {
+                        if response.clicked() {
+                            crate::general::click_select(self.selection, item, modifiers);
+                        } else if let (true, Some(pointer)) =
+                            (response.dragged_by(egui::PointerButton::Primary), pointer)
+                        {
+                            if !self.selection.contains(&item) {
+                                *self.selection = vec![item];
+                            }
+                            group_drag = Some(
+                                self.grid
+                                    .drag_delta(ui, &response, sch.pos, pointer, &targets, zoom),
+                            );
                         }
+                        response
+                    }
+                    MouseMode::TextDrag => response,
+                    MouseMode::NewText => response,
+                    MouseMode::NewComponent => response,
+                    MouseMode::NewBus | MouseMode::NewBusEntry | MouseMode::NewBusLabel => response,
+                    MouseMode::NewNoConnect => response,
+                };
+                pr = pr.union(response);
+            }
+        }
+
+        let bus_color = Colors::Bus.get_color32(crate::general::ColorMode::ScreenModeDark);
+        let bus_font = egui::FontId {
+            size: crate::component::FIELD_SIZE.get_screen(*self.zoom, zoom_origin),
+            family: egui::FontFamily::Name("computermodern".into()),
+        };
+        for i in visible.iter().filter_map(|o| match o {
+            PageObject::Bus(i) => Some(*i),
+            PageObject::Symbol(_) | PageObject::Text(_) => None,
+        }) {
+            let part = &cur_page.bus[i];
+            let item = SchematicSelection::Bus {
+                page: self.page,
+                partnum: i,
+            };
+            let areas: Vec<crate::geometry::HitArea> = match part {
+                BusPart::Wire { from, to } => {
+                    let ends = [from.get_pos2(zoom, origin), to.get_pos2(zoom, origin)];
+                    pntr.line_segment(ends, egui::Stroke::new(BUS_SCREEN_WIDTH, bus_color));
+                    vec![crate::geometry::HitArea::Segment {
+                        from: ends[0].into(),
+                        to: ends[1].into(),
+                        reach: BUS_SCREEN_WIDTH / 2.0,
+                    }]
+                }
+                BusPart::Entry { from, to, member } => {
+                    let ends = [from.get_pos2(zoom, origin), to.get_pos2(zoom, origin)];
+                    pntr.line_segment(ends, egui::Stroke::new(2.0, bus_color));
+                    let r = pntr.text(
+                        ends[1],
+                        egui::Align2::LEFT_BOTTOM,
+                        member,
+                        bus_font.clone(),
+                        bus_color,
+                    );
+                    vec![
+                        crate::geometry::HitArea::Segment {
+                            from: ends[0].into(),
+                            to: ends[1].into(),
+                            reach: 1.0,
+                        },
+                        r.into(),
+                    ]
+                }
+                BusPart::Label { location, text } => {
+                    let at = location.get_pos2(zoom, origin);
+                    vec![pntr
+                        .text(
+                            at,
+                            egui::Align2::LEFT_BOTTOM,
+                            text,
+                            bus_font.clone(),
+                            bus_color,
+                        )
+                        .into()]
+                }
+            };
+            // Boxes are cut to the widget, and segments are kept while they reach into it
+            let areas: Vec<crate::geometry::HitArea> = areas
+                .into_iter()
+                .filter_map(|a| match a {
+                    crate::geometry::HitArea::Box(b) => {
+                        let r = b.to_rect().intersect(area);
+                        r.is_positive().then(|| r.into())
+                    }
+                    crate::geometry::HitArea::Segment { .. } => {
+                        a.intersects(&area.into()).then(|| a)
+                    }
+                })
+                .collect();
+            if areas.is_empty() {
+                continue;
+            }
+            selectable.push((item.clone(), areas.clone()));
+            if near.binary_search(&PageObject::Bus(i)).is_err() && !self.selection.contains(&item) {
+                continue;
+            }
+            let response = crate::general::respond(ui, format!("bus{}", i), areas);
+            if let MouseMode::Selection = &self.mm {
+                if response.clicked() {
+                    crate::general::click_select(self.selection, item, modifiers);
+                } else if let (true, Some(pointer)) =
+                    (response.dragged_by(egui::PointerButton::Primary), pointer)
+                {
+                    if !self.selection.contains(&item) {
+                        *self.selection = vec![item];
                     }
+                    group_drag = Some(self.grid.drag_delta(
+                        ui,
+                        &response,
+                        part.anchor(),
+                        pointer,
+                        &targets,
+                        zoom,
+                    ));
                 }
             }
+            pr = pr.union(response);
+        }
+
+        if let Some(delta) = group_drag.filter(|d| !d.is_zero()) {
+            actions.extend(self.sch.schematic.move_action(self.selection, delta));
         }
 
         for a in actions {
             self.sch.schematic_log.apply(&mut self.sch.schematic, a);
         }
 
+        if let MouseMode::Selection = &self.mm {
+            for (item, rects) in &selectable {
+                if self.selection.contains(item) {
+                    crate::general::draw_selected(ui, &pntr, rects);
+                }
+            }
+            if let Some(band) = crate::general::RubberBand::track(ui, &background, &pntr) {
+                if !(modifiers.shift || modifiers.command) {
+                    self.selection.clear();
+                }
+                for (item, rects) in selectable {
+                    if band.selects(&rects) && !self.selection.contains(&item) {
+                        self.selection.push(item);
+                    }
+                }
+            }
+        }
+
         let mut actions = Vec::new();
         if let MouseMode::NewComponent = &self.mm {
             let pos = ui.input(|i| i.pointer.interact_pos());
@@ -840,22 +3271,139 @@ impl<'a> egui::Widget for SchematicWidget<'a> {
                 if let Some(variantref) = self.component {
                     let sd = variantref.get_symbol(self.libs);
                     if let Some(symdef) = sd {
-                        let pos2 = (pos - zoom_origin).to_pos2();
+                        let snapped = self.grid.snap(to_page(pos), &targets, zoom);
+                        let pos2 = snapped.get_pos2(zoom, origin) - zoom_origin.to_vec2();
                         if pr.clicked() {
                             let mut vr = variantref.clone();
-                            vr.pos = crate::general::Coordinates::from_pos2(
-                                (pos - origin).to_pos2(),
-                                *self.zoom,
-                            );
-                            actions.push(SchematicAction::AddComponentVariant {
+                            vr.pos = snapped;
+                            // A copy of the component is stored right away, so the area it covers on the page is known
+                            let cache = &self.sch.schematic.cache;
+                            let mut placing = if cache.cached_component(&vr).is_none() {
+                                let item = crate::library::LibraryItem::Component(
+                                    vr.lib.clone(),
+                                    vr.com.clone(),
+                                );
+                                SchematicAction::update_cache(cache, &item, self.libs)
+                            } else {
+                                Vec::new()
+                            };
+                            placing.push(SchematicAction::AddComponentVariant {
                                 pagenum: self.page,
                                 var: vr,
                             });
+                            actions.push(if placing.len() == 1 {
+                                placing.remove(0)
+                            } else {
+                                SchematicAction::Compound { actions: placing }
+                            });
                         } else {
-                            symdef.draw(*self.zoom, zoom_origin, &pntr, pos2, area);
+                            symdef.draw(
+                                *self.zoom,
+                                zoom_origin,
+                                &pntr,
+                                pos2,
+                                area,
+                                variantref.placement(),
+                            );
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(pointer) = pointer {
+            let at = self.grid.snap(pointer, &targets, zoom);
+            let start_id = background.id.with("bus start");
+            match &self.mm {
+                MouseMode::NewBus => {
+                    if pr.drag_started() {
+                        ui.data_mut(|d| d.insert_temp(start_id, at));
+                    }
+                    let start: Option<crate::general::Coordinates> =
+                        ui.data(|d| d.get_temp(start_id));
+                    if let Some(from) = start.filter(|from| *from != at) {
+                        if pr.drag_stopped() {
+                            ui.data_mut(|d| d.remove::<crate::general::Coordinates>(start_id));
+                            actions.push(SchematicAction::CreateBusPart {
+                                pagenum: self.page,
+                                part: BusPart::Wire { from, to: at },
+                            });
+                        } else if pr.dragged() {
+                            pntr.line_segment(
+                                [from.get_pos2(zoom, origin), at.get_pos2(zoom, origin)],
+                                egui::Stroke::new(BUS_SCREEN_WIDTH, bus_color),
+                            );
+                        }
+                    }
+                }
+                MouseMode::NewBusEntry => {
+                    let to = at + crate::bus::ENTRY_SIZE;
+                    if pr.clicked() {
+                        let page = &self.sch.schematic.pages[self.page];
+                        actions.push(SchematicAction::CreateBusPart {
+                            pagenum: self.page,
+                            part: BusPart::Entry {
+                                from: at,
+                                to,
+                                member: page.next_bus_member(at),
+                            },
+                        });
+                    } else {
+                        pntr.line_segment(
+                            [at.get_pos2(zoom, origin), to.get_pos2(zoom, origin)],
+                            egui::Stroke::new(2.0, bus_color),
+                        );
+                    }
+                }
+                MouseMode::NewBusLabel => {
+                    if pr.clicked() {
+                        actions.push(SchematicAction::CreateBusPart {
+                            pagenum: self.page,
+                            part: BusPart::Label {
+                                location: at,
+                                text: NEW_BUS_LABEL.to_string(),
+                            },
+                        });
+                    } else {
+                        let pos = at.get_pos2(zoom, origin);
+                        pntr.text(
+                            pos,
+                            egui::Align2::LEFT_BOTTOM,
+                            NEW_BUS_LABEL,
+                            bus_font,
+                            bus_color,
+                        );
+                    }
+                }
+                MouseMode::NewNoConnect => {
+                    if pr.clicked() {
+                        let page = &self.sch.schematic.pages[self.page];
+                        let pins = page.pins(&self.sch.schematic.cache, self.libs);
+                        let key = crate::netlist::connection_key(self.page, at);
+                        if let Some((symnum, pin, _)) = pins
+                            .into_iter()
+                            .find(|(_, _, p)| crate::netlist::connection_key(self.page, *p) == key)
+                        {
+                            let marked = !page.syms[symnum].no_connect.contains(&pin);
+                            actions.push(SchematicAction::SetNoConnect {
+                                pagenum: self.page,
+                                symnum,
+                                pin,
+                                marked,
+                            });
+                        }
+                    } else {
+                        for (a, b) in no_connect_lines(at) {
+                            pntr.line_segment(
+                                [a.get_pos2(zoom, origin), b.get_pos2(zoom, origin)],
+                                no_connect_stroke,
+                            );
                         }
                     }
                 }
+                MouseMode::Selection
+                | MouseMode::TextDrag
+                | MouseMode::NewText
+                | MouseMode::NewComponent => {}
             }
         }
         for a in actions {
@@ -0,0 +1,108 @@
+//! Defines the settings for the program, stored in the configuration folder.
+
+use crate::general::{StorageFormat, StoragePath};
+
+/// The name of the settings file in the configuration folder
+const SETTINGS_FILE: &str = "settings.toml";
+
+/// The name of the folder next to a schematic that holds libraries for that project
+pub const PROJECT_LIBRARY_FOLDER: &str = "libs";
+
+/// The priority of the user library folder. Folders with a higher priority are searched first.
+pub const USER_LIBRARY_PRIORITY: i32 = 0;
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+/// A folder that is searched for libraries
+pub struct LibraryFolder {
+    /// The folder to search
+    pub path: std::path::PathBuf,
+    /// The priority of the folder. When libraries with the same name exist in more than one folder, the one with the highest priority is used.
+    pub priority: i32,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone, Default)]
+/// The settings for the program
+pub struct Settings {
+    /// Additional folders to search for libraries, besides the user library folder
+    #[serde(default)]
+    pub library_folders: Vec<LibraryFolder>,
+    /// The grid of the schematic and symbol editors
+    #[serde(default)]
+    pub grid: crate::grid::Grid,
+    /// The units to display and modify for everything
+    #[serde(default)]
+    pub units: crate::general::DisplayMode,
+}
+
+impl Settings {
+    /// The path of the settings file
+    fn path(dirs: &Option<directories::ProjectDirs>) -> Option<std::path::PathBuf> {
+        dirs.as_ref().map(|d| d.config_dir().join(SETTINGS_FILE))
+    }
+
+    /// Load the settings, using the defaults when there are no settings saved
+    pub fn load(dirs: &Option<directories::ProjectDirs>) -> Self {
+        let path = if let Some(p) = Self::path(dirs) {
+            p
+        } else {
+            return Self::default();
+        };
+        let mut reader = if let Ok(r) = std::fs::File::open(&path) {
+            r
+        } else {
+            return Self::default();
+        };
+        match StorageFormat::Toml.load(&mut reader) {
+            Ok(s) => s,
+            Err(e) => {
+                println!("ERROR loading settings {} {}", path.display(), e);
+                Self::default()
+            }
+        }
+    }
+
+    /// Save the settings
+    pub fn save(
+        &self,
+        dirs: &Option<directories::ProjectDirs>,
+    ) -> Result<(), crate::general::StorageSaveError> {
+        if let Some(path) = Self::path(dirs) {
+            if let Some(folder) = path.parent() {
+                let _ = std::fs::create_dir_all(folder);
+            }
+            let p = StoragePath::LocalFilesystem(path.into_os_string().into_string().unwrap());
+            let mut writer = p.writer()?;
+            StorageFormat::Toml.save(&mut writer, self)?;
+            writer.commit()?;
+        }
+        Ok(())
+    }
+
+    /// The folders to search for libraries, in the order they are searched.
+    /// The library folder of the project, when there is one, comes first, then folders in order of priority.
+    pub fn library_search_path(
+        &self,
+        dirs: &Option<directories::ProjectDirs>,
+        project: Option<&std::path::Path>,
+    ) -> Vec<LibraryFolder> {
+        let mut folders = self.library_folders.clone();
+        if let Some(dirs) = dirs {
+            folders.push(LibraryFolder {
+                path: dirs.data_dir().to_path_buf(),
+                priority: USER_LIBRARY_PRIORITY,
+            });
+        }
+        // The sort is stable, so folders with the same priority stay in the order listed
+        folders.sort_by_key(|f| std::cmp::Reverse(f.priority));
+        if let Some(p) = project {
+            folders.insert(
+                0,
+                LibraryFolder {
+                    path: p.join(PROJECT_LIBRARY_FOLDER),
+                    priority: i32::MAX,
+                },
+            );
+        }
+        folders
+    }
+}
@@ -0,0 +1,349 @@
+//! Defines the format of a schematic page: its paper size and orientation, the zone border around the drawing and the title block.
+//!
+//! Title blocks are templates stored in libraries, made of lines and text. The text usually holds variables such as ${TITLE},
+//! so the same title block fills in the details of each schematic and page it is used on.
+
+use std::collections::HashMap;
+
+use crate::general::Coordinates;
+use crate::library::LibraryHolder;
+use crate::schematic::{Colors, TextOnPage};
+use crate::variables::TextVariables;
+
+/// The distance in millimeters from the edge of the paper to the border
+const BORDER_MARGIN: f32 = 10.0;
+
+/// The width in millimeters of the strip of the border that holds the zone labels
+const ZONE_STRIP: f32 = 5.0;
+
+/// The number of zone columns across the page, labeled with numbers
+const ZONE_COLUMNS: usize = 8;
+
+/// The number of zone rows down the page, labeled with letters
+const ZONE_ROWS: usize = 6;
+
+/// The size in millimeters of the zone labels
+const ZONE_LABEL_SIZE: f32 = 3.0;
+
+/// The standard paper sizes
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq)]
+pub enum PaperSize {
+    /// ISO A4, 210 x 297 mm
+    A4,
+    /// ISO A3, 297 x 420 mm
+    A3,
+    /// ISO A2, 420 x 594 mm
+    A2,
+    /// ISO A1, 594 x 841 mm
+    A1,
+    /// ISO A0, 841 x 1189 mm
+    A0,
+    /// ANSI A, 8.5 x 11 inches
+    AnsiA,
+    /// ANSI B, 11 x 17 inches
+    AnsiB,
+    /// ANSI C, 17 x 22 inches
+    AnsiC,
+    /// ANSI D, 22 x 34 inches
+    AnsiD,
+    /// ANSI E, 34 x 44 inches
+    AnsiE,
+    /// A size given by the page itself
+    Custom,
+}
+
+impl std::fmt::Display for PaperSize {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::A4 => write!(f, "A4"),
+            Self::A3 => write!(f, "A3"),
+            Self::A2 => write!(f, "A2"),
+            Self::A1 => write!(f, "A1"),
+            Self::A0 => write!(f, "A0"),
+            Self::AnsiA => write!(f, "ANSI A"),
+            Self::AnsiB => write!(f, "ANSI B"),
+            Self::AnsiC => write!(f, "ANSI C"),
+            Self::AnsiD => write!(f, "ANSI D"),
+            Self::AnsiE => write!(f, "ANSI E"),
+            Self::Custom => write!(f, "Custom"),
+        }
+    }
+}
+
+impl PaperSize {
+    /// All of the paper sizes, in the order they are offered to the user
+    pub const ALL: [PaperSize; 11] = [
+        Self::A4,
+        Self::A3,
+        Self::A2,
+        Self::A1,
+        Self::A0,
+        Self::AnsiA,
+        Self::AnsiB,
+        Self::AnsiC,
+        Self::AnsiD,
+        Self::AnsiE,
+        Self::Custom,
+    ];
+
+    /// The width and height of the paper when it is upright, None for a custom size
+    pub fn dimensions(&self) -> Option<Coordinates> {
+        match self {
+            Self::A4 => Some(Coordinates::from_mm(210.0, 297.0)),
+            Self::A3 => Some(Coordinates::from_mm(297.0, 420.0)),
+            Self::A2 => Some(Coordinates::from_mm(420.0, 594.0)),
+            Self::A1 => Some(Coordinates::from_mm(594.0, 841.0)),
+            Self::A0 => Some(Coordinates::from_mm(841.0, 1189.0)),
+            Self::AnsiA => Some(Coordinates::from_inches(8.5, 11.0)),
+            Self::AnsiB => Some(Coordinates::from_inches(11.0, 17.0)),
+            Self::AnsiC => Some(Coordinates::from_inches(17.0, 22.0)),
+            Self::AnsiD => Some(Coordinates::from_inches(22.0, 34.0)),
+            Self::AnsiE => Some(Coordinates::from_inches(34.0, 44.0)),
+            Self::Custom => None,
+        }
+    }
+}
+
+/// Which way round the paper is
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq)]
+pub enum PageOrientation {
+    /// Taller than it is wide
+    Portrait,
+    /// Wider than it is tall
+    Landscape,
+}
+
+/// The border drawn inside the edge of the paper
+#[derive(serde::Serialize, serde::Deserialize, Clone, Copy, PartialEq)]
+pub enum BorderStyle {
+    /// No border
+    None,
+    /// A plain frame
+    Plain,
+    /// A frame divided into zones, with numbered columns and lettered rows, so that a place on the page can be referred to as B3
+    Zones,
+}
+
+impl std::fmt::Display for BorderStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => write!(f, "None"),
+            Self::Plain => write!(f, "Plain"),
+            Self::Zones => write!(f, "Zones"),
+        }
+    }
+}
+
+/// A reference to a title block in a library
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+pub struct TitleBlockReference {
+    /// The name of the library
+    pub lib: String,
+    /// The name of the title block
+    pub name: String,
+}
+
+/// A title block template, placed in the bottom right corner of the border of a page
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+pub struct TitleBlockDefinition {
+    /// The name of the title block
+    pub name: String,
+    /// The lines of the title block, relative to the bottom right corner of the border
+    pub lines: Vec<(Coordinates, Coordinates)>,
+    /// The text of the title block, relative to the bottom right corner of the border. Text can contain variables.
+    pub texts: Vec<TextOnPage>,
+}
+
+impl TitleBlockDefinition {
+    /// A title block with the title, revision, date and page number of the schematic
+    pub fn standard(name: String) -> Self {
+        let line = |x1: f32, y1: f32, x2: f32, y2: f32| {
+            (Coordinates::from_mm(x1, y1), Coordinates::from_mm(x2, y2))
+        };
+        let text = |text: &str, x: f32, y: f32, size: f32| TextOnPage {
+            text: text.to_string(),
+            location: Coordinates::from_mm(x, y),
+            color: Colors::Standard,
+            size: crate::general::Length::from_mm(size),
+        };
+        Self {
+            name,
+            lines: vec![
+                line(-180.0, 0.0, -180.0, 30.0),
+                line(-180.0, 30.0, 0.0, 30.0),
+                line(-180.0, 12.0, 0.0, 12.0),
+                line(-120.0, 0.0, -120.0, 12.0),
+                line(-60.0, 0.0, -60.0, 12.0),
+            ],
+            texts: vec![
+                text("Title", -178.0, 26.0, 2.0),
+                text("${TITLE}", -176.0, 16.0, 6.0),
+                text("Revision", -178.0, 8.5, 2.0),
+                text("${REVISION}", -176.0, 2.0, 4.0),
+                text("Date", -118.0, 8.5, 2.0),
+                text("${DATE}", -116.0, 2.0, 4.0),
+                text("Page", -58.0, 8.5, 2.0),
+                text("${PAGE} of ${PAGECOUNT}", -56.0, 2.0, 4.0),
+            ],
+        }
+    }
+}
+
+/// How a page is laid out
+#[derive(serde::Serialize, serde::Deserialize, Clone, PartialEq)]
+pub struct PageFormat {
+    /// The size of the paper
+    pub paper: PaperSize,
+    /// Which way round the paper is, not used for custom sizes
+    pub orientation: PageOrientation,
+    /// The border of the page
+    pub border: BorderStyle,
+    /// The title block drawn on the page
+    pub title_block: Option<TitleBlockReference>,
+}
+
+impl Default for PageFormat {
+    fn default() -> Self {
+        Self {
+            paper: PaperSize::Custom,
+            orientation: PageOrientation::Landscape,
+            border: BorderStyle::None,
+            title_block: None,
+        }
+    }
+}
+
+/// A piece of text on a page format, with its location and size in millimeters
+pub struct SheetText {
+    /// The text, with its variables replaced
+    pub text: String,
+    /// Where the text goes. This is the middle of the text when it is centered, otherwise the bottom left corner.
+    pub location: (f32, f32),
+    /// The height of the text
+    pub size: f32,
+    /// True when the text is centered on its location
+    pub centered: bool,
+}
+
+/// The lines and text drawn for the border and title block of a page, in millimeters from the bottom left corner of the page
+#[derive(Default)]
+pub struct SheetDrawing {
+    /// The lines, each from one point to another
+    pub lines: Vec<((f32, f32), (f32, f32))>,
+    /// The text
+    pub texts: Vec<SheetText>,
+}
+
+impl PageFormat {
+    /// The size of the page with this format, where custom is the size used for a custom paper size
+    pub fn size(&self, custom: Coordinates) -> Coordinates {
+        match self.paper.dimensions() {
+            None => custom,
+            Some(d) => match self.orientation {
+                PageOrientation::Portrait => d,
+                PageOrientation::Landscape => Coordinates::from_nm(d.y, d.x),
+            },
+        }
+    }
+
+    /// Find the title block of the format, preferring the libraries and using the copy in the cache when the library is not available
+    pub fn get_title_block<'a>(
+        &self,
+        cache: &'a crate::cache::LibraryCache,
+        libs: &'a HashMap<String, LibraryHolder>,
+    ) -> Option<&'a TitleBlockDefinition> {
+        let tref = self.title_block.as_ref()?;
+        libs.get(&tref.lib)
+            .and_then(|l| l.library.as_ref())
+            .and_then(|l| l.title_blocks.get(&tref.name))
+            .or_else(|| {
+                cache
+                    .libraries
+                    .get(&tref.lib)
+                    .and_then(|l| l.title_blocks.get(&tref.name))
+            })
+    }
+
+    /// The lines and text of the border and title block for a page of the given size
+    pub fn drawing(
+        &self,
+        size: Coordinates,
+        title_block: Option<&TitleBlockDefinition>,
+        vars: &TextVariables,
+    ) -> SheetDrawing {
+        let mut drawing = SheetDrawing::default();
+        let (w, h) = size.get_mm();
+        let rect = |d: &mut SheetDrawing, x1: f32, y1: f32, x2: f32, y2: f32| {
+            d.lines.push(((x1, y1), (x2, y1)));
+            d.lines.push(((x2, y1), (x2, y2)));
+            d.lines.push(((x2, y2), (x1, y2)));
+            d.lines.push(((x1, y2), (x1, y1)));
+        };
+        let outer = BORDER_MARGIN;
+        if self.border != BorderStyle::None {
+            rect(&mut drawing, outer, outer, w - outer, h - outer);
+        }
+        let inner = if self.border == BorderStyle::Zones {
+            let inner = outer + ZONE_STRIP;
+            rect(&mut drawing, inner, inner, w - inner, h - inner);
+            let zone_width = (w - 2.0 * inner) / ZONE_COLUMNS as f32;
+            for i in 0..ZONE_COLUMNS {
+                let x = inner + zone_width * i as f32;
+                if i > 0 {
+                    drawing.lines.push(((x, outer), (x, inner)));
+                    drawing.lines.push(((x, h - inner), (x, h - outer)));
+                }
+                for y in [outer + ZONE_STRIP / 2.0, h - outer - ZONE_STRIP / 2.0] {
+                    drawing.texts.push(SheetText {
+                        text: (i + 1).to_string(),
+                        location: (x + zone_width / 2.0, y),
+                        size: ZONE_LABEL_SIZE,
+                        centered: true,
+                    });
+                }
+            }
+            // Rows are lettered from the top of the page down
+            let zone_height = (h - 2.0 * inner) / ZONE_ROWS as f32;
+            for i in 0..ZONE_ROWS {
+                let y = h - inner - zone_height * i as f32;
+                if i > 0 {
+                    drawing.lines.push(((outer, y), (inner, y)));
+                    drawing.lines.push(((w - inner, y), (w - outer, y)));
+                }
+                let letter = ((b'A' + i as u8) as char).to_string();
+                for x in [outer + ZONE_STRIP / 2.0, w - outer - ZONE_STRIP / 2.0] {
+                    drawing.texts.push(SheetText {
+                        text: letter.clone(),
+                        location: (x, y - zone_height / 2.0),
+                        size: ZONE_LABEL_SIZE,
+                        centered: true,
+                    });
+                }
+            }
+            inner
+        } else {
+            outer
+        };
+        if let Some(tb) = title_block {
+            let corner = (w - inner, inner);
+            for (a, b) in &tb.lines {
+                let (ax, ay) = a.get_mm();
+                let (bx, by) = b.get_mm();
+                drawing.lines.push((
+                    (corner.0 + ax, corner.1 + ay),
+                    (corner.0 + bx, corner.1 + by),
+                ));
+            }
+            for t in &tb.texts {
+                let (x, y) = t.location.get_mm();
+                drawing.texts.push(SheetText {
+                    text: vars.substitute(&t.text).into_owned(),
+                    location: (corner.0 + x, corner.1 + y),
+                    size: t.size.get_mm(),
+                    centered: false,
+                });
+            }
+        }
+        drawing
+    }
+}
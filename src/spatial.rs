@@ -0,0 +1,208 @@
+//! An index of where the objects of a schematic page are, so that drawing and picking only look at the objects in
+//! the part of the page that matters instead of every object on the page.
+
+use std::collections::HashMap;
+
+use crate::geometry::BoundingBox;
+
+/// The width and height in millimeters of the square cells the page is divided into
+const CELL_SIZE: f32 = 25.4;
+
+/// An object on a schematic page
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum PageObject {
+    /// A placed component, by its index in the components of the page
+    Symbol(usize),
+    /// A text, by its index in the texts of the page
+    Text(usize),
+    /// A piece of a bus, by its index in the bus parts of the page
+    Bus(usize),
+}
+
+impl PageObject {
+    /// The object with its index changed by f
+    fn renumbered(self, f: impl Fn(usize) -> usize) -> Self {
+        match self {
+            PageObject::Symbol(i) => PageObject::Symbol(f(i)),
+            PageObject::Text(i) => PageObject::Text(f(i)),
+            PageObject::Bus(i) => PageObject::Bus(f(i)),
+        }
+    }
+
+    /// Returns true when both objects are the same kind of object
+    fn same_kind(&self, other: &PageObject) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+}
+
+/// Divides a page into square cells and lists the objects reaching into each cell. The index is empty until it is built,
+/// and is kept up to date by the actions done to the page after that.
+#[derive(Clone, Default)]
+pub struct SpatialIndex {
+    /// True once the index holds every object of the page
+    built: bool,
+    /// The area covered by each placed component, in the order of the components of the page
+    syms: Vec<BoundingBox>,
+    /// The area covered by each text, in the order of the texts of the page
+    texts: Vec<BoundingBox>,
+    /// The area covered by each bus part, in the order of the bus parts of the page
+    bus: Vec<BoundingBox>,
+    /// The objects reaching into each cell, by the column and row of the cell
+    cells: HashMap<(i32, i32), Vec<PageObject>>,
+}
+
+/// The first and last columns and rows of the cells that a box reaches into
+fn cell_range(b: &BoundingBox) -> ((i32, i32), (i32, i32)) {
+    let cell = |v: f32| (v / CELL_SIZE).floor() as i32;
+    (
+        (cell(b.min.x), cell(b.max.x)),
+        (cell(b.min.y), cell(b.max.y)),
+    )
+}
+
+/// The columns and rows of the cells that a box reaches into
+fn cells_of(b: &BoundingBox) -> impl Iterator<Item = (i32, i32)> {
+    let ((x0, x1), (y0, y1)) = cell_range(b);
+    (x0..=x1).flat_map(move |x| (y0..=y1).map(move |y| (x, y)))
+}
+
+impl SpatialIndex {
+    /// Returns true when the index holds every object of the page
+    pub fn is_built(&self) -> bool {
+        self.built
+    }
+
+    /// Fill the index with the areas covered by the placed components, texts and bus parts of the page, in order
+    pub fn build(
+        &mut self,
+        syms: Vec<BoundingBox>,
+        texts: Vec<BoundingBox>,
+        bus: Vec<BoundingBox>,
+    ) {
+        self.cells.clear();
+        for (i, b) in syms.iter().enumerate() {
+            self.add_to_cells(PageObject::Symbol(i), b);
+        }
+        for (i, b) in texts.iter().enumerate() {
+            self.add_to_cells(PageObject::Text(i), b);
+        }
+        for (i, b) in bus.iter().enumerate() {
+            self.add_to_cells(PageObject::Bus(i), b);
+        }
+        self.syms = syms;
+        self.texts = texts;
+        self.bus = bus;
+        self.built = true;
+    }
+
+    /// Empty the index, so that it is built again before it is used
+    pub fn invalidate(&mut self) {
+        *self = Self::default();
+    }
+
+    /// The list of areas for the kind of object
+    fn boxes(&mut self, obj: PageObject) -> (&mut Vec<BoundingBox>, usize) {
+        match obj {
+            PageObject::Symbol(i) => (&mut self.syms, i),
+            PageObject::Text(i) => (&mut self.texts, i),
+            PageObject::Bus(i) => (&mut self.bus, i),
+        }
+    }
+
+    /// List the object in the cells the area reaches into
+    fn add_to_cells(&mut self, obj: PageObject, b: &BoundingBox) {
+        for c in cells_of(b) {
+            self.cells.entry(c).or_default().push(obj);
+        }
+    }
+
+    /// Take the object out of the cells the area reaches into
+    fn remove_from_cells(&mut self, obj: PageObject, b: &BoundingBox) {
+        for c in cells_of(b) {
+            if let Some(list) = self.cells.get_mut(&c) {
+                list.retain(|o| *o != obj);
+                if list.is_empty() {
+                    self.cells.remove(&c);
+                }
+            }
+        }
+    }
+
+    /// Change the index of every object of the same kind as obj by f
+    fn renumber(&mut self, obj: PageObject, f: impl Fn(usize) -> usize) {
+        for list in self.cells.values_mut() {
+            for o in list.iter_mut().filter(|o| o.same_kind(&obj)) {
+                *o = o.renumbered(&f);
+            }
+        }
+    }
+
+    /// Add an object that was inserted into the page, moving the objects after it along
+    pub fn insert(&mut self, obj: PageObject, b: BoundingBox) {
+        if !self.built {
+            return;
+        }
+        let (boxes, i) = self.boxes(obj);
+        let last = i == boxes.len();
+        boxes.insert(i, b);
+        if !last {
+            self.renumber(obj, |n| if n >= i { n + 1 } else { n });
+        }
+        self.add_to_cells(obj, &b);
+    }
+
+    /// Take out an object that was removed from the page, moving the objects after it back
+    pub fn remove(&mut self, obj: PageObject) {
+        if !self.built {
+            return;
+        }
+        let (boxes, i) = self.boxes(obj);
+        let b = boxes.remove(i);
+        self.remove_from_cells(obj, &b);
+        self.renumber(obj, |n| if n > i { n - 1 } else { n });
+    }
+
+    /// Change the area covered by an object that moved or changed
+    pub fn update(&mut self, obj: PageObject, b: BoundingBox) {
+        if !self.built {
+            return;
+        }
+        let (boxes, i) = self.boxes(obj);
+        let old = std::mem::replace(&mut boxes[i], b);
+        if old != b {
+            self.remove_from_cells(obj, &old);
+            self.add_to_cells(obj, &b);
+        }
+    }
+
+    /// The objects that cover part of the area, components, then texts, then bus parts, each in page order
+    pub fn query(&self, area: &BoundingBox) -> Vec<PageObject> {
+        let ((x0, x1), (y0, y1)) = cell_range(area);
+        let covered = (x1 as i64 - x0 as i64 + 1) * (y1 as i64 - y0 as i64 + 1);
+        // A large area, such as the whole page zoomed out, is quicker to check against the cells that have objects
+        let mut found: Vec<PageObject> = if covered > self.cells.len() as i64 {
+            self.cells
+                .iter()
+                .filter(|((x, y), _)| (x0..=x1).contains(x) && (y0..=y1).contains(y))
+                .flat_map(|(_, list)| list.iter().copied())
+                .collect()
+        } else {
+            cells_of(area)
+                .filter_map(|c| self.cells.get(&c))
+                .flatten()
+                .copied()
+                .collect()
+        };
+        found.sort();
+        found.dedup();
+        found.retain(|o| {
+            let b = match o {
+                PageObject::Symbol(i) => &self.syms[*i],
+                PageObject::Text(i) => &self.texts[*i],
+                PageObject::Bus(i) => &self.bus[*i],
+            };
+            b.intersects(area)
+        });
+        found
+    }
+}
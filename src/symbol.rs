@@ -1,54 +1,58 @@
 //! This module contains definitions and code pertaining to schematic symbols and their definitions
 
-use crate::library::{Library, LibraryAction};
+use std::collections::HashMap;
+
+use crate::general::Orientation;
+use crate::geometry::{BoundingBox, Point, Transform};
+use crate::library::{Library, LibraryAction, LibraryHolder};
 use crate::schematic::TextOnPage;
+use crate::variables::TextVariables;
 use egui_multiwin::egui;
 
-#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
 #[non_exhaustive]
 /// Defines a pin for a symbol definition
 pub struct Pin {
     /// The location of the pin
     pub location: crate::general::Coordinates,
-    /// The rotation of the pin
+    /// The rotation of the pin, in degrees counterclockwise
     #[serde(default)]
-    rotation: f32,
+    pub rotation: f32,
+    /// The number of the pin on the part, which identifies the pin in a netlist. Pins of different units of a part need different numbers.
+    #[serde(default)]
+    pub number: String,
 }
 
+/// The length of the line drawn for a pin
+const PIN_LENGTH: crate::general::Length = crate::general::Length::from_mils(100);
+
+/// The width of a character of text in a pdf, as a fraction of the size of the text, used to estimate how much room text takes
+pub const PDF_CHARACTER_WIDTH: f32 = 0.5;
+
+/// Half the width of the box drawn at the connection end of a pin, in millimeters
+const PIN_MARKER_SIZE: f32 = 0.635;
+
 impl Pin {
-    /// Draw the pin with a painter
-    fn draw(
-        &self,
-        zoom: f32,
-        zoom_center: egui_multiwin::egui::Pos2,
-        pntr: &egui::Painter,
-        pos: egui::Pos2,
-        bounds: egui::Rect,
-    ) -> Vec<egui::Rect> {
-        let mult = crate::general::Length::Inches(0.1).get_screen(zoom, zoom_center);
-        let pos2 = pos
-            + egui::Vec2 {
-                x: self.rotation.to_radians().sin() * mult,
-                y: self.rotation.to_radians().cos() * mult,
-            };
+    /// The end of the line of the pin away from its connection, in millimeters in the symbol
+    fn end(&self) -> Point {
+        Point::from(self.location)
+            + Transform::rotate(self.rotation).apply_vector(Point::new(0.0, -PIN_LENGTH.get_mm()))
+    }
+
+    /// Draw the pin with a painter, where t takes the symbol to the screen
+    fn draw(&self, pntr: &egui::Painter, t: &Transform, bounds: egui::Rect) -> Vec<egui::Rect> {
+        let start = Point::from(self.location);
         pntr.line_segment(
-            [pos, pos2],
+            [t.apply(start).to_pos2(), t.apply(self.end()).to_pos2()],
             egui::Stroke {
                 width: 2.0,
                 color: crate::schematic::Colors::Standard
                     .get_color32(crate::general::ColorMode::ScreenModeDark),
             },
         );
-        let rect = egui::Rect {
-            min: (pos
-                - crate::general::Coordinates::Inches(0.025, -0.025)
-                    .get_pos2(zoom, egui::pos2(0.0, 0.0)))
-            .to_pos2(),
-            max: (pos
-                + crate::general::Coordinates::Inches(0.025, -0.025)
-                    .get_pos2(zoom, egui::pos2(0.0, 0.0))
-                    .to_vec2()),
-        };
+        let rect = BoundingBox::around(start, PIN_MARKER_SIZE)
+            .transformed(t)
+            .to_rect();
         pntr.rect_stroke(
             rect,
             0.0,
@@ -93,11 +97,33 @@ pub enum LibraryReference {
 }
 
 impl LibraryReference {
-    /// Return the name of the referenced library
-    pub fn get_name(&self, r: &Library) -> String {
+    /// The name of the referenced library, where containing is the name of the library containing the reference
+    pub fn name<'a>(&'a self, containing: &'a str) -> &'a str {
         match self {
-            Self::ThisOne => r.name.to_owned(),
-            Self::Another(s) => s.to_owned(),
+            Self::ThisOne => containing,
+            Self::Another(s) => s,
+        }
+    }
+
+    /// Create a reference to the library named target, from the library named containing
+    pub fn to(target: &str, containing: &str) -> Self {
+        if target == containing {
+            Self::ThisOne
+        } else {
+            Self::Another(target.to_string())
+        }
+    }
+
+    /// Find the referenced library, where r is the library containing the reference.
+    /// Libraries are loaded from the library search path, so this is the first library with the name found in the search path.
+    pub fn resolve<'a>(
+        &self,
+        r: &'a Library,
+        libs: &'a HashMap<String, LibraryHolder>,
+    ) -> Option<&'a Library> {
+        match self {
+            Self::ThisOne => Some(r),
+            Self::Another(s) => libs.get(s).and_then(|l| l.library.as_ref()),
         }
     }
 }
@@ -112,7 +138,64 @@ pub struct SymbolReference {
     pub sym: String,
 }
 
-#[derive(serde::Serialize, serde::Deserialize)]
+impl SymbolReference {
+    /// Get the symbol that is referenced, where r is the library containing the reference
+    pub fn get_symbol<'a>(
+        &self,
+        r: &'a Library,
+        libs: &'a HashMap<String, LibraryHolder>,
+    ) -> Option<&'a SymbolDefinition> {
+        self.lib
+            .resolve(r, libs)
+            .and_then(|l| l.syms.get(&self.sym))
+    }
+}
+
+/// The name given to the first alternate body style of a symbol
+pub const DEMORGAN_STYLE: &str = "De Morgan";
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
+#[non_exhaustive]
+/// An alternate body style of a symbol, such as the De Morgan equivalent of a logic gate.
+/// Body styles have their own graphics but share the pins of the symbol.
+pub struct SymbolStyle {
+    /// The name of the body style
+    pub name: String,
+    /// The text in the body style
+    pub texts: Vec<TextOnPage>,
+}
+
+#[derive(Clone, Copy, Default)]
+/// How a symbol is drawn for a placed component
+pub struct SymbolPlacement<'a> {
+    /// The body style to draw, None for the normal body style
+    pub style: Option<&'a str>,
+    /// How the symbol is turned
+    pub orientation: Orientation,
+    /// The values for the variables in the texts of the symbol, None to draw the texts as they are
+    pub variables: Option<&'a TextVariables>,
+}
+
+impl SymbolPlacement<'_> {
+    /// The text to show for a text of the symbol, with its variables replaced
+    fn text<'b>(&self, text: &'b str) -> std::borrow::Cow<'b, str> {
+        match self.variables {
+            Some(v) => v.substitute(text),
+            None => std::borrow::Cow::Borrowed(text),
+        }
+    }
+}
+
+/// The box taken by text anchored at its bottom left corner at location, with the given width and height in millimeters
+fn text_box(location: crate::general::Coordinates, width: f32, height: f32) -> BoundingBox {
+    let min = Point::from(location);
+    BoundingBox {
+        min,
+        max: min + Point::new(width, height),
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Clone)]
 #[non_exhaustive]
 /// Defines what a symbol should look like
 pub struct SymbolDefinition {
@@ -123,6 +206,9 @@ pub struct SymbolDefinition {
     /// The pins for a symbol
     #[serde(default)]
     pub pins: Vec<Pin>,
+    /// The alternate body styles of the symbol, the normal body style is the texts of the symbol
+    #[serde(default)]
+    pub styles: Vec<SymbolStyle>,
 }
 
 impl SymbolDefinition {
@@ -132,9 +218,53 @@ impl SymbolDefinition {
             name,
             texts: Vec::new(),
             pins: Vec::new(),
+            styles: Vec::new(),
         }
     }
-    /// Draw the symbol on the specified painter
+
+    /// Change the name of the symbol
+    pub fn rename(&mut self, name: String) {
+        self.name = name;
+    }
+
+    /// Get an alternate body style by name
+    pub fn style(&self, name: &str) -> Option<&SymbolStyle> {
+        self.styles.iter().find(|s| s.name == name)
+    }
+
+    /// The text of the named body style, or of the normal body style when style is None or does not exist
+    pub fn style_texts(&self, style: Option<&str>) -> &Vec<TextOnPage> {
+        match style.and_then(|s| self.style(s)) {
+            Some(s) => &s.texts,
+            None => &self.texts,
+        }
+    }
+
+    /// The text of the named body style for modification, or of the normal body style when style is None
+    pub fn style_texts_mut(&mut self, style: Option<&str>) -> Option<&mut Vec<TextOnPage>> {
+        match style {
+            Some(name) => self
+                .styles
+                .iter_mut()
+                .find(|s| s.name == name)
+                .map(|s| &mut s.texts),
+            None => Some(&mut self.texts),
+        }
+    }
+
+    /// The name for the next body style added to the symbol
+    pub fn next_style_name(&self) -> String {
+        let mut n = self.styles.len() + 1;
+        let mut name = DEMORGAN_STYLE.to_string();
+        while self.style(&name).is_some() {
+            n += 1;
+            name = format!("Style {}", n);
+        }
+        name
+    }
+
+    /// Draw the symbol on the specified painter, placed as given.
+    /// Text is turned and mirrored along with the rest of the symbol, but is always drawn so that it can be read.
     pub fn draw(
         &self,
         zoom: f32,
@@ -142,20 +272,33 @@ impl SymbolDefinition {
         pntr: &egui::Painter,
         pos: egui::Pos2,
         bounds: egui::Rect,
+        placement: SymbolPlacement,
     ) -> Vec<egui::Rect> {
+        let origin = pos + zoom_center.to_vec2();
+        let t =
+            Transform::orientation(placement.orientation).then(&Transform::screen(zoom, origin));
         let mut response = vec![];
-        for t in &self.texts {
-            let pos = t.location.get_pos2(zoom, zoom_center).to_vec2() + pos.to_vec2();
-            let align = egui::Align2::LEFT_BOTTOM;
+        for text in self.style_texts(placement.style) {
             let font = egui::FontId {
-                size: t.size.get_screen(zoom, zoom_center),
+                size: text.size.get_screen(zoom, zoom_center),
                 family: egui::FontFamily::Monospace,
             };
-            let temp = pos.to_pos2();
-            let color = t
+            let color = text
                 .color
                 .get_color32(crate::general::ColorMode::ScreenModeDark);
-            let r = pntr.text(temp, align, t.text.clone(), font, color);
+            let galley = pntr.layout_no_wrap(placement.text(&text.text).into_owned(), font, color);
+            let size = galley.size() / t.scale_factor();
+            let r = text_box(text.location, size.x, size.y)
+                .transformed(&t)
+                .to_rect();
+            if t.sideways() {
+                pntr.add(
+                    egui::epaint::TextShape::new(r.left_bottom(), galley, color)
+                        .with_angle(-std::f32::consts::FRAC_PI_2),
+                );
+            } else {
+                pntr.galley(r.min, galley, color);
+            }
             let rcheck = r.intersect(bounds);
             if rcheck.is_positive() {
                 response.push(rcheck);
@@ -163,13 +306,81 @@ impl SymbolDefinition {
         }
 
         for p in &self.pins {
-            let pos = p.location.get_pos2(zoom, zoom_center).to_vec2() + pos.to_vec2();
-            let temp = pos;
-            let mut rects = p.draw(zoom, zoom_center, &pntr, temp.to_pos2(), bounds);
+            let mut rects = p.draw(pntr, &t, bounds);
             response.append(&mut rects);
         }
         response
     }
+
+    /// The area covered by the symbol placed as given with its origin at pos, in millimeters on the page, with the
+    /// width of text estimated as it is for a pdf. None when the symbol has nothing to draw.
+    pub fn bounding_box(
+        &self,
+        pos: crate::general::Coordinates,
+        placement: SymbolPlacement,
+    ) -> Option<BoundingBox> {
+        let t =
+            Transform::orientation(placement.orientation).then(&Transform::translate(pos.into()));
+        let texts = self.style_texts(placement.style).iter().map(|text| {
+            let height = text.size.get_mm();
+            let width =
+                placement.text(&text.text).chars().count() as f32 * height * PDF_CHARACTER_WIDTH;
+            text_box(text.location, width, height)
+        });
+        let pins = self.pins.iter().flat_map(|p| {
+            [
+                BoundingBox::around(p.location.into(), PIN_MARKER_SIZE),
+                BoundingBox::around(p.end(), 0.0),
+            ]
+        });
+        texts
+            .chain(pins)
+            .map(|b| b.transformed(&t))
+            .reduce(|a, b| a.union(&b))
+    }
+
+    /// Draw the symbol on a pdf layer, placed as given, with the origin of the symbol at pos
+    pub fn draw_on(
+        &self,
+        layer: &printpdf::PdfLayerReference,
+        font: &printpdf::IndirectFontRef,
+        pos: crate::general::Coordinates,
+        placement: SymbolPlacement,
+    ) {
+        let t =
+            Transform::orientation(placement.orientation).then(&Transform::translate(pos.into()));
+        for text in self.style_texts(placement.style) {
+            let height = text.size.get_mm();
+            let s = placement.text(&text.text);
+            let width = s.chars().count() as f32 * height * PDF_CHARACTER_WIDTH;
+            let b = text_box(text.location, width, height).transformed(&t);
+            let (tx, angle) = if t.sideways() {
+                (b.max.x, 90.0)
+            } else {
+                (b.min.x, 0.0)
+            };
+            layer.begin_text_section();
+            layer.set_font(font, (height * 2.85).into());
+            layer.set_text_matrix(printpdf::TextMatrix::TranslateRotate(
+                printpdf::Mm(tx.into()).into(),
+                printpdf::Mm(b.min.y.into()).into(),
+                angle,
+            ));
+            layer.write_text(s, font);
+            layer.end_text_section();
+        }
+        for p in &self.pins {
+            let start = t.apply(Point::from(p.location));
+            let end = t.apply(p.end());
+            layer.add_shape(printpdf::Line {
+                points: vec![(start.to_pdf(), false), (end.to_pdf(), false)],
+                is_closed: false,
+                has_fill: false,
+                has_stroke: true,
+                is_clipping_path: false,
+            });
+        }
+    }
 }
 
 /// Separates stored and non-stored data for a symbol definition
@@ -178,16 +389,169 @@ pub struct SymbolDefinitionHolder<'a> {
     sym: &'a SymbolDefinition,
     /// The name of the containing library
     libname: String,
+    /// The body style being edited, None for the normal body style
+    style: Option<String>,
 }
 
 impl<'a> SymbolDefinitionHolder<'a> {
-    /// Create a new symbol definition holder
-    pub fn new(sym: &'a SymbolDefinition, libname: String) -> Self {
-        Self { sym, libname }
+    /// Create a new symbol definition holder, for editing the named body style or the normal body style when style is None
+    pub fn new(sym: &'a SymbolDefinition, libname: String, style: Option<String>) -> Self {
+        Self {
+            sym,
+            libname,
+            style,
+        }
+    }
+
+    /// Where a selected object is, None when it does not exist
+    fn anchor(&self, sel: &SymbolWidgetSelection) -> Option<crate::general::Coordinates> {
+        match sel {
+            SymbolWidgetSelection::Text { textnum } => self
+                .sym
+                .style_texts(self.style.as_deref())
+                .get(*textnum)
+                .map(|t| t.location),
+            SymbolWidgetSelection::Pin { pinnum } => self.sym.pins.get(*pinnum).map(|p| p.location),
+        }
+    }
+
+    /// The action for moving each of the selected objects by its own amount
+    fn move_action_for(
+        &self,
+        sel: &SymbolWidgetSelection,
+        delta: crate::general::Coordinates,
+    ) -> LibraryAction {
+        match sel {
+            SymbolWidgetSelection::Text { textnum } => LibraryAction::MoveText {
+                libname: self.libname.clone(),
+                symname: self.sym.name.clone(),
+                style: self.style.clone(),
+                textnum: *textnum,
+                delta,
+            },
+            SymbolWidgetSelection::Pin { pinnum } => LibraryAction::MovePin {
+                libname: self.libname.clone(),
+                symname: self.sym.name.clone(),
+                pinnum: *pinnum,
+                delta,
+            },
+        }
+    }
+
+    /// The action that moves all of the selected objects by delta, or None when nothing is selected
+    pub fn move_action(
+        &self,
+        selection: &[SymbolWidgetSelection],
+        delta: crate::general::Coordinates,
+    ) -> Option<LibraryAction> {
+        let actions: Vec<LibraryAction> = selection
+            .iter()
+            .filter(|s| self.anchor(s).is_some())
+            .map(|s| self.move_action_for(s, delta))
+            .collect();
+        if actions.is_empty() {
+            None
+        } else {
+            Some(LibraryAction::Compound { actions })
+        }
+    }
+
+    /// The action that lines up the selected objects along one edge of the group, or None when nothing is selected
+    pub fn align_action(
+        &self,
+        selection: &[SymbolWidgetSelection],
+        alignment: crate::general::Alignment,
+    ) -> Option<LibraryAction> {
+        let placed: Vec<(&SymbolWidgetSelection, crate::general::Coordinates)> = selection
+            .iter()
+            .filter_map(|s| self.anchor(s).map(|a| (s, a)))
+            .collect();
+        if placed.is_empty() {
+            return None;
+        }
+        let anchors: Vec<crate::general::Coordinates> = placed.iter().map(|(_, a)| *a).collect();
+        let actions = placed
+            .iter()
+            .zip(alignment.deltas(&anchors))
+            .map(|((s, _), delta)| self.move_action_for(s, delta))
+            .collect();
+        Some(LibraryAction::Compound { actions })
+    }
+
+    /// The action that turns the selected objects a quarter turn counterclockwise about the middle of the group,
+    /// or None when nothing is selected
+    pub fn rotate_action(&self, selection: &[SymbolWidgetSelection]) -> Option<LibraryAction> {
+        let placed: Vec<(&SymbolWidgetSelection, crate::general::Coordinates)> = selection
+            .iter()
+            .filter_map(|s| self.anchor(s).map(|a| (s, a)))
+            .collect();
+        let first = placed.first()?.1;
+        let (min, max) = placed.iter().fold((first, first), |(min, max), (_, a)| {
+            (
+                crate::general::Coordinates::from_nm(min.x.min(a.x), min.y.min(a.y)),
+                crate::general::Coordinates::from_nm(max.x.max(a.x), max.y.max(a.y)),
+            )
+        });
+        let center = crate::general::Coordinates::from_nm((min.x + max.x) / 2, (min.y + max.y) / 2);
+        let op = Orientation::default().rotated();
+        let mut actions = Vec::new();
+        for (s, a) in placed {
+            let delta = center + op.transform(a - center) - a;
+            match s {
+                SymbolWidgetSelection::Text { .. } => actions.push(self.move_action_for(s, delta)),
+                SymbolWidgetSelection::Pin { pinnum } => {
+                    actions.push(self.move_action_for(s, delta));
+                    actions.push(LibraryAction::SetPinRotation {
+                        libname: self.libname.clone(),
+                        symname: self.sym.name.clone(),
+                        pinnum: *pinnum,
+                        rotation: (self.sym.pins[*pinnum].rotation + 90.0) % 360.0,
+                    });
+                }
+            }
+        }
+        Some(LibraryAction::Compound { actions })
+    }
+
+    /// The action that deletes the selected objects, or None when nothing is selected.
+    /// Objects are deleted from the end of each list first, so that the indexes of the others stay the same.
+    pub fn delete_action(&self, selection: &[SymbolWidgetSelection]) -> Option<LibraryAction> {
+        let mut selection = selection.to_vec();
+        selection.sort_by_key(|s| match s {
+            SymbolWidgetSelection::Text { textnum } => (0, *textnum),
+            SymbolWidgetSelection::Pin { pinnum } => (1, *pinnum),
+        });
+        selection.dedup();
+        let actions: Vec<LibraryAction> = selection
+            .iter()
+            .rev()
+            .filter(|s| self.anchor(s).is_some())
+            .map(|s| match s {
+                SymbolWidgetSelection::Text { textnum } => LibraryAction::DeleteText {
+                    libname: self.libname.clone(),
+                    symname: self.sym.name.clone(),
+                    style: self.style.clone(),
+                    textnum: *textnum,
+                    text: None,
+                },
+                SymbolWidgetSelection::Pin { pinnum } => LibraryAction::DeletePin {
+                    libname: self.libname.clone(),
+                    symname: self.sym.name.clone(),
+                    pinnum: *pinnum,
+                    pin: None,
+                },
+            })
+            .collect();
+        if actions.is_empty() {
+            None
+        } else {
+            Some(LibraryAction::Compound { actions })
+        }
     }
 }
 
 /// The possible objects to select in a symbol widget
+#[derive(Clone, PartialEq)]
 pub enum SymbolWidgetSelection {
     /// A basic text field of a symbol
     Text {
@@ -219,6 +583,10 @@ pub struct SymbolDefinitionWidget<'a> {
     recenter: bool,
     /// The angle to draw new pins at, in degrees
     pin_angle: &'a mut f32,
+    /// The grid that objects are placed on
+    grid: crate::grid::Grid,
+    /// Where the pointer is in the symbol, set when the widget is drawn
+    cursor: Option<&'a mut Option<crate::general::Coordinates>>,
 }
 
 impl<'a> SymbolDefinitionWidget<'a> {
@@ -242,8 +610,54 @@ impl<'a> SymbolDefinitionWidget<'a> {
             zoom,
             recenter,
             pin_angle,
+            grid: crate::grid::Grid::default(),
+            cursor: None,
         }
     }
+
+    /// Draw and snap to the given grid instead of the default one
+    pub fn with_grid(mut self, grid: crate::grid::Grid) -> Self {
+        self.grid = grid;
+        self
+    }
+
+    /// Report where the pointer is in the symbol, None when it is not over the widget
+    pub fn with_cursor(mut self, cursor: &'a mut Option<crate::general::Coordinates>) -> Self {
+        self.cursor = Some(cursor);
+        self
+    }
+
+    /// The places in the symbol that objects snap to: its origin, pins and the locations of text.
+    /// The selected objects, such as the ones being moved, are left out.
+    fn snap_targets(&self) -> Vec<crate::general::Coordinates> {
+        let texts = self.sym.sym.style_texts(self.sym.style.as_deref());
+        std::iter::once(crate::general::Coordinates::from_nm(0, 0))
+            .chain(
+                texts
+                    .iter()
+                    .enumerate()
+                    .filter(|(textnum, _)| {
+                        !self
+                            .selection
+                            .contains(&SymbolWidgetSelection::Text { textnum: *textnum })
+                    })
+                    .map(|(_, t)| t.location),
+            )
+            .chain(
+                self.sym
+                    .sym
+                    .pins
+                    .iter()
+                    .enumerate()
+                    .filter(|(pinnum, _)| {
+                        !self
+                            .selection
+                            .contains(&SymbolWidgetSelection::Pin { pinnum: *pinnum })
+                    })
+                    .map(|(_, p)| p.location),
+            )
+            .collect()
+    }
 }
 
 impl<'a> egui::Widget for SymbolDefinitionWidget<'a> {
@@ -258,13 +672,15 @@ impl<'a> egui::Widget for SymbolDefinitionWidget<'a> {
         area.max.y = ui.available_height() + area.min.y;
         let size = egui::vec2(area.max.x - area.min.x, area.max.y - area.min.y);
         if self.recenter {
-            *self.origin = crate::general::Coordinates::Inches(0.0, 0.0);
+            *self.origin = crate::general::Coordinates::from_nm(0, 0);
         }
 
         let zoom_origin =
             (area.left_top().to_vec2() + egui::vec2(size.x / 2.0, size.y / 2.0)).to_pos2();
 
         let (mut pr, pntr) = ui.allocate_painter(size, sense);
+        let background = pr.clone();
+        let modifiers = ui.input(|i| i.modifiers);
 
         match &self.mm {
             MouseMode::NewText | MouseMode::TextDrag => {
@@ -298,6 +714,22 @@ impl<'a> egui::Widget for SymbolDefinitionWidget<'a> {
         };
 
         let origin = self.origin.get_pos2(*self.zoom, zoom_origin);
+        let zoom = *self.zoom;
+        let targets = self.snap_targets();
+        let screen = Transform::screen(zoom, origin);
+        let from_screen = screen.inverse().unwrap_or_default();
+        let to_symbol = |p: egui::Pos2| {
+            let p = from_screen.apply(p.into());
+            crate::general::Coordinates::from_mm(p.x, p.y)
+        };
+        let pointer = ui.input(|i| i.pointer.interact_pos()).map(to_symbol);
+        if let Some(cursor) = self.cursor {
+            *cursor = ui
+                .input(|i| i.pointer.hover_pos())
+                .filter(|p| area.contains(*p))
+                .map(|p| self.grid.snap(to_symbol(p), &targets, zoom));
+        }
+        self.grid.draw(ui, &pntr, area, zoom, origin);
         pntr.line_segment(
             [
                 egui::pos2(area.min.x, origin.y),
@@ -313,14 +745,38 @@ impl<'a> egui::Widget for SymbolDefinitionWidget<'a> {
             stroke,
         );
 
-        if pr.clicked() && self.mm == &MouseMode::Selection {
-            let inp = ui.input(|i| i.modifiers);
-            if !inp.shift && !inp.ctrl {
-                self.selection.clear();
-            }
+        if pr.clicked()
+            && self.mm == &MouseMode::Selection
+            && !(modifiers.shift || modifiers.command)
+        {
+            self.selection.clear();
         }
+        if self.mm == &MouseMode::Selection
+            && !ui.ctx().wants_keyboard_input()
+            && ui.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::A))
+        {
+            *self.selection = (0..self.sym.sym.style_texts(self.sym.style.as_deref()).len())
+                .map(|textnum| SymbolWidgetSelection::Text { textnum })
+                .chain(
+                    (0..self.sym.sym.pins.len())
+                        .map(|pinnum| SymbolWidgetSelection::Pin { pinnum }),
+                )
+                .collect();
+        }
+
+        // The objects that can be selected, with the areas of the screen they cover
+        let mut selectable: Vec<(SymbolWidgetSelection, Vec<crate::geometry::HitArea>)> =
+            Vec::new();
+        // How far the selected objects are dragged this frame
+        let mut group_drag: Option<crate::general::Coordinates> = None;
 
-        for (i, t) in self.sym.sym.texts.iter().enumerate() {
+        for (i, t) in self
+            .sym
+            .sym
+            .style_texts(self.sym.style.as_deref())
+            .iter()
+            .enumerate()
+        {
             let pos = t.location.get_pos2(*self.zoom, origin).to_vec2();
             let align = egui::Align2::LEFT_BOTTOM;
             let font = egui::FontId {
@@ -333,20 +789,26 @@ impl<'a> egui::Widget for SymbolDefinitionWidget<'a> {
                 .get_color32(crate::general::ColorMode::ScreenModeDark);
             let r = pntr.text(temp, align, t.text.clone(), font, color);
             let r = r.intersect(area);
-            let id = egui::Id::new(1 + i);
             if r.is_positive() {
-                let response = ui.interact(r, id, sense);
+                let response = crate::general::respond(ui, format!("text {}", i), vec![r]);
+                let item = SymbolWidgetSelection::Text { textnum: i };
+                selectable.push((item.clone(), vec![r.into()]));
                 let response = match self.mm {
                     MouseMode::NewPin => response,
                     MouseMode::NewText => response,
                     MouseMode::Selection => {
                         if response.clicked() {
-                            let inp = ui.input(|i| i.modifiers);
-                            if !inp.shift && !inp.ctrl {
-                                self.selection.clear();
+                            crate::general::click_select(self.selection, item, modifiers);
+                        } else if let (true, Some(pointer)) =
+                            (response.dragged_by(egui::PointerButton::Primary), pointer)
+                        {
+                            if !self.selection.contains(&item) {
+                                *self.selection = vec![item];
                             }
-                            self.selection
-                                .push(SymbolWidgetSelection::Text { textnum: i });
+                            group_drag = Some(
+                                self.grid
+                                    .drag_delta(ui, &response, t.location, pointer, &targets, zoom),
+                            );
                         }
                         response.context_menu(|ui| {
                             if ui.button("Properties").clicked() {
@@ -356,18 +818,21 @@ impl<'a> egui::Widget for SymbolDefinitionWidget<'a> {
                         response
                     }
                     MouseMode::TextDrag => {
-                        if response.dragged_by(egui::PointerButton::Primary) {
-                            let amount = response.drag_delta() / *self.zoom;
-                            let a = LibraryAction::MoveText {
-                                libname: self.sym.libname.clone(),
-                                symname: self.sym.sym.name.clone(),
-                                textnum: i,
-                                delta: crate::general::Coordinates::from_pos2(
-                                    amount.to_pos2(),
-                                    *self.zoom,
-                                ),
-                            };
-                            self.actions.push(a);
+                        if let (true, Some(pointer)) =
+                            (response.dragged_by(egui::PointerButton::Primary), pointer)
+                        {
+                            let delta = self
+                                .grid
+                                .drag_delta(ui, &response, t.location, pointer, &targets, zoom);
+                            if !delta.is_zero() {
+                                self.actions.push(LibraryAction::MoveText {
+                                    libname: self.sym.libname.clone(),
+                                    symname: self.sym.sym.name.clone(),
+                                    style: self.sym.style.clone(),
+                                    textnum: i,
+                                    delta,
+                                });
+                            }
                         }
                         response.context_menu(|ui| {
                             if ui.button("Properties").clicked() {
@@ -382,21 +847,29 @@ impl<'a> egui::Widget for SymbolDefinitionWidget<'a> {
         }
 
         for (i, p) in self.sym.sym.pins.iter().enumerate() {
-            let pos = p.location.get_pos2(*self.zoom, origin).to_vec2();
-            let temp = pos;
-            let rects = p.draw(*self.zoom, zoom_origin, &pntr, temp.to_pos2(), area);
+            let rects = p.draw(&pntr, &screen, area);
+            if rects.is_empty() {
+                continue;
+            }
+            let item = SymbolWidgetSelection::Pin { pinnum: i };
+            selectable.push((item.clone(), rects.iter().map(|r| (*r).into()).collect()));
             let response = crate::general::respond(ui, format!("pin {}", i), rects);
             let response = match self.mm {
                 MouseMode::NewPin => response,
                 MouseMode::NewText => response,
                 MouseMode::Selection => {
                     if response.clicked() {
-                        let inp = ui.input(|i| i.modifiers);
-                        if !inp.shift && !inp.ctrl {
-                            self.selection.clear();
+                        crate::general::click_select(self.selection, item, modifiers);
+                    } else if let (true, Some(pointer)) =
+                        (response.dragged_by(egui::PointerButton::Primary), pointer)
+                    {
+                        if !self.selection.contains(&item) {
+                            *self.selection = vec![item];
                         }
-                        self.selection
-                            .push(SymbolWidgetSelection::Pin { pinnum: i });
+                        group_drag = Some(
+                            self.grid
+                                .drag_delta(ui, &response, p.location, pointer, &targets, zoom),
+                        );
                     }
                     response.context_menu(|ui| {
                         if ui.button("Properties").clicked() {
@@ -417,6 +890,28 @@ impl<'a> egui::Widget for SymbolDefinitionWidget<'a> {
             pr = pr.union(response);
         }
 
+        if let MouseMode::Selection = &self.mm {
+            if let Some(delta) = group_drag.filter(|d| !d.is_zero()) {
+                self.actions
+                    .extend(self.sym.move_action(self.selection, delta));
+            }
+            for (item, rects) in &selectable {
+                if self.selection.contains(item) {
+                    crate::general::draw_selected(ui, &pntr, rects);
+                }
+            }
+            if let Some(band) = crate::general::RubberBand::track(ui, &background, &pntr) {
+                if !(modifiers.shift || modifiers.command) {
+                    self.selection.clear();
+                }
+                for (item, rects) in selectable {
+                    if band.selects(&rects) && !self.selection.contains(&item) {
+                        self.selection.push(item);
+                    }
+                }
+            }
+        }
+
         let ipr = pr.context_menu(|ui| {
             if ui.button("Do a thing").clicked() {
                 ui.close_menu();
@@ -428,7 +923,8 @@ impl<'a> egui::Widget for SymbolDefinitionWidget<'a> {
         if let Some(pr) = ipr {
             let pos = ui.input(|i| i.pointer.interact_pos());
             if let Some(pos) = pos {
-                let pos2 = pos - zoom_origin.to_vec2();
+                let location = self.grid.snap(to_symbol(pos), &targets, zoom);
+                let snapped = location.get_pos2(zoom, origin);
                 match self.mm {
                     MouseMode::Selection => {}
                     MouseMode::TextDrag => {}
@@ -437,20 +933,21 @@ impl<'a> egui::Widget for SymbolDefinitionWidget<'a> {
                             self.actions.push(LibraryAction::CreateText {
                                 libname: self.sym.libname.clone(),
                                 symname: self.sym.sym.name.clone(),
+                                style: self.sym.style.clone(),
                                 text: TextOnPage {
                                     text: "New text".to_string(),
-                                    location: crate::general::Coordinates::from_pos2(pos2, *self.zoom),
+                                    location,
                                     color: crate::schematic::Colors::Standard,
-                                    size: crate::general::Length::Inches(0.2),
+                                    size: crate::general::Length::from_inches(0.2),
                                 },
                             });
                         } else {
                             pntr.text(
-                                pos,
+                                snapped,
                                 egui::Align2::LEFT_BOTTOM,
                                 "New text".to_string(),
                                 egui::FontId {
-                                    size: crate::general::Length::Inches(0.2)
+                                    size: crate::general::Length::from_inches(0.2)
                                         .get_screen(*self.zoom, zoom_origin),
                                     family: egui::FontFamily::Monospace,
                                 },
@@ -461,8 +958,9 @@ impl<'a> egui::Widget for SymbolDefinitionWidget<'a> {
                     }
                     MouseMode::NewPin => {
                         let pin = crate::symbol::Pin {
-                            location: crate::general::Coordinates::from_pos2(pos2, *self.zoom),
+                            location,
                             rotation: *self.pin_angle,
+                            number: String::new(),
                         };
                         if pr.response.clicked() {
                             self.actions.push(LibraryAction::CreatePin {
@@ -471,7 +969,7 @@ impl<'a> egui::Widget for SymbolDefinitionWidget<'a> {
                                 pin: Some(pin),
                             });
                         } else {
-                            pin.draw(*self.zoom, zoom_origin, &pntr, pos, area);
+                            pin.draw(&pntr, &screen, area);
                         }
                     }
                 }
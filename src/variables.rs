@@ -0,0 +1,90 @@
+//! Text variables are placeholders such as ${TITLE} in text, which are replaced with their values when the text is drawn.
+//!
+//! The variables of a schematic, such as the title and revision, are stored in the schematic and apply to all of its pages.
+//! Each page adds its page number and the number of pages, and each placed component adds its designator and properties,
+//! so the same symbol shows the right designator and value wherever it is placed.
+
+use std::collections::HashMap;
+
+/// The variable for the title of the schematic, which is the name of the schematic when it is not set
+pub const TITLE: &str = "TITLE";
+
+/// The variable for the revision of the schematic
+pub const REVISION: &str = "REVISION";
+
+/// The variable for the date of the schematic, which is the current date when it is not set
+pub const DATE: &str = "DATE";
+
+/// The variable for the number of the page, starting at 1
+pub const PAGE: &str = "PAGE";
+
+/// The variable for the number of pages in the schematic
+pub const PAGECOUNT: &str = "PAGECOUNT";
+
+/// The variable for the designator of a placed component, along with its unit
+pub const REF: &str = "REF";
+
+/// The variable for the value of a placed component
+pub const VALUE: &str = "VALUE";
+
+/// The variables of a schematic that are shown in the document properties, in the order shown
+pub const DOCUMENT_VARIABLES: [&str; 3] = [TITLE, REVISION, DATE];
+
+/// The values of the variables available to a piece of text
+#[derive(Clone, Default)]
+pub struct TextVariables {
+    /// The value of each variable, by name
+    values: HashMap<String, String>,
+}
+
+impl TextVariables {
+    /// Set the value of a variable
+    pub fn set(&mut self, name: &str, value: String) {
+        self.values.insert(name.to_string(), value);
+    }
+
+    /// Replace each placeholder in text with the value of its variable.
+    /// Placeholders for variables that do not exist are left as they are.
+    pub fn substitute<'a>(&self, text: &'a str) -> std::borrow::Cow<'a, str> {
+        if !text.contains("${") {
+            return std::borrow::Cow::Borrowed(text);
+        }
+        let mut result = String::new();
+        let mut rest = text;
+        while let Some(start) = rest.find("${") {
+            let after = &rest[start + 2..];
+            let end = if let Some(e) = after.find('}') {
+                e
+            } else {
+                break;
+            };
+            result.push_str(&rest[..start]);
+            match self.values.get(&after[..end]) {
+                Some(v) => result.push_str(v),
+                None => result.push_str(&rest[start..start + end + 3]),
+            }
+            rest = &after[end + 1..];
+        }
+        result.push_str(rest);
+        std::borrow::Cow::Owned(result)
+    }
+}
+
+/// The current date in the UTC time zone, as year-month-day
+pub fn today() -> String {
+    let days = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() / 86400)
+        .unwrap_or(0) as i64;
+    // Convert days since 1970-01-01 to a civil date, counting in 400 year eras that start on March 1st
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
@@ -0,0 +1,63 @@
+//! Watches the files of loaded libraries, so that changes made by other programs can be picked up.
+
+use std::collections::HashSet;
+
+/// Watches the folders containing library files, sending an event to the application when a library file changes
+pub struct LibraryWatcher {
+    /// The watcher, which stops watching when dropped
+    watcher: notify::RecommendedWatcher,
+    /// The folders being watched
+    folders: HashSet<std::path::PathBuf>,
+}
+
+impl LibraryWatcher {
+    /// Create a watcher that sends a [crate::ipc::IpcMessage::LibraryFileChanged] to the event loop for every library file that changes
+    pub fn new(
+        proxy: egui_multiwin::winit::event_loop::EventLoopProxy<crate::ipc::IpcMessage>,
+    ) -> Result<Self, notify::Error> {
+        let watcher =
+            notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
+                Ok(event) => {
+                    if matches!(
+                        event.kind,
+                        notify::EventKind::Create(_) | notify::EventKind::Modify(_)
+                    ) {
+                        for path in event.paths {
+                            if path.extension().map_or(false, |e| e == "uol") {
+                                if let Ok(p) = path.into_os_string().into_string() {
+                                    proxy
+                                        .send_event(crate::ipc::IpcMessage::LibraryFileChanged(p))
+                                        .ok();
+                                }
+                            }
+                        }
+                    }
+                }
+                Err(e) => println!("ERROR watching libraries {}", e),
+            })?;
+        Ok(Self {
+            watcher,
+            folders: HashSet::new(),
+        })
+    }
+
+    /// Start watching the folder that contains the given library file, if it is not already watched.
+    /// Folders are watched instead of files, because saving a file usually replaces it.
+    pub fn watch(&mut self, path: &crate::general::StoragePath) {
+        use notify::Watcher;
+        let folder = path
+            .open_path()
+            .and_then(|p| p.parent().map(|p| p.to_path_buf()));
+        if let Some(folder) = folder {
+            if !self.folders.contains(&folder) {
+                if let Err(e) = self
+                    .watcher
+                    .watch(&folder, notify::RecursiveMode::NonRecursive)
+                {
+                    println!("ERROR watching {} {}", folder.display(), e);
+                }
+                self.folders.insert(folder);
+            }
+        }
+    }
+}
@@ -0,0 +1,133 @@
+//! This window shows the differences between the library items stored in the schematic and the libraries, and updates them
+
+use crate::egui_multiwin_dynamic::{
+    multi_window::NewWindowRequest,
+    tracked_window::{RedrawResponse, TrackedWindow},
+};
+use egui_multiwin::egui;
+use egui_multiwin::egui_glow::EguiGlow;
+
+use crate::library::LibraryItem;
+use crate::schematic::SchematicAction;
+use crate::MyApp;
+
+/// The window structure
+pub struct CacheUpdate {}
+
+impl CacheUpdate {
+    /// Create a new window
+    pub fn request() -> NewWindowRequest {
+        NewWindowRequest::new(
+            super::Windows::CacheUpdate(Self {}),
+            egui_multiwin::winit::window::WindowBuilder::new()
+                .with_resizable(true)
+                .with_inner_size(egui_multiwin::winit::dpi::LogicalSize {
+                    width: 480.0,
+                    height: 480.0,
+                })
+                .with_title("Update from library"),
+            egui_multiwin::tracked_window::TrackedWindowOptions {
+                vsync: false,
+                shader: None,
+            },
+            egui_multiwin::multi_window::new_id(),
+        )
+    }
+}
+
+impl TrackedWindow for CacheUpdate {
+    fn is_root(&self) -> bool {
+        false
+    }
+
+    fn set_root(&mut self, _root: bool) {}
+
+    fn redraw(
+        &mut self,
+        c: &mut MyApp,
+        egui: &mut EguiGlow,
+        _window: &egui_multiwin::winit::window::Window,
+        _clipboard: &mut egui_multiwin::arboard::Clipboard,
+    ) -> RedrawResponse {
+        let mut quit = false;
+
+        let windows_to_create = vec![];
+
+        let sch = if let Some(sch) = &mut c.schematic {
+            sch
+        } else {
+            return RedrawResponse {
+                quit: true,
+                new_windows: windows_to_create,
+            };
+        };
+
+        let diffs = sch.schematic.cache.differences(&c.libraries);
+        let mut update: Vec<LibraryItem> = Vec::new();
+
+        egui::TopBottomPanel::bottom("buttons").show(&egui.egui_ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(
+                        diffs.iter().any(|d| !d.missing()),
+                        egui::Button::new("Update all"),
+                    )
+                    .clicked()
+                {
+                    update = diffs
+                        .iter()
+                        .filter(|d| !d.missing())
+                        .map(|d| d.item.clone())
+                        .collect();
+                }
+                if ui.button("Close").clicked() {
+                    quit = true;
+                }
+            });
+        });
+
+        egui::CentralPanel::default().show(&egui.egui_ctx, |ui| {
+            if diffs.is_empty() {
+                ui.label("The schematic matches the libraries");
+            }
+            egui::ScrollArea::vertical()
+                .auto_shrink([false, false])
+                .show(ui, |ui| {
+                    for d in &diffs {
+                        ui.horizontal(|ui| {
+                            ui.label(d.item.to_string());
+                            if ui
+                                .add_enabled(!d.missing(), egui::Button::new("Update"))
+                                .clicked()
+                            {
+                                update.push(d.item.clone());
+                            }
+                        });
+                        ui.indent(d.item.to_string(), |ui| {
+                            if d.missing() {
+                                ui.colored_label(
+                                    egui::Color32::YELLOW,
+                                    "Not found in the library, the copy in the schematic is used",
+                                );
+                            }
+                            for change in &d.changes {
+                                ui.label(change);
+                            }
+                        });
+                        ui.separator();
+                    }
+                });
+        });
+
+        for item in update {
+            for a in SchematicAction::update_cache(&sch.schematic.cache, &item, &c.libraries) {
+                sch.schematic_log.apply(&mut sch.schematic, a);
+            }
+        }
+
+        RedrawResponse {
+            quit,
+            new_windows: windows_to_create,
+        }
+    }
+}
@@ -0,0 +1,147 @@
+//! This window edits the text variables of the schematic, such as its title, revision and date
+
+use crate::egui_multiwin_dynamic::{
+    multi_window::NewWindowRequest,
+    tracked_window::{RedrawResponse, TrackedWindow},
+};
+use egui_multiwin::egui;
+use egui_multiwin::egui_glow::EguiGlow;
+
+use crate::schematic::SchematicAction;
+use crate::variables::DOCUMENT_VARIABLES;
+use crate::MyApp;
+
+/// The window structure
+pub struct DocumentProperties {
+    /// The name for a new variable
+    new_name: String,
+}
+
+impl DocumentProperties {
+    /// Create a new window
+    pub fn request() -> NewWindowRequest {
+        NewWindowRequest::new(
+            super::Windows::DocumentProperties(Self {
+                new_name: String::new(),
+            }),
+            egui_multiwin::winit::window::WindowBuilder::new()
+                .with_resizable(true)
+                .with_inner_size(egui_multiwin::winit::dpi::LogicalSize {
+                    width: 400.0,
+                    height: 320.0,
+                })
+                .with_title("Document properties"),
+            egui_multiwin::tracked_window::TrackedWindowOptions {
+                vsync: false,
+                shader: None,
+            },
+            egui_multiwin::multi_window::new_id(),
+        )
+    }
+}
+
+impl TrackedWindow for DocumentProperties {
+    fn is_root(&self) -> bool {
+        false
+    }
+
+    fn set_root(&mut self, _root: bool) {}
+
+    fn redraw(
+        &mut self,
+        c: &mut MyApp,
+        egui: &mut EguiGlow,
+        _window: &egui_multiwin::winit::window::Window,
+        _clipboard: &mut egui_multiwin::arboard::Clipboard,
+    ) -> RedrawResponse {
+        let mut quit = false;
+
+        let windows_to_create = vec![];
+
+        let sch = if let Some(sch) = &mut c.schematic {
+            sch
+        } else {
+            return RedrawResponse {
+                quit: true,
+                new_windows: windows_to_create,
+            };
+        };
+
+        let mut actionlog = Vec::new();
+
+        egui::CentralPanel::default().show(&egui.egui_ctx, |ui| {
+            let vars = &sch.schematic.variables;
+            let mut custom: Vec<&String> = vars
+                .keys()
+                .filter(|n| !DOCUMENT_VARIABLES.contains(&n.as_str()))
+                .collect();
+            custom.sort();
+            egui::Grid::new("variables").show(ui, |ui| {
+                for name in DOCUMENT_VARIABLES.iter().copied().chain(custom.iter().map(|n| n.as_str())) {
+                    ui.label(name);
+                    let mut value = vars.get(name).cloned().unwrap_or_default();
+                    let hint = match name {
+                        crate::variables::TITLE => sch.schematic.name().to_string(),
+                        crate::variables::DATE => crate::variables::today(),
+                        _ => String::new(),
+                    };
+                    let te = egui::TextEdit::singleline(&mut value).hint_text(hint);
+                    if ui.add(te).changed() {
+                        actionlog.push(SchematicAction::SetVariable {
+                            name: name.to_string(),
+                            value: Some(value),
+                        });
+                    }
+                    if name == crate::variables::DATE && ui.button("Today").clicked() {
+                        actionlog.push(SchematicAction::SetVariable {
+                            name: name.to_string(),
+                            value: Some(crate::variables::today()),
+                        });
+                    }
+                    if vars.contains_key(name) {
+                        let label = if DOCUMENT_VARIABLES.contains(&name) {
+                            "Reset"
+                        } else {
+                            "Remove"
+                        };
+                        if ui.button(label).clicked() {
+                            actionlog.push(SchematicAction::SetVariable {
+                                name: name.to_string(),
+                                value: None,
+                            });
+                        }
+                    }
+                    ui.end_row();
+                }
+            });
+            ui.separator();
+            ui.horizontal(|ui| {
+                let te = egui::TextEdit::singleline(&mut self.new_name).hint_text("New variable");
+                ui.add(te);
+                let valid = !self.new_name.is_empty()
+                    && !self.new_name.contains(['{', '}'])
+                    && !vars.contains_key(&self.new_name)
+                    && !DOCUMENT_VARIABLES.contains(&self.new_name.as_str());
+                if ui.add_enabled(valid, egui::Button::new("Add")).clicked() {
+                    actionlog.push(SchematicAction::SetVariable {
+                        name: std::mem::take(&mut self.new_name),
+                        value: Some(String::new()),
+                    });
+                }
+            });
+            ui.label("Use a variable in text as ${NAME}. PAGE, PAGECOUNT, REF and VALUE are also available.");
+            if ui.button("Close").clicked() {
+                quit = true;
+            }
+        });
+
+        for a in actionlog {
+            sch.schematic_log.apply(&mut sch.schematic, a);
+        }
+
+        RedrawResponse {
+            quit,
+            new_windows: windows_to_create,
+        }
+    }
+}
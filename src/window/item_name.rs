@@ -0,0 +1,197 @@
+//! This window asks for the name and library of a symbol or component that is renamed, copied or derived from another
+
+use crate::egui_multiwin_dynamic::{
+    multi_window::NewWindowRequest,
+    tracked_window::{RedrawResponse, TrackedWindow},
+};
+use egui_multiwin::egui;
+use egui_multiwin::egui_glow::EguiGlow;
+
+use crate::library::{LibraryAction, LibraryItem};
+use crate::MyApp;
+
+/// What is done with the item
+#[derive(Clone, Copy, PartialEq)]
+pub enum ItemOperation {
+    /// Rename the item, or move it to another library
+    Rename,
+    /// Make a copy of the item
+    Duplicate,
+    /// Make a component derived from the item, only for components
+    Derive,
+}
+
+impl std::fmt::Display for ItemOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rename => write!(f, "Rename"),
+            Self::Duplicate => write!(f, "Duplicate"),
+            Self::Derive => write!(f, "Derive from"),
+        }
+    }
+}
+
+/// The window structure
+pub struct ItemName {
+    /// The item being renamed, copied or derived from
+    item: LibraryItem,
+    /// What to do with the item
+    operation: ItemOperation,
+    /// The library for the resulting item
+    lib_name: String,
+    /// The name of the resulting item
+    name: String,
+}
+
+impl ItemName {
+    /// Create a new window
+    pub fn request(item: LibraryItem, operation: ItemOperation) -> NewWindowRequest {
+        let (lib_name, name) = match &item {
+            LibraryItem::Component(l, n) => (l.clone(), n.clone()),
+            LibraryItem::Symbol(l, n) => (l.clone(), n.clone()),
+        };
+        let name = match operation {
+            ItemOperation::Rename => name,
+            ItemOperation::Duplicate | ItemOperation::Derive => format!("{} copy", name),
+        };
+        let title = format!("{} {}", operation, item);
+        NewWindowRequest::new(
+            super::Windows::ItemName(Self {
+                item,
+                operation,
+                lib_name,
+                name,
+            }),
+            egui_multiwin::winit::window::WindowBuilder::new()
+                .with_resizable(true)
+                .with_inner_size(egui_multiwin::winit::dpi::LogicalSize {
+                    width: 320.0,
+                    height: 240.0,
+                })
+                .with_title(title),
+            egui_multiwin::tracked_window::TrackedWindowOptions {
+                vsync: false,
+                shader: None,
+            },
+            egui_multiwin::multi_window::new_id(),
+        )
+    }
+
+    /// The action that does the operation
+    fn action(&self) -> LibraryAction {
+        let newlib = self.lib_name.clone();
+        let newname = self.name.clone();
+        match (&self.item, self.operation) {
+            (LibraryItem::Component(l, n), ItemOperation::Rename) => {
+                LibraryAction::RenameComponent {
+                    libname: l.clone(),
+                    comname: n.clone(),
+                    newlib,
+                    newname,
+                }
+            }
+            (LibraryItem::Component(l, n), op) => LibraryAction::DuplicateComponent {
+                libname: l.clone(),
+                comname: n.clone(),
+                newlib,
+                newname,
+                derive: op == ItemOperation::Derive,
+            },
+            (LibraryItem::Symbol(l, n), ItemOperation::Rename) => LibraryAction::RenameSymbol {
+                libname: l.clone(),
+                symname: n.clone(),
+                newlib,
+                newname,
+            },
+            (LibraryItem::Symbol(l, n), _) => LibraryAction::DuplicateSymbol {
+                libname: l.clone(),
+                symname: n.clone(),
+                newlib,
+                newname,
+            },
+        }
+    }
+}
+
+impl TrackedWindow for ItemName {
+    fn is_root(&self) -> bool {
+        false
+    }
+
+    fn set_root(&mut self, _root: bool) {}
+
+    fn redraw(
+        &mut self,
+        c: &mut MyApp,
+        egui: &mut EguiGlow,
+        _window: &egui_multiwin::winit::window::Window,
+        _clipboard: &mut egui_multiwin::arboard::Clipboard,
+    ) -> RedrawResponse {
+        let mut quit = false;
+
+        let windows_to_create = vec![];
+
+        let mut actionlog = Vec::new();
+
+        egui::CentralPanel::default().show(&egui.egui_ctx, |ui| {
+            ui.label(format!("{} {}", self.operation, self.item));
+            let te = egui::widgets::TextEdit::singleline(&mut self.name).hint_text("New name");
+            ui.add(te).request_focus();
+            let mut names: Vec<&String> = c.libraries.keys().collect();
+            names.sort();
+            egui::ComboBox::from_label("Library")
+                .selected_text(self.lib_name.clone())
+                .show_ui(ui, |ui| {
+                    for n in names {
+                        ui.selectable_value(&mut self.lib_name, n.clone(), n);
+                    }
+                });
+            let (current_lib, current_name) = match &self.item {
+                LibraryItem::Component(l, n) => (l, n),
+                LibraryItem::Symbol(l, n) => (l, n),
+            };
+            let exists = c
+                .libraries
+                .get(&self.lib_name)
+                .and_then(|l| l.library.as_ref())
+                .map(|l| match &self.item {
+                    LibraryItem::Component(_, _) => l.components.contains_key(&self.name),
+                    LibraryItem::Symbol(_, _) => l.syms.contains_key(&self.name),
+                });
+            let unchanged = *current_lib == self.lib_name && *current_name == self.name;
+            let renaming = self.operation == ItemOperation::Rename;
+            match exists {
+                None => {
+                    ui.label("Library does not exist for some reason");
+                }
+                Some(true) if !(renaming && unchanged) => {
+                    ui.colored_label(egui::Color32::RED, "Name already exists");
+                }
+                Some(_) => {
+                    if self.name.is_empty() {
+                        ui.colored_label(egui::Color32::RED, "Please enter a name");
+                    } else if ui.button(self.operation.to_string()).clicked()
+                        || ui.input(|i| i.key_pressed(egui::Key::Enter))
+                    {
+                        if !unchanged {
+                            actionlog.push(self.action());
+                        }
+                        quit = true;
+                    }
+                }
+            }
+            if ui.button("Cancel").clicked() {
+                quit = true;
+            }
+        });
+
+        for a in actionlog {
+            c.apply_library(a);
+        }
+
+        RedrawResponse {
+            quit,
+            new_windows: windows_to_create,
+        }
+    }
+}
@@ -12,6 +12,7 @@ use crate::component::ComponentVariant;
 use crate::library::LibraryAction;
 use crate::symbol::SymbolWidgetSelection;
 use crate::symbol::{LibraryReference, MouseMode};
+use crate::window::item_name::{ItemName, ItemOperation};
 use crate::MyApp;
 
 /// An enumeration of things that be selected in the library editor
@@ -47,6 +48,21 @@ pub struct Library {
     zoom: f32,
     /// The angle for new pins, in degrees
     pin_angle: f32,
+    /// The problems found the last time the selected library was checked, with the name of the library and the number
+    /// of library changes at the time, so that it is checked again only after the libraries change
+    lint_report: Option<(String, usize, Vec<crate::lint::LintIssue>)>,
+    /// The name of a property to add to the selected component
+    new_property: String,
+    /// The selected unit of the selected variant, for components with more than one unit
+    selected_unit: Option<String>,
+    /// The body style of the selected symbol being edited, None for the normal body style
+    selected_style: Option<String>,
+    /// The name of a title block to add to the selected library
+    new_title_block: String,
+    /// Where the pointer is in the symbol being edited, for the status bar
+    cursor: Option<crate::general::Coordinates>,
+    /// The selected component with everything it inherits included
+    flattened: crate::component::FlattenedCache,
 }
 
 impl Library {
@@ -61,10 +77,17 @@ impl Library {
                 selection: Vec::new(),
                 old_saved_status: false,
                 mm: MouseMode::Selection,
-                origin: crate::general::Coordinates::Inches(0.0, 0.0),
+                origin: crate::general::Coordinates::from_nm(0, 0),
                 recenter: false,
                 zoom: 115.0,
                 pin_angle: 0.0,
+                lint_report: None,
+                new_property: String::new(),
+                selected_unit: None,
+                selected_style: None,
+                new_title_block: String::new(),
+                cursor: None,
+                flattened: Default::default(),
             }),
             egui_multiwin::winit::window::WindowBuilder::new()
                 .with_resizable(true)
@@ -98,6 +121,9 @@ impl TrackedWindow for Library {
     ) -> RedrawResponse {
         let mut quit = false;
 
+        c.check_autosave(&egui.egui_ctx);
+        c.watch_libraries();
+
         let mut windows_to_create = vec![];
 
         let is_saved = c.library_log.is_saved();
@@ -116,17 +142,18 @@ impl TrackedWindow for Library {
                 ui.menu_button("File", |ui| {
                     if ui.button("Save all libraries").clicked() {
                         let mut no_errors = true;
-                        for libh in &mut c.libraries.values() {
-                            if let Some(library) = &libh.library {
+                        for libh in c.libraries.values_mut() {
+                            if let Some(name) = libh.library.as_ref().map(|l| l.name.clone()) {
+                                // Libraries on a library server are published from their menu instead
+                                if libh.is_remote() {
+                                    continue;
+                                }
                                 if libh.can_save() {
                                     if let Err(e) = libh.save() {
                                         no_errors = false;
                                         let _e = native_dialog::MessageDialog::new()
                                             .set_type(native_dialog::MessageType::Error)
-                                            .set_title(&format!(
-                                                "Failed to save {} library",
-                                                library.name
-                                            ))
+                                            .set_title(&format!("Failed to save {} library", name))
                                             .set_text(e.to_string().as_str())
                                             .show_alert();
                                     }
@@ -145,6 +172,14 @@ impl TrackedWindow for Library {
                         }
                         ui.close_menu();
                     }
+                    if ui.button("Library folders").clicked() {
+                        windows_to_create.push(super::library_folders::LibraryFolders::request());
+                        ui.close_menu();
+                    }
+                    if ui.button("Connect to library server").clicked() {
+                        windows_to_create.push(super::library_server::LibraryServer::request());
+                        ui.close_menu();
+                    }
                     ui.menu_button("Recent", |ui| {
                         if ui.button("Thing 1").clicked() {
                             ui.close_menu();
@@ -161,14 +196,14 @@ impl TrackedWindow for Library {
                         .add_enabled(undoable, egui::Button::new("Undo"))
                         .clicked()
                     {
-                        c.library_log.undo(&mut c.libraries);
+                        c.undo_library();
                         ui.close_menu();
                     }
                     if ui
                         .add_enabled(redoable, egui::Button::new("Redo"))
                         .clicked()
                     {
-                        c.library_log.redo(&mut c.libraries);
+                        c.redo_library();
                         ui.close_menu();
                     }
                 });
@@ -188,7 +223,7 @@ impl TrackedWindow for Library {
             })
         });
         if input && c.library_log.can_undo() {
-            c.library_log.undo(&mut c.libraries);
+            c.undo_library();
         }
         let input = egui.egui_ctx.input_mut(|i| {
             i.consume_shortcut(&egui::KeyboardShortcut {
@@ -203,7 +238,7 @@ impl TrackedWindow for Library {
             })
         });
         if input && c.library_log.can_redo() {
-            c.library_log.redo(&mut c.libraries);
+            c.redo_library();
         }
 
         egui::TopBottomPanel::top("button bar").show(&egui.egui_ctx, |ui| {
@@ -227,6 +262,96 @@ impl TrackedWindow for Library {
             });
         });
 
+        if let Some(Thing::Symbol(_)) = &self.selected_thing {
+            egui::TopBottomPanel::bottom("status bar").show(&egui.egui_ctx, |ui| {
+                ui.horizontal(|ui| {
+                    let units_changed =
+                        crate::grid::cursor_readout(ui, self.cursor, &mut c.settings.units);
+                    ui.separator();
+                    ui.label("Grid");
+                    if c.settings.grid.controls(ui) || units_changed {
+                        if let Err(e) = c.settings.save(&c.dirs) {
+                            let _ = native_dialog::MessageDialog::new()
+                                .set_type(native_dialog::MessageType::Error)
+                                .set_title("Failed to save settings")
+                                .set_text(&e.to_string())
+                                .show_alert();
+                        }
+                    }
+                });
+            });
+        }
+
+        let mut publish = None;
+        let mut check_library = self
+            .lint_report
+            .as_ref()
+            .filter(|(_, changes, _)| *changes != c.library_log.changes())
+            .map(|(l, _, _)| l.clone());
+
+        if let Some((libname, _, issues)) = &self.lint_report {
+            let mut close = false;
+            egui::TopBottomPanel::bottom("lint report")
+                .resizable(true)
+                .show(&egui.egui_ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.label(format!("Problems in library {}", libname));
+                        if ui.button("Close").clicked() {
+                            close = true;
+                        }
+                    });
+                    ui.separator();
+                    egui::ScrollArea::vertical()
+                        .auto_shrink([false, true])
+                        .show(ui, |ui| {
+                            if issues.is_empty() {
+                                ui.label("No problems found");
+                            }
+                            for issue in issues {
+                                let color = match issue.severity {
+                                    crate::lint::Severity::Error => egui::Color32::RED,
+                                    crate::lint::Severity::Warning => egui::Color32::YELLOW,
+                                };
+                                let text = egui::RichText::new(issue.to_string()).color(color);
+                                if ui
+                                    .add(egui::Label::new(text).sense(Sense::click()))
+                                    .clicked()
+                                {
+                                    self.selected_library = Some(issue.library.clone());
+                                    match &issue.location {
+                                        crate::lint::LintLocation::Library => {}
+                                        crate::lint::LintLocation::Symbol(s) => {
+                                            self.selected_thing = Some(Thing::Symbol(s.clone()));
+                                            self.selection.clear();
+                                        }
+                                        crate::lint::LintLocation::Component(com) => {
+                                            self.selected_thing =
+                                                Some(Thing::Component(com.clone()));
+                                        }
+                                        crate::lint::LintLocation::Variant(com, var) => {
+                                            self.selected_thing =
+                                                Some(Thing::Component(com.clone()));
+                                            self.selected_variant = Some(var.clone());
+                                        }
+                                    }
+                                }
+                            }
+                        });
+                });
+            if close {
+                check_library = None;
+                self.lint_report = None;
+            }
+        }
+
+        if !c.library_conflicts.is_empty() {
+            egui::TopBottomPanel::bottom("conflicts").show(&egui.egui_ctx, |ui| {
+                for conflict in &c.library_conflicts {
+                    ui.colored_label(egui::Color32::YELLOW, conflict.to_string());
+                }
+            });
+        }
+
         egui::SidePanel::left("left panel")
             .resizable(true)
             .show(&egui.egui_ctx, |ui| {
@@ -250,6 +375,9 @@ impl TrackedWindow for Library {
                                                 );
                                             }
                                             if let Some(l) = &self.selected_library {
+                                                if ui.button("Check library").clicked() {
+                                                    check_library = Some(l.clone());
+                                                }
                                                 if ui.button("Delete Library").clicked() {
                                                     c.library_log.apply(
                                                     &mut c.libraries,
@@ -291,6 +419,15 @@ impl TrackedWindow for Library {
                                                 if !haspath {
                                                     ui.label("Cannot browse to");
                                                 }
+                                                if let Some(crate::general::StoragePath::Remote(_)) = &lib.path {
+                                                    ui.separator();
+                                                    for (major, label) in [(false, "Publish minor version"), (true, "Publish major version")] {
+                                                        if ui.button(label).clicked() {
+                                                            publish = Some((name.clone(), major));
+                                                            ui.close_menu();
+                                                        }
+                                                    }
+                                                }
                                             });
                                         }
                                     });
@@ -326,6 +463,20 @@ impl TrackedWindow for Library {
                                                 );
                                             }
                                             if let Some(Thing::Symbol(symname)) = &self.selected_thing {
+                                                for (label, op) in [
+                                                    ("Rename", ItemOperation::Rename),
+                                                    ("Duplicate", ItemOperation::Duplicate),
+                                                ] {
+                                                    if ui.button(label).clicked() {
+                                                        windows_to_create.push(ItemName::request(
+                                                            crate::library::LibraryItem::Symbol(
+                                                                l.clone(),
+                                                                symname.clone(),
+                                                            ),
+                                                            op,
+                                                        ));
+                                                    }
+                                                }
                                                 if ui.button("Delete Symbol").clicked() {
                                                     actionlog.push(LibraryAction::DeleteSymbol {
                                                         libname: l.clone(),
@@ -372,6 +523,21 @@ impl TrackedWindow for Library {
                                                 );
                                             }
                                             if let Some(Thing::Component(comname)) = &self.selected_thing {
+                                                for (label, op) in [
+                                                    ("Rename", ItemOperation::Rename),
+                                                    ("Duplicate", ItemOperation::Duplicate),
+                                                    ("Derive", ItemOperation::Derive),
+                                                ] {
+                                                    if ui.button(label).clicked() {
+                                                        windows_to_create.push(ItemName::request(
+                                                            crate::library::LibraryItem::Component(
+                                                                l.clone(),
+                                                                comname.clone(),
+                                                            ),
+                                                            op,
+                                                        ));
+                                                    }
+                                                }
                                                 if ui.button("Delete Component").clicked() {
                                                     actionlog.push(LibraryAction::DeleteComponent {
                                                         libname: l.clone(),
@@ -397,6 +563,133 @@ impl TrackedWindow for Library {
                                         }
                                     });
                                     });
+                                ui.separator();
+                                egui::CollapsingHeader::new("Title blocks")
+                                    .default_open(false)
+                                    .show(ui, |ui| {
+                                        ui.horizontal(|ui| {
+                                            let te = egui::TextEdit::singleline(&mut self.new_title_block)
+                                                .hint_text("Title block name");
+                                            ui.add(te);
+                                            let valid = !self.new_title_block.is_empty()
+                                                && !library.title_blocks.contains_key(&self.new_title_block);
+                                            if ui.add_enabled(valid, egui::Button::new("New title block")).clicked() {
+                                                let name = std::mem::take(&mut self.new_title_block);
+                                                actionlog.push(LibraryAction::SetTitleBlock {
+                                                    libname: l.clone(),
+                                                    name: name.clone(),
+                                                    block: Some(crate::sheet::TitleBlockDefinition::standard(name)),
+                                                });
+                                            }
+                                        });
+                                        ui.separator();
+                                        let mut names: Vec<&String> = library.title_blocks.keys().collect();
+                                        names.sort();
+                                        for name in names {
+                                            let block = &library.title_blocks[name];
+                                            let mut edited: Option<crate::sheet::TitleBlockDefinition> = None;
+                                            ui.horizontal(|ui| {
+                                                ui.label(name);
+                                                if ui.button("Delete").clicked() {
+                                                    actionlog.push(LibraryAction::SetTitleBlock {
+                                                        libname: l.clone(),
+                                                        name: name.clone(),
+                                                        block: None,
+                                                    });
+                                                }
+                                            });
+                                            egui::CollapsingHeader::new("Lines")
+                                                .id_source(("title block lines", name))
+                                                .show(ui, |ui| {
+                                                    for (i, (from, to)) in block.lines.iter().enumerate() {
+                                                        ui.horizontal(|ui| {
+                                                            let ends = [from.x, from.y, to.x, to.y];
+                                                            for (j, value) in ends.into_iter().enumerate() {
+                                                                if let Some(v) = crate::general::length_edit(ui, ("line", name, i, j), value, c.settings.units) {
+                                                                    let mut b = block.clone();
+                                                                    let (from, to) = &mut b.lines[i];
+                                                                    match j {
+                                                                        0 => from.x = v,
+                                                                        1 => from.y = v,
+                                                                        2 => to.x = v,
+                                                                        _ => to.y = v,
+                                                                    }
+                                                                    edited = Some(b);
+                                                                }
+                                                            }
+                                                            if ui.button("Delete").clicked() {
+                                                                let mut b = block.clone();
+                                                                b.lines.remove(i);
+                                                                edited = Some(b);
+                                                            }
+                                                        });
+                                                    }
+                                                    if ui.button("Add line").clicked() {
+                                                        let mut b = block.clone();
+                                                        b.lines.push((
+                                                            crate::general::Coordinates::from_mm(-20.0, 0.0),
+                                                            crate::general::Coordinates::from_mm(-20.0, 10.0),
+                                                        ));
+                                                        edited = Some(b);
+                                                    }
+                                                });
+                                            egui::CollapsingHeader::new("Text")
+                                                .id_source(("title block text", name))
+                                                .show(ui, |ui| {
+                                                    for (i, t) in block.texts.iter().enumerate() {
+                                                        ui.horizontal(|ui| {
+                                                            if let Some(text) = crate::general::text_edit(ui, ("text", name, i), &t.text) {
+                                                                let mut b = block.clone();
+                                                                b.texts[i].text = text;
+                                                                edited = Some(b);
+                                                            }
+                                                            if ui.button("Delete").clicked() {
+                                                                let mut b = block.clone();
+                                                                b.texts.remove(i);
+                                                                edited = Some(b);
+                                                            }
+                                                        });
+                                                        ui.horizontal(|ui| {
+                                                            ui.label("X ");
+                                                            if let Some(x) = crate::general::length_edit(ui, ("text x", name, i), t.location.x, c.settings.units) {
+                                                                let mut b = block.clone();
+                                                                b.texts[i].location.x = x;
+                                                                edited = Some(b);
+                                                            }
+                                                            ui.label("Y ");
+                                                            if let Some(y) = crate::general::length_edit(ui, ("text y", name, i), t.location.y, c.settings.units) {
+                                                                let mut b = block.clone();
+                                                                b.texts[i].location.y = y;
+                                                                edited = Some(b);
+                                                            }
+                                                            ui.label("Size ");
+                                                            if let Some(size) = crate::general::length_edit(ui, ("text size", name, i), t.size.0, c.settings.units).filter(|s| *s > 0) {
+                                                                let mut b = block.clone();
+                                                                b.texts[i].size = crate::general::Length(size);
+                                                                edited = Some(b);
+                                                            }
+                                                        });
+                                                    }
+                                                    if ui.button("Add text").clicked() {
+                                                        let mut b = block.clone();
+                                                        b.texts.push(crate::schematic::TextOnPage {
+                                                            text: "Text".to_string(),
+                                                            location: crate::general::Coordinates::from_mm(-20.0, 2.0),
+                                                            color: crate::schematic::Colors::Standard,
+                                                            size: crate::general::Length::from_mm(2.0),
+                                                        });
+                                                        edited = Some(b);
+                                                    }
+                                                });
+                                            if let Some(b) = edited {
+                                                actionlog.push(LibraryAction::SetTitleBlock {
+                                                    libname: l.clone(),
+                                                    name: name.clone(),
+                                                    block: Some(b),
+                                                });
+                                            }
+                                        }
+                                    });
                             });
                         }
                     }
@@ -407,6 +700,32 @@ impl TrackedWindow for Library {
                 }
             });
 
+        if let Some((name, major)) = publish {
+            if let Some(libh) = c.libraries.get_mut(&name) {
+                if let Err(e) = libh.publish(major) {
+                    let _e = native_dialog::MessageDialog::new()
+                        .set_type(native_dialog::MessageType::Error)
+                        .set_title(&format!("Failed to publish {} library", name))
+                        .set_text(e.to_string().as_str())
+                        .show_alert();
+                }
+            }
+        }
+
+        if let Some(l) = check_library {
+            self.lint_report = c
+                .libraries
+                .get(&l)
+                .and_then(|h| h.library.as_ref())
+                .map(|lib| {
+                    (
+                        l.clone(),
+                        c.library_log.changes(),
+                        crate::lint::check_library(lib, &c.libraries),
+                    )
+                });
+        }
+
         let mut actionlog = Vec::new();
 
         if let Some(l) = &self.selected_library {
@@ -424,14 +743,78 @@ impl TrackedWindow for Library {
                                 .auto_shrink([false, false])
                                 .stick_to_right(true)
                                 .show(ui, |ui| {
+                                    let symbol = &library.syms[sym];
+                                    if self
+                                        .selected_style
+                                        .as_ref()
+                                        .map_or(false, |s| symbol.style(s).is_none())
+                                    {
+                                        self.selected_style = None;
+                                        self.selection.clear();
+                                    }
+                                    let old_style = self.selected_style.clone();
+                                    egui::ComboBox::from_label("Body style")
+                                        .selected_text(
+                                            self.selected_style
+                                                .clone()
+                                                .unwrap_or_else(|| "Normal".to_string()),
+                                        )
+                                        .show_ui(ui, |ui| {
+                                            ui.selectable_value(&mut self.selected_style, None, "Normal");
+                                            for st in &symbol.styles {
+                                                ui.selectable_value(
+                                                    &mut self.selected_style,
+                                                    Some(st.name.clone()),
+                                                    &st.name,
+                                                );
+                                            }
+                                        });
+                                    if self.selected_style != old_style {
+                                        self.selection.clear();
+                                    }
+                                    ui.horizontal(|ui| {
+                                        if ui.button("Add body style").clicked() {
+                                            let stylename = symbol.next_style_name();
+                                            actionlog.push(LibraryAction::CreateSymbolStyle {
+                                                libname: l.clone(),
+                                                symname: sym.clone(),
+                                                stylename: stylename.clone(),
+                                            });
+                                            self.selected_style = Some(stylename);
+                                            self.selection.clear();
+                                        }
+                                        if let Some(style) = &self.selected_style {
+                                            if ui.button("Delete body style").clicked() {
+                                                actionlog.push(LibraryAction::DeleteSymbolStyle {
+                                                    libname: l.clone(),
+                                                    symname: sym.clone(),
+                                                    stylename: style.clone(),
+                                                    style: None,
+                                                });
+                                            }
+                                        }
+                                    });
+                                    ui.separator();
+                                    let text_count = symbol.style_texts(self.selected_style.as_deref()).len();
+                                    self.selection.retain(|s| match s {
+                                        SymbolWidgetSelection::Text { textnum } => *textnum < text_count,
+                                        SymbolWidgetSelection::Pin { pinnum } => *pinnum < symbol.pins.len(),
+                                    });
+                                    let holder = crate::symbol::SymbolDefinitionHolder::new(
+                                        symbol,
+                                        l.clone(),
+                                        self.selected_style.clone(),
+                                    );
+                                    let delete_key = !ui.ctx().wants_keyboard_input()
+                                        && ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, egui::Key::Delete));
                                     match self.selection.len() {
                                         0 => {}
                                         1 => {
                                             let sel = &self.selection[0];
-                                            let symbol = &library.syms[sym];
+                                            let style = self.selected_style.clone();
                                             match sel {
                                                 SymbolWidgetSelection::Text { textnum } => {
-                                                    let t = &symbol.texts[*textnum];
+                                                    let t = &symbol.style_texts(style.as_deref())[*textnum];
                                                     ui.label("Text Properties");
                                                     let mut text = t.text.clone();
                                                     ui.horizontal(|ui| {
@@ -442,42 +825,48 @@ impl TrackedWindow for Library {
                                                         actionlog.push(LibraryAction::EditText {
                                                             libname: l.clone(),
                                                             symname: sym.clone(),
+                                                            style: style.clone(),
                                                             textnum: *textnum,
                                                             old: t.text.clone(),
                                                             new: text,
                                                         });
                                                     }
-                                                    let units = t.location.get_units(c.units);
-                                                    let mut xstr = format!("{:.4}", units.0);
                                                     ui.horizontal(|ui| {
                                                         ui.label("X ");
-                                                        ui.add(egui::TextEdit::singleline(&mut xstr));
-                                                    });
-                                                    if let Ok(x) = xstr.parse::<f32>() {
-                                                        if t.location.changed_x(x) {
+                                                        if let Some(x) = crate::general::length_edit(ui, "x", t.location.x, c.settings.units) {
                                                             actionlog.push(LibraryAction::MoveText {
                                                                 libname: l.clone(),
                                                                 symname: sym.clone(),
+                                                                style: style.clone(),
                                                                 textnum: *textnum,
-                                                                delta: crate::general::Coordinates::from_pos2(egui::pos2(x - units.0, 0.0), 1.0),
+                                                                delta: t.location.delta_to_x(x),
                                                             });
                                                         }
-                                                    }
-                                                    let mut ystr = format!("{:.4}", units.1);
+                                                    });
                                                     ui.horizontal(|ui| {
                                                         ui.label("Y ");
-                                                        ui.add(egui::TextEdit::singleline(&mut ystr));
-                                                    });
-                                                    if let Ok(y) = ystr.parse::<f32>() {
-                                                        if t.location.changed_y(y) {
+                                                        if let Some(y) = crate::general::length_edit(ui, "y", t.location.y, c.settings.units) {
                                                             actionlog.push(LibraryAction::MoveText {
                                                                 libname: l.clone(),
                                                                 symname: sym.clone(),
+                                                                style: style.clone(),
                                                                 textnum: *textnum,
-                                                                delta: crate::general::Coordinates::from_pos2(egui::pos2(0.0, units.1 - y), 1.0),
+                                                                delta: t.location.delta_to_y(y),
                                                             });
                                                         }
-                                                    }
+                                                    });
+                                                    ui.horizontal(|ui| {
+                                                        ui.label("Size ");
+                                                        if let Some(size) = crate::general::length_edit(ui, "size", t.size.0, c.settings.units).filter(|s| *s > 0) {
+                                                            actionlog.push(LibraryAction::SetTextSize {
+                                                                libname: l.clone(),
+                                                                symname: sym.clone(),
+                                                                style: style.clone(),
+                                                                textnum: *textnum,
+                                                                size: crate::general::Length(size),
+                                                            });
+                                                        }
+                                                    });
                                                     let mut color = t.color.get_color32(crate::general::ColorMode::ScreenModeDark);
                                                     if ui.color_edit_button_srgba(&mut color).changed()
                                                     {
@@ -485,6 +874,7 @@ impl TrackedWindow for Library {
                                                             LibraryAction::ChangeTextColor {
                                                                 libname: l.clone(),
                                                                 symname: sym.clone(),
+                                                                style: style.clone(),
                                                                 textnum: *textnum,
                                                                 old: t.color,
                                                                 new: crate::schematic::Colors::Custom(color.to_srgba_unmultiplied()),
@@ -495,15 +885,68 @@ impl TrackedWindow for Library {
                                                 SymbolWidgetSelection::Pin { pinnum } => {
                                                     if symbol.pins.len() >= (pinnum + 1) {
                                                         let p = &symbol.pins[*pinnum];
-                                                        ui.label("Pin has properties");
+                                                        ui.label("Pin Properties");
+                                                        let mut number = p.number.clone();
+                                                        ui.horizontal(|ui| {
+                                                            ui.label("Number ");
+                                                            ui.add(egui::TextEdit::singleline(&mut number));
+                                                        });
+                                                        if number != p.number {
+                                                            actionlog.push(LibraryAction::SetPinNumber {
+                                                                libname: l.clone(),
+                                                                symname: sym.clone(),
+                                                                pinnum: *pinnum,
+                                                                number,
+                                                            });
+                                                        }
+                                                        ui.horizontal(|ui| {
+                                                            ui.label("X ");
+                                                            if let Some(x) = crate::general::length_edit(ui, "pin x", p.location.x, c.settings.units) {
+                                                                actionlog.push(LibraryAction::MovePin {
+                                                                    libname: l.clone(),
+                                                                    symname: sym.clone(),
+                                                                    pinnum: *pinnum,
+                                                                    delta: p.location.delta_to_x(x),
+                                                                });
+                                                            }
+                                                        });
+                                                        ui.horizontal(|ui| {
+                                                            ui.label("Y ");
+                                                            if let Some(y) = crate::general::length_edit(ui, "pin y", p.location.y, c.settings.units) {
+                                                                actionlog.push(LibraryAction::MovePin {
+                                                                    libname: l.clone(),
+                                                                    symname: sym.clone(),
+                                                                    pinnum: *pinnum,
+                                                                    delta: p.location.delta_to_y(y),
+                                                                });
+                                                            }
+                                                        });
                                                     }
                                                 }
                                             }
                                         }
                                         _ => {
-                                            ui.label("There are multiple selections");
+                                            ui.label(format!("{} objects are selected", self.selection.len()));
+                                            ui.horizontal_wrapped(|ui| {
+                                                for alignment in crate::general::Alignment::ALL {
+                                                    if ui.button(alignment.to_string()).clicked() {
+                                                        actionlog.extend(holder.align_action(&self.selection, alignment));
+                                                    }
+                                                }
+                                            });
+                                            if ui.button("Rotate").clicked() {
+                                                actionlog.extend(holder.rotate_action(&self.selection));
+                                            }
+                                            if ui.button("Delete").on_hover_text("Delete").clicked() {
+                                                actionlog.extend(holder.delete_action(&self.selection));
+                                                self.selection.clear();
+                                            }
                                         }
                                     }
+                                    if delete_key {
+                                        actionlog.extend(holder.delete_action(&self.selection));
+                                        self.selection.clear();
+                                    }
                                     ui.label("Right");
                                 });
                         },
@@ -518,12 +961,14 @@ impl TrackedWindow for Library {
         }
 
         let mut component_modify = None;
+        let mut modify_unit: Option<String> = None;
+        let mut new_symbol = None;
 
         let mut actions = Vec::new();
         let mut component_changed = false;
         egui::CentralPanel::default().show(&egui.egui_ctx, |ui| {
             self.selected_library.as_ref().and_then(|l| {
-                c.libraries.get_mut(l).and_then(|lh| {
+                c.libraries.get(l).and_then(|lh| {
                     self.selected_thing.as_ref().map(|thing| {
                         let lib = &lh.library;
                         if let Some(library) = lib {
@@ -533,6 +978,7 @@ impl TrackedWindow for Library {
                                         let mut sym = crate::symbol::SymbolDefinitionHolder::new(
                                             sym,
                                             l.clone(),
+                                            self.selected_style.clone(),
                                         );
                                         let sym = crate::symbol::SymbolDefinitionWidget::new(
                                             &mut sym,
@@ -543,7 +989,9 @@ impl TrackedWindow for Library {
                                             &mut self.zoom,
                                             self.recenter,
                                             &mut self.pin_angle,
-                                        );
+                                        )
+                                        .with_grid(c.settings.grid)
+                                        .with_cursor(&mut self.cursor);
                                         self.recenter = false;
                                         let resp = ui.add(sym);
                                         if resp.dragged_by(egui::PointerButton::Middle) {
@@ -564,17 +1012,35 @@ impl TrackedWindow for Library {
                                     }
                                 }
                                 Thing::Component(comname) => {
-                                    if let Some(com) = library.components.get(comname) {
+                                    let flat = self.flattened.get(l, comname, &c.libraries, c.library_log.changes());
+                                    if let (Some(com), Some(flat)) = (library.components.get(comname), flat) {
+                                        if let Some(p) = &com.parent {
+                                            ui.label(format!(
+                                                "Derived from {} in library {}",
+                                                p.com,
+                                                p.lib.name(&library.name)
+                                            ));
+                                            if let Some(problem) = com.parent_problem(library, &c.libraries) {
+                                                ui.colored_label(egui::Color32::RED, problem);
+                                            }
+                                        }
                                         let mut cb = egui::ComboBox::from_label("Select variant");
                                         if let Some(selvar) = &self.selected_variant {
-                                            if let Some(var) = com.variants.get(selvar) {
-                                                component_modify = Some(var.to_owned());
+                                            if let Some(var) = flat.variants.get(selvar) {
+                                                if !com.inherits(selvar) {
+                                                    component_modify = Some(var.to_owned());
+                                                }
                                                 cb = cb.selected_text(&var.name);
                                             }
                                         }
                                         cb.show_ui(ui, |ui| {
-                                            for (name, var) in com.variants.iter() {
-                                                if ui.selectable_label(false, &var.name).clicked() {
+                                            for (name, var) in flat.variants.iter() {
+                                                let label = if com.inherits(name) {
+                                                    format!("{} (inherited)", var.name)
+                                                } else {
+                                                    var.name.clone()
+                                                };
+                                                if ui.selectable_label(false, label).clicked() {
                                                     self.selected_variant = Some(name.clone());
                                                 }
                                             }
@@ -588,7 +1054,19 @@ impl TrackedWindow for Library {
                                         );
                                         }
                                         if let Some(selvar) = &self.selected_variant {
-                                            if ui.button("Delete variant").clicked() {
+                                            if com.inherits(selvar) {
+                                                if let Some(var) = flat.variants.get(selvar) {
+                                                    if ui.button("Override variant").clicked() {
+                                                        actions.push(
+                                                            LibraryAction::OverrideComponentVariant {
+                                                                libname: l.clone(),
+                                                                comname: comname.clone(),
+                                                                variant: var.clone(),
+                                                            },
+                                                        );
+                                                    }
+                                                }
+                                            } else if ui.button("Delete variant").clicked() {
                                                 actions.push(
                                                     LibraryAction::DeleteComponentVariant {
                                                         libname: l.clone(),
@@ -598,8 +1076,102 @@ impl TrackedWindow for Library {
                                                     },
                                                 );
                                             }
+                                            if let Some(var) = com.variants.get(selvar) {
+                                                if var.units.iter().all(|u| Some(&u.name) != self.selected_unit.as_ref()) {
+                                                    self.selected_unit = None;
+                                                }
+                                                modify_unit = self.selected_unit.clone();
+                                                ui.label("Units");
+                                                ui.horizontal_wrapped(|ui| {
+                                                    for unit in &var.units {
+                                                        let label = if unit.power {
+                                                            format!("{} (power)", unit.name)
+                                                        } else {
+                                                            unit.name.clone()
+                                                        };
+                                                        if ui
+                                                            .selectable_label(self.selected_unit.as_ref() == Some(&unit.name), label)
+                                                            .clicked()
+                                                        {
+                                                            self.selected_unit = Some(unit.name.clone());
+                                                        }
+                                                    }
+                                                });
+                                                ui.horizontal(|ui| {
+                                                    let has_power = var.units.iter().any(|u| u.power);
+                                                    for (label, power) in [("Add unit", false), ("Add power unit", true)] {
+                                                        if (!power || !has_power) && ui.button(label).clicked() {
+                                                            actions.push(LibraryAction::CreateComponentUnit {
+                                                                libname: l.clone(),
+                                                                comname: comname.clone(),
+                                                                varname: selvar.clone(),
+                                                                unitname: var.next_unit_name(power),
+                                                                power,
+                                                            });
+                                                        }
+                                                    }
+                                                    if let Some(unit) = &self.selected_unit {
+                                                        if ui.button("Delete unit").clicked() {
+                                                            actions.push(LibraryAction::DeleteComponentUnit {
+                                                                libname: l.clone(),
+                                                                comname: comname.clone(),
+                                                                varname: selvar.clone(),
+                                                                unitname: unit.clone(),
+                                                                unit: None,
+                                                            });
+                                                        }
+                                                    }
+                                                });
+                                            }
                                             ui.separator();
                                         }
+                                        ui.label("Properties");
+                                        let mut names: Vec<&String> = flat.properties.keys().collect();
+                                        names.sort();
+                                        egui::Grid::new("properties").show(ui, |ui| {
+                                            for name in names {
+                                                let value = &flat.properties[name];
+                                                ui.label(name);
+                                                let mut newvalue = value.clone();
+                                                ui.text_edit_singleline(&mut newvalue);
+                                                if newvalue != *value {
+                                                    actions.push(LibraryAction::SetComponentProperty {
+                                                        libname: l.clone(),
+                                                        comname: comname.clone(),
+                                                        name: name.clone(),
+                                                        value: Some(newvalue),
+                                                    });
+                                                }
+                                                if !com.properties.contains_key(name) {
+                                                    ui.label("(inherited)");
+                                                } else if ui.button("Remove").clicked() {
+                                                    actions.push(LibraryAction::SetComponentProperty {
+                                                        libname: l.clone(),
+                                                        comname: comname.clone(),
+                                                        name: name.clone(),
+                                                        value: None,
+                                                    });
+                                                }
+                                                ui.end_row();
+                                            }
+                                        });
+                                        ui.horizontal(|ui| {
+                                            ui.add(
+                                                egui::TextEdit::singleline(&mut self.new_property)
+                                                    .hint_text("Property name"),
+                                            );
+                                            if !self.new_property.is_empty()
+                                                && !flat.properties.contains_key(&self.new_property)
+                                                && ui.button("Add property").clicked()
+                                            {
+                                                actions.push(LibraryAction::SetComponentProperty {
+                                                    libname: l.clone(),
+                                                    comname: comname.clone(),
+                                                    name: std::mem::take(&mut self.new_property),
+                                                    value: Some(String::new()),
+                                                });
+                                            }
+                                        });
                                     }
                                 }
                             }
@@ -623,8 +1195,12 @@ impl TrackedWindow for Library {
                     let olib = c.libraries.get(lib);
                     if let Some(libr) = olib {
                         if let Some(library) = &libr.library {
-                            let mut cb = egui::ComboBox::from_label("Select symbol from library");
-                            if let Some(l) = &component.symbol {
+                            let label = match &modify_unit {
+                                Some(u) => format!("Select symbol for unit {} from library", u),
+                                None => "Select symbol from library".to_string(),
+                            };
+                            let mut cb = egui::ComboBox::from_label(label);
+                            if let Some(l) = component.unit_symbol(modify_unit.as_deref()) {
                                 cb = cb.selected_text(l.sym.clone());
                             }
                             cb.show_ui(ui, |ui| {
@@ -635,7 +1211,7 @@ impl TrackedWindow for Library {
                                         } else {
                                             LibraryReference::Another(lib.clone())
                                         };
-                                        component.symbol = Some(crate::symbol::SymbolReference {
+                                        new_symbol = Some(crate::symbol::SymbolReference {
                                             lib: lr,
                                             sym: l.clone(),
                                         });
@@ -648,18 +1224,17 @@ impl TrackedWindow for Library {
                 }
             }
         });
-        if component_changed {
-            if let Some(component) = &mut component_modify {
-                if let Some(lib) = &self.selected_library {
-                    if let Some(Thing::Component(comm)) = &self.selected_thing {
-                        if let Some(var) = &self.selected_variant {
-                            actions.push(LibraryAction::ChangeComponentVariantSymbol {
-                                libname: lib.clone(),
-                                comname: comm.clone(),
-                                varname: var.clone(),
-                                sref: component.symbol.clone(),
-                            });
-                        }
+        if component_changed && component_modify.is_some() {
+            if let Some(lib) = &self.selected_library {
+                if let Some(Thing::Component(comm)) = &self.selected_thing {
+                    if let Some(var) = &self.selected_variant {
+                        actions.push(LibraryAction::ChangeComponentVariantSymbol {
+                            libname: lib.clone(),
+                            comname: comm.clone(),
+                            varname: var.clone(),
+                            unit: modify_unit,
+                            sref: new_symbol,
+                        });
                     }
                 }
             }
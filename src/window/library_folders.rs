@@ -0,0 +1,156 @@
+//! This window edits the list of folders that are searched for libraries
+
+use crate::egui_multiwin_dynamic::{
+    multi_window::NewWindowRequest,
+    tracked_window::{RedrawResponse, TrackedWindow},
+};
+use egui_multiwin::egui;
+use egui_multiwin::egui_glow::EguiGlow;
+
+use crate::settings::LibraryFolder;
+use crate::MyApp;
+
+/// Defines messages that can some from other threads
+enum Message {
+    /// A folder was selected to add to the list
+    AddFolder(std::path::PathBuf),
+}
+
+/// The window structure
+pub struct LibraryFolders {
+    /// The folders being edited
+    folders: Option<Vec<LibraryFolder>>,
+    /// The message channel for communicating with the main thread, when needed.
+    message_channel: (
+        std::sync::mpsc::Sender<Message>,
+        std::sync::mpsc::Receiver<Message>,
+    ),
+}
+
+impl LibraryFolders {
+    /// Create a new window
+    pub fn request() -> NewWindowRequest {
+        NewWindowRequest::new(
+            super::Windows::LibraryFolders(Self {
+                folders: None,
+                message_channel: std::sync::mpsc::channel(),
+            }),
+            egui_multiwin::winit::window::WindowBuilder::new()
+                .with_resizable(true)
+                .with_inner_size(egui_multiwin::winit::dpi::LogicalSize {
+                    width: 640.0,
+                    height: 320.0,
+                })
+                .with_title("Library Folders"),
+            egui_multiwin::tracked_window::TrackedWindowOptions {
+                vsync: false,
+                shader: None,
+            },
+            egui_multiwin::multi_window::new_id(),
+        )
+    }
+}
+
+impl TrackedWindow for LibraryFolders {
+    fn is_root(&self) -> bool {
+        false
+    }
+
+    fn set_root(&mut self, _root: bool) {}
+
+    fn redraw(
+        &mut self,
+        c: &mut MyApp,
+        egui: &mut EguiGlow,
+        _window: &egui_multiwin::winit::window::Window,
+        _clipboard: &mut egui_multiwin::arboard::Clipboard,
+    ) -> RedrawResponse {
+        let mut quit = false;
+
+        let windows_to_create = vec![];
+
+        let folders = self
+            .folders
+            .get_or_insert_with(|| c.settings.library_folders.clone());
+
+        while let Ok(message) = self.message_channel.1.try_recv() {
+            match message {
+                Message::AddFolder(path) => {
+                    if !folders.iter().any(|f| f.path == path) {
+                        folders.push(LibraryFolder {
+                            path,
+                            priority: crate::settings::USER_LIBRARY_PRIORITY,
+                        });
+                    }
+                }
+            }
+        }
+
+        egui::TopBottomPanel::bottom("buttons").show(&egui.egui_ctx, |ui| {
+            ui.horizontal(|ui| {
+                if ui.button("Add folder").clicked() {
+                    let f = rfd::AsyncFileDialog::new()
+                        .set_title("Library folder")
+                        .pick_folder();
+                    let message_sender = self.message_channel.0.clone();
+                    crate::execute(async move {
+                        if let Some(folder) = f.await {
+                            message_sender
+                                .send(Message::AddFolder(folder.path().to_path_buf()))
+                                .ok();
+                        }
+                    });
+                }
+                if ui.button("Save").clicked() {
+                    c.settings.library_folders = folders.clone();
+                    if let Err(e) = c.settings.save(&c.dirs) {
+                        let _e = native_dialog::MessageDialog::new()
+                            .set_type(native_dialog::MessageType::Error)
+                            .set_title("Failed to save settings")
+                            .set_text(e.to_string().as_str())
+                            .show_alert();
+                    } else {
+                        c.load_libraries();
+                        quit = true;
+                    }
+                }
+                if ui.button("Cancel").clicked() {
+                    quit = true;
+                }
+            });
+        });
+
+        egui::CentralPanel::default().show(&egui.egui_ctx, |ui| {
+            ui.label("Folders with a higher priority are searched first. The library folder of the open schematic is always searched first, and the user library folder has priority 0.");
+            ui.separator();
+            let mut remove = None;
+            egui::Grid::new("folders").striped(true).show(ui, |ui| {
+                ui.label("Folder");
+                ui.label("Priority");
+                ui.end_row();
+                for (i, f) in folders.iter_mut().enumerate() {
+                    ui.label(f.path.display().to_string());
+                    ui.add(egui::DragValue::new(&mut f.priority));
+                    if ui.button("Remove").clicked() {
+                        remove = Some(i);
+                    }
+                    ui.end_row();
+                }
+            });
+            if let Some(i) = remove {
+                folders.remove(i);
+            }
+            if !c.library_conflicts.is_empty() {
+                ui.separator();
+                for conflict in &c.library_conflicts {
+                    ui.colored_label(egui::Color32::YELLOW, conflict.to_string());
+                }
+            }
+        });
+
+        RedrawResponse {
+            quit,
+            new_windows: windows_to_create,
+        }
+    }
+}
@@ -115,7 +115,7 @@ impl TrackedWindow for LibraryName {
                             }
                         }
                     });
-                match &self.selected_path {
+                match &mut self.selected_path {
                     crate::general::StoragePath::LocalFilesystem(_p) => {
                         if ui.button("Select save path").clicked() {
                             let f = rfd::AsyncFileDialog::new()
@@ -142,6 +142,31 @@ impl TrackedWindow for LibraryName {
                             });
                         }
                     }
+                    crate::general::StoragePath::Remote(url) => {
+                        ui.horizontal(|ui| {
+                            ui.label("Server ");
+                            ui.add(
+                                egui::TextEdit::singleline(url).hint_text("http://127.0.0.1:8080"),
+                            );
+                        });
+                        if ui
+                            .add_enabled(
+                                !url.is_empty() && !self.name.is_empty(),
+                                egui::Button::new("Create"),
+                            )
+                            .clicked()
+                        {
+                            let client = crate::remote::Client::new(url);
+                            self.message_channel
+                                .0
+                                .send(Message::CreateNewLibrary(
+                                    crate::general::StoragePath::Remote(
+                                        client.library_url(&self.name),
+                                    ),
+                                ))
+                                .ok();
+                        }
+                    }
                 }
             }
         });
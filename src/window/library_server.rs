@@ -0,0 +1,279 @@
+//! This window connects to a library server, to browse and load the libraries on it
+
+use crate::egui_multiwin_dynamic::{
+    multi_window::NewWindowRequest,
+    tracked_window::{RedrawResponse, TrackedWindow},
+};
+use egui_multiwin::egui;
+use egui_multiwin::egui_glow::EguiGlow;
+
+use crate::component::ComponentDefinition;
+use crate::remote::{Client, LibraryListing, RemoteError};
+use crate::symbol::{LibraryReference, SymbolDefinition};
+use crate::MyApp;
+
+/// Defines messages that can some from other threads
+enum Message {
+    /// The list of libraries on the server has arrived
+    Listing(Result<Vec<LibraryListing>, RemoteError>),
+    /// A symbol for previewing has arrived
+    Symbol(String, Result<SymbolDefinition, RemoteError>),
+    /// A component for previewing has arrived
+    Component(String, Result<ComponentDefinition, RemoteError>),
+}
+
+/// The item on the server being previewed
+enum Preview {
+    /// A symbol, with the name of the symbol
+    Symbol(String, SymbolDefinition),
+    /// A component, with the name of the component
+    Component(String, ComponentDefinition),
+}
+
+/// The window structure
+pub struct LibraryServer {
+    /// The url of the server
+    url: String,
+    /// The libraries listed by the server
+    listing: Vec<LibraryListing>,
+    /// The library selected for browsing
+    selected_library: Option<String>,
+    /// The item being previewed
+    preview: Option<Preview>,
+    /// The most recent error
+    error: Option<String>,
+    /// The message channel for communicating with the main thread, when needed.
+    message_channel: (
+        std::sync::mpsc::Sender<Message>,
+        std::sync::mpsc::Receiver<Message>,
+    ),
+}
+
+impl LibraryServer {
+    /// Create a new window
+    pub fn request() -> NewWindowRequest {
+        NewWindowRequest::new(
+            super::Windows::LibraryServer(Self {
+                url: "http://127.0.0.1:8080".to_string(),
+                listing: Vec::new(),
+                selected_library: None,
+                preview: None,
+                error: None,
+                message_channel: std::sync::mpsc::channel(),
+            }),
+            egui_multiwin::winit::window::WindowBuilder::new()
+                .with_resizable(true)
+                .with_inner_size(egui_multiwin::winit::dpi::LogicalSize {
+                    width: 640.0,
+                    height: 480.0,
+                })
+                .with_title("Library Server"),
+            egui_multiwin::tracked_window::TrackedWindowOptions {
+                vsync: false,
+                shader: None,
+            },
+            egui_multiwin::multi_window::new_id(),
+        )
+    }
+}
+
+impl TrackedWindow for LibraryServer {
+    fn is_root(&self) -> bool {
+        false
+    }
+
+    fn set_root(&mut self, _root: bool) {}
+
+    fn redraw(
+        &mut self,
+        c: &mut MyApp,
+        egui: &mut EguiGlow,
+        _window: &egui_multiwin::winit::window::Window,
+        _clipboard: &mut egui_multiwin::arboard::Clipboard,
+    ) -> RedrawResponse {
+        let quit = false;
+
+        let windows_to_create = vec![];
+
+        while let Ok(message) = self.message_channel.1.try_recv() {
+            match message {
+                Message::Listing(Ok(l)) => {
+                    self.listing = l;
+                    self.error = None;
+                }
+                Message::Symbol(name, Ok(s)) => {
+                    self.preview = Some(Preview::Symbol(name, s));
+                    self.error = None;
+                }
+                Message::Component(name, Ok(com)) => {
+                    self.preview = Some(Preview::Component(name, com));
+                    self.error = None;
+                }
+                Message::Listing(Err(e))
+                | Message::Symbol(_, Err(e))
+                | Message::Component(_, Err(e)) => {
+                    self.error = Some(e.to_string());
+                }
+            }
+        }
+
+        let client = Client::new(&self.url);
+
+        egui::TopBottomPanel::top("server").show(&egui.egui_ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("Server ");
+                ui.add(egui::TextEdit::singleline(&mut self.url));
+                if ui.button("Connect").clicked() {
+                    self.listing.clear();
+                    self.selected_library = None;
+                    self.preview = None;
+                    let client = client.clone();
+                    let message_sender = self.message_channel.0.clone();
+                    crate::execute(async move {
+                        message_sender.send(Message::Listing(client.list())).ok();
+                    });
+                }
+            });
+            if let Some(e) = &self.error {
+                ui.colored_label(egui::Color32::RED, e);
+            }
+        });
+
+        egui::SidePanel::left("libraries")
+            .resizable(true)
+            .show(&egui.egui_ctx, |ui| {
+                egui::ScrollArea::vertical()
+                    .auto_shrink([false, false])
+                    .show(ui, |ui| {
+                        for l in &self.listing {
+                            ui.horizontal(|ui| {
+                                if ui
+                                    .selectable_label(
+                                        self.selected_library == Some(l.name.clone()),
+                                        format!("{} {}", l.name, l.version),
+                                    )
+                                    .clicked()
+                                {
+                                    self.selected_library = Some(l.name.clone());
+                                    self.preview = None;
+                                }
+                                if ui
+                                    .add_enabled(
+                                        !c.libraries.contains_key(&l.name),
+                                        egui::Button::new("Load"),
+                                    )
+                                    .clicked()
+                                {
+                                    let path = crate::general::StoragePath::Remote(
+                                        client.library_url(&l.name),
+                                    );
+                                    match crate::library::LibraryHolder::load(path) {
+                                        Ok(lib) => {
+                                            c.libraries.insert(l.name.clone(), lib);
+                                            c.library_log.changed();
+                                        }
+                                        Err(e) => {
+                                            self.error = Some(e.to_string());
+                                        }
+                                    }
+                                }
+                            });
+                            if self.selected_library == Some(l.name.clone()) {
+                                ui.indent(&l.name, |ui| {
+                                    ui.label("Symbols");
+                                    for s in &l.symbols {
+                                        if ui.selectable_label(false, s).clicked() {
+                                            let client = client.clone();
+                                            let (lib, s) = (l.name.clone(), s.clone());
+                                            let message_sender = self.message_channel.0.clone();
+                                            crate::execute(async move {
+                                                let sym = client.fetch_symbol(&lib, &s);
+                                                message_sender.send(Message::Symbol(s, sym)).ok();
+                                            });
+                                        }
+                                    }
+                                    ui.label("Components");
+                                    for com in &l.components {
+                                        if ui.selectable_label(false, com).clicked() {
+                                            let client = client.clone();
+                                            let (lib, com) = (l.name.clone(), com.clone());
+                                            let message_sender = self.message_channel.0.clone();
+                                            crate::execute(async move {
+                                                let component = client.fetch_component(&lib, &com);
+                                                message_sender
+                                                    .send(Message::Component(com, component))
+                                                    .ok();
+                                            });
+                                        }
+                                    }
+                                });
+                            }
+                        }
+                    });
+            });
+
+        egui::CentralPanel::default().show(&egui.egui_ctx, |ui| match &self.preview {
+            Some(Preview::Symbol(name, sym)) => {
+                ui.label(format!("Symbol {}", name));
+                if !sym.styles.is_empty() {
+                    let styles: Vec<&str> = sym.styles.iter().map(|s| s.name.as_str()).collect();
+                    ui.label(format!("Body styles: {}", styles.join(", ")));
+                }
+                let size = ui.available_size();
+                let (resp, pntr) = ui.allocate_painter(size, egui::Sense::hover());
+                let area = resp.rect;
+                sym.draw(
+                    115.0,
+                    area.center(),
+                    &pntr,
+                    egui::pos2(0.0, 0.0),
+                    area,
+                    crate::symbol::SymbolPlacement::default(),
+                );
+            }
+            Some(Preview::Component(name, com)) => {
+                ui.label(format!("Component {}", name));
+                if let Some(p) = &com.parent {
+                    let lib = match &p.lib {
+                        LibraryReference::ThisOne => "this library".to_string(),
+                        LibraryReference::Another(l) => l.clone(),
+                    };
+                    ui.label(format!("Derived from {} from {}", p.com, lib));
+                }
+                ui.separator();
+                for (varname, var) in &com.variants {
+                    let mut parts = vec![(varname.clone(), &var.symbol)];
+                    if !var.units.is_empty() {
+                        parts = var
+                            .units
+                            .iter()
+                            .map(|u| (format!("{} unit {}", varname, u.name), &u.symbol))
+                            .collect();
+                    }
+                    for (name, symbol) in parts {
+                        match symbol {
+                            Some(sref) => {
+                                let lib = match &sref.lib {
+                                    LibraryReference::ThisOne => "this library".to_string(),
+                                    LibraryReference::Another(l) => l.clone(),
+                                };
+                                ui.label(format!("{}: symbol {} from {}", name, sref.sym, lib));
+                            }
+                            None => {
+                                ui.label(format!("{}: no symbol", name));
+                            }
+                        }
+                    }
+                }
+            }
+            None => {
+                ui.label("Select a symbol or component to preview it");
+            }
+        });
+
+        RedrawResponse {
+            quit,
+            new_windows: windows_to_create,
+        }
+    }
+}
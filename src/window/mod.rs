@@ -1,9 +1,15 @@
 //! Defines the various window types used by the program
 
+pub mod cache_update;
 pub mod component_name;
 pub mod component_variant_name;
+pub mod document_properties;
+pub mod item_name;
 pub mod library;
+pub mod library_folders;
 pub mod library_name;
+pub mod library_server;
+pub mod page_setup;
 pub mod schematic;
 pub mod symbol_name;
 
@@ -16,14 +22,26 @@ use crate::ipc;
 /// The windows for the program
 #[enum_dispatch(TrackedWindow)]
 pub enum Windows {
+    /// The window for updating the library items stored in a schematic
+    CacheUpdate(cache_update::CacheUpdate),
     /// The component name window
     ComponentName(component_name::Name),
     /// The component variant name window
     ComponentVariantName(component_variant_name::Name),
+    /// The window for editing the text variables of the schematic
+    DocumentProperties(document_properties::DocumentProperties),
+    /// The library folders window
+    LibraryFolders(library_folders::LibraryFolders),
+    /// The window for renaming, copying or deriving a symbol or component
+    ItemName(item_name::ItemName),
     /// Library name window
     LibraryName(library_name::LibraryName),
     /// The library window
     Library(library::Library),
+    /// The library server window
+    LibraryServer(library_server::LibraryServer),
+    /// The window for setting the paper size, border and title block of a page
+    PageSetup(page_setup::PageSetup),
     /// The schematic window
     Schematic(schematic::SchematicWindow),
     /// The symbol name window
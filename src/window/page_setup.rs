@@ -0,0 +1,185 @@
+//! This window sets the paper size, border and title block of a page of the schematic
+
+use crate::egui_multiwin_dynamic::{
+    multi_window::NewWindowRequest,
+    tracked_window::{RedrawResponse, TrackedWindow},
+};
+use egui_multiwin::egui;
+use egui_multiwin::egui_glow::EguiGlow;
+
+use crate::schematic::SchematicAction;
+use crate::sheet::{BorderStyle, PageOrientation, PaperSize, TitleBlockReference};
+use crate::MyApp;
+
+/// The window structure
+pub struct PageSetup {
+    /// The index of the page being set up
+    page: usize,
+    /// The page changes count of the schematic when the window was opened. The window closes once pages are added,
+    /// removed or moved, because the page index may then point at another page.
+    page_changes: u32,
+}
+
+impl PageSetup {
+    /// Create a new window for a page, where page_changes is the current page changes count of the schematic
+    pub fn request(page: usize, page_changes: u32) -> NewWindowRequest {
+        NewWindowRequest::new(
+            super::Windows::PageSetup(Self { page, page_changes }),
+            egui_multiwin::winit::window::WindowBuilder::new()
+                .with_resizable(true)
+                .with_inner_size(egui_multiwin::winit::dpi::LogicalSize {
+                    width: 360.0,
+                    height: 280.0,
+                })
+                .with_title(format!("Page setup, page {}", page + 1)),
+            egui_multiwin::tracked_window::TrackedWindowOptions {
+                vsync: false,
+                shader: None,
+            },
+            egui_multiwin::multi_window::new_id(),
+        )
+    }
+}
+
+impl TrackedWindow for PageSetup {
+    fn is_root(&self) -> bool {
+        false
+    }
+
+    fn set_root(&mut self, _root: bool) {}
+
+    fn redraw(
+        &mut self,
+        c: &mut MyApp,
+        egui: &mut EguiGlow,
+        _window: &egui_multiwin::winit::window::Window,
+        _clipboard: &mut egui_multiwin::arboard::Clipboard,
+    ) -> RedrawResponse {
+        let mut quit = false;
+
+        let windows_to_create = vec![];
+
+        let sch = if let Some(sch) = c.schematic.as_mut().filter(|s| {
+            self.page < s.schematic.pages.len() && s.schematic.page_changes == self.page_changes
+        }) {
+            sch
+        } else {
+            return RedrawResponse {
+                quit: true,
+                new_windows: windows_to_create,
+            };
+        };
+
+        let mut actionlog = Vec::new();
+
+        egui::CentralPanel::default().show(&egui.egui_ctx, |ui| {
+            let page = &sch.schematic.pages[self.page];
+            let mut format = page.format.clone();
+            let (mut width, mut height) = (page.size.x, page.size.y);
+            let range = 10 * crate::general::NM_PER_MM..=5000 * crate::general::NM_PER_MM;
+            egui::Grid::new("page setup").show(ui, |ui| {
+                ui.label("Paper size");
+                egui::ComboBox::from_id_source("paper size")
+                    .selected_text(format.paper.to_string())
+                    .show_ui(ui, |ui| {
+                        for p in PaperSize::ALL {
+                            ui.selectable_value(&mut format.paper, p, p.to_string());
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Orientation");
+                ui.add_enabled_ui(format.paper != PaperSize::Custom, |ui| {
+                    ui.horizontal(|ui| {
+                        ui.radio_value(
+                            &mut format.orientation,
+                            PageOrientation::Portrait,
+                            "Portrait",
+                        );
+                        ui.radio_value(
+                            &mut format.orientation,
+                            PageOrientation::Landscape,
+                            "Landscape",
+                        );
+                    });
+                });
+                ui.end_row();
+
+                if format.paper == PaperSize::Custom {
+                    ui.label("Width");
+                    if let Some(w) =
+                        crate::general::length_edit(ui, "width", width, c.settings.units)
+                    {
+                        width = w.clamp(*range.start(), *range.end());
+                    }
+                    ui.end_row();
+                    ui.label("Height");
+                    if let Some(h) =
+                        crate::general::length_edit(ui, "height", height, c.settings.units)
+                    {
+                        height = h.clamp(*range.start(), *range.end());
+                    }
+                    ui.end_row();
+                }
+
+                ui.label("Border");
+                egui::ComboBox::from_id_source("border")
+                    .selected_text(format.border.to_string())
+                    .show_ui(ui, |ui| {
+                        for b in [BorderStyle::None, BorderStyle::Plain, BorderStyle::Zones] {
+                            ui.selectable_value(&mut format.border, b, b.to_string());
+                        }
+                    });
+                ui.end_row();
+
+                ui.label("Title block");
+                let mut blocks: Vec<TitleBlockReference> = c
+                    .libraries
+                    .iter()
+                    .filter_map(|(name, l)| l.library.as_ref().map(|l| (name, l)))
+                    .flat_map(|(lib, l)| {
+                        l.title_blocks.keys().map(move |name| TitleBlockReference {
+                            lib: lib.clone(),
+                            name: name.clone(),
+                        })
+                    })
+                    .collect();
+                blocks.sort_by(|a, b| (&a.lib, &a.name).cmp(&(&b.lib, &b.name)));
+                let describe = |t: &Option<TitleBlockReference>| match t {
+                    Some(t) => format!("{} from {}", t.name, t.lib),
+                    None => "None".to_string(),
+                };
+                egui::ComboBox::from_id_source("title block")
+                    .selected_text(describe(&format.title_block))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut format.title_block, None, "None");
+                        for b in blocks {
+                            let label = describe(&Some(b.clone()));
+                            ui.selectable_value(&mut format.title_block, Some(b), label);
+                        }
+                    });
+                ui.end_row();
+            });
+            let size = format.size(crate::general::Coordinates::from_nm(width, height));
+            if format != page.format || size != page.size {
+                actionlog.push(SchematicAction::SetPageFormat {
+                    pagenum: self.page,
+                    format,
+                    size,
+                });
+            }
+            if ui.button("Close").clicked() {
+                quit = true;
+            }
+        });
+
+        for a in actionlog {
+            sch.schematic_log.apply(&mut sch.schematic, a);
+        }
+
+        RedrawResponse {
+            quit,
+            new_windows: windows_to_create,
+        }
+    }
+}
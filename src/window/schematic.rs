@@ -7,8 +7,15 @@ use crate::egui_multiwin_dynamic::{
     tracked_window::{RedrawResponse, TrackedWindow},
 };
 
-use crate::component::ComponentVariantReference;
-use crate::schematic::{MouseMode, Schematic, SchematicAction, SchematicHolder, SchematicWidget};
+use crate::bus::BusPart;
+use crate::component::{
+    ComponentVariantReference, DESIGNATOR_FIELD, PART_NUMBER_PROPERTY, VALUE_PROPERTY,
+};
+use crate::fragment::{SchematicFragment, PASTE_OFFSET};
+use crate::general::Orientation;
+use crate::schematic::{
+    MouseMode, Schematic, SchematicAction, SchematicHolder, SchematicSelection, SchematicWidget,
+};
 use crate::MyApp;
 
 /// Defines messages that can some from other threads
@@ -19,14 +26,16 @@ enum Message {
     LoadSchematicName(crate::general::StoragePath, crate::general::StorageFormat),
     /// Create a pdf of the current schematic
     CreatePdf(crate::general::StoragePath),
+    /// Write the netlist of the current schematic
+    ExportNetlist(crate::general::StoragePath),
 }
 
 /// The window structure
 pub struct SchematicWindow {
     /// Set when the title needs to change.
     new_title: Option<String>,
-    /// The object currently selected, will eventually change to Vec<SchematicSelection>
-    selection: Option<crate::schematic::SchematicSelection>,
+    /// The objects currently selected
+    selection: Vec<SchematicSelection>,
     /// The message channel for communicating with the main thread, when needed.
     message_channel: (
         std::sync::mpsc::Sender<Message>,
@@ -42,24 +51,148 @@ pub struct SchematicWindow {
     selected_library: Option<String>,
     /// The component selected for adding to schematic
     selected_component: Option<String>,
+    /// The selected component with everything it inherits included
+    flattened: crate::component::FlattenedCache,
     /// The variant of the component selected for addition to schematic
     selected_variant: Option<String>,
+    /// The unit of the variant selected for addition to schematic, for components with more than one unit
+    selected_unit: Option<String>,
+    /// How the component being added to the schematic is turned
+    placing_orientation: Orientation,
+    /// The name of a property to add to the selected component
+    new_property: String,
+    /// The index of the page being shown
+    page: usize,
+    /// The number of times the clipboard has been pasted since it was last copied to, which sets how far the paste is moved
+    paste_count: u32,
+    /// Where the pointer is on the page, for the status bar
+    cursor: Option<crate::general::Coordinates>,
+}
+
+/// The commands for cutting, copying, pasting and deleting the selection
+#[derive(Clone, Copy, PartialEq)]
+enum EditCommand {
+    /// Copy the selection to the clipboard and delete it
+    Cut,
+    /// Copy the selection to the clipboard
+    Copy,
+    /// Paste the clipboard onto the current page
+    Paste,
+    /// Delete the selection
+    Delete,
+    /// Select everything on the current page
+    SelectAll,
+}
+
+/// The size in points of the box that a page thumbnail is fitted into, in the page navigator
+const THUMBNAIL_SIZE: f32 = 96.0;
+
+/// Draw a small picture of a page, with its border and title block, and a mark for each placed component and text
+fn page_thumbnail(
+    ui: &mut egui::Ui,
+    page: &crate::schematic::Page,
+    sheet: &crate::sheet::SheetDrawing,
+    selected: bool,
+) -> egui::Response {
+    let (w, h) = page.size.get_mm();
+    let scale = THUMBNAIL_SIZE / w.max(h).max(1.0);
+    let (response, pntr) =
+        ui.allocate_painter(egui::vec2(w * scale, h * scale), egui::Sense::click());
+    let rect = response.rect;
+    let to_screen = |(x, y): (f32, f32)| egui::pos2(rect.min.x + x * scale, rect.max.y - y * scale);
+    pntr.rect_filled(rect, 0.0, ui.visuals().extreme_bg_color);
+    let detail = egui::Stroke::new(0.5, ui.visuals().weak_text_color());
+    for (a, b) in &sheet.lines {
+        pntr.line_segment([to_screen(*a), to_screen(*b)], detail);
+    }
+    for t in &page.texts {
+        pntr.circle_filled(
+            to_screen(t.location.get_mm()),
+            1.0,
+            ui.visuals().weak_text_color(),
+        );
+    }
+    for sym in &page.syms {
+        pntr.circle_filled(to_screen(sym.pos.get_mm()), 1.5, ui.visuals().text_color());
+    }
+    let outline = if selected {
+        ui.visuals().selection.stroke
+    } else {
+        detail
+    };
+    pntr.rect_stroke(rect, 0.0, outline);
+    response
+}
+
+/// Show a checkbox for showing a property of a placed component as a field, returning the action when it is changed
+fn field_checkbox(
+    ui: &mut egui::Ui,
+    var_ref: &ComponentVariantReference,
+    page: usize,
+    sym: usize,
+    name: &str,
+) -> Option<SchematicAction> {
+    let shown = var_ref.field(name).is_some();
+    let mut show = shown;
+    ui.checkbox(&mut show, "Show");
+    if show == shown {
+        return None;
+    }
+    Some(SchematicAction::SetField {
+        pagenum: page,
+        symnum: sym,
+        name: name.to_string(),
+        location: if show {
+            Some(var_ref.next_field_location())
+        } else {
+            None
+        },
+    })
 }
 
 impl SchematicWindow {
+    /// Make sure the page shown and the selection still exist, after changes such as undo that can remove them
+    fn keep_valid(&mut self, sch: &Schematic) {
+        self.page = self.page.min(sch.pages.len().saturating_sub(1));
+        self.selection.retain(|s| s.is_valid(sch));
+    }
+
+    /// Keep showing the same page and selecting the same objects when an action that moves pages is applied or redone
+    fn follow_apply(&mut self, a: &SchematicAction) {
+        if let Some(p) = a.page_after(self.page) {
+            self.page = p;
+        }
+        self.selection = self.selection.iter().filter_map(|s| s.after(a)).collect();
+    }
+
+    /// Keep showing the same page and selecting the same objects when an action that moves pages is undone
+    fn follow_undo(&mut self, a: &SchematicAction) {
+        if let Some(p) = a.page_before(self.page) {
+            self.page = p;
+        }
+        self.selection = self.selection.iter().filter_map(|s| s.before(a)).collect();
+    }
+
     /// Create a new window
     pub fn request() -> NewWindowRequest {
         NewWindowRequest::new(
             super::Windows::Schematic(Self {
                 new_title: None,
-                selection: None,
+                selection: Vec::new(),
                 message_channel: std::sync::mpsc::channel(),
                 mm: MouseMode::Selection,
-                origin: crate::general::Coordinates::Inches(0.0, 0.0),
+                origin: crate::general::Coordinates::from_nm(0, 0),
                 zoom: 115.0,
                 selected_library: None,
                 selected_component: None,
+                flattened: Default::default(),
                 selected_variant: None,
+                selected_unit: None,
+                placing_orientation: Orientation::default(),
+                new_property: String::new(),
+                page: 0,
+                paste_count: 0,
+                cursor: None,
             }),
             egui_multiwin::winit::window::WindowBuilder::new()
                 .with_resizable(true)
@@ -89,39 +222,101 @@ impl TrackedWindow for SchematicWindow {
         c: &mut MyApp,
         egui: &mut EguiGlow,
         _window: &egui_multiwin::winit::window::Window,
-        _clipboard: &mut egui_multiwin::arboard::Clipboard,
+        clipboard: &mut egui_multiwin::arboard::Clipboard,
     ) -> RedrawResponse {
         let mut quit = false;
 
-        let windows_to_create = vec![];
+        c.check_autosave(&egui.egui_ctx);
+        c.watch_libraries();
+
+        let mut windows_to_create = vec![];
+        let mut edit: Option<EditCommand> = None;
 
         while let Ok(message) = self.message_channel.1.try_recv() {
             match message {
                 Message::CreatePdf(path) => {
                     if let Some(sch) = &c.schematic {
-                        let width =
-                            printpdf::Mm(crate::general::Length::Inches(11.0).get_mm().into());
-                        let height =
-                            printpdf::Mm(crate::general::Length::Inches(11.0).get_mm().into());
+                        let page_size = |p: &crate::schematic::Page| {
+                            let (w, h) = p.size.get_mm();
+                            (printpdf::Mm(w.into()), printpdf::Mm(h.into()))
+                        };
+                        let (width, height) = sch
+                            .schematic
+                            .pages
+                            .first()
+                            .map(page_size)
+                            .unwrap_or((printpdf::Mm(279.4), printpdf::Mm(215.9)));
                         let (doc, page1, layer1) =
                             printpdf::PdfDocument::new(sch.name(), width, height, "Layer 1");
                         let font = doc.add_external_font(crate::COMPUTER_MODERN_FONT).unwrap();
                         if !sch.schematic.pages.is_empty() {
                             let current_layer = doc.get_page(page1).get_layer(layer1);
-                            sch.schematic.pages[0].draw_on(current_layer, &font);
+                            sch.schematic.pages[0].draw_on(
+                                current_layer,
+                                &font,
+                                &sch.schematic.cache,
+                                &c.libraries,
+                                &sch.schematic.page_variables(0),
+                            );
                         }
-                        for page in sch.schematic.pages[1..].iter() {
+                        for (i, page) in sch.schematic.pages.iter().enumerate().skip(1) {
+                            let (width, height) = page_size(page);
                             let (pdfpage, layer) = doc.add_page(width, height, "Layer 1");
                             let current_layer = doc.get_page(pdfpage).get_layer(layer);
-                            page.draw_on(current_layer, &font);
+                            page.draw_on(
+                                current_layer,
+                                &font,
+                                &sch.schematic.cache,
+                                &c.libraries,
+                                &sch.schematic.page_variables(i),
+                            );
+                        }
+                        let exported =
+                            path.writer()
+                                .map_err(|e| e.to_string())
+                                .and_then(|mut writer| {
+                                    doc.save(&mut std::io::BufWriter::new(&mut writer))
+                                        .map_err(|e| e.to_string())?;
+                                    writer.commit().map_err(|e| e.to_string())
+                                });
+                        if let Err(e) = exported {
+                            let _ = native_dialog::MessageDialog::new()
+                                .set_type(native_dialog::MessageType::Error)
+                                .set_title("Failed to export pdf")
+                                .set_text(&e)
+                                .show_alert();
+                        }
+                    }
+                }
+                Message::ExportNetlist(path) => {
+                    if let Some(sch) = &c.schematic {
+                        let netlist = crate::netlist::Netlist::build(&sch.schematic, &c.libraries);
+                        let result =
+                            path.writer()
+                                .map_err(|e| e.to_string())
+                                .and_then(|mut writer| {
+                                    netlist.write(&mut writer).map_err(|e| e.to_string())?;
+                                    writer.commit().map_err(|e| e.to_string())
+                                });
+                        if let Err(e) = result {
+                            let _ = native_dialog::MessageDialog::new()
+                                .set_type(native_dialog::MessageType::Error)
+                                .set_title("Failed to export netlist")
+                                .set_text(&e)
+                                .show_alert();
+                        } else if !netlist.problems.is_empty() {
+                            let _ = native_dialog::MessageDialog::new()
+                                .set_type(native_dialog::MessageType::Warning)
+                                .set_title("Netlist problems")
+                                .set_text(&netlist.problems.join("\n"))
+                                .show_alert();
                         }
-                        let _e = doc.save(&mut std::io::BufWriter::new(path.writer().unwrap()));
                     }
                 }
                 Message::SaveSchematicName(n) => {
                     if let Some(s) = &mut c.schematic {
                         s.set_path(n);
-                        if let Err(_e) = s.save() {
+                        if let Err(_e) = s.save(&c.libraries) {
                             //TODO show the actual error to the user?
                             native_dialog::MessageDialog::new()
                                 .set_type(native_dialog::MessageType::Error)
@@ -135,13 +330,30 @@ impl TrackedWindow for SchematicWindow {
                 Message::LoadSchematicName(n, format) => match n.reader() {
                     Ok(mut reader) => match format.load::<Schematic>(&mut reader) {
                         Ok(sch) => {
-                            c.schematic = Some(SchematicHolder {
-                                schematic: sch,
-                                schematic_log: undo::Record::new(),
-                                schematic_was_saved: false,
-                                path: Some(n),
-                                format,
-                            });
+                            let pending = crate::journal::pending(&n);
+                            let mut holder = SchematicHolder::loaded(sch, n.clone(), format);
+                            if !pending.is_empty() {
+                                if crate::journal::ask_replay(&n.to_string(), pending.len()) {
+                                    holder.schematic_log.replay(&mut holder.schematic, pending);
+                                } else {
+                                    crate::journal::mark(&n, true);
+                                }
+                            }
+                            c.schematic = Some(holder);
+                            c.load_libraries();
+                            if let Some(sch) = &c.schematic {
+                                let missing = sch.schematic.missing_libraries(&c.libraries);
+                                if !missing.is_empty() {
+                                    let _ = native_dialog::MessageDialog::new()
+                                        .set_type(native_dialog::MessageType::Warning)
+                                        .set_title("Missing libraries")
+                                        .set_text(&format!(
+                                            "The schematic uses libraries that were not found: {}",
+                                            missing.join(", ")
+                                        ))
+                                        .show_alert();
+                                }
+                            }
                         }
                         Err(e) => {
                             let _ = native_dialog::MessageDialog::new()
@@ -215,7 +427,7 @@ impl TrackedWindow for SchematicWindow {
                     {
                         if let Some(s) = &mut c.schematic {
                             if s.has_path() {
-                                if let Err(e) = s.save() {
+                                if let Err(e) = s.save(&c.libraries) {
                                     let s: String = format!("Unable to save file {}", e);
                                     native_dialog::MessageDialog::new()
                                         .set_type(native_dialog::MessageType::Error)
@@ -287,6 +499,50 @@ impl TrackedWindow for SchematicWindow {
                         });
                         ui.close_menu();
                     }
+                    if ui
+                        .add_enabled(c.schematic.is_some(), egui::Button::new("Export netlist"))
+                        .clicked()
+                    {
+                        let f = rfd::AsyncFileDialog::new()
+                            .add_filter("Netlist", &["net"])
+                            .set_title("Export netlist")
+                            .save_file();
+                        let message_sender = self.message_channel.0.clone();
+                        crate::execute(async move {
+                            let file = f.await;
+                            if let Some(file) = file {
+                                let mut fname = file.path().to_path_buf();
+                                fname.set_extension("net");
+                                message_sender
+                                    .send(Message::ExportNetlist(
+                                        crate::general::StoragePath::LocalFilesystem(
+                                            fname.into_os_string().into_string().unwrap(),
+                                        ),
+                                    ))
+                                    .ok();
+                            }
+                        });
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(c.schematic.is_some(), egui::Button::new("Page setup"))
+                        .clicked()
+                    {
+                        if let Some(sch) = &c.schematic {
+                            windows_to_create.push(super::page_setup::PageSetup::request(
+                                self.page,
+                                sch.schematic.page_changes,
+                            ));
+                        }
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(c.schematic.is_some(), egui::Button::new("Document properties"))
+                        .clicked()
+                    {
+                        windows_to_create.push(super::document_properties::DocumentProperties::request());
+                        ui.close_menu();
+                    }
                     ui.menu_button("Recent", |ui| {
                         if ui.button("Thing 1").clicked() {
                             ui.close_menu();
@@ -307,17 +563,73 @@ impl TrackedWindow for SchematicWindow {
                         .add_enabled(undoable, egui::Button::new("Undo"))
                         .clicked()
                     {
-                        if let Some(sch) = &mut c.schematic {
-                            sch.schematic_log.undo(&mut sch.schematic);
+                        if let Some(a) = c.schematic.as_ref().and_then(|s| s.schematic_log.next_undo()) {
+                            self.follow_undo(a);
                         }
+                        c.undo_schematic();
                         ui.close_menu();
                     }
                     if ui
                         .add_enabled(redoable, egui::Button::new("Redo"))
                         .clicked()
+                    {
+                        if let Some(a) = c.schematic.as_ref().and_then(|s| s.schematic_log.next_redo()) {
+                            self.follow_apply(a);
+                        }
+                        c.redo_schematic();
+                        ui.close_menu();
+                    }
+                    let selected = !self.selection.is_empty();
+                    for (label, command, enabled) in [
+                        ("Cut", EditCommand::Cut, selected),
+                        ("Copy", EditCommand::Copy, selected),
+                        ("Paste", EditCommand::Paste, c.schematic.is_some()),
+                        ("Delete", EditCommand::Delete, selected),
+                        ("Select all", EditCommand::SelectAll, c.schematic.is_some()),
+                    ] {
+                        if ui.add_enabled(enabled, egui::Button::new(label)).clicked() {
+                            edit = Some(command);
+                            ui.close_menu();
+                        }
+                    }
+                    if ui
+                        .add_enabled(c.schematic.is_some(), egui::Button::new("Update from library"))
+                        .clicked()
+                    {
+                        windows_to_create.push(super::cache_update::CacheUpdate::request());
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(c.schematic.is_some(), egui::Button::new("Annotate"))
+                        .clicked()
+                    {
+                        if let Some(sch) = &mut c.schematic {
+                            if let Some(a) = sch.schematic.annotate(&c.libraries) {
+                                sch.schematic_log.apply(&mut sch.schematic, a);
+                            }
+                        }
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(c.schematic.is_some(), egui::Button::new("Clear annotation"))
+                        .clicked()
+                    {
+                        if let Some(sch) = &mut c.schematic {
+                            if let Some(a) = sch.schematic.clear_annotation() {
+                                sch.schematic_log.apply(&mut sch.schematic, a);
+                            }
+                        }
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(selected, egui::Button::new("Mark unconnected pins"))
+                        .on_hover_text("Put no-connect markers on the unconnected pins of the selected components")
+                        .clicked()
                     {
                         if let Some(sch) = &mut c.schematic {
-                            sch.schematic_log.redo(&mut sch.schematic);
+                            if let Some(a) = sch.schematic.no_connect_action(&self.selection, &c.libraries) {
+                                sch.schematic_log.apply(&mut sch.schematic, a);
+                            }
                         }
                         ui.close_menu();
                     }
@@ -325,6 +637,10 @@ impl TrackedWindow for SchematicWindow {
             });
         });
 
+        if let Some(sch) = &c.schematic {
+            self.keep_valid(&sch.schematic);
+        }
+
         egui::TopBottomPanel::top("button bar").show(&egui.egui_ctx, |ui| {
             if c.schematic.is_some() {
                 ui.horizontal(|ui| {
@@ -344,6 +660,22 @@ impl TrackedWindow for SchematicWindow {
                         .on_hover_ui(|ui| {
                             ui.label("Add component mode");
                         });
+                    ui.selectable_value(&mut self.mm, MouseMode::NewBus, "B")
+                        .on_hover_ui(|ui| {
+                            ui.label("Draw bus mode");
+                        });
+                    ui.selectable_value(&mut self.mm, MouseMode::NewBusEntry, "E")
+                        .on_hover_ui(|ui| {
+                            ui.label("Add bus entry mode");
+                        });
+                    ui.selectable_value(&mut self.mm, MouseMode::NewBusLabel, "L")
+                        .on_hover_ui(|ui| {
+                            ui.label("Add bus label mode");
+                        });
+                    ui.selectable_value(&mut self.mm, MouseMode::NewNoConnect, "N")
+                        .on_hover_ui(|ui| {
+                            ui.label("No-connect marker mode");
+                        });
                 });
             }
         });
@@ -378,7 +710,7 @@ impl TrackedWindow for SchematicWindow {
                     });
 
                 if let Some(l) = &self.selected_library {
-                    let check = c.libraries.get_mut(l);
+                    let check = c.libraries.get(l);
                     if let Some(lib) = check {
                         if let Some(library) = &lib.library {
                             egui::TopBottomPanel::top("component select")
@@ -408,7 +740,12 @@ impl TrackedWindow for SchematicWindow {
                                         });
                                 });
                             if let Some(component) = &self.selected_component {
-                                if let Some(component) = library.components.get(component) {
+                                if let Some(component) = self.flattened.get(
+                                    l,
+                                    component,
+                                    &c.libraries,
+                                    c.library_log.changes(),
+                                ) {
                                     egui::TopBottomPanel::top("variant select")
                                         .resizable(true)
                                         .show_inside(ui, |ui| {
@@ -431,11 +768,40 @@ impl TrackedWindow for SchematicWindow {
                                                             .clicked()
                                                         {
                                                             self.selected_variant = Some(name.clone());
+                                                            self.selected_unit = None;
                                                             self.mm = MouseMode::NewComponent;
                                                         }
                                                     }
                                                 });
                                         });
+                                    let variant = self
+                                        .selected_variant
+                                        .as_ref()
+                                        .and_then(|v| component.variants.get(v));
+                                    if let Some(variant) = variant {
+                                        if !variant.units.is_empty() {
+                                            if variant.unit(self.selected_unit.as_deref().unwrap_or_default()).is_none() {
+                                                self.selected_unit = variant.units.first().map(|u| u.name.clone());
+                                            }
+                                            ui.label("Units");
+                                            ui.horizontal_wrapped(|ui| {
+                                                for unit in &variant.units {
+                                                    if ui
+                                                        .selectable_label(
+                                                            self.selected_unit.as_ref() == Some(&unit.name),
+                                                            &unit.name,
+                                                        )
+                                                        .clicked()
+                                                    {
+                                                        self.selected_unit = Some(unit.name.clone());
+                                                        self.mm = MouseMode::NewComponent;
+                                                    }
+                                                }
+                                            });
+                                        } else {
+                                            self.selected_unit = None;
+                                        }
+                                    }
                                 }
                             }
                         }
@@ -450,41 +816,250 @@ impl TrackedWindow for SchematicWindow {
             .show(&egui.egui_ctx, |ui| {
                 ui.label("Right");
                 if let Some(sch) = &mut c.schematic {
-                    if let Some(sel) = &self.selection {
+                    if self.selection.len() > 1 {
+                        ui.label(format!("{} objects are selected", self.selection.len()));
+                        let selection = &self.selection;
+                        ui.horizontal_wrapped(|ui| {
+                            for alignment in crate::general::Alignment::ALL {
+                                if ui.button(alignment.to_string()).clicked() {
+                                    actionlog
+                                        .extend(sch.schematic.align_action(selection, alignment));
+                                }
+                            }
+                        });
+                        ui.horizontal(|ui| {
+                            let rotate = ui.button("Rotate").on_hover_text("R").clicked();
+                            let mirror_x = ui.button("Mirror X").on_hover_text("X").clicked();
+                            let mirror_y = ui.button("Mirror Y").on_hover_text("Y").clicked();
+                            let turn: Option<fn(Orientation) -> Orientation> = if rotate {
+                                Some(Orientation::rotated)
+                            } else if mirror_x {
+                                Some(Orientation::mirrored_x)
+                            } else if mirror_y {
+                                Some(Orientation::mirrored_y)
+                            } else {
+                                None
+                            };
+                            if let Some(turn) = turn {
+                                actionlog.extend(sch.schematic.turn_action(selection, turn));
+                            }
+                        });
+                        if ui.button("Delete").on_hover_text("Delete").clicked() {
+                            edit = Some(EditCommand::Delete);
+                        }
+                    } else if let Some(sel) = self.selection.first() {
                         match sel {
                             crate::schematic::SchematicSelection::Symbol { page, sym } => {
                                 let var_ref = &sch.schematic.pages[*page].syms[*sym];
-                                let units = var_ref.pos.get_units(c.units);
-                                let mut xstr = format!("{:.4}", units.0);
+                                let component =
+                                    sch.schematic.cache.get_component(var_ref, &c.libraries);
+                                let prefix = component
+                                    .as_ref()
+                                    .map(|c| c.prefix().to_string())
+                                    .unwrap_or_else(|| {
+                                        crate::component::DEFAULT_PREFIX.to_string()
+                                    });
+                                let defaults = component
+                                    .as_ref()
+                                    .map(|c| c.properties.clone())
+                                    .unwrap_or_default();
+                                ui.label(format!("{} {}", var_ref.label(&prefix), var_ref.com));
+                                let mut designator = var_ref.designator.clone().unwrap_or_default();
                                 ui.horizontal(|ui| {
-                                    ui.label("X ");
-                                    ui.add(egui::TextEdit::singleline(&mut xstr));
+                                    ui.label("Designator ");
+                                    ui.add(egui::TextEdit::singleline(&mut designator));
                                 });
-                                if let Ok(x) = xstr.parse::<f32>() {
-                                    actionlog.push(SchematicAction::MoveSymbol {
+                                let designator = Some(designator).filter(|d| !d.is_empty());
+                                if designator != var_ref.designator {
+                                    actionlog.push(SchematicAction::SetDesignator {
                                         pagenum: *page,
                                         symnum: *sym,
-                                        delta: crate::general::Coordinates::from_pos2(
-                                            egui::pos2(x - units.0, 0.0),
-                                            1.0,
-                                        ),
+                                        designator,
                                     });
                                 }
-                                let mut ystr = format!("{:.4}", units.1);
+                                let variant =
+                                    component.and_then(|c| c.variants.get(&var_ref.var).cloned());
+                                if let Some(variant) = variant.filter(|v| !v.units.is_empty()) {
+                                    let mut unit = var_ref.unit.clone();
+                                    egui::ComboBox::from_label("Unit")
+                                        .selected_text(unit.clone().unwrap_or_default())
+                                        .show_ui(ui, |ui| {
+                                            for u in &variant.units {
+                                                ui.selectable_value(
+                                                    &mut unit,
+                                                    Some(u.name.clone()),
+                                                    &u.name,
+                                                );
+                                            }
+                                        });
+                                    if unit != var_ref.unit {
+                                        actionlog.push(SchematicAction::SetUnit {
+                                            pagenum: *page,
+                                            symnum: *sym,
+                                            unit,
+                                        });
+                                    }
+                                }
+                                let styles: Vec<String> = sch
+                                    .schematic
+                                    .cache
+                                    .get_symbol(var_ref, &c.libraries)
+                                    .map(|s| s.styles.iter().map(|st| st.name.clone()).collect())
+                                    .unwrap_or_default();
+                                if !styles.is_empty() {
+                                    let mut style = var_ref.style.clone();
+                                    egui::ComboBox::from_label("Body style")
+                                        .selected_text(
+                                            style.clone().unwrap_or_else(|| "Normal".to_string()),
+                                        )
+                                        .show_ui(ui, |ui| {
+                                            ui.selectable_value(&mut style, None, "Normal");
+                                            for st in &styles {
+                                                ui.selectable_value(
+                                                    &mut style,
+                                                    Some(st.clone()),
+                                                    st,
+                                                );
+                                            }
+                                        });
+                                    if style != var_ref.style {
+                                        actionlog.push(SchematicAction::SetStyle {
+                                            pagenum: *page,
+                                            symnum: *sym,
+                                            style,
+                                        });
+                                    }
+                                }
+                                ui.horizontal(|ui| {
+                                    let o = var_ref.orientation;
+                                    let rotate = ui.button("Rotate").on_hover_text("R").clicked();
+                                    let mirror_x =
+                                        ui.button("Mirror X").on_hover_text("X").clicked();
+                                    let mirror_y =
+                                        ui.button("Mirror Y").on_hover_text("Y").clicked();
+                                    let turned = if rotate {
+                                        Some(o.rotated())
+                                    } else if mirror_x {
+                                        Some(o.mirrored_x())
+                                    } else if mirror_y {
+                                        Some(o.mirrored_y())
+                                    } else {
+                                        None
+                                    };
+                                    if let Some(orientation) = turned {
+                                        actionlog.push(SchematicAction::SetOrientation {
+                                            pagenum: *page,
+                                            symnum: *sym,
+                                            orientation,
+                                        });
+                                    }
+                                });
+                                ui.separator();
+                                ui.label("Properties");
+                                let reserved =
+                                    [PART_NUMBER_PROPERTY.to_string(), VALUE_PROPERTY.to_string()];
+                                let mut names: Vec<&String> = reserved
+                                    .iter()
+                                    .chain(defaults.keys())
+                                    .chain(var_ref.properties.keys())
+                                    .collect();
+                                names.sort();
+                                names.dedup();
+                                egui::Grid::new("properties").show(ui, |ui| {
+                                    ui.label(DESIGNATOR_FIELD);
+                                    ui.label(var_ref.label(&prefix));
+                                    actionlog.extend(field_checkbox(
+                                        ui,
+                                        var_ref,
+                                        *page,
+                                        *sym,
+                                        DESIGNATOR_FIELD,
+                                    ));
+                                    ui.end_row();
+                                    for name in names {
+                                        ui.label(name);
+                                        let current = var_ref.property(name, &defaults);
+                                        let mut value = current.cloned().unwrap_or_default();
+                                        ui.add(egui::TextEdit::singleline(&mut value));
+                                        if Some(&value) != current {
+                                            actionlog.push(SchematicAction::SetProperty {
+                                                pagenum: *page,
+                                                symnum: *sym,
+                                                name: name.clone(),
+                                                value: Some(value),
+                                            });
+                                        }
+                                        actionlog
+                                            .extend(field_checkbox(ui, var_ref, *page, *sym, name));
+                                        if var_ref.properties.contains_key(name) {
+                                            let reset = if defaults.contains_key(name) {
+                                                "Reset"
+                                            } else {
+                                                "Remove"
+                                            };
+                                            if ui.button(reset).clicked() {
+                                                actionlog.push(SchematicAction::SetProperty {
+                                                    pagenum: *page,
+                                                    symnum: *sym,
+                                                    name: name.clone(),
+                                                    value: None,
+                                                });
+                                            }
+                                        }
+                                        ui.end_row();
+                                    }
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.add(
+                                        egui::TextEdit::singleline(&mut self.new_property)
+                                            .hint_text("New property"),
+                                    );
+                                    let exists =
+                                        var_ref.property(&self.new_property, &defaults).is_some()
+                                            || self.new_property == DESIGNATOR_FIELD;
+                                    if ui.button("Add").clicked()
+                                        && !self.new_property.is_empty()
+                                        && !exists
+                                    {
+                                        actionlog.push(SchematicAction::SetProperty {
+                                            pagenum: *page,
+                                            symnum: *sym,
+                                            name: std::mem::take(&mut self.new_property),
+                                            value: Some(String::new()),
+                                        });
+                                    }
+                                });
+                                ui.separator();
+                                ui.horizontal(|ui| {
+                                    ui.label("X ");
+                                    if let Some(x) = crate::general::length_edit(
+                                        ui,
+                                        "x",
+                                        var_ref.pos.x,
+                                        c.settings.units,
+                                    ) {
+                                        actionlog.push(SchematicAction::MoveSymbol {
+                                            pagenum: *page,
+                                            symnum: *sym,
+                                            delta: var_ref.pos.delta_to_x(x),
+                                        });
+                                    }
+                                });
                                 ui.horizontal(|ui| {
                                     ui.label("Y ");
-                                    ui.add(egui::TextEdit::singleline(&mut ystr));
+                                    if let Some(y) = crate::general::length_edit(
+                                        ui,
+                                        "y",
+                                        var_ref.pos.y,
+                                        c.settings.units,
+                                    ) {
+                                        actionlog.push(SchematicAction::MoveSymbol {
+                                            pagenum: *page,
+                                            symnum: *sym,
+                                            delta: var_ref.pos.delta_to_y(y),
+                                        });
+                                    }
                                 });
-                                if let Ok(y) = ystr.parse::<f32>() {
-                                    actionlog.push(SchematicAction::MoveSymbol {
-                                        pagenum: *page,
-                                        symnum: *sym,
-                                        delta: crate::general::Coordinates::from_pos2(
-                                            egui::pos2(0.0, units.1 - y),
-                                            1.0,
-                                        ),
-                                    });
-                                }
                                 ui.label("A symbol has been selected");
                             }
                             crate::schematic::SchematicSelection::Text { page, textnum } => {
@@ -503,37 +1078,36 @@ impl TrackedWindow for SchematicWindow {
                                         new: text,
                                     });
                                 }
-                                let units = t.location.get_units(c.units);
-                                let mut xstr = format!("{:.4}", units.0);
                                 ui.horizontal(|ui| {
                                     ui.label("X ");
-                                    ui.add(egui::TextEdit::singleline(&mut xstr));
+                                    if let Some(x) = crate::general::length_edit(
+                                        ui,
+                                        "x",
+                                        t.location.x,
+                                        c.settings.units,
+                                    ) {
+                                        actionlog.push(SchematicAction::MoveText {
+                                            pagenum: *page,
+                                            textnum: *textnum,
+                                            delta: t.location.delta_to_x(x),
+                                        });
+                                    }
                                 });
-                                if let Ok(x) = xstr.parse::<f32>() {
-                                    actionlog.push(SchematicAction::MoveText {
-                                        pagenum: *page,
-                                        textnum: *textnum,
-                                        delta: crate::general::Coordinates::from_pos2(
-                                            egui::pos2(x - units.0, 0.0),
-                                            1.0,
-                                        ),
-                                    });
-                                }
-                                let mut ystr = format!("{:.4}", units.1);
                                 ui.horizontal(|ui| {
                                     ui.label("Y ");
-                                    ui.add(egui::TextEdit::singleline(&mut ystr));
+                                    if let Some(y) = crate::general::length_edit(
+                                        ui,
+                                        "y",
+                                        t.location.y,
+                                        c.settings.units,
+                                    ) {
+                                        actionlog.push(SchematicAction::MoveText {
+                                            pagenum: *page,
+                                            textnum: *textnum,
+                                            delta: t.location.delta_to_y(y),
+                                        });
+                                    }
                                 });
-                                if let Ok(y) = ystr.parse::<f32>() {
-                                    actionlog.push(SchematicAction::MoveText {
-                                        pagenum: *page,
-                                        textnum: *textnum,
-                                        delta: crate::general::Coordinates::from_pos2(
-                                            egui::pos2(0.0, units.1 - y),
-                                            1.0,
-                                        ),
-                                    });
-                                }
                                 let mut color = t
                                     .color
                                     .get_color32(crate::general::ColorMode::ScreenModeDark);
@@ -548,6 +1122,75 @@ impl TrackedWindow for SchematicWindow {
                                     });
                                 }
                             }
+                            crate::schematic::SchematicSelection::Bus { page, partnum } => {
+                                let part = &sch.schematic.pages[*page].bus[*partnum];
+                                let (kind, name) = match part {
+                                    BusPart::Wire { .. } => ("Bus wire", None),
+                                    BusPart::Entry { member, .. } => {
+                                        ("Bus entry", Some(("Member ", member)))
+                                    }
+                                    BusPart::Label { text, .. } => {
+                                        ("Bus label", Some(("Name ", text)))
+                                    }
+                                };
+                                ui.label(kind);
+                                if let Some((label, name)) = name {
+                                    let mut text = name.clone();
+                                    ui.horizontal(|ui| {
+                                        ui.label(label);
+                                        ui.add(egui::TextEdit::singleline(&mut text));
+                                    });
+                                    if text != *name {
+                                        let part = match part.clone() {
+                                            BusPart::Entry { from, to, .. } => BusPart::Entry {
+                                                from,
+                                                to,
+                                                member: text,
+                                            },
+                                            BusPart::Label { location, .. } => {
+                                                BusPart::Label { location, text }
+                                            }
+                                            wire => wire,
+                                        };
+                                        actionlog.push(SchematicAction::SetBusPart {
+                                            pagenum: *page,
+                                            partnum: *partnum,
+                                            part,
+                                        });
+                                    }
+                                }
+                                let anchor = part.anchor();
+                                ui.horizontal(|ui| {
+                                    ui.label("X ");
+                                    if let Some(x) = crate::general::length_edit(
+                                        ui,
+                                        "x",
+                                        anchor.x,
+                                        c.settings.units,
+                                    ) {
+                                        actionlog.push(SchematicAction::MoveBusPart {
+                                            pagenum: *page,
+                                            partnum: *partnum,
+                                            delta: anchor.delta_to_x(x),
+                                        });
+                                    }
+                                });
+                                ui.horizontal(|ui| {
+                                    ui.label("Y ");
+                                    if let Some(y) = crate::general::length_edit(
+                                        ui,
+                                        "y",
+                                        anchor.y,
+                                        c.settings.units,
+                                    ) {
+                                        actionlog.push(SchematicAction::MoveBusPart {
+                                            pagenum: *page,
+                                            partnum: *partnum,
+                                            delta: anchor.delta_to_y(y),
+                                        });
+                                    }
+                                });
+                            }
                         }
                     }
                 }
@@ -559,18 +1202,231 @@ impl TrackedWindow for SchematicWindow {
             }
         }
 
+        // The text pasted with the keyboard, which arrives with the paste instead of being on the clipboard
+        let mut pasted = None;
+        if !egui.egui_ctx.wants_keyboard_input() {
+            let key = egui.egui_ctx.input_mut(|i| {
+                // Cut, copy and paste shortcuts arrive as their own events instead of as key presses
+                let mut command = None;
+                i.events.retain(|e| {
+                    if command.is_some() {
+                        return true;
+                    }
+                    match e {
+                        egui::Event::Cut => command = Some(EditCommand::Cut),
+                        egui::Event::Copy => command = Some(EditCommand::Copy),
+                        egui::Event::Paste(text) => {
+                            command = Some(EditCommand::Paste);
+                            pasted = Some(text.clone());
+                        }
+                        _ => return true,
+                    }
+                    false
+                });
+                if command.is_some() {
+                    command
+                } else if i.consume_key(egui::Modifiers::COMMAND, egui::Key::A) {
+                    Some(EditCommand::SelectAll)
+                } else if i.consume_key(egui::Modifiers::NONE, egui::Key::Delete) {
+                    Some(EditCommand::Delete)
+                } else {
+                    None
+                }
+            });
+            edit = edit.or(key);
+        }
+
+        if let (Some(command), Some(sch)) = (edit, &mut c.schematic) {
+            let selection = self.selection.clone();
+            if command == EditCommand::Cut || command == EditCommand::Copy {
+                let text = SchematicFragment::copy(&sch.schematic, &selection, &c.libraries)
+                    .and_then(|f| f.to_clipboard());
+                if let Some(text) = text {
+                    if clipboard.set_text(text).is_ok() {
+                        self.paste_count = 0;
+                    }
+                }
+            }
+            if command == EditCommand::Cut || command == EditCommand::Delete {
+                if let Some(a) = sch.schematic.delete_action(&selection) {
+                    sch.schematic_log.apply(&mut sch.schematic, a);
+                    self.selection.clear();
+                }
+            }
+            if command == EditCommand::SelectAll {
+                self.selection = sch.schematic.select_all(self.page);
+            }
+            if command == EditCommand::Paste {
+                let fragment = pasted
+                    .or_else(|| clipboard.get_text().ok())
+                    .and_then(|t| SchematicFragment::from_clipboard(&t));
+                if let Some(f) = fragment {
+                    self.paste_count += 1;
+                    let n = self.paste_count as i64;
+                    let offset = crate::general::Coordinates::from_nm(
+                        PASTE_OFFSET.x * n,
+                        PASTE_OFFSET.y * n,
+                    );
+                    let a = f.paste_action(&sch.schematic, self.page, offset);
+                    sch.schematic_log.apply(&mut sch.schematic, a);
+                }
+            }
+        }
+
+        if !egui.egui_ctx.wants_keyboard_input() {
+            let (rotate, mirror_x, mirror_y) = egui.egui_ctx.input(|i| {
+                (
+                    i.key_pressed(egui::Key::R),
+                    i.key_pressed(egui::Key::X),
+                    i.key_pressed(egui::Key::Y),
+                )
+            });
+            let turn = |mut o: Orientation| {
+                if rotate {
+                    o = o.rotated();
+                }
+                if mirror_x {
+                    o = o.mirrored_x();
+                }
+                if mirror_y {
+                    o = o.mirrored_y();
+                }
+                o
+            };
+            if rotate || mirror_x || mirror_y {
+                if self.mm == MouseMode::NewComponent {
+                    self.placing_orientation = turn(self.placing_orientation);
+                } else if let Some(sch) = &mut c.schematic {
+                    if let Some(a) = sch.schematic.turn_action(&self.selection, turn) {
+                        sch.schematic_log.apply(&mut sch.schematic, a);
+                    }
+                }
+            }
+        }
+
         let mut component: Option<crate::component::ComponentVariantReference> = None;
         if let Some(libname) = &self.selected_library {
             if let Some(sch) = &self.selected_component {
                 if let Some(var) = &self.selected_variant {
-                    component = Some(ComponentVariantReference {
-                        lib: libname.to_owned(),
-                        com: sch.to_owned(),
-                        var: var.to_owned(),
-                        pos: crate::general::Coordinates::Inches(0.0, 0.0),
+                    let mut vr = ComponentVariantReference::new(
+                        libname.to_owned(),
+                        sch.to_owned(),
+                        var.to_owned(),
+                        crate::general::Coordinates::from_nm(0, 0),
+                    );
+                    vr.unit = self.selected_unit.clone();
+                    vr.orientation = self.placing_orientation;
+                    component = Some(vr);
+                }
+            }
+        }
+
+        egui::TopBottomPanel::bottom("status bar").show(&egui.egui_ctx, |ui| {
+            ui.horizontal(|ui| {
+                let units_changed =
+                    crate::grid::cursor_readout(ui, self.cursor, &mut c.settings.units);
+                ui.separator();
+                ui.label("Grid");
+                if c.settings.grid.controls(ui) || units_changed {
+                    if let Err(e) = c.settings.save(&c.dirs) {
+                        let _ = native_dialog::MessageDialog::new()
+                            .set_type(native_dialog::MessageType::Error)
+                            .set_title("Failed to save settings")
+                            .set_text(&e.to_string())
+                            .show_alert();
+                    }
+                }
+            });
+        });
+
+        let mut page_actions = Vec::new();
+        egui::TopBottomPanel::bottom("page navigator")
+            .resizable(false)
+            .show(&egui.egui_ctx, |ui| {
+                if let Some(sch) = c
+                    .schematic
+                    .as_ref()
+                    .filter(|s| !s.schematic.pages.is_empty())
+                {
+                    let pages = &sch.schematic.pages;
+                    ui.horizontal(|ui| {
+                        if ui.button("Add page").clicked() {
+                            page_actions.push(SchematicAction::InsertPage {
+                                pagenum: self.page + 1,
+                                page: Some(crate::schematic::Page::blank_like(&pages[self.page])),
+                            });
+                        }
+                        if ui.button("Duplicate page").clicked() {
+                            let name = format!("{} copy", pages[self.page].title(self.page));
+                            page_actions.push(SchematicAction::InsertPage {
+                                pagenum: self.page + 1,
+                                page: Some(pages[self.page].duplicate(name)),
+                            });
+                        }
+                        if ui
+                            .add_enabled(pages.len() > 1, egui::Button::new("Delete page"))
+                            .clicked()
+                        {
+                            page_actions.push(SchematicAction::RemovePage {
+                                pagenum: self.page,
+                                page: None,
+                            });
+                        }
+                        if ui
+                            .add_enabled(self.page > 0, egui::Button::new("Move left"))
+                            .clicked()
+                        {
+                            page_actions.push(SchematicAction::MovePage {
+                                from: self.page,
+                                to: self.page - 1,
+                            });
+                        }
+                        if ui
+                            .add_enabled(
+                                self.page + 1 < pages.len(),
+                                egui::Button::new("Move right"),
+                            )
+                            .clicked()
+                        {
+                            page_actions.push(SchematicAction::MovePage {
+                                from: self.page,
+                                to: self.page + 1,
+                            });
+                        }
+                        let mut name = pages[self.page].name.clone();
+                        let te = egui::TextEdit::singleline(&mut name)
+                            .hint_text(pages[self.page].title(self.page));
+                        if ui.add(te).changed() {
+                            page_actions.push(SchematicAction::RenamePage {
+                                pagenum: self.page,
+                                name,
+                            });
+                        }
+                    });
+                    egui::ScrollArea::horizontal().show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            for (i, page) in pages.iter().enumerate() {
+                                ui.vertical(|ui| {
+                                    let vars = sch.schematic.page_variables(i);
+                                    let sheet =
+                                        page.sheet(&sch.schematic.cache, &c.libraries, &vars);
+                                    if page_thumbnail(ui, page, &sheet, i == self.page).clicked() {
+                                        self.page = i;
+                                    }
+                                    ui.label(page.title(i));
+                                });
+                            }
+                        });
                     });
                 }
+            });
+
+        if let Some(sch) = &mut c.schematic {
+            for a in page_actions {
+                self.follow_apply(&a);
+                sch.schematic_log.apply(&mut sch.schematic, a);
             }
+            self.keep_valid(&sch.schematic);
         }
 
         egui::CentralPanel::default().show(&egui.egui_ctx, |ui| {
@@ -583,7 +1439,10 @@ impl TrackedWindow for SchematicWindow {
                     &mut self.zoom,
                     component,
                     &c.libraries,
-                );
+                )
+                .with_page(self.page)
+                .with_grid(c.settings.grid)
+                .with_cursor(&mut self.cursor);
                 let resp = ui.add(sch);
                 if resp.dragged_by(egui::PointerButton::Middle) {
                     self.origin += crate::general::Coordinates::from_pos2(